@@ -5,12 +5,13 @@ use gtk::prelude::CellLayoutExt;
 use gtk::prelude::TreeStoreExtManual;
 use gtk::prelude::TreeViewExt;
 use java_class::attributes;
+use java_class::annotations;
+use java_class::annotations::AnnotationValue;
+use java_class::annotations::ResolvedAnnotation;
 use java_class::attributes::Annotation;
 use java_class::attributes::Attribute::*;
 use java_class::attributes::Attribute;
-use java_class::attributes::ElementValue;
 use java_class::attributes::StackMapFrame;
-use java_class::attributes::TargetInfo;
 use java_class::attributes::TypeAnnotation;
 use java_class::attributes::VerificationTypeInfo;
 use java_class::class::AccessFlags;
@@ -18,11 +19,13 @@ use java_class::class::JavaClass;
 use java_class::cp::CPIndex;
 use java_class::cp::ConstantPool;
 use java_class::cp_info::CPInfo;
+use java_class::frames::FrameType;
 use java_class::fields;
 use java_class::methods;
+use java_class::opcode_info::{OpcodeInfo, OperandKind};
 use java_class::opcodes::Opcode;
+use java_class::resolved::ClassInfo;
 use java_class::opcodes::Opcode::*;
-use std::str;
 
 pub fn insert_with_values(ts: &TreeStore, parent: Option<&TreeIter>,
     position: Option<u32>,
@@ -52,7 +55,15 @@ pub fn class_to_tree(class: JavaClass) -> TreeView {
     tree.set_headers_visible(false);
 
     let ans = TreeStore::new(&[String::static_type(), String::static_type()]);
-    let name = get_name(&class.constant_pool, class.this_class);
+    let (name, super_name, field_labels, method_labels): (String, String, Vec<String>, Vec<String>) = match ClassInfo::new(&class) {
+        Ok(info) => (
+            info.name.clone(),
+            info.super_name.clone().unwrap_or_default(),
+            info.fields.iter().map(|f| f.to_string()).collect(),
+            info.methods.iter().map(|m| m.to_string()).collect(),
+        ),
+        Err(e) => (format!("{}", e), String::new(), vec![], vec![]),
+    };
     let iter = insert_with_values(&ans, None, None, &[0, 1], &[&"Class", &name]);
     insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"minor_version", &format!("{}", class.minor_version)]);
     insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"major_version", &format!("{}", class.major_version)]);
@@ -60,26 +71,40 @@ pub fn class_to_tree(class: JavaClass) -> TreeView {
     insert_constant_pool(&ans, &iter, constants);
     insert_access_class(&ans, &iter, class.access_flags);
     insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"this_class", &format!("{}", class.this_class)]);
-    insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"super_class", &(format!("{} ({})", class.super_class, if class.super_class.as_u16() == 0 { "".to_owned() } else { get_name(constants, class.super_class) }))]);
+    insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"super_class", &format!("{} ({})", class.super_class, super_name)]);
+    //computed before the fields and methods are moved out of `class` below
+    let frames: Vec<_> = class.methods.iter().map(|m| class.stack_map_frames(m)).collect();
     let interfaces = insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"interfaces", &""]);
     for interface in class.interfaces {
-        let name = get_name(&class.constant_pool, interface);
+        let name = class.constant_pool.get_class_name(interface).unwrap_or_default();
         insert_with_values(&ans, Some(&interfaces), None, &[0, 1], &[&format!("{}", interface), &name.to_string()]);
     }
     let fields = insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"Fields", &""]);
-    for f in class.fields {
-        let field = insert_with_values(&ans, Some(&fields), None, &[0, 1], &[&"Field", &format!("{} {}", get_name(&class.constant_pool, f.name_index), get_name(&class.constant_pool, f.descriptor_index))]);
+    for (i, f) in class.fields.into_iter().enumerate() {
+        let label = field_labels.get(i).cloned().unwrap_or_default();
+        let field = insert_with_values(&ans, Some(&fields), None, &[0, 1], &[&"Field", &label]);
         insert_access_field(&ans, &field, f.access_flags);
         insert_with_values(&ans, Some(&field), None, &[0, 1], &[&"name_index", &format!("{}", f.name_index)]);
         insert_with_values(&ans, Some(&field), None, &[0, 1], &[&"descriptor_index", &format!("{}", f.descriptor_index)]);
         insert_attributes(constants, &ans, &field, f.attributes);
     }
     let methods = insert_with_values(&ans, Some(&iter), None, &[0, 1], &[&"Methods", &""]);
-    for m in class.methods {
-        let method = insert_with_values(&ans, Some(&methods), None, &[0, 1], &[&"Method", &format!("{}{}", get_name(&class.constant_pool, m.name_index), get_name(&class.constant_pool, m.descriptor_index))]);
+    for ((i, m), frames) in class.methods.into_iter().enumerate().zip(frames) {
+        let label = method_labels.get(i).cloned().unwrap_or_default();
+        let method = insert_with_values(&ans, Some(&methods), None, &[0, 1], &[&"Method", &label]);
         insert_access_method(&ans, &method, m.access_flags);
         insert_with_values(&ans, Some(&method), None, &[0, 1], &[&"name_index", &format!("{}", m.name_index)]);
         insert_with_values(&ans, Some(&method), None, &[0, 1], &[&"descriptor_index", &format!("{}", m.descriptor_index)]);
+        match frames {
+            Ok(frames) if !frames.is_empty() => {
+                let iter_f = insert_with_values(&ans, Some(&method), None, &[0, 1], &[&"Frames", &""]);
+                for f in frames {
+                    insert_with_values(&ans, Some(&iter_f), None, &[0, 1], &[&format!("{}", f.offset), &format!("locals [{}] stack [{}]", join(&f.locals), join(&f.stack))]);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => { insert_with_values(&ans, Some(&method), None, &[0, 1], &[&"Frames", &e.to_string()]); }
+        }
         insert_attributes(constants, &ans, &method, m.attributes);
     }
     insert_attributes(constants, &ans, &iter, class.attributes);
@@ -87,109 +112,81 @@ pub fn class_to_tree(class: JavaClass) -> TreeView {
     tree
 }
 
-fn get_name(cp: &ConstantPool, index: CPIndex) -> String {
-    match &cp[index] {
-        CPInfo::Class { name_index } => {
-            get_name(cp, *name_index)
-        }
-        CPInfo::Utf8 { bytes, .. } => { str::from_utf8(bytes.as_slice()).unwrap().to_owned() }
-        CPInfo::String { string_index } => { get_name(cp, *string_index) }
-        CPInfo::NameAndType { name_index, descriptor_index } => {
-            get_name(cp, *name_index) + " " + &get_name(cp, *descriptor_index)
-        }
-        CPInfo::Methodref { class_index, name_and_type_index } |
-        CPInfo::Fieldref { class_index, name_and_type_index } |
-        CPInfo::InterfaceMethodref { class_index, name_and_type_index } => {
-            get_name(cp, *class_index) + " " + &get_name(cp, *name_and_type_index)
-        }
-        CPInfo::Integer { bytes } => {
-            format!("{}", *bytes as i32)
-        }
-        CPInfo::Float { bytes } => {
-            format!("{}", f32::from_bits(*bytes))
-        }
-        CPInfo::Long { bytes } => {
-            format!("{}", *bytes as i64)
-        }
-        CPInfo::Double { bytes } => {
-            format!("{}", f64::from_bits(*bytes))
-        }
-        CPInfo::InvokeDynamic { name_and_type_index, .. } => {
-            get_name(cp, *name_and_type_index)
-        }
-        _ => "Constant Pool index did not point to Utf8".to_owned()
-    }
+fn join(types: &[FrameType]) -> String {
+    types.iter().map(FrameType::to_string).collect::<Vec<_>>().join(", ")
 }
 
 fn insert_constant_pool(store: &TreeStore, iter: &TreeIter, constants: &ConstantPool) {
     let cp = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"Constant Pool", &""]);
+    let member = |i| constants.get_member_ref(i).map(|(class, name, descriptor)| format!("{} {} {}", class, name, descriptor)).unwrap_or_default();
+    let name_and_type = |i| constants.get_name_and_type(i).map(|(name, descriptor)| format!("{} {}", name, descriptor)).unwrap_or_default();
     for i in 1..=constants.items().len() as u16 {
         let i = i.into();
         let cp_item = &constants[i];
         match cp_item {
             CPInfo::Class { name_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Class", i), &get_name(constants, *name_index)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Class", i), &constants.get_utf8(*name_index).unwrap_or_default()]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"name_index", &format!("{}", name_index)]);
             }
             CPInfo::Fieldref { class_index, name_and_type_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Fieldref", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Fieldref", i), &member(i)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"class_index", &format!("{}", class_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"name_and_type_index", &format!("{}", name_and_type_index)]);
             }
             CPInfo::Methodref { class_index, name_and_type_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Methodref", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Methodref", i), &member(i)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"class_index", &format!("{}", class_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"name_and_type_index", &format!("{}", name_and_type_index)]);
             }
             CPInfo::InterfaceMethodref { class_index, name_and_type_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. InterfaceMethodref", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. InterfaceMethodref", i), &member(i)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"class_index", &format!("{}", class_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"name_and_type_index", &format!("{}", name_and_type_index)]);
             }
             CPInfo::String { string_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. String", i), &get_name(constants, *string_index)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. String", i), &constants.get_utf8(*string_index).unwrap_or_default()]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"string_index", &format!("{}", string_index)]);
             }
             CPInfo::Integer { bytes } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Integer", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Integer", i), &format!("{}", *bytes as i32)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bytes", &format!("{}", bytes)]);
             }
             CPInfo::Float { bytes } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Float", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Float", i), &format!("{}", f32::from_bits(*bytes))]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bytes", &format!("{}", bytes)]);
             }
             CPInfo::Long { bytes } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Long", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Long", i), &format!("{}", *bytes as i64)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bytes", &format!("{}", bytes)]);
             }
             CPInfo::Double { bytes } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Double", i), &get_name(constants, i)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Double", i), &format!("{}", f64::from_bits(*bytes))]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bytes", &format!("{}", bytes)]);
             }
             CPInfo::NameAndType { name_index, descriptor_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. NameAndType", i), &(get_name(constants, i))]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. NameAndType", i), &name_and_type(i)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"name_index", &format!("{}", name_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"descriptor_index", &format!("{}", descriptor_index)]);
             }
             CPInfo::Utf8 { length, bytes } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Utf8", i), &str::from_utf8(bytes).unwrap()]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. Utf8", i), &constants.get_utf8(i).unwrap_or_default()]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"length", &format!("{}", length)]);
-                let iter_bytes = insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bytes", &str::from_utf8(bytes).unwrap()]);
+                let iter_bytes = insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bytes", &constants.get_utf8(i).unwrap_or_default()]);
                 for byte in bytes {
                     insert_with_values(store, Some(&iter_bytes), None, &[0, 1], &[&format!("{}", byte), &""]);
                 }
             }
             CPInfo::MethodHandle { reference_kind, reference_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. MethodHandle", i), &get_name(constants, *reference_index)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. MethodHandle", i), &member(*reference_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"reference_kind", &format!("{}", reference_kind)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"reference_index", &format!("{}", reference_index)]);
             }
             CPInfo::MethodType { descriptor_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. MethodType", i), &get_name(constants, *descriptor_index)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. MethodType", i), &constants.get_utf8(*descriptor_index).unwrap_or_default()]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"descriptor_index", &format!("{}", descriptor_index)]);
             }
             CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
-                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. InvokeDynamic", i), &get_name(constants, *name_and_type_index)]);
+                let iter_n = insert_with_values(store, Some(&cp), None, &[0, 1], &[&format!("{}. InvokeDynamic", i), &name_and_type(*name_and_type_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"bootstrap_method_attr_index", &format!("{}", bootstrap_method_attr_index)]);
                 insert_with_values(store, Some(&iter_n), None, &[0, 1], &[&"name_and_type_index", &format!("{}", name_and_type_index)]);
             }
@@ -202,44 +199,48 @@ fn insert_constant_pool(store: &TreeStore, iter: &TreeIter, constants: &Constant
 }
 
 fn insert_access_class(store: &TreeStore, iter: &TreeIter, access_flags: u16) {
+    let flags = AccessFlags::from_bits(access_flags);
     let access = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"access_flags", &format!("{:#06X}", access_flags)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"public", &format!("{}", (access_flags & AccessFlags::Public as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"final", &format!("{}", (access_flags & AccessFlags::Final as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"super", &format!("{}", (access_flags & AccessFlags::Super as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"interface", &format!("{}", (access_flags & AccessFlags::Interface as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"abstract", &format!("{}", (access_flags & AccessFlags::Abstract as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synthetic", &format!("{}", (access_flags & AccessFlags::Synthetic as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"annotation", &format!("{}", (access_flags & AccessFlags::Annotation as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"enum", &format!("{}", (access_flags & AccessFlags::Enum as u16) != 0)]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"public", &format!("{}", flags.contains(AccessFlags::PUBLIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"final", &format!("{}", flags.contains(AccessFlags::FINAL))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"super", &format!("{}", flags.contains(AccessFlags::SUPER))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"interface", &format!("{}", flags.contains(AccessFlags::INTERFACE))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"abstract", &format!("{}", flags.contains(AccessFlags::ABSTRACT))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synthetic", &format!("{}", flags.contains(AccessFlags::SYNTHETIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"annotation", &format!("{}", flags.contains(AccessFlags::ANNOTATION))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"enum", &format!("{}", flags.contains(AccessFlags::ENUM))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"module", &format!("{}", flags.contains(AccessFlags::MODULE))]);
 }
 
 fn insert_access_field(store: &TreeStore, iter: &TreeIter, access_flags: u16) {
+    let flags = fields::AccessFlags::from_bits(access_flags);
     let access = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"access_flags", &format!("{:#06X}", access_flags)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"public", &format!("{}", (access_flags & fields::AccessFlags::Public as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"private", &format!("{}", (access_flags & fields::AccessFlags::Private as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"protected", &format!("{}", (access_flags & fields::AccessFlags::Protected as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"static", &format!("{}", (access_flags & fields::AccessFlags::Static as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"final", &format!("{}", (access_flags & fields::AccessFlags::Final as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"volatile", &format!("{}", (access_flags & fields::AccessFlags::Volatile as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"transient", &format!("{}", (access_flags & fields::AccessFlags::Transient as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synthetic", &format!("{}", (access_flags & fields::AccessFlags::Synthetic as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"enum", &format!("{}", (access_flags & fields::AccessFlags::Enum as u16) != 0)]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"public", &format!("{}", flags.contains(fields::AccessFlags::PUBLIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"private", &format!("{}", flags.contains(fields::AccessFlags::PRIVATE))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"protected", &format!("{}", flags.contains(fields::AccessFlags::PROTECTED))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"static", &format!("{}", flags.contains(fields::AccessFlags::STATIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"final", &format!("{}", flags.contains(fields::AccessFlags::FINAL))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"volatile", &format!("{}", flags.contains(fields::AccessFlags::VOLATILE))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"transient", &format!("{}", flags.contains(fields::AccessFlags::TRANSIENT))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synthetic", &format!("{}", flags.contains(fields::AccessFlags::SYNTHETIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"enum", &format!("{}", flags.contains(fields::AccessFlags::ENUM))]);
 }
 
 fn insert_access_method(store: &TreeStore, iter: &TreeIter, access_flags: u16) {
+    let flags = methods::AccessFlags::from_bits(access_flags);
     let access = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"access_flags", &format!("{:#06X}", access_flags)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"public", &format!("{}", (access_flags & methods::AccessFlags::Public as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"private", &format!("{}", (access_flags & methods::AccessFlags::Private as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"protected", &format!("{}", (access_flags & methods::AccessFlags::Protected as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"static", &format!("{}", (access_flags & methods::AccessFlags::Static as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"final", &format!("{}", (access_flags & methods::AccessFlags::Final as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synchronized", &format!("{}", (access_flags & methods::AccessFlags::Synchronized as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"bridge", &format!("{}", (access_flags & methods::AccessFlags::Bridge as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"varargs", &format!("{}", (access_flags & methods::AccessFlags::Varargs as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"native", &format!("{}", (access_flags & methods::AccessFlags::Native as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"abstract", &format!("{}", (access_flags & methods::AccessFlags::Abstract as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"strict", &format!("{}", (access_flags & methods::AccessFlags::Strict as u16) != 0)]);
-    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synthetic", &format!("{}", (access_flags & methods::AccessFlags::Synthetic as u16) != 0)]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"public", &format!("{}", flags.contains(methods::AccessFlags::PUBLIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"private", &format!("{}", flags.contains(methods::AccessFlags::PRIVATE))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"protected", &format!("{}", flags.contains(methods::AccessFlags::PROTECTED))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"static", &format!("{}", flags.contains(methods::AccessFlags::STATIC))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"final", &format!("{}", flags.contains(methods::AccessFlags::FINAL))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synchronized", &format!("{}", flags.contains(methods::AccessFlags::SYNCHRONIZED))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"bridge", &format!("{}", flags.contains(methods::AccessFlags::BRIDGE))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"varargs", &format!("{}", flags.contains(methods::AccessFlags::VARARGS))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"native", &format!("{}", flags.contains(methods::AccessFlags::NATIVE))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"abstract", &format!("{}", flags.contains(methods::AccessFlags::ABSTRACT))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"strict", &format!("{}", flags.contains(methods::AccessFlags::STRICT))]);
+    insert_with_values(store, Some(&access), None, &[0, 1], &[&"synthetic", &format!("{}", flags.contains(methods::AccessFlags::SYNTHETIC))]);
 }
 
 fn insert_access_method_param(store: &TreeStore, iter: &TreeIter, access_flags: u16) {
@@ -342,7 +343,7 @@ fn insert_attributes(cp: &ConstantPool, store: &TreeStore, iter: &TreeIter, attr
             InnerClasses { classes } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"InnerClasses", &""]);
                 for class in classes {
-                    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"InnerClass", &if class.inner_name_index.as_u16() == 0 { "Anonymous class".to_owned() } else { get_name(cp, class.inner_name_index) }]);
+                    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"InnerClass", &if class.inner_name_index.as_u16() == 0 { "Anonymous class".to_owned() } else { cp.get_utf8(class.inner_name_index).unwrap_or_default() }]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"inner_java_class_index", &format!("{}", class.inner_class_info_index)]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"outer_java_class_index", &format!("{}", class.outer_class_info_index)]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"inner_name_index", &format!("{}", class.inner_name_index)]);
@@ -383,7 +384,7 @@ fn insert_attributes(cp: &ConstantPool, store: &TreeStore, iter: &TreeIter, attr
             LocalVariableTable { local_variable_table } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"LocalVariableTable", &""]);
                 for lv in local_variable_table {
-                    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"Entry", &format!("{} {}", get_name(cp, lv.descriptor_index), get_name(cp, lv.name_index))]);
+                    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"Entry", &format!("{} {}", cp.get_utf8(lv.descriptor_index).unwrap_or_default(), cp.get_utf8(lv.name_index).unwrap_or_default())]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"start_pc", &format!("{}", lv.start_pc)]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"length", &format!("{}", lv.length)]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"name_index", &format!("{}", lv.name_index)]);
@@ -407,42 +408,40 @@ fn insert_attributes(cp: &ConstantPool, store: &TreeStore, iter: &TreeIter, attr
             }
             RuntimeVisibleAnnotations { annotations } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"RuntimeVisibleAnnotations", &""]);
-                insert_annotations(store, &iter_b, annotations);
+                insert_annotations(cp, store, &iter_b, &annotations, true);
             }
             RuntimeInvisibleAnnotations { annotations } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"RuntimeInvisibleAnnotations", &""]);
-                insert_annotations(store, &iter_b, annotations);
+                insert_annotations(cp, store, &iter_b, &annotations, false);
             }
             RuntimeVisibleParameterAnnotations { parameter_annotations } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"RuntimeVisibleParameterAnnotations", &""]);
                 for (i, e) in parameter_annotations.iter().enumerate() {
                     let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&format!("{}", i), &""]);
-                    for a in e {
-                        insert_annotation(store, &iter_c, a);
-                    }
+                    insert_annotations(cp, store, &iter_c, e, true);
                 }
             }
             RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"RuntimeInvisibleParameterAnnotations", &""]);
                 for (i, e) in parameter_annotations.iter().enumerate() {
                     let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&format!("{}", i), &""]);
-                    for a in e {
-                        insert_annotation(store, &iter_c, a);
-                    }
+                    insert_annotations(cp, store, &iter_c, e, false);
                 }
             }
             RuntimeVisibleTypeAnnotations { annotations } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"RuntimeVisibleTypeAnnotations", &""]);
-                insert_type_annotations(store, &iter_b, annotations);
+                insert_type_annotations(cp, store, &iter_b, &annotations, true);
             }
             RuntimeInvisibleTypeAnnotations { annotations } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"RuntimeInvisibleTypeAnnotations", &""]);
-                insert_type_annotations(store, &iter_b, annotations);
+                insert_type_annotations(cp, store, &iter_b, &annotations, false);
             }
             AnnotationDefault { default_value } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"AnnotationDefault", &""]);
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"default_value", &""]);
-                insert_element_value(store, &iter_c, &default_value);
+                match annotations::resolve_element_value(&default_value, cp) {
+                    Ok(v) => insert_annotation_value(store, &iter_b, "default_value", &v),
+                    Err(e) => { insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"default_value", &format!("{}", e)]); }
+                }
             }
             BootstrapMethods { bootstrap_methods } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"BootstrapMethods", &""]);
@@ -458,111 +457,69 @@ fn insert_attributes(cp: &ConstantPool, store: &TreeStore, iter: &TreeIter, attr
             MethodParameters { parameters } => {
                 let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"MethodParameters", &""]);
                 for p in parameters {
-                    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"Entry", &get_name(cp, p.name_index)]);
+                    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"Entry", &cp.get_utf8(p.name_index).unwrap_or_default()]);
                     insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"name_index", &format!("{}", p.name_index)]);
                     insert_access_method_param(store, &iter_c, p.access_flags);
                 }
             }
+            Unknown { name_index, info } => {
+                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"Unknown", &cp.get_utf8(name_index).unwrap_or_default()]);
+                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"name_index", &format!("{}", name_index)]);
+                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"length", &format!("{}", info.len())]);
+            }
         }
     }
 }
 
-fn insert_type_annotations(store: &TreeStore, iter: &TreeIter, annotations: Vec<TypeAnnotation>) {
-    let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&"Annotations", &""]);
+fn insert_type_annotations(cp: &ConstantPool, store: &TreeStore, iter: &TreeIter, annotations: &[TypeAnnotation], visible: bool) {
     for a in annotations {
-        let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"TypeAnnotation", &""]);
-        match a.target_info {
-            TargetInfo::TypeParameterTarget { type_parameter_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"TypeParameterTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"type_parameter_index", &format!("{}", type_parameter_index)]);
-            }
-            TargetInfo::SupertypeTarget { supertype_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"SupertypeTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"supertype_index", &format!("{}", supertype_index)]);
-            }
-            TargetInfo::TypeParameterBoundTarget { type_parameter_index, bound_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"TypeParameterTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"type_parameter_index", &format!("{}", type_parameter_index)]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"bound_index", &format!("{}", bound_index)]);
-            }
-            TargetInfo::EmptyTarget => {
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"EmptyTarget", &""]);
-            }
-            TargetInfo::FormalParameterTarget { formal_parameter_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"FormalParameterTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"formal_parameter_index", &format!("{}", formal_parameter_index)]);
-            }
-            TargetInfo::ThrowsTarget { throws_type_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"ThrowsTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"throws_type_index", &format!("{}", throws_type_index)]);
-            }
-            TargetInfo::LocalVarTarget { table } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"LocalVarTarget", &""]);
-                let iter_d = insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"table", &""]);
-                for te in table {
-                    insert_with_values(store, Some(&iter_d), None, &[0, 1], &[&"start_pc", &format!("{}", te.start_pc)]);
-                    insert_with_values(store, Some(&iter_d), None, &[0, 1], &[&"length", &format!("{}", te.length)]);
-                    insert_with_values(store, Some(&iter_d), None, &[0, 1], &[&"index", &format!("{}", te.index)]);
+        match annotations::resolve_type_annotation(a, visible, cp) {
+            Ok(a) => {
+                let iter_a = insert_annotation(store, iter, &a.annotation);
+                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"target", &format!("{}", a.target)]);
+                if !a.path.is_empty() {
+                    let path: Vec<String> = a.path.iter().map(|step| format!("{}", step)).collect();
+                    insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"path", &path.join(", ")]);
                 }
             }
-            TargetInfo::CatchTarget { exception_table_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"CatchTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"exception_table_index", &format!("{}", exception_table_index)]);
-            }
-            TargetInfo::OffsetTarget { offset } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"OffsetTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"offset", &format!("{}", offset)]);
-            }
-            TargetInfo::TypeArgumentTarget { offset, type_argument_index } => {
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"TypeArgumentTarget", &""]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"offset", &format!("{}", offset)]);
-                insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"type_argument_index", &format!("{}", type_argument_index)]);
+            Err(e) => {
+                insert_with_values(store, Some(iter), None, &[0, 1], &[&"TypeAnnotation", &format!("{}", e)]);
             }
         }
     }
 }
 
-fn insert_annotations(store: &TreeStore, iter: &TreeIter, annotations: Vec<Annotation>) {
-    let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&"Annotations", &""]);
+fn insert_annotations(cp: &ConstantPool, store: &TreeStore, iter: &TreeIter, annotations: &[Annotation], visible: bool) {
     for a in annotations {
-        insert_annotation(store, &iter_a, &a);
+        match annotations::resolve_annotation(a, visible, cp) {
+            Ok(a) => { insert_annotation(store, iter, &a); }
+            Err(e) => { insert_with_values(store, Some(iter), None, &[0, 1], &[&"Annotation", &format!("{}", e)]); }
+        }
     }
 }
 
-fn insert_annotation(store: &TreeStore, iter: &TreeIter, annotation: &Annotation) {
-    let iter_b = insert_with_values(store, Some(iter), None, &[0, 1], &[&"Annotation", &""]);
-    insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"type_index", &format!("{}", annotation.type_index)]);
-    let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"element_value_pairs", &""]);
-    for pair in &annotation.element_value_pairs {
-        let iter_d = insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&"Pair", &""]);
-        insert_with_values(store, Some(&iter_d), None, &[0, 1], &[&"element_name_index", &format!("{}", pair.element_name_index)]);
-        insert_element_value(store, &iter_d, &pair.value);
+fn insert_annotation(store: &TreeStore, iter: &TreeIter, annotation: &ResolvedAnnotation) -> TreeIter {
+    let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&format!("@{}", annotation.type_name().replace('/', ".")), &format!("{}", annotation)]);
+    for (name, value) in &annotation.elements {
+        insert_annotation_value(store, &iter_a, name, value);
     }
+    iter_a
 }
 
-fn insert_element_value(store: &TreeStore, iter: &TreeIter, val: &ElementValue) {
-    match val {
-        ElementValue::ConstValueIndex(a) => {
-            insert_with_values(store, Some(iter), None, &[0, 1], &[&"ConstValueIndex", &format!("{}", a)]);
-        }
-        ElementValue::EnumConstValue { type_name_index, const_name_index } => {
-            let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&"EnumConstValue", &""]);
-            insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"type_name_index", &format!("{}", type_name_index)]);
-            insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"const_name_index", &format!("{}", const_name_index)]);
-        }
-        ElementValue::ClassInfoIndex(a) => {
-            insert_with_values(store, Some(iter), None, &[0, 1], &[&"ClassInfoIndex", &format!("{}", a)]);
-        }
-        ElementValue::AnnotationValue(a) => {
-            let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&"AnnotationValue", &""]);
-            insert_annotation(store, &iter_a, &a);
+fn insert_annotation_value(store: &TreeStore, iter: &TreeIter, name: &str, value: &AnnotationValue) {
+    let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&name, &format!("{}", value)]);
+    match value {
+        AnnotationValue::Annotation(a) => {
+            for (name, value) in &a.elements {
+                insert_annotation_value(store, &iter_a, name, value);
+            }
         }
-        ElementValue::ArrayValue(vs) => {
-            let iter_a = insert_with_values(store, Some(iter), None, &[0, 1], &[&"ArrayValue", &""]);
-            for v in vs {
-                insert_element_value(store, &iter_a, v);
+        AnnotationValue::Array(vs) => {
+            for (i, v) in vs.iter().enumerate() {
+                insert_annotation_value(store, &iter_a, &format!("{}", i), v);
             }
         }
+        _ => {}
     }
 }
 
@@ -584,7 +541,7 @@ fn insert_vti(store: &TreeStore, iter: &TreeIter, vti: VerificationTypeInfo, cp:
             insert_with_values(store, Some(&iter), None, &[0, 1], &[&"VerificationTypeInfo", &"UninitializedThis"]);
         }
         VerificationTypeInfo::Object { cpool_index } => {
-            let iter_a = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"VerificationTypeInfo", &format!("Object ({})", get_name(cp, cpool_index))]);
+            let iter_a = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"VerificationTypeInfo", &format!("Object ({})", cp.get_class_name(cpool_index).unwrap_or_default())]);
             insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&"cpool_index", &format!("{}", cpool_index)]);
         }
         VerificationTypeInfo::UninitializedVariable { offset } => {
@@ -603,918 +560,52 @@ fn insert_vti(store: &TreeStore, iter: &TreeIter, vti: VerificationTypeInfo, cp:
 fn insert_code(store: &TreeStore, iter: &TreeIter, code: Vec<Opcode>, cp: &ConstantPool, pos: &mut u32) {
     let iter_a = insert_with_values(store, Some(&iter), None, &[0, 1], &[&"Code", &""]);
     for op in code {
-        match op {
-            aaload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aaload", pos), &""]);
-                *pos += 1;
-            }
-            aastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aastore", pos), &""]);
-                *pos += 1;
-            }
-            aconst_null => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aconst_null", pos), &""]);
-                *pos += 1;
-            }
-            aload { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aload", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            aload_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aload_0", pos), &""]);
-                *pos += 1;
-            }
-            aload_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aload_1", pos), &""]);
-                *pos += 1;
-            }
-            aload_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aload_2", pos), &""]);
-                *pos += 1;
-            }
-            aload_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. aload_3", pos), &""]);
-                *pos += 1;
-            }
-            anewarray { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. anewarray", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            areturn => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. areturn", pos), &""]);
-                *pos += 1;
-            }
-            arraylength => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. arraylength", pos), &""]);
-                *pos += 1;
-            }
-            astore { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. astore", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            astore_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. astore_0", pos), &""]);
-                *pos += 1;
-            }
-            astore_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. astore_1", pos), &""]);
-                *pos += 1;
-            }
-            astore_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. astore_2", pos), &""]);
-                *pos += 1;
-            }
-            astore_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. astore_3", pos), &""]);
-                *pos += 1;
-            }
-            athrow => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. athrow", pos), &""]);
-                *pos += 1;
-            }
-            baload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. baload", pos), &""]);
-                *pos += 1;
-            }
-            bastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. bastore", pos), &""]);
-                *pos += 1;
-            }
-            bipush { val } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. bipush", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"val", &format!("{}", val)]);
-                *pos += 2;
-            }
-            breakpoint => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. breakpoint", pos), &""]);
-                *pos += 1;
-            }
-            caload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. caload", pos), &""]);
-                *pos += 1;
-            }
-            castore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. castore", pos), &""]);
-                *pos += 1;
-            }
-            checkcast { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. checkcast", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            d2f => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. d2f", pos), &""]);
-                *pos += 1;
-            }
-            d2i => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. d2i", pos), &""]);
-                *pos += 1;
-            }
-            d2l => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. d2l", pos), &""]);
-                *pos += 1;
-            }
-            dadd => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dadd", pos), &""]);
-                *pos += 1;
-            }
-            daload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. daload", pos), &""]);
-                *pos += 1;
-            }
-            dastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dastore", pos), &""]);
-                *pos += 1;
-            }
-            dcmpg => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dcmpg", pos), &""]);
-                *pos += 1;
-            }
-            dcmpl => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dcmpl", pos), &""]);
-                *pos += 1;
-            }
-            dconst_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dconst_0", pos), &""]);
-                *pos += 1;
-            }
-            dconst_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dconst_1", pos), &""]);
-                *pos += 1;
-            }
-            ddiv => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ddiv", pos), &""]);
-                *pos += 1;
-            }
-            dload { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dload", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            dload_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dload_0", pos), &""]);
-                *pos += 1;
-            }
-            dload_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dload_1", pos), &""]);
-                *pos += 1;
-            }
-            dload_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dload_2", pos), &""]);
-                *pos += 1;
-            }
-            dload_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dload_3", pos), &""]);
-                *pos += 1;
-            }
-            dmul => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dmul", pos), &""]);
-                *pos += 1;
-            }
-            dneg => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dneg", pos), &""]);
-                *pos += 1;
-            }
-            drem => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. drem", pos), &""]);
-                *pos += 1;
-            }
-            dreturn => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dreturn", pos), &""]);
-                *pos += 1;
-            }
-            dstore { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dstore", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            dstore_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dstore_0", pos), &""]);
-                *pos += 1;
-            }
-            dstore_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dstore_1", pos), &""]);
-                *pos += 1;
-            }
-            dstore_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dstore_2", pos), &""]);
-                *pos += 1;
-            }
-            dstore_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dstore_3", pos), &""]);
-                *pos += 1;
-            }
-            dsub => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dsub", pos), &""]);
-                *pos += 1;
-            }
-            dup => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dup", pos), &""]);
-                *pos += 1;
-            }
-            dup_x1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dup_x1", pos), &""]);
-                *pos += 1;
-            }
-            dup_x2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dup_x2", pos), &""]);
-                *pos += 1;
-            }
-            dup2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dup2", pos), &""]);
-                *pos += 1;
-            }
-            dup2_x1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dup2_x1", pos), &""]);
-                *pos += 1;
-            }
-            dup2_x2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. dup2_x2", pos), &""]);
-                *pos += 1;
-            }
-            f2d => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. f2d", pos), &""]);
-                *pos += 1;
-            }
-            f2i => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. f2i", pos), &""]);
-                *pos += 1;
-            }
-            f2l => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. f2l", pos), &""]);
-                *pos += 1;
-            }
-            fadd => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fadd", pos), &""]);
-                *pos += 1;
-            }
-            faload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. faload", pos), &""]);
-                *pos += 1;
-            }
-            fastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fastore", pos), &""]);
-                *pos += 1;
-            }
-            fcmpg => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fcmpg", pos), &""]);
-                *pos += 1;
-            }
-            fcmpl => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fcmpl", pos), &""]);
-                *pos += 1;
-            }
-            fconst_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fconst_0", pos), &""]);
-                *pos += 1;
-            }
-            fconst_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fconst_1", pos), &""]);
-                *pos += 1;
-            }
-            fconst_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fconst_2", pos), &""]);
-                *pos += 1;
-            }
-            fdiv => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fdiv", pos), &""]);
-                *pos += 1;
-            }
-            fload { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fload", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            fload_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fload_0", pos), &""]);
-                *pos += 1;
-            }
-            fload_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fload_1", pos), &""]);
-                *pos += 1;
-            }
-            fload_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fload_2", pos), &""]);
-                *pos += 1;
-            }
-            fload_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fload_3", pos), &""]);
-                *pos += 1;
-            }
-            fmul => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fmul", pos), &""]);
-                *pos += 1;
-            }
-            fneg => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fneg", pos), &""]);
-                *pos += 1;
-            }
-            frem => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. frem", pos), &""]);
-                *pos += 1;
-            }
-            freturn => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. freturn", pos), &""]);
-                *pos += 1;
-            }
-            fstore { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fstore", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            fstore_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fstore_0", pos), &""]);
-                *pos += 1;
-            }
-            fstore_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fstore_1", pos), &""]);
-                *pos += 1;
-            }
-            fstore_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fstore_2", pos), &""]);
-                *pos += 1;
-            }
-            fstore_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fstore_3", pos), &""]);
-                *pos += 1;
-            }
-            fsub => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. fsub", pos), &""]);
-                *pos += 1;
-            }
-            getfield { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. getfield", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            getstatic { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. getstatic", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            goto { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. goto", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            goto_w { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. goto_w", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 5;
-            }
-            i2b => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. i2b", pos), &""]);
-                *pos += 1;
-            }
-            i2c => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. i2c", pos), &""]);
-                *pos += 1;
-            }
-            i2d => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. i2d", pos), &""]);
-                *pos += 1;
-            }
-            i2f => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. i2f", pos), &""]);
-                *pos += 1;
-            }
-            i2l => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. i2l", pos), &""]);
-                *pos += 1;
-            }
-            i2s => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. i2s", pos), &""]);
-                *pos += 1;
-            }
-            iadd => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iadd", pos), &""]);
-                *pos += 1;
-            }
-            iaload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iaload", pos), &""]);
-                *pos += 1;
-            }
-            iand => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iand", pos), &""]);
-                *pos += 1;
-            }
-            iastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iastore", pos), &""]);
-                *pos += 1;
-            }
-            iconst_m1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_m1", pos), &""]);
-                *pos += 1;
-            }
-            iconst_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_0", pos), &""]);
-                *pos += 1;
-            }
-            iconst_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_1", pos), &""]);
-                *pos += 1;
-            }
-            iconst_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_2", pos), &""]);
-                *pos += 1;
-            }
-            iconst_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_3", pos), &""]);
-                *pos += 1;
-            }
-            iconst_4 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_4", pos), &""]);
-                *pos += 1;
-            }
-            iconst_5 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iconst_5", pos), &""]);
-                *pos += 1;
-            }
-            idiv => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. idiv", pos), &""]);
-                *pos += 1;
-            }
-            if_acmpeq { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_acmpeq", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_acmpne { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_acmpne", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_icmpeq { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_icmpeq", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_icmpge { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_icmpge", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_icmpgt { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_icmpgt", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_icmple { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_icmple", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_icmplt { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_icmplt", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            if_icmpne { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. if_icmpne", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifeq { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifeq", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifge { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifge", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifgt { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifgt", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifle { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifle", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            iflt { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iflt", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifne { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifne", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifnonnull { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifnonnull", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            ifnull { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ifnull", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            iinc { index, const_ } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iinc", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"const", &format!("{}", const_)]);
-                *pos += 3;
-            }
-            iload { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iload", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            iload_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iload_0", pos), &""]);
-                *pos += 1;
-            }
-            iload_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iload_1", pos), &""]);
-                *pos += 1;
-            }
-            iload_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iload_2", pos), &""]);
-                *pos += 1;
-            }
-            iload_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iload_3", pos), &""]);
-                *pos += 1;
-            }
-            imul => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. imul", pos), &""]);
-                *pos += 1;
-            }
-            ineg => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ineg", pos), &""]);
-                *pos += 1;
-            }
-            instanceof { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. instanceof", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            invokedynamic { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. invokedynamic", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            invokeinterface { index, count } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. invokeinterface", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"count", &format!("{}", count)]);
-                *pos += 4;
-            }
-            invokespecial { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. invokespecial", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            invokestatic { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. invokestatic", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            invokevirtual { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. invokevirtual", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            ior => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ior", pos), &""]);
-                *pos += 1;
-            }
-            irem => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. irem", pos), &""]);
-                *pos += 1;
-            }
-            ireturn => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ireturn", pos), &""]);
-                *pos += 1;
-            }
-            ishl => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ishl", pos), &""]);
-                *pos += 1;
-            }
-            ishr => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ishr", pos), &""]);
-                *pos += 1;
-            }
-            istore { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. istore", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            istore_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. istore_0", pos), &""]);
-                *pos += 1;
-            }
-            istore_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. istore_1", pos), &""]);
-                *pos += 1;
-            }
-            istore_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. istore_2", pos), &""]);
-                *pos += 1;
-            }
-            istore_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. istore_3", pos), &""]);
-                *pos += 1;
-            }
-            isub => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. isub", pos), &""]);
-                *pos += 1;
-            }
-            iushr => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. iushr", pos), &""]);
-                *pos += 1;
-            }
-            ixor => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ixor", pos), &""]);
-                *pos += 1;
-            }
-            jsr { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. jsr", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 3;
-            }
-            jsr_w { branch } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. jsr_w", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"branch", &format!("{} ({})", branch, branch as i32 + *pos as i32)]);
-                *pos += 5;
-            }
-            l2d => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. l2d", pos), &""]);
-                *pos += 1;
-            }
-            l2f => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. l2f", pos), &""]);
-                *pos += 1;
-            }
-            l2i => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. l2i", pos), &""]);
-                *pos += 1;
-            }
-            ladd => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ladd", pos), &""]);
-                *pos += 1;
-            }
-            laload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. laload", pos), &""]);
-                *pos += 1;
-            }
-            land => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. land", pos), &""]);
-                *pos += 1;
-            }
-            lastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lastore", pos), &""]);
-                *pos += 1;
-            }
-            lcmp => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lcmp", pos), &""]);
-                *pos += 1;
-            }
-            lconst_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lconst_0", pos), &""]);
-                *pos += 1;
-            }
-            lconst_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lconst_1", pos), &""]);
-                *pos += 1;
-            }
-            ldc { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ldc", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            ldc_w { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ldc_w", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            ldc2_w { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ldc2_w", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            ldiv => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ldiv", pos), &""]);
-                *pos += 1;
-            }
-            lload_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lload_0", pos), &""]);
-                *pos += 1;
-            }
-            lload_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lload_1", pos), &""]);
-                *pos += 1;
-            }
-            lload_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lload_2", pos), &""]);
-                *pos += 1;
-            }
-            lload_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lload_3", pos), &""]);
-                *pos += 1;
-            }
-            lload { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lload", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            lmul => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lmul", pos), &""]);
-                *pos += 1;
-            }
-            lneg => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lneg", pos), &""]);
-                *pos += 1;
-            }
-            lookupswitch { default, match_offset_pairs, .. } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lookupswitch", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"default", &format!("{} ({})", default, default as u32 + *pos)]);
-                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"match_offset_pairs", &""]);
-                for pair in &match_offset_pairs {
-                    insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&format!("{}", pair.0), &format!("{} ({})", pair.1, pair.1 as u32 + *pos)]);
+        let info = op.info();
+        let operands: Vec<(&(&str, OperandKind), i64)> = info.operands.iter().zip(op.operands()).collect();
+        //instructions are labelled with the constant or array type they use
+        let label = match operands.first() {
+            Some(((_, OperandKind::Constant), index)) => cp.describe(CPIndex::from(*index as u16)).unwrap_or_default(),
+            Some(((_, OperandKind::ArrayType), atype)) => array_type_name(*atype).to_owned(),
+            _ => String::new()
+        };
+        let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. {}", pos, info.mnemonic), &label]);
+        for ((name, kind), value) in operands {
+            let text = match kind {
+                OperandKind::Branch => format!("{} ({})", value, value + *pos as i64),
+                OperandKind::Opcode => OpcodeInfo::from_byte(value as u8).map_or_else(|| value.to_string(), |i| i.mnemonic.to_owned()),
+                _ => value.to_string()
+            };
+            insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&name.trim_end_matches('_'), &text]);
+        }
+        match &op {
+            tableswitch { low, jump_offsets, .. } => {
+                let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"jump_offsets", &""]);
+                for (i, offset) in jump_offsets.iter().enumerate() {
+                    insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&format!("{}", *low as i64 + i as i64), &format!("{} ({})", offset, *offset + *pos as i32)]);
                 }
-                *pos += 5 + (&match_offset_pairs.len() * 8) as u32;
-            }
-            lor => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lor", pos), &""]);
-                *pos += 1;
-            }
-            lrem => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lrem", pos), &""]);
-                *pos += 1;
-            }
-            lreturn => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lreturn", pos), &""]);
-                *pos += 1;
-            }
-            lshl => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lshl", pos), &""]);
-                *pos += 1;
-            }
-            lshr => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lshr", pos), &""]);
-                *pos += 1;
-            }
-            lstore { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lstore", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            lstore_0 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lstore_0", pos), &""]);
-                *pos += 1;
-            }
-            lstore_1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lstore_1", pos), &""]);
-                *pos += 1;
             }
-            lstore_2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lstore_2", pos), &""]);
-                *pos += 1;
-            }
-            lstore_3 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lstore_3", pos), &""]);
-                *pos += 1;
-            }
-            lsub => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lsub", pos), &""]);
-                *pos += 1;
-            }
-            lushr => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lushr", pos), &""]);
-                *pos += 1;
-            }
-            lxor => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. lxor", pos), &""]);
-                *pos += 1;
-            }
-            monitorenter => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. monitorenter", pos), &""]);
-                *pos += 1;
-            }
-            monitorexit => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. monitorexit", pos), &""]);
-                *pos += 1;
-            }
-            multianewarray { index, dimensions } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. multianewarray", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"dimensions", &format!("{}", dimensions)]);
-                *pos += 4;
-            }
-            new { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. new", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            newarray { atype } => {
-                let type_ = match atype {
-                    4 => "T_BOOLEAN",
-                    5 => "T_CHAR",
-                    6 => "T_FLOAT",
-                    7 => "T_DOUBLE",
-                    8 => "T_BYTE",
-                    9 => "T_SHORT",
-                    10 => "T_INT",
-                    11 => "T_LONG",
-                    _ => "Invalid type code"
-                };
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. newarray", pos), &type_]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"atype", &format!("{}", atype)]);
-                *pos += 2;
-            }
-            nop => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. nop", pos), &""]);
-                *pos += 1;
-            }
-            pop => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. pop", pos), &""]);
-                *pos += 1;
-            }
-            pop2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. pop2", pos), &""]);
-                *pos += 1;
-            }
-            putfield { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. putfield", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            putstatic { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. putstatic", pos), &get_name(cp, index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 3;
-            }
-            ret { index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. ret", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 2;
-            }
-            return_ => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. return", pos), &""]);
-                *pos += 1;
-            }
-            saload => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. saload", pos), &""]);
-                *pos += 1;
-            }
-            sastore => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. sastore", pos), &""]);
-                *pos += 1;
-            }
-            sipush { val } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. sipush", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"val", &format!("{}", val)]);
-                *pos += 3;
-            }
-            swap => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. swap", pos), &""]);
-                *pos += 1;
-            }
-            tableswitch { default, low, high, jump_offsets, .. } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. tableswitch", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"default", &format!("{} ({})", default, default as u32 + *pos)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"low", &format!("{}", low)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"high", &format!("{}", high)]);
+            lookupswitch { match_offset_pairs, .. } => {
                 let iter_c = insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"match_offset_pairs", &""]);
-                for offset in &jump_offsets {
-                    insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&format!("{} ({})", offset, *offset + *pos as i32), &""]);
+                for pair in match_offset_pairs {
+                    insert_with_values(store, Some(&iter_c), None, &[0, 1], &[&format!("{}", pair.0), &format!("{} ({})", pair.1, pair.1 + *pos as i32)]);
                 }
-                *pos += 13 + (jump_offsets.len() * 8) as u32;
-            }
-            wide { opcode, index } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. wide", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"opcode", &format!("{}", opcode)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                *pos += 4;
-            }
-            wide_iinc { index, const_ } => {
-                let iter_b = insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. wide", pos), &""]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"opcode", &format!("{}", 0x84)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"index", &format!("{}", index)]);
-                insert_with_values(store, Some(&iter_b), None, &[0, 1], &[&"const", &format!("{}", const_)]);
-                *pos += 6;
-            }
-            reserved => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. reserved", pos), &""]);
-                *pos += 1;
-            }
-            impdep1 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. impdep1", pos), &""]);
-                *pos += 1;
-            }
-            impdep2 => {
-                insert_with_values(store, Some(&iter_a), None, &[0, 1], &[&format!("{}. impdep2", pos), &""]);
-                *pos += 1;
             }
+            _ => {}
         }
+        *pos += op.len_bytes() as u32;
+    }
+}
+
+fn array_type_name(atype: i64) -> &'static str {
+    match atype {
+        4 => "T_BOOLEAN",
+        5 => "T_CHAR",
+        6 => "T_FLOAT",
+        7 => "T_DOUBLE",
+        8 => "T_BYTE",
+        9 => "T_SHORT",
+        10 => "T_INT",
+        11 => "T_LONG",
+        _ => "Invalid type code"
     }
 }
\ No newline at end of file
@@ -0,0 +1,21 @@
+//! Feeds arbitrary bytes to the class file parsers.
+//! Run with `cargo +nightly fuzz run parse` from the `java_class` directory.
+#![no_main]
+
+use java_class::check::check_format;
+use java_class::class::JavaClass;
+use java_class::visitor::ClassReader;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    //both readers must reject bad input with an error rather than a panic
+    let _ = ClassReader::new(data.to_vec());
+    if let Ok(class) = JavaClass::parse(data.to_vec()) {
+        let _ = check_format(&class);
+        let _ = class.get_name();
+        //whatever was read must survive being written back out
+        if let Err(e) = JavaClass::parse(class.to_bytes()) {
+            panic!("written class failed to parse again: {}", e);
+        }
+    }
+});
@@ -0,0 +1,545 @@
+use attributes::*;
+use attributes::Attribute::*;
+use check::is_field_descriptor;
+use class::JavaClass;
+use cp::ConstantPool;
+use cp_info::CPInfo;
+use descriptor::FieldType;
+use fields::FieldInfo;
+use malformed;
+use methods::MethodInfo;
+use std::fmt;
+use std::io;
+
+/// An annotation with its type and element values resolved against the constant pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedAnnotation {
+    /// the annotation type as a field descriptor, e.g. `Ljava/lang/Deprecated;`
+    pub descriptor: String,
+    /// whether the annotation came from a `RuntimeVisible*` attribute
+    pub visible: bool,
+    /// element values in class file order; elements left at their default are absent
+    pub elements: Vec<(String, AnnotationValue)>,
+}
+
+impl ResolvedAnnotation {
+    /// The binary name of the annotation type, e.g. `java/lang/Deprecated`
+    pub fn type_name(&self) -> &str {
+        &self.descriptor[1..self.descriptor.len() - 1]
+    }
+
+    /// Whether this annotation is of type `type_name`, given as a binary name,
+    /// a dotted name or a descriptor
+    pub fn is(&self, type_name: &str) -> bool {
+        let name = if type_name.starts_with('L') && type_name.ends_with(';') {
+            &type_name[1..type_name.len() - 1]
+        } else {
+            type_name
+        };
+        name.replace('.', "/") == self.type_name()
+    }
+
+    /// The value of an element, if it is present in the class file
+    pub fn get(&self, element: &str) -> Option<&AnnotationValue> {
+        self.elements.iter().find(|(name, _)| name == element).map(|(_, v)| v)
+    }
+}
+
+impl fmt::Display for ResolvedAnnotation {
+    /// Formats the annotation as Java source, e.g. `@java.lang.annotation.Retention(java.lang.annotation.RetentionPolicy.RUNTIME)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{}", self.type_name().replace('/', "."))?;
+        match self.elements.as_slice() {
+            [] => Ok(()),
+            [(name, value)] if name == "value" => write!(f, "({})", value),
+            elements => {
+                write!(f, "(")?;
+                for (i, (name, value)) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}={}", name, value)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// A resolved annotation element value
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationValue {
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    /// an enum constant; `descriptor` is the enum type's field descriptor
+    Enum { descriptor: String, name: String },
+    /// a class literal, as a return descriptor (`V` for `void.class`)
+    Class(String),
+    Annotation(ResolvedAnnotation),
+    Array(Vec<AnnotationValue>),
+}
+
+impl AnnotationValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AnnotationValue::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            AnnotationValue::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    /// The value of a `byte`, `char`, `short`, `int` or `long` element
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            AnnotationValue::Byte(v) => Some(*v as i64),
+            AnnotationValue::Char(v) => Some(*v as i64),
+            AnnotationValue::Short(v) => Some(*v as i64),
+            AnnotationValue::Int(v) => Some(*v as i64),
+            AnnotationValue::Long(v) => Some(*v),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[AnnotationValue]> {
+        match self {
+            AnnotationValue::Array(vs) => Some(vs),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for AnnotationValue {
+    /// Formats the value as a Java expression
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnnotationValue::Byte(v) => write!(f, "{}", v),
+            AnnotationValue::Char(v) => {
+                write!(f, "'")?;
+                write_escaped(f, *v, '\'')?;
+                write!(f, "'")
+            }
+            AnnotationValue::Short(v) => write!(f, "{}", v),
+            AnnotationValue::Int(v) => write!(f, "{}", v),
+            AnnotationValue::Long(v) => write!(f, "{}L", v),
+            AnnotationValue::Float(v) if v.is_nan() => write!(f, "Float.NaN"),
+            AnnotationValue::Float(v) if v.is_infinite() => write!(f, "Float.{}_INFINITY", if *v > 0.0 { "POSITIVE" } else { "NEGATIVE" }),
+            AnnotationValue::Float(v) => write!(f, "{:?}f", v),
+            AnnotationValue::Double(v) if v.is_nan() => write!(f, "Double.NaN"),
+            AnnotationValue::Double(v) if v.is_infinite() => write!(f, "Double.{}_INFINITY", if *v > 0.0 { "POSITIVE" } else { "NEGATIVE" }),
+            AnnotationValue::Double(v) => write!(f, "{:?}", v),
+            AnnotationValue::Boolean(v) => write!(f, "{}", v),
+            AnnotationValue::String(s) => {
+                write!(f, "\"")?;
+                for c in s.encode_utf16() {
+                    write_escaped(f, c, '"')?;
+                }
+                write!(f, "\"")
+            }
+            AnnotationValue::Enum { descriptor, name } => write!(f, "{}.{}", java_type(descriptor), name),
+            AnnotationValue::Class(descriptor) => write!(f, "{}.class", java_type(descriptor)),
+            AnnotationValue::Annotation(a) => write!(f, "{}", a),
+            AnnotationValue::Array(vs) => {
+                write!(f, "{{")?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// A type annotation together with the type it annotates
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedTypeAnnotation {
+    pub target: TypeTarget,
+    /// the path from the target type to the annotated part of it; empty for the target type itself
+    pub path: Vec<TypePathStep>,
+    pub annotation: ResolvedAnnotation,
+}
+
+impl fmt::Display for ResolvedTypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on {}", self.annotation, self.target)?;
+        if !self.path.is_empty() {
+            write!(f, " at [")?;
+            for (i, step) in self.path.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", step)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+/// The type a type annotation applies to, one variant per `target_type` value (§4.7.20.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeTarget {
+    ClassTypeParameter(u8),
+    MethodTypeParameter(u8),
+    Superclass,
+    /// an index into the class's `interfaces`
+    Interface(u16),
+    ClassTypeParameterBound { type_parameter: u8, bound: u8 },
+    MethodTypeParameterBound { type_parameter: u8, bound: u8 },
+    Field,
+    Return,
+    Receiver,
+    FormalParameter(u8),
+    /// an index into the method's `Exceptions` attribute
+    Throws(u16),
+    LocalVariable(Vec<LocalVarTagetTableEntry>),
+    ResourceVariable(Vec<LocalVarTagetTableEntry>),
+    /// an index into the `Code` attribute's exception table
+    ExceptionParameter(u16),
+    /// the following variants hold the pc of the instruction
+    InstanceOf(u16),
+    New(u16),
+    ConstructorReference(u16),
+    MethodReference(u16),
+    Cast { offset: u16, type_argument: u8 },
+    ConstructorInvocationTypeArgument { offset: u16, type_argument: u8 },
+    MethodInvocationTypeArgument { offset: u16, type_argument: u8 },
+    ConstructorReferenceTypeArgument { offset: u16, type_argument: u8 },
+    MethodReferenceTypeArgument { offset: u16, type_argument: u8 },
+}
+
+impl fmt::Display for TypeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::TypeTarget::*;
+        match self {
+            ClassTypeParameter(i) => write!(f, "class type parameter {}", i),
+            MethodTypeParameter(i) => write!(f, "method type parameter {}", i),
+            Superclass => write!(f, "superclass"),
+            Interface(i) => write!(f, "interface {}", i),
+            ClassTypeParameterBound { type_parameter, bound } => write!(f, "bound {} of class type parameter {}", bound, type_parameter),
+            MethodTypeParameterBound { type_parameter, bound } => write!(f, "bound {} of method type parameter {}", bound, type_parameter),
+            Field => write!(f, "field"),
+            Return => write!(f, "return type"),
+            Receiver => write!(f, "receiver"),
+            FormalParameter(i) => write!(f, "parameter {}", i),
+            Throws(i) => write!(f, "throws {}", i),
+            LocalVariable(table) => write!(f, "local variable {}", local_variable_slots(table)),
+            ResourceVariable(table) => write!(f, "resource variable {}", local_variable_slots(table)),
+            ExceptionParameter(i) => write!(f, "exception parameter {}", i),
+            InstanceOf(pc) => write!(f, "instanceof at pc {}", pc),
+            New(pc) => write!(f, "new at pc {}", pc),
+            ConstructorReference(pc) => write!(f, "constructor reference at pc {}", pc),
+            MethodReference(pc) => write!(f, "method reference at pc {}", pc),
+            Cast { offset, type_argument } => write!(f, "cast {} at pc {}", type_argument, offset),
+            ConstructorInvocationTypeArgument { offset, type_argument } => write!(f, "constructor invocation type argument {} at pc {}", type_argument, offset),
+            MethodInvocationTypeArgument { offset, type_argument } => write!(f, "method invocation type argument {} at pc {}", type_argument, offset),
+            ConstructorReferenceTypeArgument { offset, type_argument } => write!(f, "constructor reference type argument {} at pc {}", type_argument, offset),
+            MethodReferenceTypeArgument { offset, type_argument } => write!(f, "method reference type argument {} at pc {}", type_argument, offset),
+        }
+    }
+}
+
+/// One step of a `type_path` (§4.7.20.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypePathStep {
+    /// into the element type of an array type
+    Array,
+    /// into a nested type
+    Nested,
+    /// onto the bound of a wildcard type argument
+    WildcardBound,
+    /// onto a type argument of a parameterized type
+    TypeArgument(u8),
+}
+
+impl fmt::Display for TypePathStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypePathStep::Array => write!(f, "array"),
+            TypePathStep::Nested => write!(f, "nested"),
+            TypePathStep::WildcardBound => write!(f, "wildcard bound"),
+            TypePathStep::TypeArgument(i) => write!(f, "type argument {}", i),
+        }
+    }
+}
+
+/// Resolves the `Runtime(In)VisibleAnnotations` in `attributes`, visible annotations first
+pub fn annotations(attributes: &[Attribute], cp: &ConstantPool) -> io::Result<Vec<ResolvedAnnotation>> {
+    let mut ans = Vec::new();
+    for visible in [true, false] {
+        for attr in attributes {
+            match attr {
+                RuntimeVisibleAnnotations { annotations } if visible => {
+                    for a in annotations {
+                        ans.push(resolve_annotation(a, true, cp)?);
+                    }
+                }
+                RuntimeInvisibleAnnotations { annotations } if !visible => {
+                    for a in annotations {
+                        ans.push(resolve_annotation(a, false, cp)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(ans)
+}
+
+/// Resolves the `Runtime(In)VisibleParameterAnnotations` in `attributes`, one list per parameter
+pub fn parameter_annotations(attributes: &[Attribute], cp: &ConstantPool) -> io::Result<Vec<Vec<ResolvedAnnotation>>> {
+    let mut ans: Vec<Vec<ResolvedAnnotation>> = Vec::new();
+    for visible in [true, false] {
+        for attr in attributes {
+            let parameters = match attr {
+                RuntimeVisibleParameterAnnotations { parameter_annotations } if visible => parameter_annotations,
+                RuntimeInvisibleParameterAnnotations { parameter_annotations } if !visible => parameter_annotations,
+                _ => continue
+            };
+            if ans.len() < parameters.len() {
+                ans.resize(parameters.len(), Vec::new());
+            }
+            for (i, annotations) in parameters.iter().enumerate() {
+                for a in annotations {
+                    ans[i].push(resolve_annotation(a, visible, cp)?);
+                }
+            }
+        }
+    }
+    Ok(ans)
+}
+
+/// Resolves the `Runtime(In)VisibleTypeAnnotations` in `attributes`,
+/// including those nested in a `Code` attribute
+pub fn type_annotations(attributes: &[Attribute], cp: &ConstantPool) -> io::Result<Vec<ResolvedTypeAnnotation>> {
+    let mut ans = Vec::new();
+    collect_type_annotations(attributes, cp, &mut ans)?;
+    Ok(ans)
+}
+
+fn collect_type_annotations(attributes: &[Attribute], cp: &ConstantPool, ans: &mut Vec<ResolvedTypeAnnotation>) -> io::Result<()> {
+    for attr in attributes {
+        match attr {
+            RuntimeVisibleTypeAnnotations { annotations } => {
+                for a in annotations {
+                    ans.push(resolve_type_annotation(a, true, cp)?);
+                }
+            }
+            RuntimeInvisibleTypeAnnotations { annotations } => {
+                for a in annotations {
+                    ans.push(resolve_type_annotation(a, false, cp)?);
+                }
+            }
+            Code { attributes, .. } => collect_type_annotations(attributes, cp, ans)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the `AnnotationDefault` in `attributes`, if there is one
+pub fn annotation_default(attributes: &[Attribute], cp: &ConstantPool) -> io::Result<Option<AnnotationValue>> {
+    for attr in attributes {
+        if let AnnotationDefault { default_value } = attr {
+            return resolve_element_value(default_value, cp).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the annotation of type `type_name`, given as a binary name, a dotted name or a descriptor
+pub fn find<'a>(annotations: &'a [ResolvedAnnotation], type_name: &str) -> Option<&'a ResolvedAnnotation> {
+    annotations.iter().find(|a| a.is(type_name))
+}
+
+pub fn resolve_annotation(annotation: &Annotation, visible: bool, cp: &ConstantPool) -> io::Result<ResolvedAnnotation> {
+    let descriptor = cp.utf8(annotation.type_index)?;
+    if !descriptor.starts_with('L') || !is_field_descriptor(&descriptor) {
+        return Err(malformed("Annotation type is not a class descriptor"));
+    }
+    let mut elements = Vec::with_capacity(annotation.element_value_pairs.len());
+    for pair in &annotation.element_value_pairs {
+        elements.push((cp.utf8(pair.element_name_index)?, resolve_element_value(&pair.value, cp)?));
+    }
+    Ok(ResolvedAnnotation { descriptor, visible, elements })
+}
+
+pub fn resolve_element_value(value: &ElementValue, cp: &ConstantPool) -> io::Result<AnnotationValue> {
+    Ok(match value {
+        ElementValue::ConstValueIndex { tag: b's', index } => AnnotationValue::String(cp.utf8(*index)?),
+        ElementValue::ConstValueIndex { tag, index } => match (*tag, cp.get(*index)) {
+            (b'B', Some(CPInfo::Integer { bytes })) => AnnotationValue::Byte(*bytes as i8),
+            (b'C', Some(CPInfo::Integer { bytes })) => AnnotationValue::Char(*bytes as u16),
+            (b'S', Some(CPInfo::Integer { bytes })) => AnnotationValue::Short(*bytes as i16),
+            (b'I', Some(CPInfo::Integer { bytes })) => AnnotationValue::Int(*bytes as i32),
+            (b'Z', Some(CPInfo::Integer { bytes })) => AnnotationValue::Boolean(*bytes != 0),
+            (b'J', Some(CPInfo::Long { bytes })) => AnnotationValue::Long(*bytes as i64),
+            (b'F', Some(CPInfo::Float { bytes })) => AnnotationValue::Float(f32::from_bits(*bytes)),
+            (b'D', Some(CPInfo::Double { bytes })) => AnnotationValue::Double(f64::from_bits(*bytes)),
+            _ => return Err(malformed("Element value does not match its tag"))
+        },
+        ElementValue::EnumConstValue { type_name_index, const_name_index } => {
+            let descriptor = cp.utf8(*type_name_index)?;
+            if !descriptor.starts_with('L') || !is_field_descriptor(&descriptor) {
+                return Err(malformed("Enum element type is not a class descriptor"));
+            }
+            AnnotationValue::Enum { descriptor, name: cp.utf8(*const_name_index)? }
+        }
+        ElementValue::ClassInfoIndex(index) => {
+            let descriptor = cp.utf8(*index)?;
+            if descriptor != "V" && !is_field_descriptor(&descriptor) {
+                return Err(malformed("Class element is not a return descriptor"));
+            }
+            AnnotationValue::Class(descriptor)
+        }
+        ElementValue::AnnotationValue(a) => AnnotationValue::Annotation(resolve_annotation(a, true, cp)?),
+        ElementValue::ArrayValue(vs) => {
+            let mut ans = Vec::with_capacity(vs.len());
+            for v in vs {
+                ans.push(resolve_element_value(v, cp)?);
+            }
+            AnnotationValue::Array(ans)
+        }
+    })
+}
+
+pub fn resolve_type_annotation(annotation: &TypeAnnotation, visible: bool, cp: &ConstantPool) -> io::Result<ResolvedTypeAnnotation> {
+    use self::TypeTarget::*;
+    let target = match (annotation.target_type, &annotation.target_info) {
+        (0x00, TargetInfo::TypeParameterTarget { type_parameter_index }) => ClassTypeParameter(*type_parameter_index),
+        (0x01, TargetInfo::TypeParameterTarget { type_parameter_index }) => MethodTypeParameter(*type_parameter_index),
+        (0x10, TargetInfo::SupertypeTarget { supertype_index: 65535 }) => Superclass,
+        (0x10, TargetInfo::SupertypeTarget { supertype_index }) => Interface(*supertype_index),
+        (0x11, TargetInfo::TypeParameterBoundTarget { type_parameter_index, bound_index }) => ClassTypeParameterBound { type_parameter: *type_parameter_index, bound: *bound_index },
+        (0x12, TargetInfo::TypeParameterBoundTarget { type_parameter_index, bound_index }) => MethodTypeParameterBound { type_parameter: *type_parameter_index, bound: *bound_index },
+        (0x13, TargetInfo::EmptyTarget) => Field,
+        (0x14, TargetInfo::EmptyTarget) => Return,
+        (0x15, TargetInfo::EmptyTarget) => Receiver,
+        (0x16, TargetInfo::FormalParameterTarget { formal_parameter_index }) => FormalParameter(*formal_parameter_index),
+        (0x17, TargetInfo::ThrowsTarget { throws_type_index }) => Throws(*throws_type_index),
+        (0x40, TargetInfo::LocalVarTarget { table }) => LocalVariable(table.clone()),
+        (0x41, TargetInfo::LocalVarTarget { table }) => ResourceVariable(table.clone()),
+        (0x42, TargetInfo::CatchTarget { exception_table_index }) => ExceptionParameter(*exception_table_index),
+        (0x43, TargetInfo::OffsetTarget { offset }) => InstanceOf(*offset),
+        (0x44, TargetInfo::OffsetTarget { offset }) => New(*offset),
+        (0x45, TargetInfo::OffsetTarget { offset }) => ConstructorReference(*offset),
+        (0x46, TargetInfo::OffsetTarget { offset }) => MethodReference(*offset),
+        (0x47, TargetInfo::TypeArgumentTarget { offset, type_argument_index }) => Cast { offset: *offset, type_argument: *type_argument_index },
+        (0x48, TargetInfo::TypeArgumentTarget { offset, type_argument_index }) => ConstructorInvocationTypeArgument { offset: *offset, type_argument: *type_argument_index },
+        (0x49, TargetInfo::TypeArgumentTarget { offset, type_argument_index }) => MethodInvocationTypeArgument { offset: *offset, type_argument: *type_argument_index },
+        (0x4A, TargetInfo::TypeArgumentTarget { offset, type_argument_index }) => ConstructorReferenceTypeArgument { offset: *offset, type_argument: *type_argument_index },
+        (0x4B, TargetInfo::TypeArgumentTarget { offset, type_argument_index }) => MethodReferenceTypeArgument { offset: *offset, type_argument: *type_argument_index },
+        _ => return Err(malformed("Type annotation target_info does not match its target_type"))
+    };
+    let mut path = Vec::with_capacity(annotation.target_path.path.len());
+    for entry in &annotation.target_path.path {
+        path.push(match (entry.type_path_kind, entry.type_argument_index) {
+            (0, 0) => TypePathStep::Array,
+            (1, 0) => TypePathStep::Nested,
+            (2, 0) => TypePathStep::WildcardBound,
+            (3, i) => TypePathStep::TypeArgument(i),
+            _ => return Err(malformed("Invalid type_path entry"))
+        });
+    }
+    let annotation = resolve_annotation(&Annotation {
+        type_index: annotation.type_index,
+        element_value_pairs: annotation.element_value_pairs.clone(),
+    }, visible, cp)?;
+    Ok(ResolvedTypeAnnotation { target, path, annotation })
+}
+
+impl JavaClass {
+    /// The class's annotations, resolved
+    pub fn annotations(&self) -> io::Result<Vec<ResolvedAnnotation>> {
+        annotations(&self.attributes, &self.constant_pool)
+    }
+
+    /// The class's annotation of type `type_name`, if it has one
+    pub fn annotation(&self, type_name: &str) -> io::Result<Option<ResolvedAnnotation>> {
+        Ok(self.annotations()?.into_iter().find(|a| a.is(type_name)))
+    }
+
+    /// The type annotations on the class declaration
+    pub fn type_annotations(&self) -> io::Result<Vec<ResolvedTypeAnnotation>> {
+        type_annotations(&self.attributes, &self.constant_pool)
+    }
+}
+
+impl FieldInfo {
+    pub fn annotations(&self, cp: &ConstantPool) -> io::Result<Vec<ResolvedAnnotation>> {
+        annotations(&self.attributes, cp)
+    }
+
+    pub fn annotation(&self, cp: &ConstantPool, type_name: &str) -> io::Result<Option<ResolvedAnnotation>> {
+        Ok(self.annotations(cp)?.into_iter().find(|a| a.is(type_name)))
+    }
+
+    pub fn type_annotations(&self, cp: &ConstantPool) -> io::Result<Vec<ResolvedTypeAnnotation>> {
+        type_annotations(&self.attributes, cp)
+    }
+}
+
+impl MethodInfo {
+    pub fn annotations(&self, cp: &ConstantPool) -> io::Result<Vec<ResolvedAnnotation>> {
+        annotations(&self.attributes, cp)
+    }
+
+    pub fn annotation(&self, cp: &ConstantPool, type_name: &str) -> io::Result<Option<ResolvedAnnotation>> {
+        Ok(self.annotations(cp)?.into_iter().find(|a| a.is(type_name)))
+    }
+
+    pub fn parameter_annotations(&self, cp: &ConstantPool) -> io::Result<Vec<Vec<ResolvedAnnotation>>> {
+        parameter_annotations(&self.attributes, cp)
+    }
+
+    /// The type annotations on the method signature and in its code
+    pub fn type_annotations(&self, cp: &ConstantPool) -> io::Result<Vec<ResolvedTypeAnnotation>> {
+        type_annotations(&self.attributes, cp)
+    }
+
+    /// The default value of an annotation interface element
+    pub fn annotation_default(&self, cp: &ConstantPool) -> io::Result<Option<AnnotationValue>> {
+        annotation_default(&self.attributes, cp)
+    }
+}
+
+/// Converts a return descriptor to a Java type, e.g. `[Ljava/lang/String;` to `java.lang.String[]`
+fn java_type(descriptor: &str) -> String {
+    match descriptor {
+        "V" => "void".to_owned(),
+        d => FieldType::parse(d).map(|t| t.java_name()).unwrap_or_else(|_| d.to_owned())
+    }
+}
+
+fn local_variable_slots(table: &[LocalVarTagetTableEntry]) -> String {
+    table.iter().map(|e| format!("{}", e.index)).collect::<Vec<_>>().join("/")
+}
+
+/// Writes a UTF-16 code unit the way it would appear in a Java literal quoted with `quote`
+fn write_escaped(f: &mut fmt::Formatter, c: u16, quote: char) -> fmt::Result {
+    match ::std::char::from_u32(c as u32) {
+        Some('\\') => write!(f, "\\\\"),
+        Some('\n') => write!(f, "\\n"),
+        Some('\r') => write!(f, "\\r"),
+        Some('\t') => write!(f, "\\t"),
+        Some(ch) if ch == quote => write!(f, "\\{}", ch),
+        Some(ch) if !ch.is_control() => write!(f, "{}", ch),
+        _ => write!(f, "\\u{:04x}", c)
+    }
+}
+
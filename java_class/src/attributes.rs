@@ -1,10 +1,16 @@
+use builders::cp::CPBuilder;
+use class::{read_attribute, JavaClassReader};
+use malformed;
 use opcodes::Opcode;
+use std::io;
+use writer::write_attribute_body;
 
-use crate::cp::CPIndex;
+use crate::cp::{CPIndex, ConstantPool};
 
 /// enum containing JVM Attributes
 /// for more information refer to the [JVM specification](https://docs.oracle.com/javase/specs/jvms/se8/html/index.html)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Attribute {
     ConstantValue {
         constantvalue_index: CPIndex
@@ -36,6 +42,7 @@ pub enum Attribute {
     SourceFile {
         sourcefile_index: CPIndex
     },
+    #[cfg_attr(feature = "serde", serde(rename = "SourceDebugExtension"))]
     SourceDebugExtenson {
         debug_extension: Vec<u8>
     },
@@ -76,6 +83,44 @@ pub enum Attribute {
     MethodParameters {
         parameters: Vec<MethodParameterEntry>
     },
+    /// an attribute this library doesn't recognize, kept as its raw bytes
+    Unknown {
+        name_index: CPIndex,
+        info: Vec<u8>
+    },
+}
+
+impl Attribute {
+    /// Returns the name the attribute is stored under in a class file, or `None` for `Unknown` attributes
+    pub fn name(&self) -> Option<&'static str> {
+        use self::Attribute::*;
+        Some(match self {
+            ConstantValue { .. } => "ConstantValue",
+            Code { .. } => "Code",
+            StackMapTable { .. } => "StackMapTable",
+            Exceptions { .. } => "Exceptions",
+            InnerClasses { .. } => "InnerClasses",
+            EnclosingMethod { .. } => "EnclosingMethod",
+            Synthetic => "Synthetic",
+            Signature { .. } => "Signature",
+            SourceFile { .. } => "SourceFile",
+            SourceDebugExtenson { .. } => "SourceDebugExtension",
+            LineNumberTable { .. } => "LineNumberTable",
+            LocalVariableTable { .. } => "LocalVariableTable",
+            LocalVariableTypeTable { .. } => "LocalVariableTypeTable",
+            Deprecated => "Deprecated",
+            RuntimeVisibleAnnotations { .. } => "RuntimeVisibleAnnotations",
+            RuntimeInvisibleAnnotations { .. } => "RuntimeInvisibleAnnotations",
+            RuntimeVisibleParameterAnnotations { .. } => "RuntimeVisibleParameterAnnotations",
+            RuntimeInvisibleParameterAnnotations { .. } => "RuntimeInvisibleParameterAnnotations",
+            RuntimeVisibleTypeAnnotations { .. } => "RuntimeVisibleTypeAnnotations",
+            RuntimeInvisibleTypeAnnotations { .. } => "RuntimeInvisibleTypeAnnotations",
+            AnnotationDefault { .. } => "AnnotationDefault",
+            BootstrapMethods { .. } => "BootstrapMethods",
+            MethodParameters { .. } => "MethodParameters",
+            Unknown { .. } => return None,
+        })
+    }
 }
 
 pub enum MethodParameterAccessFlags {
@@ -84,27 +129,32 @@ pub enum MethodParameterAccessFlags {
     Mandated = 0x8000,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MethodParameterEntry {
     pub name_index: CPIndex,
     pub access_flags: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BootstrapMethodsEntry {
     pub bootstrap_method_ref: CPIndex,
     pub bootstrap_arguments: Vec<CPIndex>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TypeAnnotation {
+    pub target_type: u8,
     pub target_info: TargetInfo,
     pub target_path: TypePath,
     pub type_index: CPIndex,
     pub element_value_pairs: Vec<ElementValuePair>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TargetInfo {
     TypeParameterTarget { type_parameter_index: u8 },
     SupertypeTarget { supertype_index: u16 },
@@ -118,46 +168,54 @@ pub enum TargetInfo {
     TypeArgumentTarget { offset: u16, type_argument_index: u8 },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TypePath {
     pub path: Vec<TypePathEntry>
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TypePathEntry {
     pub type_path_kind: u8,
     pub type_argument_index: u8,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocalVarTagetTableEntry {
     pub start_pc: u16,
     pub length: u16,
     pub index: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Annotation {
     pub type_index: CPIndex,
     pub element_value_pairs: Vec<ElementValuePair>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ElementValuePair {
     pub element_name_index: CPIndex,
     pub value: ElementValue,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ElementValue {
-    ConstValueIndex(CPIndex),
+    /// `tag` is the element's type character: one of `BCDFIJSZs`
+    ConstValueIndex { tag: u8, index: CPIndex },
     EnumConstValue { type_name_index: CPIndex, const_name_index: CPIndex },
     ClassInfoIndex(CPIndex),
     AnnotationValue(Annotation),
     ArrayValue(Vec<ElementValue>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocalVariableTypeTableEntry {
     pub start_pc: u16,
     pub length: u16,
@@ -166,7 +224,8 @@ pub struct LocalVariableTypeTableEntry {
     pub index: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocalVariableTableEntry {
     pub start_pc: u16,
     pub length: u16,
@@ -175,13 +234,15 @@ pub struct LocalVariableTableEntry {
     pub index: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LineNumberTableEntry {
     pub start_pc: u16,
     pub line_number: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InnerClassInfo {
     pub inner_class_info_index: CPIndex,
     pub outer_class_info_index: CPIndex,
@@ -189,7 +250,8 @@ pub struct InnerClassInfo {
     pub inner_class_access_flags: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum VerificationTypeInfo {
     Top,
     //0
@@ -210,7 +272,8 @@ pub enum VerificationTypeInfo {
     Double, //3
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StackMapFrame {
     SameFrame { offset_delta: u8 },
     //0-63
@@ -227,10 +290,100 @@ pub enum StackMapFrame {
     FullFrame { offset_delta: u16, locals: Vec<VerificationTypeInfo>, stack: Vec<VerificationTypeInfo> }, //255
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ExceptionTableEntry {
     pub start_pc: u16,
     pub end_pc: u16,
     pub handler_pc: u16,
     pub catch_type: CPIndex,
-}
\ No newline at end of file
+}
+/// The contents of the `Unknown` attributes whose layout is known, so the constant pool indices in them can be found.
+/// Any other undecoded attribute may hold indices that can't be told apart from other data,
+/// so it can't be moved to another constant pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum UndecodedContents {
+    NestHost(CPIndex),
+    /// the classes of `NestMembers` or `PermittedSubclasses`
+    ClassList(Vec<CPIndex>),
+    Record(Vec<RecordComponent>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RecordComponent {
+    pub name_index: CPIndex,
+    pub descriptor_index: CPIndex,
+    /// each attribute along with the index of its name
+    pub attributes: Vec<(CPIndex, Attribute)>,
+}
+
+impl UndecodedContents {
+    /// Decodes the contents of the attribute called `name`, or returns `None` if its layout isn't known
+    pub(crate) fn read(name: &str, info: &[u8], cp: &ConstantPool) -> io::Result<Option<UndecodedContents>> {
+        let mut r = JavaClassReader::new_from_bytes(info.to_vec())?;
+        let ans = match name {
+            "NestHost" => UndecodedContents::NestHost(r.next16()?.into()),
+            "NestMembers" | "PermittedSubclasses" => {
+                let count = r.next16()?;
+                let mut classes = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    classes.push(r.next16()?.into());
+                }
+                UndecodedContents::ClassList(classes)
+            }
+            "Record" => {
+                let count = r.next16()?;
+                let mut components = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let name_index = r.next16()?.into();
+                    let descriptor_index = r.next16()?.into();
+                    let attributes_count = r.next16()?;
+                    let mut attributes = Vec::with_capacity(attributes_count as usize);
+                    for _ in 0..attributes_count {
+                        let name_index = r.next16()?.into();
+                        let len = r.next32()?;
+                        attributes.push((name_index, read_attribute(&mut r, cp, name_index, len)?));
+                    }
+                    components.push(RecordComponent { name_index, descriptor_index, attributes });
+                }
+                UndecodedContents::Record(components)
+            }
+            _ => return Ok(None),
+        };
+        if r.dist() as usize != info.len() {
+            return Err(malformed("Attribute length did not match its contents"));
+        }
+        Ok(Some(ans))
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut ans = Vec::new();
+        match self {
+            UndecodedContents::NestHost(i) => ans.extend_from_slice(&i.as_u16().to_be_bytes()),
+            UndecodedContents::ClassList(classes) => {
+                ans.extend_from_slice(&(classes.len() as u16).to_be_bytes());
+                for i in classes {
+                    ans.extend_from_slice(&i.as_u16().to_be_bytes());
+                }
+            }
+            UndecodedContents::Record(components) => {
+                ans.extend_from_slice(&(components.len() as u16).to_be_bytes());
+                for c in components {
+                    ans.extend_from_slice(&c.name_index.as_u16().to_be_bytes());
+                    ans.extend_from_slice(&c.descriptor_index.as_u16().to_be_bytes());
+                    ans.extend_from_slice(&(c.attributes.len() as u16).to_be_bytes());
+                    for (name_index, a) in &c.attributes {
+                        //the attributes of a record component never contain attributes themselves,
+                        //so nothing gets added to this builder
+                        let mut body = Vec::new();
+                        write_attribute_body(a, &mut CPBuilder::new(), &mut body);
+                        ans.extend_from_slice(&name_index.as_u16().to_be_bytes());
+                        ans.extend_from_slice(&(body.len() as u32).to_be_bytes());
+                        ans.extend_from_slice(&body);
+                    }
+                }
+            }
+        }
+        ans
+    }
+}
@@ -0,0 +1,126 @@
+//! Bytecode statistics and queries over a classpath.
+//!
+//! ```text
+//! classquery stats [--top N] [--release N] [--json] <classpath>...
+//! classquery find [--class P] [--version N|N-M|N-|-M] [--class-flags a,b] [--method P]
+//!                 [--descriptor P] [--method-flags a,b] [--min-size N] [--insn PATTERN]...
+//!                 [--release N] [--json] <classpath>...
+//! ```
+
+extern crate java_class;
+
+use java_class::class::AccessFlags as ClassFlags;
+use java_class::classpath::ClassPath;
+use java_class::methods::AccessFlags as MethodFlags;
+use java_class::query::{matches_to_json, MethodFilter, NamePattern, Query, Statistics};
+use std::env;
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "usage:
+    classquery stats [--top N] [--release N] [--json] <classpath>...
+    classquery find [--class P] [--version N|N-M|N-|-M] [--class-flags a,b] [--method P]
+                    [--descriptor P] [--method-flags a,b] [--min-size N] [--insn PATTERN]...
+                    [--release N] [--json] <classpath>...";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => {}
+        //the output was closed early, e.g. piped into `head`
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            if e.kind() == io::ErrorKind::InvalidInput {
+                eprintln!("{}", USAGE);
+            }
+            process::exit(1);
+        }
+    }
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let command = args.first().ok_or_else(|| invalid("Expected a command"))?;
+    if command != "stats" && command != "find" {
+        return Err(invalid(&format!("Unknown command {}", command)));
+    }
+    let mut args = args[1..].iter();
+    let mut paths = vec![];
+    let mut json = false;
+    let mut release = None;
+    let mut top = 20;
+    let mut query = Query::default();
+    let mut method: Option<MethodFilter> = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| invalid(&format!("Expected a value after {}", arg)));
+        match arg.as_str() {
+            "--json" => json = true,
+            "--release" => release = Some(number(value()?)?),
+            "--top" if command == "stats" => top = number(value()?)?,
+            "--class" => query.class.name = NamePattern::new(value()?),
+            "--version" => {
+                let v = value()?;
+                let (min, max) = match v.find('-') {
+                    Some(i) => (&v[..i], &v[i + 1..]),
+                    None => (v.as_str(), v.as_str()),
+                };
+                query.class.min_version = if min.is_empty() { None } else { Some(number(min)?) };
+                query.class.max_version = if max.is_empty() { None } else { Some(number(max)?) };
+            }
+            "--class-flags" => query.class.flags = flags(value()?, ClassFlags::from_name)?,
+            "--method" => method.get_or_insert_with(MethodFilter::default).name = NamePattern::new(value()?),
+            "--descriptor" => method.get_or_insert_with(MethodFilter::default).descriptor = NamePattern::new(value()?),
+            "--method-flags" => method.get_or_insert_with(MethodFilter::default).flags = flags(value()?, MethodFlags::from_name)?,
+            "--min-size" => method.get_or_insert_with(MethodFilter::default).min_code_size = Some(number(value()?)?),
+            "--insn" => query.instructions.push(value()?.parse()?),
+            a if a.starts_with("--") => return Err(invalid(&format!("Unknown option {}", a))),
+            path => paths.push(path),
+        }
+    }
+    query.method = method;
+    if paths.is_empty() {
+        return Err(invalid("Expected a classpath"));
+    }
+    let mut cp = ClassPath::new();
+    for path in paths {
+        for p in env::split_paths(path) {
+            cp.push(p)?;
+        }
+    }
+    cp.set_release(release);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if command == "stats" {
+        let stats = Statistics::from_classpath(&cp, top)?;
+        if json {
+            writeln!(out, "{}", stats.to_json())?;
+        } else {
+            write!(out, "{}", stats.to_text())?;
+        }
+    } else {
+        let matches = query.run(&cp)?;
+        if json {
+            writeln!(out, "{}", matches_to_json(&matches))?;
+        } else {
+            for m in matches {
+                writeln!(out, "{}", m)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn number<T: std::str::FromStr>(s: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid(&format!("Expected a number, found {}", s)))
+}
+
+/// Parses a comma separated list of flag names, such as `public,abstract`
+fn flags<T: std::ops::BitOr<Output = T> + Default>(s: &str, from_name: fn(&str) -> Option<T>) -> io::Result<T> {
+    s.split(',').filter(|f| !f.is_empty()).try_fold(T::default(), |acc, f| {
+        from_name(&f.to_uppercase()).map(|flag| acc | flag).ok_or_else(|| invalid(&format!("Unknown flag {}", f)))
+    })
+}
+
+fn invalid(err: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
@@ -1,11 +1,11 @@
 #![allow(dead_code)]
 
+use class::{read_string, write_string};
 use cp_info::CPInfo;
 use std::collections::HashMap;
 
 use crate::cp::{CPIndex, ConstantPool};
 
-//TODO: MethodHandle, MethodType, and InvokeDynamic
 pub struct CPBuilder {
     items: Vec<CPInfo>,
     string_table: HashMap<String, CPIndex>,
@@ -32,10 +32,63 @@ impl CPBuilder {
         }
     }
 
+    /// Creates a `CPBuilder` that starts out with every constant of `cp` at its existing index,
+    /// so indices into `cp` stay valid and new constants are appended after them
+    pub fn from_constant_pool(cp: &ConstantPool) -> CPBuilder {
+        let mut ans = CPBuilder::new();
+        ans.items = cp.items().clone();
+        for i in 1..cp.len() {
+            let ind: CPIndex = i.into();
+            //a constant that can't be resolved is kept but never deduplicated against
+            match &cp[ind] {
+                CPInfo::Utf8 { bytes, .. } => {
+                    ans.utf8_table.entry(read_string(bytes)).or_insert(ind);
+                }
+                CPInfo::Class { .. } => if let Some(name) = cp.get_class_name(ind) {
+                    ans.class_table.entry(name).or_insert(ind);
+                },
+                CPInfo::String { string_index } => if let Some(s) = cp.get_utf8(*string_index) {
+                    ans.string_table.entry(s).or_insert(ind);
+                },
+                CPInfo::NameAndType { .. } => if let Some((name, type_)) = cp.get_name_and_type(ind) {
+                    ans.name_type_table.entry(name+"%"+&type_).or_insert(ind);
+                },
+                CPInfo::Fieldref { .. } => if let Some((c_name, name, type_)) = cp.get_member_ref(ind) {
+                    ans.fieldref_table.entry(c_name+"%"+&name+"%"+&type_).or_insert(ind);
+                },
+                CPInfo::Methodref { .. } => if let Some((c_name, name, type_)) = cp.get_member_ref(ind) {
+                    ans.methodref_table.entry(c_name+"%"+&name+"%"+&type_).or_insert(ind);
+                },
+                CPInfo::InterfaceMethodref { .. } => if let Some((c_name, name, type_)) = cp.get_member_ref(ind) {
+                    ans.imethodref_table.entry(c_name+"%"+&name+"%"+&type_).or_insert(ind);
+                },
+                _ => {}
+            }
+        }
+        ans
+    }
+
     pub fn build(self) -> ConstantPool {
         ConstantPool::new_with_info(self.items)
     }
 
+    /// Returns the 1-indexed length of the constant pool being built
+    pub fn len(&self) -> u16 {
+        self.items.len() as u16 + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the string held by the `Utf8` constant at `index`
+    pub fn get_utf8(&self, index: CPIndex) -> Option<String> {
+        match self.items.get((index.as_u16() as usize).checked_sub(1)?)? {
+            CPInfo::Utf8 { bytes, .. } => Some(read_string(bytes)),
+            _ => None
+        }
+    }
+
     pub fn add_integer(&mut self, val: u32) -> CPIndex {
         for i in 0..self.items.len() {
             if let CPInfo::Integer { bytes } = &self.items[i] {
@@ -126,8 +179,9 @@ impl CPBuilder {
 
     pub fn add_name_type(&mut self, name: String, type_: String) -> CPIndex {
         let key = name.to_owned()+"%"+&type_;
-        if self.class_table.contains_key(&key) {
-            self.class_table[&key]
+        #[allow(clippy::map_entry)] //since we modify `self`, `.entry().or_insert()` is a pain
+        if self.name_type_table.contains_key(&key) {
+            self.name_type_table[&key]
         } else {
             let name_index = self.add_utf8(name);
             let type_index = self.add_utf8(type_);
@@ -183,13 +237,80 @@ impl CPBuilder {
         }
     }
 
+    pub fn add_method_handle(&mut self, reference_kind: u8, reference_index: CPIndex) -> CPIndex {
+        let x = CPInfo::MethodHandle { reference_kind, reference_index };
+        self.add_unique(x)
+    }
+
+    pub fn add_method_type(&mut self, descriptor: String) -> CPIndex {
+        let descriptor_index = self.add_utf8(descriptor);
+        self.add_unique(CPInfo::MethodType { descriptor_index })
+    }
+
+    pub fn add_invoke_dynamic(&mut self, bootstrap_method_attr_index: u16, name: String, type_: String) -> CPIndex {
+        let name_and_type_index = self.add_name_type(name, type_);
+        self.add_unique(CPInfo::InvokeDynamic { bootstrap_method_attr_index: bootstrap_method_attr_index.into(), name_and_type_index })
+    }
+
+    /// Copies the constant at `index` in `from`, along with the constants it refers to, and returns its index here.
+    /// `InvokeDynamic` keeps its bootstrap method index as is.
+    /// Returns `None` if `index` doesn't point at a valid constant.
+    pub fn import(&mut self, from: &ConstantPool, index: CPIndex) -> Option<CPIndex> {
+        Some(match from.get(index)? {
+            CPInfo::Utf8 { bytes, .. } => self.add_utf8(read_string(bytes)),
+            CPInfo::Integer { bytes } => self.add_integer(*bytes),
+            CPInfo::Float { bytes } => self.add_float(*bytes),
+            CPInfo::Long { bytes } => self.add_long(*bytes),
+            CPInfo::Double { bytes } => self.add_double(*bytes),
+            CPInfo::Class { .. } => self.add_class(from.get_class_name(index)?),
+            CPInfo::String { string_index } => self.add_string(from.get_utf8(*string_index)?),
+            CPInfo::NameAndType { .. } => {
+                let (name, type_) = from.get_name_and_type(index)?;
+                self.add_name_type(name, type_)
+            }
+            CPInfo::Fieldref { .. } => {
+                let (c_name, name, type_) = from.get_member_ref(index)?;
+                self.add_fieldref(c_name, name, type_)
+            }
+            CPInfo::Methodref { .. } => {
+                let (c_name, name, type_) = from.get_member_ref(index)?;
+                self.add_methodref(c_name, name, type_)
+            }
+            CPInfo::InterfaceMethodref { .. } => {
+                let (c_name, name, type_) = from.get_member_ref(index)?;
+                self.add_interface_methodref(c_name, name, type_)
+            }
+            CPInfo::MethodHandle { reference_kind, reference_index } => {
+                let reference_index = self.import(from, *reference_index)?;
+                self.add_method_handle(*reference_kind, reference_index)
+            }
+            CPInfo::MethodType { descriptor_index } => self.add_method_type(from.get_utf8(*descriptor_index)?),
+            CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                let (name, type_) = from.get_name_and_type(*name_and_type_index)?;
+                self.add_invoke_dynamic(bootstrap_method_attr_index.as_u16(), name, type_)
+            }
+            CPInfo::LongDoubleDummy => return None,
+        })
+    }
+
+    /// adds a constant that is only deduplicated by a linear search, for the rarely used kinds
+    fn add_unique(&mut self, x: CPInfo) -> CPIndex {
+        if let Some(i) = self.items.iter().position(|c| *c == x) {
+            return (i as u16 + 1).into();
+        }
+        let ind = self.items.len() as u16 + 1;
+        self.items.push(x);
+        ind.into()
+    }
+
     pub fn add_utf8(&mut self, s: String) -> CPIndex {
         #[allow(clippy::map_entry)] //since we modify `self`, `.entry().or_insert()` is a pain
         if self.utf8_table.contains_key(&s) {
             self.utf8_table[&s]
         } else {
             let ind = (self.items.len() as u16 + 1).into();
-            self.items.push(CPInfo::Utf8 { length: s.len() as u16, bytes: s.as_bytes().to_vec() });
+            let bytes = write_string(&s);
+            self.items.push(CPInfo::Utf8 { length: bytes.len() as u16, bytes });
             self.utf8_table.insert(s, ind);
             ind
         }
@@ -2,6 +2,18 @@ use class::JavaClassReader;
 use opcodes::*;
 use opcodes::Opcode::*;
 
+/// Returns the bytecode offset of each opcode in `code`, plus the offset one past the end
+pub fn instruction_offsets(code: &[Opcode]) -> Vec<u32> {
+    let mut ans = Vec::with_capacity(code.len() + 1);
+    let mut pos = 0;
+    for op in code {
+        ans.push(pos);
+        pos += op.len_bytes() as u32;
+    }
+    ans.push(pos);
+    ans
+}
+
 /// Reads in a single opcode from a `JavaClassReader` and returns the read-in Opcode.
 /// Should never fail with a well-formed Java 8 class file.
 pub fn to_opcode(r: &mut JavaClassReader, method_start: u32) -> Option<Opcode> {
@@ -184,7 +196,7 @@ pub fn to_opcode(r: &mut JavaClassReader, method_start: u32) -> Option<Opcode> {
             let default = r.next32().ok()? as i32;
             let low = r.next32().ok()? as i32;
             let high = r.next32().ok()? as i32;
-            let npairs = high - low + 1;
+            let npairs = high as i64 - low as i64 + 1;
             let mut jump_offsets = Vec::new();
             for _ in 0..npairs {
                 jump_offsets.push(r.next32().ok()? as i32);
@@ -245,11 +257,14 @@ pub fn to_opcode(r: &mut JavaClassReader, method_start: u32) -> Option<Opcode> {
                     index: r.next16().ok()?,
                     const_: r.next16().ok()? as i16,
                 }
-            } else {
+            } else if let 0x15..=0x19 | 0x36..=0x3a | 0xa9 = opcode {
                 wide {
                     opcode,
                     index: r.next16().ok()?,
                 }
+            } else {
+                //only loads, stores, ret and iinc can be widened
+                return None;
             }
         }
         0xc5 => multianewarray { index: r.next16().ok()?.into(), dimensions: r.next8().ok()? },
@@ -594,6 +609,11 @@ pub fn to_bytecode(opcode: Opcode) -> (u8, Vec<u8>) {
             args.push((default >> 16) as u8);
             args.push((default >> 8) as u8);
             args.push(default as u8);
+            let npairs = match_offset_pairs.len() as u32;
+            args.push((npairs >> 24) as u8);
+            args.push((npairs >> 16) as u8);
+            args.push((npairs >> 8) as u8);
+            args.push(npairs as u8);
             for (a, b) in match_offset_pairs {
                 args.push((a >> 24) as u8);
                 args.push((a >> 16) as u8);
@@ -0,0 +1,526 @@
+use attributes::Attribute;
+use class::JavaClass;
+use classpath::ClassPath;
+use hierarchy::{ClassNode, Hierarchy};
+use json;
+use malformed;
+use methods::AccessFlags;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use symbolic::{to_symbolic, Constant, MemberRef, SymOpcode};
+
+/// A method, identified by the class that declares it, its name and its descriptor
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MethodId {
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl MethodId {
+    pub fn new(owner: &str, name: &str, descriptor: &str) -> MethodId {
+        MethodId { owner: owner.to_owned(), name: name.to_owned(), descriptor: descriptor.to_owned() }
+    }
+}
+
+impl<'a> From<&'a MemberRef> for MethodId {
+    fn from(m: &'a MemberRef) -> MethodId {
+        MethodId::new(&m.owner, &m.name, &m.descriptor)
+    }
+}
+
+impl fmt::Display for MethodId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}{}", self.owner, self.name, self.descriptor)
+    }
+}
+
+/// How a call is made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CallKind {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+    /// an `invokedynamic`. The callee is the lambda's implementation method for `LambdaMetafactory`
+    /// call sites, and the bootstrap method otherwise.
+    Dynamic,
+}
+
+impl CallKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CallKind::Virtual => "virtual",
+            CallKind::Special => "special",
+            CallKind::Static => "static",
+            CallKind::Interface => "interface",
+            CallKind::Dynamic => "dynamic",
+        }
+    }
+}
+
+/// An edge of the call graph
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Call {
+    pub caller: MethodId,
+    pub callee: MethodId,
+    pub kind: CallKind,
+}
+
+/// How the possible targets of virtual and interface calls are found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Class hierarchy analysis: any override in any subtype of the receiver's static type
+    Cha,
+    /// Rapid type analysis: like CHA, but only overrides in classes that are instantiated
+    /// by a method reachable from the entry points
+    Rta,
+}
+
+/// A call graph. Methods that are called but couldn't be found on the classpath are included
+/// under the name they were called by.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    pub methods: BTreeSet<MethodId>,
+    pub calls: BTreeSet<Call>,
+    callees: HashMap<MethodId, BTreeSet<MethodId>>,
+    callers: HashMap<MethodId, BTreeSet<MethodId>>,
+}
+
+impl CallGraph {
+    fn add_method(&mut self, m: MethodId) {
+        self.methods.insert(m);
+    }
+
+    fn add_call(&mut self, call: Call) -> bool {
+        self.methods.insert(call.caller.clone());
+        self.methods.insert(call.callee.clone());
+        self.callees.entry(call.caller.clone()).or_default().insert(call.callee.clone());
+        self.callers.entry(call.callee.clone()).or_default().insert(call.caller.clone());
+        self.calls.insert(call)
+    }
+
+    /// Returns the methods `m` may call directly
+    pub fn callees(&self, m: &MethodId) -> BTreeSet<MethodId> {
+        self.callees.get(m).cloned().unwrap_or_default()
+    }
+
+    /// Returns the methods that may call `m` directly
+    pub fn callers(&self, m: &MethodId) -> BTreeSet<MethodId> {
+        self.callers.get(m).cloned().unwrap_or_default()
+    }
+
+    /// Returns every method reachable from `entry_points`, including the entry points
+    pub fn reachable_from(&self, entry_points: &[MethodId]) -> BTreeSet<MethodId> {
+        walk(entry_points, &self.callees)
+    }
+
+    /// Returns every method that can reach `target`, including `target`
+    pub fn reaching(&self, target: &MethodId) -> BTreeSet<MethodId> {
+        walk(::std::slice::from_ref(target), &self.callers)
+    }
+
+    /// Formats the graph for Graphviz
+    pub fn to_dot(&self) -> String {
+        let mut ans = String::from("digraph callgraph {\n");
+        for m in &self.methods {
+            ans += &format!("    {};\n", dot_string(&m.to_string()));
+        }
+        for c in &self.calls {
+            ans += &format!("    {} -> {} [label={}];\n", dot_string(&c.caller.to_string()), dot_string(&c.callee.to_string()), c.kind.name());
+        }
+        ans += "}\n";
+        ans
+    }
+
+    /// Formats the graph as JSON: `{"methods": [...], "calls": [{"caller": i, "callee": j, "kind": ...}]}`,
+    /// where calls refer to methods by their position in `methods`
+    pub fn to_json(&self) -> String {
+        let ids: HashMap<&MethodId, usize> = self.methods.iter().enumerate().map(|(i, m)| (m, i)).collect();
+        let methods = self.methods.iter().map(|m| json::object(&[
+            ("owner", json::string(&m.owner)),
+            ("name", json::string(&m.name)),
+            ("descriptor", json::string(&m.descriptor)),
+        ]));
+        let calls = self.calls.iter().map(|c| json::object(&[
+            ("caller", ids[&c.caller].to_string()),
+            ("callee", ids[&c.callee].to_string()),
+            ("kind", json::string(c.kind.name())),
+        ]));
+        json::object(&[("methods", json::array(methods)), ("calls", json::array(calls))])
+    }
+}
+
+fn walk(start: &[MethodId], edges: &HashMap<MethodId, BTreeSet<MethodId>>) -> BTreeSet<MethodId> {
+    let mut ans: BTreeSet<MethodId> = start.iter().cloned().collect();
+    let mut queue: VecDeque<MethodId> = start.iter().cloned().collect();
+    while let Some(m) = queue.pop_front() {
+        for n in edges.get(&m).into_iter().flatten() {
+            if ans.insert(n.clone()) {
+                queue.push_back(n.clone());
+            }
+        }
+    }
+    ans
+}
+
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A call instruction, before its targets are worked out
+#[derive(Debug, Clone)]
+struct Site {
+    kind: CallKind,
+    target: MethodId,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MethodData {
+    access_flags: u16,
+    sites: Vec<Site>,
+    /// classes this method instantiates with `new`
+    instantiates: Vec<String>,
+    /// classes whose static members this method uses, which causes them to be initialized
+    initializes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ClassData {
+    is_abstract: bool,
+    methods: BTreeMap<(String, String), MethodData>,
+}
+
+/// Collects the methods and call instructions of a set of classes, and builds call graphs over them
+#[derive(Debug, Clone, Default)]
+pub struct CallGraphBuilder {
+    hierarchy: Hierarchy,
+    classes: HashMap<String, ClassData>,
+}
+
+impl CallGraphBuilder {
+    pub fn new() -> CallGraphBuilder {
+        CallGraphBuilder::default()
+    }
+
+    /// Collects every class on a classpath
+    pub fn from_classpath(cp: &ClassPath) -> io::Result<CallGraphBuilder> {
+        let mut ans = CallGraphBuilder::new();
+        for (_, class) in cp.classes()? {
+            ans.add_class(&class?)?;
+        }
+        Ok(ans)
+    }
+
+    /// Adds a class, replacing any class with the same name
+    pub fn add_class(&mut self, class: &JavaClass) -> io::Result<()> {
+        let node = ClassNode::from_class(class)?;
+        let cp = &class.constant_pool;
+        let mut methods = BTreeMap::new();
+        for m in &class.methods {
+            let name = cp.get_utf8(m.name_index).ok_or_else(|| malformed("Method name did not point to Utf8"))?;
+            let descriptor = cp.get_utf8(m.descriptor_index).ok_or_else(|| malformed("Method descriptor did not point to Utf8"))?;
+            let mut data = MethodData { access_flags: m.access_flags, ..MethodData::default() };
+            for a in &m.attributes {
+                if let Attribute::Code { code, .. } = a {
+                    for op in to_symbolic(code, cp, class.bootstrap_methods())? {
+                        collect(&op, &mut data);
+                    }
+                }
+            }
+            methods.insert((name, descriptor), data);
+        }
+        let is_abstract = class.flags().intersects(::class::AccessFlags::ABSTRACT | ::class::AccessFlags::INTERFACE);
+        self.classes.insert(node.name.clone(), ClassData { is_abstract, methods });
+        self.hierarchy.add(node);
+        Ok(())
+    }
+
+    pub fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    /// Lists every method of every class that has been added
+    pub fn methods(&self) -> BTreeSet<MethodId> {
+        self.classes.iter().flat_map(|(owner, c)| {
+            c.methods.keys().map(move |(name, desc)| MethodId::new(owner, name, desc))
+        }).collect()
+    }
+
+    /// Builds a call graph. CHA covers every method that has been added, since it doesn't depend on where it starts.
+    /// RTA covers only the methods reachable from `entry_points`.
+    pub fn build(&self, mode: Mode, entry_points: &[MethodId]) -> CallGraph {
+        match mode {
+            Mode::Cha => self.cha(),
+            Mode::Rta => self.rta(entry_points),
+        }
+    }
+
+    fn cha(&self) -> CallGraph {
+        let mut ans = CallGraph::default();
+        for m in self.methods() {
+            let data = self.method(&m).unwrap();
+            ans.add_method(m.clone());
+            for site in &data.sites {
+                for callee in self.targets(site, None) {
+                    ans.add_call(Call { caller: m.clone(), callee, kind: site.kind });
+                }
+            }
+        }
+        ans
+    }
+
+    fn rta(&self, entry_points: &[MethodId]) -> CallGraph {
+        let mut ans = CallGraph::default();
+        let mut reached = HashSet::new();
+        let mut queue: VecDeque<MethodId> = VecDeque::new();
+        let mut instantiated = HashSet::new();
+        let mut initialized = HashSet::new();
+        //virtual call sites seen so far, which gain targets as more classes are instantiated
+        let mut dynamic_sites: Vec<(MethodId, Site)> = vec!();
+        for e in entry_points {
+            if reached.insert(e.clone()) {
+                ans.add_method(e.clone());
+                queue.push_back(e.clone());
+            }
+        }
+        while let Some(m) = queue.pop_front() {
+            let mut new_calls = vec!();
+            let mut new_classes = vec!();
+            if let Some(data) = self.method(&m) {
+                for site in &data.sites {
+                    match site.kind {
+                        CallKind::Virtual | CallKind::Interface => {
+                            for callee in self.targets(site, Some(&instantiated)) {
+                                new_calls.push(Call { caller: m.clone(), callee, kind: site.kind });
+                            }
+                            dynamic_sites.push((m.clone(), site.clone()));
+                        }
+                        _ => {
+                            for callee in self.targets(site, None) {
+                                new_calls.push(Call { caller: m.clone(), callee, kind: site.kind });
+                            }
+                        }
+                    }
+                }
+                for c in &data.instantiates {
+                    if instantiated.insert(c.clone()) {
+                        new_classes.push(c.clone());
+                    }
+                }
+                for c in data.initializes.iter().chain(data.instantiates.iter()) {
+                    if initialized.insert(c.clone()) {
+                        //initializing a class initializes its superclasses first
+                        for s in ::std::iter::once(c.clone()).chain(self.hierarchy.superclasses(c)) {
+                            let clinit = MethodId::new(&s, "<clinit>", "()V");
+                            if self.method(&clinit).is_some() && reached.insert(clinit.clone()) {
+                                ans.add_method(clinit.clone());
+                                queue.push_back(clinit);
+                            }
+                        }
+                    }
+                }
+            }
+            //a newly instantiated class can be the receiver of any virtual call site seen so far
+            for c in &new_classes {
+                for (caller, site) in &dynamic_sites {
+                    if !self.hierarchy.is_subtype_of(c, &site.target.owner) {
+                        continue;
+                    }
+                    if let Some(callee) = self.dispatch(c, &site.target.name, &site.target.descriptor) {
+                        new_calls.push(Call { caller: caller.clone(), callee, kind: site.kind });
+                    }
+                }
+            }
+            for call in new_calls {
+                let callee = call.callee.clone();
+                ans.add_call(call);
+                if reached.insert(callee.clone()) {
+                    queue.push_back(callee);
+                }
+            }
+        }
+        ans
+    }
+
+    fn method(&self, m: &MethodId) -> Option<&MethodData> {
+        self.classes.get(&m.owner)?.methods.get(&(m.name.clone(), m.descriptor.clone()))
+    }
+
+    fn declares(&self, class: &str, name: &str, descriptor: &str) -> Option<&MethodData> {
+        self.classes.get(class)?.methods.get(&(name.to_owned(), descriptor.to_owned()))
+    }
+
+    /// Returns the possible targets of a call.
+    /// For virtual calls, `instantiated` limits the receivers to the given classes (RTA); `None` allows any subtype (CHA).
+    fn targets(&self, site: &Site, instantiated: Option<&HashSet<String>>) -> BTreeSet<MethodId> {
+        let t = &site.target;
+        let mut ans = BTreeSet::new();
+        match site.kind {
+            CallKind::Virtual | CallKind::Interface => {
+                let receivers = ::std::iter::once(t.owner.clone()).chain(self.hierarchy.subtypes(&t.owner));
+                for r in receivers {
+                    let concrete = self.classes.get(&r).is_some_and(|c| !c.is_abstract);
+                    if !concrete || instantiated.is_some_and(|i| !i.contains(&r)) {
+                        continue;
+                    }
+                    if let Some(m) = self.dispatch(&r, &t.name, &t.descriptor) {
+                        ans.insert(m);
+                    }
+                }
+                //keep calls into missing classes visible
+                if ans.is_empty() && instantiated.is_none() {
+                    ans.insert(self.resolve(t).unwrap_or_else(|| t.clone()));
+                }
+            }
+            _ => {
+                ans.insert(self.resolve(t).unwrap_or_else(|| t.clone()));
+            }
+        }
+        ans
+    }
+
+    /// Finds the declaration a symbolic reference resolves to (JVMS §5.4.3.3):
+    /// the class and its superclasses first, then its superinterfaces
+    fn resolve(&self, t: &MethodId) -> Option<MethodId> {
+        ::std::iter::once(t.owner.clone())
+            .chain(self.hierarchy.superclasses(&t.owner))
+            .chain(self.hierarchy.supertypes(&t.owner))
+            .find(|c| self.declares(c, &t.name, &t.descriptor).is_some())
+            .map(|c| MethodId::new(&c, &t.name, &t.descriptor))
+    }
+
+    /// Finds the method invoked on an instance of `class` (JVMS §5.4.6):
+    /// the nearest non-abstract declaration in a superclass, else a default method from a superinterface
+    fn dispatch(&self, class: &str, name: &str, descriptor: &str) -> Option<MethodId> {
+        let is_instance = |m: &MethodData| !AccessFlags::from_bits(m.access_flags).intersects(AccessFlags::STATIC | AccessFlags::ABSTRACT);
+        ::std::iter::once(class.to_owned())
+            .chain(self.hierarchy.superclasses(class))
+            .chain(self.hierarchy.supertypes(class))
+            .find(|c| self.declares(c, name, descriptor).is_some_and(is_instance))
+            .map(|c| MethodId::new(&c, name, descriptor))
+    }
+}
+
+fn collect(op: &SymOpcode, data: &mut MethodData) {
+    let (kind, target) = match op {
+        SymOpcode::InvokeVirtual(m) => (CallKind::Virtual, m),
+        SymOpcode::InvokeInterface(m) => (CallKind::Interface, m),
+        SymOpcode::InvokeSpecial(m) => (CallKind::Special, m),
+        SymOpcode::InvokeStatic(m) => {
+            data.initializes.push(m.owner.clone());
+            (CallKind::Static, m)
+        }
+        SymOpcode::GetStatic(m) | SymOpcode::PutStatic(m) => {
+            data.initializes.push(m.owner.clone());
+            return;
+        }
+        SymOpcode::New(class) => {
+            data.instantiates.push(class.clone());
+            return;
+        }
+        SymOpcode::InvokeDynamic { bootstrap, .. } => {
+            let handle = &bootstrap.handle.member;
+            let is_lambda = handle.owner == "java/lang/invoke/LambdaMetafactory";
+            //the second static argument of metafactory and altMetafactory is the implementation method
+            let target = match bootstrap.arguments.get(1) {
+                Some(Constant::MethodHandle(h)) if is_lambda => {
+                    //a constructor reference (`Foo::new`) instantiates its class
+                    if h.reference_kind == 8 {
+                        data.instantiates.push(h.member.owner.clone());
+                    }
+                    &h.member
+                }
+                _ => handle
+            };
+            (CallKind::Dynamic, target)
+        }
+        _ => return
+    };
+    data.sites.push(Site { kind, target: target.into() });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcodes::Opcode::*;
+    use test_classes::{interface_method, member, ClassSpec};
+
+    fn id(owner: &str, name: &str) -> MethodId {
+        MethodId::new(owner, name, if name == "area" { "()D" } else { "()V" })
+    }
+
+    fn shape(name: &str) -> JavaClass {
+        ClassSpec::new(0x0021, name, Some("java/lang/Object")).implements(&["Shape"])
+            .method(0x0001, "<init>", "()V", vec![
+                SymOpcode::Plain(aload_0),
+                SymOpcode::InvokeSpecial(member("java/lang/Object", "<init>", "()V")),
+                SymOpcode::Plain(return_),
+            ])
+            .method(0x0001, "area", "()D", vec![SymOpcode::Plain(dconst_0), SymOpcode::Plain(dreturn)])
+            .build()
+    }
+
+    fn create(class: &str) -> Vec<SymOpcode> {
+        vec![SymOpcode::New(class.to_owned()), SymOpcode::Plain(dup), SymOpcode::InvokeSpecial(member(class, "<init>", "()V"))]
+    }
+
+    /// `main` calls `Shape.area` on a `Circle` it creates and on a `Square` from `make`,
+    /// and `Triangle` is only created by a method nothing calls
+    fn builder() -> CallGraphBuilder {
+        let area = || SymOpcode::InvokeInterface(interface_method("Shape", "area", "()D"));
+        let mut main = create("Circle");
+        main.extend(vec![area(), SymOpcode::Plain(pop2)]);
+        main.extend(vec![SymOpcode::InvokeStatic(member("Main", "make", "()LShape;")), area(), SymOpcode::Plain(pop2), SymOpcode::Plain(return_)]);
+        let mut make = create("Square");
+        make.push(SymOpcode::Plain(areturn));
+        let mut unused = create("Triangle");
+        unused.extend(vec![area(), SymOpcode::Plain(pop2), SymOpcode::Plain(return_)]);
+        let classes = vec![
+            ClassSpec::new(0x0601, "Shape", Some("java/lang/Object")).method(0x0401, "area", "()D", vec![]).build(),
+            shape("Circle"),
+            shape("Square"),
+            shape("Triangle"),
+            ClassSpec::new(0x0021, "Main", Some("java/lang/Object"))
+                .method(0x0009, "main", "()V", main)
+                .method(0x0009, "make", "()LShape;", make)
+                .method(0x0009, "unused", "()V", unused)
+                .build(),
+        ];
+        let mut ans = CallGraphBuilder::new();
+        for c in &classes {
+            ans.add_class(c).unwrap();
+        }
+        ans
+    }
+
+    #[test]
+    fn cha_calls_every_override() {
+        let b = builder();
+        let g = b.build(Mode::Cha, &[]);
+        let areas: BTreeSet<MethodId> = ["Circle", "Square", "Triangle"].iter().map(|c| id(c, "area")).collect();
+        assert_eq!(g.callees(&id("Main", "unused")), areas.iter().cloned().chain(vec![id("Triangle", "<init>")]).collect());
+        let make = MethodId::new("Main", "make", "()LShape;");
+        assert_eq!(g.callees(&id("Main", "main")), areas.iter().cloned().chain(vec![id("Circle", "<init>"), make]).collect());
+        //the abstract declaration is never a target
+        assert!(g.callers(&id("Shape", "area")).is_empty());
+        //Object isn't on the classpath, so its constructor is kept by name
+        assert_eq!(g.callers(&id("java/lang/Object", "<init>")), ["Circle", "Square", "Triangle"].iter().map(|c| id(c, "<init>")).collect());
+        assert!(g.calls.contains(&Call { caller: id("Main", "main"), callee: id("Square", "area"), kind: CallKind::Interface }));
+    }
+
+    #[test]
+    fn rta_only_calls_overrides_in_instantiated_classes() {
+        let b = builder();
+        let g = b.build(Mode::Rta, &[id("Main", "main")]);
+        let make = MethodId::new("Main", "make", "()LShape;");
+        //Square is created by `make` after the call site in `main` is seen, and still becomes a target
+        assert_eq!(g.callees(&id("Main", "main")), vec![id("Circle", "area"), id("Square", "area"), id("Circle", "<init>"), make.clone()].into_iter().collect());
+        assert_eq!(g.reachable_from(&[id("Main", "main")]), g.methods);
+        assert!(!g.methods.contains(&id("Triangle", "area")));
+        assert!(!g.methods.contains(&id("Main", "unused")));
+        assert_eq!(g.reaching(&id("Square", "area")), vec![id("Main", "main"), id("Square", "area")].into_iter().collect());
+        assert_eq!(g.reaching(&id("Square", "<init>")), vec![id("Main", "main"), make, id("Square", "<init>")].into_iter().collect());
+    }
+}
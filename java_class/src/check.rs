@@ -0,0 +1,862 @@
+use attributes::*;
+use attributes::Attribute::*;
+use bytecode_tools::instruction_offsets;
+use class::{AccessFlags as ClassFlags, JavaClass};
+use cp::{CPIndex, ConstantPool};
+use cp_info::CPInfo;
+use cp_info::CPInfo::*;
+use descriptor::MethodDescriptor;
+use fields::{AccessFlags as FieldFlags, FieldInfo};
+use methods::{AccessFlags as MethodFlags, MethodInfo};
+use std::collections::HashSet;
+use std::fmt;
+use std::string::String;
+
+/// Which kind of rule a class breaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FormatErrorKind {
+    /// a constant pool index is out of range or points at the wrong kind of constant
+    BadReference,
+    /// a Utf8 constant isn't valid modified UTF-8
+    BadUtf8,
+    /// a class, field or method name isn't well-formed (§4.2)
+    BadName,
+    /// a field or method descriptor isn't well-formed (§4.3)
+    BadDescriptor,
+    /// a combination of access flags that isn't allowed
+    IllegalFlags,
+    /// two fields or methods share a name and descriptor
+    Duplicate,
+    /// an attribute is missing, repeated, or has contents that don't fit where it is
+    BadAttribute,
+    /// the class uses something its version doesn't have
+    UnsupportedVersion,
+}
+
+/// A way in which a class breaks the format checks of JVM specification §4.8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    /// the structure at fault, in the same form as `ClassParseError::path`, e.g. `methods[3].Code`
+    pub path: String,
+    pub kind: FormatErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Runs the format checks of JVM specification §4.8 over a parsed class:
+/// every constant pool cross-reference points at the right kind of constant,
+/// names and descriptors are well-formed, access flags are legal,
+/// and attributes appear where and as often as they may.
+/// Never panics, whatever the class contains.
+pub fn check_format(class: &JavaClass) -> Vec<FormatError> {
+    let mut c = Checker { class, cp: &class.constant_pool, errors: vec!() };
+    c.constant_pool();
+    c.header();
+    let mut seen = HashSet::new();
+    for (i, f) in class.fields.iter().enumerate() {
+        let path = format!("fields[{}]", i);
+        if let (Some(name), Some(descriptor)) = (c.field(f, &path), c.cp.get_utf8(f.descriptor_index)) {
+            if !seen.insert((name.clone(), descriptor)) {
+                c.error(&path, FormatErrorKind::Duplicate, format!("Field {} is declared more than once", name));
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    for (i, m) in class.methods.iter().enumerate() {
+        let path = format!("methods[{}]", i);
+        if let (Some(name), Some(descriptor)) = (c.method(m, &path), c.cp.get_utf8(m.descriptor_index)) {
+            if !seen.insert((name.clone(), descriptor.clone())) {
+                c.error(&path, FormatErrorKind::Duplicate, format!("Method {}{} is declared more than once", name, descriptor));
+            }
+        }
+    }
+    c.attributes(&class.attributes, "", Owner::Class);
+    c.errors
+}
+
+/// Whether `s` is an unqualified name: non-empty and without `.`, `;`, `[` or `/` (§4.2.2)
+pub fn is_unqualified_name(s: &str) -> bool {
+    !s.is_empty() && !s.contains(&['.', ';', '[', '/'][..])
+}
+
+/// Whether `s` can name a method: `<init>`, `<clinit>`, or an unqualified name without `<` or `>` (§4.2.2)
+pub fn is_method_name(s: &str) -> bool {
+    s == "<init>" || s == "<clinit>" || (is_unqualified_name(s) && !s.contains(&['<', '>'][..]))
+}
+
+/// Whether `s` is a binary class name in internal form, such as `java/lang/Object` (§4.2.1)
+pub fn is_binary_name(s: &str) -> bool {
+    s.split('/').all(is_unqualified_name)
+}
+
+/// Whether `s` is a field descriptor (§4.3.2)
+pub fn is_field_descriptor(s: &str) -> bool {
+    field_type_end(s.as_bytes(), 0) == Some(s.len())
+}
+
+/// Whether `s` is a method descriptor (§4.3.3)
+pub fn is_method_descriptor(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.first() != Some(&b'(') {
+        return false;
+    }
+    let mut i = 1;
+    while b.get(i) != Some(&b')') {
+        match field_type_end(b, i) {
+            Some(end) => i = end,
+            None => return false,
+        }
+    }
+    s[i + 1..] == *"V" || is_field_descriptor(&s[i + 1..])
+}
+
+/// Whether `bytes` is valid modified UTF-8 (§4.4.7)
+pub fn is_modified_utf8(bytes: &[u8]) -> bool {
+    let mut i = 0;
+    while i < bytes.len() {
+        let continuations = match bytes[i] {
+            0x01..=0x7f => 0,
+            0xc0..=0xdf => 1,
+            0xe0..=0xef => 2,
+            _ => return false,
+        };
+        for j in 1..=continuations {
+            match bytes.get(i + j) {
+                Some(b) if b & 0xc0 == 0x80 => {}
+                _ => return false,
+            }
+        }
+        i += continuations + 1;
+    }
+    true
+}
+
+/// Returns the index just past the field type starting at `i`
+fn field_type_end(b: &[u8], mut i: usize) -> Option<usize> {
+    let start = i;
+    while b.get(i) == Some(&b'[') {
+        i += 1;
+    }
+    //§4.3.2: an array type may have at most 255 dimensions
+    if i - start > 255 {
+        return None;
+    }
+    match *b.get(i)? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => Some(i + 1),
+        b'L' => {
+            let end = i + b[i..].iter().position(|c| *c == b';')?;
+            let name = ::std::str::from_utf8(&b[i + 1..end]).ok()?;
+            if is_binary_name(name) { Some(end + 1) } else { None }
+        }
+        _ => None,
+    }
+}
+
+fn tag_name(c: &CPInfo) -> &'static str {
+    match c {
+        Class { .. } => "Class",
+        Fieldref { .. } => "Fieldref",
+        Methodref { .. } => "Methodref",
+        InterfaceMethodref { .. } => "InterfaceMethodref",
+        CPInfo::String { .. } => "String",
+        Integer { .. } => "Integer",
+        Float { .. } => "Float",
+        Long { .. } => "Long",
+        Double { .. } => "Double",
+        LongDoubleDummy => "the second half of a Long or Double",
+        NameAndType { .. } => "NameAndType",
+        Utf8 { .. } => "Utf8",
+        MethodHandle { .. } => "MethodHandle",
+        MethodType { .. } => "MethodType",
+        InvokeDynamic { .. } => "InvokeDynamic",
+    }
+}
+
+/// Which kind of structure a list of attributes belongs to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    Class,
+    Field,
+    Method,
+    Code,
+}
+
+/// Attributes that may appear at most once in the same attributes table
+const UNIQUE_ATTRIBUTES: &[&str] = &[
+    "ConstantValue", "Code", "StackMapTable", "Exceptions", "InnerClasses", "EnclosingMethod", "Signature",
+    "SourceFile", "SourceDebugExtension", "RuntimeVisibleAnnotations", "RuntimeInvisibleAnnotations",
+    "RuntimeVisibleParameterAnnotations", "RuntimeInvisibleParameterAnnotations", "RuntimeVisibleTypeAnnotations",
+    "RuntimeInvisibleTypeAnnotations", "AnnotationDefault", "BootstrapMethods", "MethodParameters",
+];
+
+struct Checker<'a> {
+    class: &'a JavaClass,
+    cp: &'a ConstantPool,
+    errors: Vec<FormatError>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, path: &str, kind: FormatErrorKind, message: String) {
+        self.errors.push(FormatError { path: path.to_owned(), kind, message });
+    }
+
+    fn major(&self) -> u16 {
+        self.class.major_version
+    }
+
+    /// checks that `index` points to one of the `expected` kinds of constant, returning it if so
+    fn expect(&mut self, path: &str, index: CPIndex, expected: &[&str]) -> Option<&'a CPInfo> {
+        let cp = self.cp;
+        match cp.get(index) {
+            None => {
+                let message = format!("Constant pool index {} is out of range for a pool of length {}", index, cp.len());
+                self.error(path, FormatErrorKind::BadReference, message);
+                None
+            }
+            Some(c) if expected.contains(&tag_name(c)) => Some(c),
+            Some(c) => {
+                let message = format!("Constant pool index {} should be {}, but is {}", index, expected.join(" or "), tag_name(c));
+                self.error(path, FormatErrorKind::BadReference, message);
+                None
+            }
+        }
+    }
+
+    /// checks that `index` points to a Utf8 constant, returning its string if so
+    fn utf8(&mut self, path: &str, index: CPIndex) -> Option<String> {
+        self.expect(path, index, &["Utf8"])?;
+        self.cp.get_utf8(index)
+    }
+
+    /// checks that `index` points to a Class constant with a valid name, returning the name if so
+    fn class(&mut self, path: &str, index: CPIndex) -> Option<String> {
+        match self.expect(path, index, &["Class"])? {
+            Class { name_index } => self.utf8(path, *name_index),
+            _ => None,
+        }
+    }
+
+    /// checks that `index` points to a Class constant for a class or interface rather than an array
+    fn non_array_class(&mut self, path: &str, index: CPIndex) -> Option<String> {
+        let name = self.class(path, index)?;
+        if name.starts_with('[') {
+            self.error(path, FormatErrorKind::BadReference, format!("{} is an array type, not a class or interface", name));
+            return None;
+        }
+        Some(name)
+    }
+
+    fn descriptor(&mut self, path: &str, index: CPIndex, method: bool) -> Option<String> {
+        let descriptor = self.utf8(path, index)?;
+        let valid = if method { is_method_descriptor(&descriptor) } else { is_field_descriptor(&descriptor) };
+        if !valid {
+            let kind = if method { "method" } else { "field" };
+            self.error(path, FormatErrorKind::BadDescriptor, format!("{:?} is not a valid {} descriptor", descriptor, kind));
+            return None;
+        }
+        Some(descriptor)
+    }
+
+    fn constant_pool(&mut self) {
+        let items = self.cp.items();
+        for (i, c) in items.iter().enumerate() {
+            let path = format!("constant_pool[{}]", i + 1);
+            let previous = if i == 0 { None } else { items.get(i - 1) };
+            match c {
+                Utf8 { length, bytes } => {
+                    if *length as usize != bytes.len() || !is_modified_utf8(bytes) {
+                        self.error(&path, FormatErrorKind::BadUtf8, "Utf8 constant is not valid modified UTF-8".to_owned());
+                    }
+                }
+                Class { name_index } => {
+                    if let Some(name) = self.utf8(&path, *name_index) {
+                        let valid = if name.starts_with('[') { is_field_descriptor(&name) } else { is_binary_name(&name) };
+                        if !valid {
+                            self.error(&path, FormatErrorKind::BadName, format!("{:?} is not a valid class name", name));
+                        }
+                    }
+                }
+                Fieldref { class_index, name_and_type_index } |
+                Methodref { class_index, name_and_type_index } |
+                InterfaceMethodref { class_index, name_and_type_index } => {
+                    let is_field = matches!(c, Fieldref { .. });
+                    if is_field || matches!(c, InterfaceMethodref { .. }) {
+                        self.non_array_class(&path, *class_index);
+                    } else {
+                        //arrays have methods, e.g. clone
+                        self.class(&path, *class_index);
+                    }
+                    self.member_name_and_type(&path, *name_and_type_index, c);
+                }
+                CPInfo::String { string_index } => {
+                    self.utf8(&path, *string_index);
+                }
+                Integer { .. } | Float { .. } => {}
+                Long { .. } | Double { .. } => {
+                    if i + 1 < items.len() && items[i + 1] != LongDoubleDummy {
+                        let message = format!("{} is not followed by an unusable entry", tag_name(c));
+                        self.error(&path, FormatErrorKind::BadReference, message);
+                    }
+                }
+                LongDoubleDummy => match previous {
+                    Some(Long { .. }) | Some(Double { .. }) => {}
+                    _ => self.error(&path, FormatErrorKind::BadReference, "Unusable entry does not follow a Long or Double".to_owned()),
+                },
+                NameAndType { name_index, descriptor_index } => {
+                    self.utf8(&path, *name_index);
+                    self.utf8(&path, *descriptor_index);
+                }
+                MethodHandle { reference_kind, reference_index } => {
+                    self.since(&path, 51, "MethodHandle constants");
+                    self.method_handle(&path, *reference_kind, *reference_index);
+                }
+                MethodType { descriptor_index } => {
+                    self.since(&path, 51, "MethodType constants");
+                    self.descriptor(&path, *descriptor_index, true);
+                }
+                InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                    self.since(&path, 51, "InvokeDynamic constants");
+                    if let Some(NameAndType { name_index, descriptor_index }) = self.expect(&path, *name_and_type_index, &["NameAndType"]) {
+                        if let Some(name) = self.utf8(&path, *name_index) {
+                            self.name(&path, &name, is_unqualified_name, "method");
+                        }
+                        self.descriptor(&path, *descriptor_index, true);
+                    }
+                    let count = self.class.bootstrap_methods().len();
+                    if bootstrap_method_attr_index.as_u16() as usize >= count {
+                        let message = format!("Bootstrap method {} is out of range for {} bootstrap methods", bootstrap_method_attr_index, count);
+                        self.error(&path, FormatErrorKind::BadReference, message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// checks the name and type of a Fieldref, Methodref or InterfaceMethodref constant
+    fn member_name_and_type(&mut self, path: &str, index: CPIndex, member: &CPInfo) -> Option<String> {
+        let (name_index, descriptor_index) = match self.expect(path, index, &["NameAndType"])? {
+            NameAndType { name_index, descriptor_index } => (*name_index, *descriptor_index),
+            _ => return None,
+        };
+        let name = self.utf8(path, name_index)?;
+        let is_field = matches!(member, Fieldref { .. });
+        let descriptor = self.descriptor(path, descriptor_index, !is_field)?;
+        if is_field {
+            self.name(path, &name, is_unqualified_name, "field");
+        } else if name.starts_with('<') {
+            //§4.4.2: the only special method that can be referred to is an instance initializer returning void
+            if name != "<init>" || !descriptor.ends_with(")V") || matches!(member, InterfaceMethodref { .. }) {
+                self.error(path, FormatErrorKind::BadName, format!("{} can't be the target of a {}", name, tag_name(member)));
+            }
+        } else {
+            self.name(path, &name, is_method_name, "method");
+        }
+        Some(name)
+    }
+
+    fn method_handle(&mut self, path: &str, kind: u8, index: CPIndex) {
+        let expected: &[&str] = match kind {
+            1..=4 => &["Fieldref"],
+            5 | 8 => &["Methodref"],
+            6 | 7 if self.major() >= 52 => &["Methodref", "InterfaceMethodref"],
+            6 | 7 => &["Methodref"],
+            9 => &["InterfaceMethodref"],
+            _ => {
+                self.error(path, FormatErrorKind::BadReference, format!("Method handle kind {} is not between 1 and 9", kind));
+                return;
+            }
+        };
+        let name = match self.expect(path, index, expected) {
+            Some(Fieldref { name_and_type_index, .. }) |
+            Some(Methodref { name_and_type_index, .. }) |
+            Some(InterfaceMethodref { name_and_type_index, .. }) => self.cp.get_name_and_type(*name_and_type_index).map(|nt| nt.0),
+            _ => None,
+        };
+        if let Some(name) = name {
+            let initializer = name == "<init>";
+            if (kind == 8) != initializer || name == "<clinit>" {
+                self.error(path, FormatErrorKind::BadReference, format!("A method handle of kind {} can't refer to {}", kind, name));
+            }
+        }
+    }
+
+    fn name(&mut self, path: &str, name: &str, valid: fn(&str) -> bool, what: &str) {
+        if !valid(name) {
+            self.error(path, FormatErrorKind::BadName, format!("{:?} is not a valid {} name", name, what));
+        }
+    }
+
+    fn since(&mut self, path: &str, major: u16, what: &str) {
+        if self.major() < major {
+            let message = format!("{} need class file version {}, but the class is version {}", what, major, self.major());
+            self.error(path, FormatErrorKind::UnsupportedVersion, message);
+        }
+    }
+
+    fn flags(&mut self, path: &str, flags: u16, message: &str) {
+        self.error(path, FormatErrorKind::IllegalFlags, format!("{} (flags {:#06x})", message, flags));
+    }
+
+    fn header(&mut self) {
+        let class = self.class;
+        let flags = class.access_flags;
+        let has = |f: ClassFlags| class.flags().contains(f);
+        if has(ClassFlags::INTERFACE) {
+            if !has(ClassFlags::ABSTRACT) || has(ClassFlags::FINAL) || has(ClassFlags::SUPER) || has(ClassFlags::ENUM) {
+                self.flags("access_flags", flags, "An interface must be abstract and can't be final, super or an enum");
+            }
+        } else if has(ClassFlags::ANNOTATION) {
+            self.flags("access_flags", flags, "Only an interface can be an annotation");
+        } else if has(ClassFlags::FINAL) && has(ClassFlags::ABSTRACT) {
+            self.flags("access_flags", flags, "A class can't be both final and abstract");
+        }
+        let name = self.non_array_class("this_class", class.this_class);
+        if class.super_class.as_u16() == 0 {
+            if name.as_ref().map(|n| n != "java/lang/Object").unwrap_or(false) {
+                self.error("super_class", FormatErrorKind::BadReference, "Only java/lang/Object has no superclass".to_owned());
+            }
+        } else if let Some(super_name) = self.non_array_class("super_class", class.super_class) {
+            if class.is_interface() && super_name != "java/lang/Object" {
+                self.error("super_class", FormatErrorKind::BadReference, "The superclass of an interface must be java/lang/Object".to_owned());
+            }
+        }
+        for (i, index) in class.interfaces.iter().enumerate() {
+            self.non_array_class(&format!("interfaces[{}]", i), *index);
+        }
+    }
+
+    /// checks a field, returning its name if it has a valid one
+    fn field(&mut self, f: &FieldInfo, path: &str) -> Option<String> {
+        let flags = f.access_flags;
+        let has = |a: FieldFlags| f.flags().contains(a);
+        let visibility = [FieldFlags::PUBLIC, FieldFlags::PRIVATE, FieldFlags::PROTECTED].iter().filter(|a| has(**a)).count();
+        if self.class.is_interface() {
+            let allowed = FieldFlags::PUBLIC | FieldFlags::STATIC | FieldFlags::FINAL | FieldFlags::SYNTHETIC;
+            if !has(FieldFlags::PUBLIC | FieldFlags::STATIC | FieldFlags::FINAL) || !(f.flags() - allowed).is_empty() {
+                self.flags(path, flags, "An interface field must be public, static and final, and nothing else but synthetic");
+            }
+        } else if visibility > 1 {
+            self.flags(path, flags, "A field can have at most one of public, private and protected");
+        } else if has(FieldFlags::FINAL) && has(FieldFlags::VOLATILE) {
+            self.flags(path, flags, "A field can't be both final and volatile");
+        }
+        let descriptor = self.descriptor(path, f.descriptor_index, false);
+        for a in &f.attributes {
+            if let ConstantValue { constantvalue_index } = a {
+                let expected: &[&str] = match descriptor.as_deref() {
+                    Some("J") => &["Long"],
+                    Some("F") => &["Float"],
+                    Some("D") => &["Double"],
+                    Some("I") | Some("S") | Some("C") | Some("B") | Some("Z") => &["Integer"],
+                    Some("Ljava/lang/String;") => &["String"],
+                    Some(d) => {
+                        let message = format!("A field of type {} can't have a ConstantValue", d);
+                        self.error(&format!("{}.ConstantValue", path), FormatErrorKind::BadAttribute, message);
+                        continue;
+                    }
+                    None => continue,
+                };
+                self.expect(&format!("{}.ConstantValue", path), *constantvalue_index, expected);
+            }
+        }
+        self.attributes(&f.attributes, path, Owner::Field);
+        let name = self.utf8(path, f.name_index)?;
+        self.name(path, &name, is_unqualified_name, "field");
+        Some(name)
+    }
+
+    /// checks a method, returning its name if it has a valid one
+    fn method(&mut self, m: &MethodInfo, path: &str) -> Option<String> {
+        let name = self.utf8(path, m.name_index);
+        if let Some(name) = &name {
+            self.name(path, name, is_method_name, "method");
+        }
+        let descriptor = self.descriptor(path, m.descriptor_index, true);
+        let name = name.unwrap_or_default();
+        let flags = m.access_flags;
+        let has = |a: MethodFlags| m.flags().contains(a);
+        let visibility = [MethodFlags::PUBLIC, MethodFlags::PRIVATE, MethodFlags::PROTECTED].iter().filter(|a| has(**a)).count();
+        let major = self.major();
+        if name == "<clinit>" {
+            //§4.6: other flags of a class initializer are ignored
+            if major >= 51 && !has(MethodFlags::STATIC) {
+                self.flags(path, flags, "A class initializer must be static");
+            }
+            if descriptor.as_ref().map(|d| d != "()V").unwrap_or(false) {
+                self.error(path, FormatErrorKind::BadDescriptor, "A class initializer must take no arguments and return void".to_owned());
+            }
+        } else if visibility > 1 {
+            self.flags(path, flags, "A method can have at most one of public, private and protected");
+        } else if self.class.is_interface() {
+            let ok = if major < 52 {
+                has(MethodFlags::PUBLIC) && has(MethodFlags::ABSTRACT)
+            } else {
+                visibility == 1 && !has(MethodFlags::PROTECTED)
+            } && !has(MethodFlags::FINAL) && !has(MethodFlags::SYNCHRONIZED) && !has(MethodFlags::NATIVE);
+            if !ok || name == "<init>" {
+                let message = if major < 52 { "An interface method must be public and abstract" } else { "An interface method must be public or private" };
+                self.flags(path, flags, &format!("{}, and can't be final, synchronized, native or an instance initializer", message));
+            }
+        } else if name == "<init>" {
+            let allowed = MethodFlags::PUBLIC | MethodFlags::PRIVATE | MethodFlags::PROTECTED |
+                MethodFlags::VARARGS | MethodFlags::STRICT | MethodFlags::SYNTHETIC;
+            if !(m.flags() - allowed).is_empty() {
+                self.flags(path, flags, "An instance initializer can only be public, private, protected, varargs, strict or synthetic");
+            }
+        }
+        if name != "<clinit>" && has(MethodFlags::ABSTRACT) {
+            //§4.6: strictfp stopped meaning anything in version 61
+            let strict = has(MethodFlags::STRICT) && (46..61).contains(&major);
+            if has(MethodFlags::PRIVATE) || has(MethodFlags::STATIC) || has(MethodFlags::FINAL) ||
+                has(MethodFlags::SYNCHRONIZED) || has(MethodFlags::NATIVE) || strict {
+                self.flags(path, flags, "An abstract method can't be private, static, final, synchronized, native or strict");
+            }
+        }
+        if name == "<init>" && descriptor.as_ref().map(|d| !d.ends_with(")V")).unwrap_or(false) {
+            self.error(path, FormatErrorKind::BadDescriptor, "An instance initializer must return void".to_owned());
+        }
+        let slots = descriptor.as_ref()
+            .and_then(|d| MethodDescriptor::parse(d).ok())
+            .map(|d| d.arg_slots() + if has(MethodFlags::STATIC) { 0 } else { 1 });
+        if slots.map(|s| s > 255).unwrap_or(false) {
+            self.error(path, FormatErrorKind::BadDescriptor, "A method can take at most 255 slots of arguments".to_owned());
+        }
+        let code = m.attributes.iter().filter(|a| matches!(a, Code { .. })).count();
+        let needs_code = !has(MethodFlags::ABSTRACT) && !has(MethodFlags::NATIVE);
+        if needs_code && code == 0 {
+            self.error(path, FormatErrorKind::BadAttribute, "A method that is neither abstract nor native needs a Code attribute".to_owned());
+        } else if !needs_code && code > 0 {
+            self.error(path, FormatErrorKind::BadAttribute, "An abstract or native method can't have a Code attribute".to_owned());
+        }
+        for a in &m.attributes {
+            if let Code { max_locals, .. } = a {
+                if slots.map(|s| s > *max_locals as u32).unwrap_or(false) {
+                    self.error(&format!("{}.Code", path), FormatErrorKind::BadAttribute, "max_locals is smaller than the arguments".to_owned());
+                }
+            }
+        }
+        self.attributes(&m.attributes, path, Owner::Method);
+        Some(name)
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute], parent: &str, owner: Owner) {
+        let mut seen = HashSet::new();
+        for a in attributes {
+            let name = match a {
+                Unknown { name_index, .. } => self.cp.get_utf8(*name_index).unwrap_or_default(),
+                _ => a.name().unwrap_or_default().to_owned(),
+            };
+            let path = if parent.is_empty() { name.clone() } else { format!("{}.{}", parent, name) };
+            if UNIQUE_ATTRIBUTES.contains(&name.as_str()) && !seen.insert(name.clone()) {
+                self.error(&path, FormatErrorKind::BadAttribute, format!("{} appears more than once", name));
+            }
+            let allowed = match a {
+                Code { .. } | Exceptions { .. } | AnnotationDefault { .. } | MethodParameters { .. } |
+                RuntimeVisibleParameterAnnotations { .. } | RuntimeInvisibleParameterAnnotations { .. } => owner == Owner::Method,
+                ConstantValue { .. } => owner == Owner::Field,
+                StackMapTable { .. } | LineNumberTable { .. } | LocalVariableTable { .. } | LocalVariableTypeTable { .. } => owner == Owner::Code,
+                SourceFile { .. } | SourceDebugExtenson { .. } | InnerClasses { .. } | EnclosingMethod { .. } | BootstrapMethods { .. } => owner == Owner::Class,
+                Signature { .. } | Synthetic | Deprecated | RuntimeVisibleAnnotations { .. } | RuntimeInvisibleAnnotations { .. } => owner != Owner::Code,
+                RuntimeVisibleTypeAnnotations { .. } | RuntimeInvisibleTypeAnnotations { .. } | Unknown { .. } => true,
+            };
+            if !allowed {
+                self.error(&path, FormatErrorKind::BadAttribute, format!("{} can't appear here", name));
+                continue;
+            }
+            self.attribute(a, &path);
+        }
+    }
+
+    fn attribute(&mut self, a: &Attribute, path: &str) {
+        match a {
+            Code { code, exception_table, attributes, .. } => {
+                if code.is_empty() || code.len() > 65535 {
+                    self.error(path, FormatErrorKind::BadAttribute, "Code must hold between 1 and 65535 bytes".to_owned());
+                }
+                let offsets = instruction_offsets(code);
+                let len = *offsets.last().unwrap_or(&0);
+                if len > 65535 {
+                    self.error(path, FormatErrorKind::BadAttribute, format!("Code is {} bytes long, more than the 65535 allowed", len));
+                }
+                let starts: HashSet<u32> = offsets.iter().cloned().collect();
+                for (i, e) in exception_table.iter().enumerate() {
+                    let path = format!("{}.exception_table[{}]", path, i);
+                    let (start, end, handler) = (e.start_pc as u32, e.end_pc as u32, e.handler_pc as u32);
+                    if start >= end || !starts.contains(&start) || !starts.contains(&end) || handler >= len || !starts.contains(&handler) {
+                        self.error(&path, FormatErrorKind::BadAttribute, "Exception handler range or target is not on an instruction".to_owned());
+                    }
+                    if e.catch_type.as_u16() != 0 {
+                        self.non_array_class(&path, e.catch_type);
+                    }
+                }
+                self.attributes(attributes, path, Owner::Code);
+            }
+            StackMapTable { entries } => {
+                for frame in entries {
+                    let (locals, stack): (&[VerificationTypeInfo], &[VerificationTypeInfo]) = match frame {
+                        StackMapFrame::SameLocals1Item { stack, .. } | StackMapFrame::SameLocals1ItemExtended { stack, .. } => (&[], ::std::slice::from_ref(stack)),
+                        StackMapFrame::AppendFrame { locals, .. } => (locals, &[]),
+                        StackMapFrame::FullFrame { locals, stack, .. } => (locals, stack),
+                        _ => (&[], &[]),
+                    };
+                    for v in locals.iter().chain(stack) {
+                        if let VerificationTypeInfo::Object { cpool_index } = v {
+                            self.class(path, *cpool_index);
+                        }
+                    }
+                }
+            }
+            Exceptions { exception_index_table } => {
+                for i in exception_index_table {
+                    self.non_array_class(path, (*i).into());
+                }
+            }
+            InnerClasses { classes } => {
+                for c in classes {
+                    self.non_array_class(path, c.inner_class_info_index);
+                    if c.outer_class_info_index.as_u16() != 0 {
+                        self.non_array_class(path, c.outer_class_info_index);
+                    }
+                    if c.inner_name_index.as_u16() != 0 {
+                        self.utf8(path, c.inner_name_index);
+                    }
+                }
+            }
+            EnclosingMethod { class_index, method_index } => {
+                self.non_array_class(path, *class_index);
+                if method_index.as_u16() != 0 {
+                    if let Some(NameAndType { name_index, descriptor_index }) = self.expect(path, *method_index, &["NameAndType"]) {
+                        self.utf8(path, *name_index);
+                        self.descriptor(path, *descriptor_index, true);
+                    }
+                }
+            }
+            Signature { signature_index } => {
+                self.utf8(path, *signature_index);
+            }
+            SourceFile { sourcefile_index } => {
+                self.utf8(path, *sourcefile_index);
+            }
+            LocalVariableTable { local_variable_table } => {
+                for v in local_variable_table {
+                    if let Some(name) = self.utf8(path, v.name_index) {
+                        self.name(path, &name, is_unqualified_name, "local variable");
+                    }
+                    self.descriptor(path, v.descriptor_index, false);
+                }
+            }
+            LocalVariableTypeTable { local_variable_type_table } => {
+                for v in local_variable_type_table {
+                    self.utf8(path, v.name_index);
+                    self.utf8(path, v.signature_index);
+                }
+            }
+            RuntimeVisibleAnnotations { annotations } | RuntimeInvisibleAnnotations { annotations } => {
+                for a in annotations {
+                    self.annotation(path, a);
+                }
+            }
+            RuntimeVisibleParameterAnnotations { parameter_annotations } | RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+                for a in parameter_annotations.iter().flatten() {
+                    self.annotation(path, a);
+                }
+            }
+            RuntimeVisibleTypeAnnotations { annotations } | RuntimeInvisibleTypeAnnotations { annotations } => {
+                for a in annotations {
+                    self.descriptor(path, a.type_index, false);
+                    for pair in &a.element_value_pairs {
+                        self.utf8(path, pair.element_name_index);
+                        self.element_value(path, &pair.value);
+                    }
+                }
+            }
+            AnnotationDefault { default_value } => self.element_value(path, default_value),
+            BootstrapMethods { bootstrap_methods } => {
+                for b in bootstrap_methods {
+                    self.expect(path, b.bootstrap_method_ref, &["MethodHandle"]);
+                    for arg in &b.bootstrap_arguments {
+                        self.expect(path, *arg, &["Integer", "Float", "Long", "Double", "Class", "String", "MethodHandle", "MethodType"]);
+                    }
+                }
+            }
+            MethodParameters { parameters } => {
+                for p in parameters {
+                    if p.name_index.as_u16() != 0 {
+                        if let Some(name) = self.utf8(path, p.name_index) {
+                            self.name(path, &name, is_unqualified_name, "parameter");
+                        }
+                    }
+                }
+            }
+            ConstantValue { .. } | Synthetic | Deprecated | SourceDebugExtenson { .. } | LineNumberTable { .. } | Unknown { .. } => {}
+        }
+    }
+
+    fn annotation(&mut self, path: &str, a: &Annotation) {
+        self.descriptor(path, a.type_index, false);
+        for pair in &a.element_value_pairs {
+            self.utf8(path, pair.element_name_index);
+            self.element_value(path, &pair.value);
+        }
+    }
+
+    fn element_value(&mut self, path: &str, v: &ElementValue) {
+        match v {
+            ElementValue::ConstValueIndex { tag, index } => {
+                let expected = match *tag {
+                    b'B' | b'C' | b'I' | b'S' | b'Z' => "Integer",
+                    b'D' => "Double",
+                    b'F' => "Float",
+                    b'J' => "Long",
+                    _ => "Utf8",
+                };
+                self.expect(path, *index, &[expected]);
+            }
+            ElementValue::EnumConstValue { type_name_index, const_name_index } => {
+                self.descriptor(path, *type_name_index, false);
+                self.utf8(path, *const_name_index);
+            }
+            ElementValue::ClassInfoIndex(index) => {
+                if let Some(descriptor) = self.utf8(path, *index) {
+                    if descriptor != "V" && !is_field_descriptor(&descriptor) {
+                        self.error(path, FormatErrorKind::BadDescriptor, format!("{:?} is not a valid return descriptor", descriptor));
+                    }
+                }
+            }
+            ElementValue::AnnotationValue(a) => self.annotation(path, a),
+            ElementValue::ArrayValue(values) => {
+                for v in values {
+                    self.element_value(path, v);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcodes::Opcode::{self, *};
+    use symbolic::SymOpcode;
+    use test_classes::{member, ClassSpec};
+    use self::FormatErrorKind::*;
+
+    /// A class that passes every check, written and read back like a compiled class
+    fn valid() -> JavaClass {
+        let class = ClassSpec::new(0x0021, "p/A", Some("java/lang/Object")).implements(&["java/lang/Runnable"])
+            .field(0x0019, "N", "I").field_attribute(|cp| ConstantValue { constantvalue_index: cp.add_integer(7) })
+            .field(0x0002, "name", "Ljava/lang/String;")
+            .method(0x0001, "<init>", "()V", vec![
+                SymOpcode::Plain(aload_0),
+                SymOpcode::InvokeSpecial(member("java/lang/Object", "<init>", "()V")),
+                SymOpcode::Plain(return_),
+            ])
+            .method(0x0001, "run", "()V", vec![SymOpcode::Plain(return_)])
+            .build();
+        JavaClass::new_from_bytes(class.to_bytes()).unwrap()
+    }
+
+    fn error(path: &str, kind: FormatErrorKind, message: &str) -> FormatError {
+        FormatError { path: path.to_owned(), kind, message: message.to_owned() }
+    }
+
+    #[test]
+    fn valid_class() {
+        assert_eq!(check_format(&valid()), vec![]);
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let mut class = valid();
+        class.super_class = 999.into();
+        let message = format!("Constant pool index 999 is out of range for a pool of length {}", class.constant_pool.len());
+        assert_eq!(check_format(&class), vec![error("super_class", BadReference, &message)]);
+    }
+
+    #[test]
+    fn wrong_kind_of_constant() {
+        let mut class = valid();
+        let name_index = class.fields[1].name_index;
+        class.interfaces[0] = name_index;
+        let message = format!("Constant pool index {} should be Class, but is Utf8", name_index);
+        assert_eq!(check_format(&class), vec![error("interfaces[0]", BadReference, &message)]);
+
+        //a ConstantValue has to match the field's type
+        let mut class = valid();
+        class.fields[0].descriptor_index = class.fields[1].descriptor_index;
+        let index = match class.fields[0].attributes[0] {
+            ConstantValue { constantvalue_index } => constantvalue_index,
+            ref a => panic!("expected ConstantValue, found {:?}", a),
+        };
+        let message = format!("Constant pool index {} should be String, but is Integer", index);
+        assert_eq!(check_format(&class), vec![error("fields[0].ConstantValue", BadReference, &message)]);
+    }
+
+    #[test]
+    fn bad_descriptors() {
+        let mut class = valid();
+        class.fields[1].descriptor_index = class.methods[1].name_index;
+        class.methods[1].descriptor_index = class.fields[1].name_index;
+        assert_eq!(check_format(&class), vec![
+            error("fields[1]", BadDescriptor, "\"run\" is not a valid field descriptor"),
+            error("methods[1]", BadDescriptor, "\"name\" is not a valid method descriptor"),
+        ]);
+
+        let class = ClassSpec::new(0x0421, "p/B", Some("java/lang/Object"))
+            .field(0x0001, "a", "[[L;")
+            .method(0x0401, "m", "(I", vec![])
+            .method(0x0401, "n", "(V)V", vec![])
+            .build();
+        assert_eq!(check_format(&class), vec![
+            error("fields[0]", BadDescriptor, "\"[[L;\" is not a valid field descriptor"),
+            error("methods[0]", BadDescriptor, "\"(I\" is not a valid method descriptor"),
+            error("methods[1]", BadDescriptor, "\"(V)V\" is not a valid method descriptor"),
+        ]);
+    }
+
+    #[test]
+    fn illegal_flags() {
+        let mut class = valid();
+        class.access_flags = 0x0431;
+        class.fields[1].access_flags = 0x0003;
+        class.methods[1].access_flags = 0x0409;
+        assert_eq!(check_format(&class), vec![
+            error("access_flags", IllegalFlags, "A class can't be both final and abstract (flags 0x0431)"),
+            error("fields[1]", IllegalFlags, "A field can have at most one of public, private and protected (flags 0x0003)"),
+            error("methods[1]", IllegalFlags, "An abstract method can't be private, static, final, synchronized, native or strict (flags 0x0409)"),
+            error("methods[1]", BadAttribute, "An abstract or native method can't have a Code attribute"),
+        ]);
+
+        let mut class = valid();
+        class.access_flags = 0x0200;
+        assert_eq!(check_format(&class), vec![
+            error("access_flags", IllegalFlags, "An interface must be abstract and can't be final, super or an enum (flags 0x0200)"),
+            error("fields[1]", IllegalFlags, "An interface field must be public, static and final, and nothing else but synthetic (flags 0x0002)"),
+            error("methods[0]", IllegalFlags,
+                  "An interface method must be public or private, and can't be final, synchronized, native or an instance initializer (flags 0x0001)"),
+        ]);
+    }
+
+    #[test]
+    fn bad_code_length() {
+        let set_code = |class: &mut JavaClass, new_code: Vec<Opcode>| match &mut class.methods[1].attributes[0] {
+            Code { code, .. } => *code = new_code,
+            a => panic!("expected Code, found {:?}", a),
+        };
+        let mut class = valid();
+        set_code(&mut class, vec![]);
+        assert_eq!(check_format(&class), vec![error("methods[1].Code", BadAttribute, "Code must hold between 1 and 65535 bytes")]);
+
+        //65535 instructions, but the last one is 3 bytes long
+        let mut code = vec![nop; 65534];
+        code.push(goto { branch: 0 });
+        let mut class = valid();
+        set_code(&mut class, code);
+        assert_eq!(check_format(&class), vec![error("methods[1].Code", BadAttribute, "Code is 65537 bytes long, more than the 65535 allowed")]);
+    }
+}
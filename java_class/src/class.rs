@@ -7,6 +7,8 @@ use cp_info::*;
 use cp_info::CPInfo::*;
 use fields::FieldInfo;
 use methods::MethodInfo;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
@@ -27,19 +29,23 @@ macro_rules! build_u32 {
     };
 }
 
-/// An enum containing the access flags of a Java Class
-pub enum AccessFlags {
-    Public = 0x0001,
-    Final = 0x0010,
-    Super = 0x0020,
-    Interface = 0x0200,
-    Abstract = 0x0400,
-    Synthetic = 0x1000,
-    Annotation = 0x2000,
-    Enum = 0x4000,
+access_flags! {
+    /// The access flags of a Java Class
+    pub struct AccessFlags {
+        PUBLIC = 0x0001,
+        FINAL = 0x0010,
+        SUPER = 0x0020,
+        INTERFACE = 0x0200,
+        ABSTRACT = 0x0400,
+        SYNTHETIC = 0x1000,
+        ANNOTATION = 0x2000,
+        ENUM = 0x4000,
+        MODULE = 0x8000,
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct JavaClass {
     pub minor_version: u16,
     pub major_version: u16,
@@ -70,20 +76,27 @@ impl JavaClass {
     }
 
     pub fn new(file_name: &str) -> io::Result<JavaClass> {
-        JavaClass::build(JavaClassReader::new(file_name)?)
+        Ok(JavaClass::build(JavaClassReader::new(file_name)?)?)
     }
 
     pub fn new_from_reader<T: Read>(reader: T) -> io::Result<JavaClass> {
-        JavaClass::build(JavaClassReader::new_from_reader(reader)?)
+        Ok(JavaClass::build(JavaClassReader::new_from_reader(reader)?)?)
     }
 
     pub fn new_from_bytes(bytes: Vec<u8>) -> io::Result<JavaClass> {
-        JavaClass::build(JavaClassReader::new_from_bytes(bytes)?)
+        Ok(JavaClass::parse(bytes)?)
+    }
+
+    /// Parses a class file, reporting exactly where and how it's malformed if it is
+    pub fn parse(bytes: Vec<u8>) -> Result<JavaClass, ClassParseError> {
+        JavaClass::build(JavaClassReader::from_buffer(bytes))
     }
 
-    fn build(mut r: JavaClassReader) -> io::Result<JavaClass> {
+    fn build(mut r: JavaClassReader) -> Result<JavaClass, ClassParseError> {
         let magic = r.next32()?;
-        if magic != 0xCAFEBABE { return malformed("Wrong magic number"); }
+        if magic != 0xCAFEBABE {
+            return Err(ClassParseError::WrongMagic { offset: 0, path: String::new(), magic });
+        }
         let minor_version = r.next16()?;
         let major_version = r.next16()?;
         let cp_count = r.next16()?;
@@ -94,48 +107,24 @@ impl JavaClass {
         let super_class = r.next16()?.into();
         let interfaces_count = r.next16()?;
         let mut interfaces = vec!();
-        for _ in 0..interfaces_count {
-            interfaces.push(r.next16()?.into());
+        for i in 0..interfaces_count {
+            interfaces.push(r.next16().map_err(|e| e.within(&format!("interfaces[{}]", i)))?.into());
         }
         let fields_count = r.next16()?;
         let mut fields = Vec::with_capacity(fields_count as usize);
-        for _i in 0..fields_count {
-            fields.push(FieldInfo {
-                access_flags: r.next16()?,
-                name_index: r.next16()?.into(),
-                descriptor_index: r.next16()?.into(),
-                attributes: {
-                    let x = read_attributes(&mut r, &cp);
-                    match x {
-                        Ok(a) => a,
-                        Err(s) => return Err(io::Error::new(io::ErrorKind::InvalidData, s))
-                    }
-                },
-            })
+        for i in 0..fields_count {
+            let (access_flags, name_index, descriptor_index, attributes) = read_member(&mut r, &cp)
+                .map_err(|e| e.within(&format!("fields[{}]", i)))?;
+            fields.push(FieldInfo { access_flags, name_index, descriptor_index, attributes });
         }
         let methods_count = r.next16()?;
         let mut methods = Vec::with_capacity(methods_count as usize);
-        for _i in 0..methods_count {
-            methods.push(MethodInfo {
-                access_flags: r.next16()?,
-                name_index: r.next16()?.into(),
-                descriptor_index: r.next16()?.into(),
-                attributes: {
-                    let x = read_attributes(&mut r, &cp);
-                    match x {
-                        Ok(a) => a,
-                        Err(s) => return Err(io::Error::new(io::ErrorKind::InvalidData, s))
-                    }
-                },
-            })
+        for i in 0..methods_count {
+            let (access_flags, name_index, descriptor_index, attributes) = read_member(&mut r, &cp)
+                .map_err(|e| e.within(&format!("methods[{}]", i)))?;
+            methods.push(MethodInfo { access_flags, name_index, descriptor_index, attributes });
         }
-        let attributes = {
-            let x = read_attributes(&mut r, &cp);
-            match x {
-                Ok(a) => a,
-                Err(s) => return Err(io::Error::new(io::ErrorKind::InvalidData, s))
-            }
-        };
+        let attributes = read_attributes(&mut r, &cp)?;
         Ok(JavaClass {
             minor_version,
             major_version,
@@ -150,59 +139,56 @@ impl JavaClass {
         })
     }
 
+    /// Returns the entries of the class's `BootstrapMethods` attribute, or an empty slice if it has none
+    pub fn bootstrap_methods(&self) -> &[BootstrapMethodsEntry] {
+        for attr in &self.attributes {
+            if let BootstrapMethods { bootstrap_methods } = attr {
+                return bootstrap_methods;
+            }
+        }
+        &[]
+    }
+
+    pub fn flags(&self) -> AccessFlags {
+        AccessFlags::from_bits(self.access_flags)
+    }
+
     pub fn is_interface(&self) -> bool {
-        self.access_flags & AccessFlags::Interface as u16 != 0
+        self.flags().contains(AccessFlags::INTERFACE)
     }
 
     pub fn get_name(&self) -> String {
-        match self.constant_pool[self.this_class] {
-            Class { name_index } => {
-                match &self.constant_pool[name_index] {
-                    Utf8 { bytes, .. } => {
-                        str::from_utf8(bytes).unwrap().to_owned()
-                    }
-                    _ => "Class Pool index did not point to Utf8".to_owned()
-                }
-            }
-            _ => "Class Pool index did not point to Utf8".to_owned()
-        }
+        self.constant_pool.get_class_name(self.this_class)
+            .unwrap_or_else(|| "Class Pool index did not point to Utf8".to_owned())
+    }
+}
+
+/// JSON export of the class file model.
+/// The schema follows the Rust types: structs are objects with the same field names,
+/// enum variants with fields are objects keyed by the variant name, unit variants are strings,
+/// and constant pool indices are plain numbers.
+/// `Utf8` constants hold a string, or an array of bytes if they aren't valid modified UTF-8.
+/// The constant pool array has an element for every index, so the unusable index after each
+/// `Long` and `Double` is the string `"LongDoubleDummy"`, and array element `i` is constant `i + 1`.
+#[cfg(feature = "serde")]
+impl JavaClass {
+    pub fn to_json(&self) -> String {
+        //every map in the model has string keys, so serializing can't fail
+        ::serde_json::to_string(self).expect("the class model always serializes")
+    }
+
+    pub fn from_json(json: &str) -> io::Result<JavaClass> {
+        Ok(::serde_json::from_str(json)?)
     }
 }
 
 /// Reads in the constant pool of a class
-fn build_cp(r: &mut JavaClassReader, cp_count: u16) -> io::Result<ConstantPool> {
+pub(crate) fn build_cp(r: &mut JavaClassReader, cp_count: u16) -> Result<ConstantPool, ClassParseError> {
     let mut cp_vec: Vec<CPInfo> = vec!();
     //we have to use an iterator so we can skip indices for Double and Long
-    let mut iter = 0..cp_count - 1;
+    let mut iter = 0..cp_count.saturating_sub(1);
     while let Some(i) = iter.next() {
-        let tag = r.next8()?;
-        if !(1..=18).contains(&tag) || tag == 2 || tag == 13 || tag == 14 || tag == 17 {
-            return malformed("Invalid constant pool tag");
-        }
-        let x: CPInfo = match tag {
-            7 => Class { name_index: r.next16()?.into() },
-            9 => Fieldref { class_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
-            10 => Methodref { class_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
-            11 => InterfaceMethodref { class_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
-            8 => CPInfo::String { string_index: r.next16()?.into() },
-            3 => Integer { bytes: r.next32()? },
-            4 => Float { bytes: r.next32()? },
-            5 => Long { bytes: r.next64()? },
-            6 => Double { bytes: r.next64()? },
-            12 => NameAndType { name_index: r.next16()?.into(), descriptor_index: r.next16()?.into() },
-            1 => {
-                let length = r.next16()?;
-                let mut bytes: Vec<u8> = vec!();
-                for j in 0..length {
-                    bytes.insert(j as usize, r.next8()?);
-                }
-                Utf8 { length, bytes }
-            }
-            15 => MethodHandle { reference_kind: r.next8()?, reference_index: r.next16()?.into() },
-            16 => MethodType { descriptor_index: r.next16()?.into() },
-            18 => InvokeDynamic { bootstrap_method_attr_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
-            _ => panic!("Unreachable code, wildcard case reached in exhaustive match") //unreachable
-        };
+        let x = read_constant(r).map_err(|e| e.within(&format!("constant_pool[{}]", i + 1)))?;
         //deal with the awful fact that long and double constant pool entries are actually 2 entries
         //seriously, what were they thinking
         //can't we have changed that by now?? class files don't have to be backwards-compatible
@@ -210,8 +196,10 @@ fn build_cp(r: &mut JavaClassReader, cp_count: u16) -> io::Result<ConstantPool>
         match x {
             Double { .. } | Long { .. } => {
                 cp_vec.insert(i as usize, x);
-                iter.next();
-                cp_vec.insert((i + 1) as usize, LongDoubleDummy);
+                //a long or double in the last slot has nothing to take up
+                if iter.next().is_some() {
+                    cp_vec.insert((i + 1) as usize, LongDoubleDummy);
+                }
             }
             _ => {
                 cp_vec.insert(i as usize, x);
@@ -221,309 +209,410 @@ fn build_cp(r: &mut JavaClassReader, cp_count: u16) -> io::Result<ConstantPool>
     Ok(ConstantPool::new_with_info(cp_vec))
 }
 
+/// Reads in a single constant pool entry
+fn read_constant(r: &mut JavaClassReader) -> Result<CPInfo, ClassParseError> {
+    let tag = r.next8()?;
+    Ok(match tag {
+        7 => Class { name_index: r.next16()?.into() },
+        9 => Fieldref { class_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
+        10 => Methodref { class_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
+        11 => InterfaceMethodref { class_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
+        8 => CPInfo::String { string_index: r.next16()?.into() },
+        3 => Integer { bytes: r.next32()? },
+        4 => Float { bytes: r.next32()? },
+        5 => Long { bytes: r.next64()? },
+        6 => Double { bytes: r.next64()? },
+        12 => NameAndType { name_index: r.next16()?.into(), descriptor_index: r.next16()?.into() },
+        1 => {
+            let length = r.next16()?;
+            let mut bytes: Vec<u8> = vec!();
+            for j in 0..length {
+                bytes.insert(j as usize, r.next8()?);
+            }
+            Utf8 { length, bytes }
+        }
+        15 => MethodHandle { reference_kind: r.next8()?, reference_index: r.next16()?.into() },
+        16 => MethodType { descriptor_index: r.next16()?.into() },
+        18 => InvokeDynamic { bootstrap_method_attr_index: r.next16()?.into(), name_and_type_index: r.next16()?.into() },
+        _ => return Err(ClassParseError::InvalidConstantTag { offset: r.dist() - 1, path: String::new(), tag }),
+    })
+}
+
 /// reads a String based on a list of bytes representing a Java-style modified UTF-8 list of bytes
 /// JVM specification §4.4.7
 pub fn read_string(bytes: &[u8]) -> String {
-    let mut chars = Vec::with_capacity(bytes.len());
+    //modified UTF-8 is really an encoding of UTF-16 code units, so decode to those first
+    //and let the standard library deal with the surrogate pairs
+    let mut units = Vec::with_capacity(bytes.len());
     let mut index: usize = 0;
     while index < bytes.len() {
-        let b = bytes[index];
-        if b == 0b11101101 {
-            //supplemental character, requires fixing for rust
-            index += 1;
-            let v = bytes[index];
+        let b = bytes[index] as u16;
+        let cont = |i: usize| bytes.get(index + i).map(|c| *c as u16 & 0x3f).unwrap_or(0);
+        if b & 0x80 == 0 {
+            units.push(b);
             index += 1;
-            let w = bytes[index];
+        } else if b & 0xe0 == 0xc0 {
+            units.push(((b & 0x1f) << 6) | cont(1));
             index += 2;
-            let y = bytes[index];
-            index += 1;
-            let z = bytes[index];
-            let code_point = ((v as u32 & 0x0f) << 16) + ((w as u32 & 0x3f) << 10) + ((y as u32 & 0x0f) << 6) + (z & 0x3f) as u32;
-            chars.push((0b11110000 + ((code_point >> 15) as u8 & 0b00000111)) as char);
-            chars.push((0b10000000 + ((code_point >> 12) as u8 & 0b00111111)) as char);
-            chars.push((0b10000000 + ((code_point >> 6) as u8 & 0b00111111)) as char);
-            chars.push((0b10000000 + (code_point as u8 & 0b00111111)) as char);
+        } else if b & 0xf0 == 0xe0 {
+            units.push(((b & 0x0f) << 12) | (cont(1) << 6) | cont(2));
+            index += 3;
         } else {
-            chars.push(b as char);
+            //not legal in modified UTF-8
+            units.push(0xfffd);
+            index += 1;
         }
-        index += 1;
     }
-    chars.into_iter().collect()
+    String::from_utf16_lossy(&units)
+}
+
+/// encodes a String as a Java-style modified UTF-8 list of bytes, the inverse of `read_string`
+/// JVM specification §4.4.7
+pub fn write_string(s: &str) -> Vec<u8> {
+    let mut ans = Vec::with_capacity(s.len());
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007f => ans.push(unit as u8),
+            0x0000 | 0x0080..=0x07ff => {
+                ans.push(0xc0 | (unit >> 6) as u8);
+                ans.push(0x80 | (unit & 0x3f) as u8);
+            }
+            _ => {
+                ans.push(0xe0 | (unit >> 12) as u8);
+                ans.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                ans.push(0x80 | (unit & 0x3f) as u8);
+            }
+        }
+    }
+    ans
+}
+
+/// reads in the flags, name, descriptor and attributes of a field or method
+fn read_member(r: &mut JavaClassReader, cp: &ConstantPool) -> Result<(u16, CPIndex, CPIndex, Vec<Attribute>), ClassParseError> {
+    Ok((r.next16()?, r.next16()?.into(), r.next16()?.into(), read_attributes(r, cp)?))
 }
 
 /// reads in the attributes of an arbitrary class file element
-fn read_attributes(r: &mut JavaClassReader, cp: &ConstantPool) -> io::Result<Vec<Attribute>> {
+fn read_attributes(r: &mut JavaClassReader, cp: &ConstantPool) -> Result<Vec<Attribute>, ClassParseError> {
     let num = r.next16()?;
-    let mut ans = Vec::with_capacity(num as usize);
-    for _i in 0..num {
+    let mut ans = Vec::with_capacity(r.capacity(num as u32));
+    for i in 0..num {
         let name_index = r.next16()?.into();
         let attribute_length = r.next32()?;
-        let name = match &cp[name_index] {
-            Utf8 { length: _, bytes } => read_string(bytes),
-            _ => return malformed("Attribute tag was not Utf8")
+        let segment = cp.get_utf8(name_index).unwrap_or_else(|| format!("attributes[{}]", i));
+        let start = r.dist();
+        let declared_end = start.saturating_add(attribute_length);
+        let mismatch = |actual| ClassParseError::AttributeLengthMismatch {
+            offset: start,
+            path: segment.clone(),
+            declared: attribute_length,
+            actual,
         };
-        ans.push(match name.as_str() {
-            "ConstantValue" => ConstantValue { constantvalue_index: r.next16()?.into() },
-            "Code" => Code {
-                max_stack: r.next16()?,
-                max_locals: r.next16()?,
-                code: {
-                    let code_len = r.next32()?;
-                    let mut ans = Vec::with_capacity(code_len as usize);
-                    let start = r.dist();
+        match r.limited(attribute_length, |r| read_attribute(r, cp, name_index, attribute_length)) {
+            Ok(a) if r.dist() == declared_end => ans.push(a),
+            Ok(_) => return Err(mismatch(r.dist() - start)),
+            //the contents run past the declared length rather than the end of the file,
+            //so read them again without any limit to report how long they really are
+            Err(ClassParseError::UnexpectedEof { offset, .. }) if offset == declared_end && (offset as usize) < r.buffer.len() => {
+                r.dist = start;
+                let end = ::std::mem::replace(&mut r.end, r.buffer.len() as u32);
+                let _ = read_attribute(r, cp, name_index, attribute_length);
+                r.end = end;
+                return Err(mismatch(r.dist() - start));
+            }
+            Err(e) => return Err(e.within(&segment)),
+        }
+    }
+    Ok(ans)
+}
+
+/// reads in the body of a single attribute, after its name index and length
+pub(crate) fn read_attribute(r: &mut JavaClassReader, cp: &ConstantPool, name_index: CPIndex, attribute_length: u32) -> Result<Attribute, ClassParseError> {
+    let name = match cp.get(name_index) {
+        Some(Utf8 { length: _, bytes }) => read_string(bytes),
+        _ => return Err(ClassParseError::InvalidAttributeName { offset: r.dist(), path: String::new(), name_index })
+    };
+    Ok(match name.as_str() {
+        "ConstantValue" => ConstantValue { constantvalue_index: r.next16()?.into() },
+        "Code" => Code {
+            max_stack: r.next16()?,
+            max_locals: r.next16()?,
+            code: {
+                let code_len = r.next32()?;
+                let start = r.dist();
+                //the last instruction can't run on into the exception table
+                r.limited(code_len, |r| {
+                    let mut ans = Vec::with_capacity(r.capacity(code_len));
                     while r.dist() - start < code_len {
-                        ans.push(to_opcode(r, start).ok_or_else(|| malformed_inner("Invalid bytecode"))?);
-                    }
-                    ans
-                },
-                exception_table: {
-                    let len = r.next16()?;
-                    let mut ans = Vec::with_capacity(len as usize);
-                    for _i in 0..len {
-                        ans.push(ExceptionTableEntry {
-                            start_pc: r.next16()?,
-                            end_pc: r.next16()?,
-                            handler_pc: r.next16()?,
-                            catch_type: r.next16()?.into(),
-                        });
+                        let pc = r.dist() - start;
+                        match to_opcode(r, start) {
+                            Some(op) => ans.push(op),
+                            None => return Err(r.invalid_instruction(start + pc).within(&format!("code@pc {}", pc))),
+                        }
                     }
-                    ans
-                },
-                attributes: {
-                    read_attributes(r, cp)?
-                },
+                    Ok(ans)
+                })?
             },
-            "StackMapTable" => StackMapTable {
-                entries: {
-                    let num = r.next16()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        let tag = r.next8()?;
-                        ans.push(match tag {
-                            0..=63 => StackMapFrame::SameFrame { offset_delta: tag },
-                            64..=127 => StackMapFrame::SameLocals1Item {
-                                offset_delta: tag - 64,
-                                stack: read_verification_type_info(r)?,
-                            },
-                            247 => StackMapFrame::SameLocals1ItemExtended {
-                                offset_delta: r.next16()?,
-                                stack: read_verification_type_info(r)?,
-                            },
-                            248..=250 => StackMapFrame::ChopFrame {
-                                absent_locals: 251 - tag,
-                                offset_delta: r.next16()?,
-                            },
-                            251 => StackMapFrame::SameFrameExtended {
-                                offset_delta: r.next16()?
+            exception_table: {
+                let len = r.next16()?;
+                let mut ans = Vec::with_capacity(len as usize);
+                for _i in 0..len {
+                    ans.push(ExceptionTableEntry {
+                        start_pc: r.next16()?,
+                        end_pc: r.next16()?,
+                        handler_pc: r.next16()?,
+                        catch_type: r.next16()?.into(),
+                    });
+                }
+                ans
+            },
+            attributes: {
+                r.nested(|r| read_attributes(r, cp))?
+            },
+        },
+        "StackMapTable" => StackMapTable {
+            entries: {
+                let num = r.next16()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for i in 0..num {
+                    let tag = r.next8()?;
+                    ans.push(match tag {
+                        0..=63 => StackMapFrame::SameFrame { offset_delta: tag },
+                        64..=127 => StackMapFrame::SameLocals1Item {
+                            offset_delta: tag - 64,
+                            stack: read_verification_type_info(r)?,
+                        },
+                        247 => StackMapFrame::SameLocals1ItemExtended {
+                            offset_delta: r.next16()?,
+                            stack: read_verification_type_info(r)?,
+                        },
+                        248..=250 => StackMapFrame::ChopFrame {
+                            absent_locals: 251 - tag,
+                            offset_delta: r.next16()?,
+                        },
+                        251 => StackMapFrame::SameFrameExtended {
+                            offset_delta: r.next16()?
+                        },
+                        252..=254 => StackMapFrame::AppendFrame {
+                            offset_delta: r.next16()?,
+                            locals: {
+                                let mut ans = vec!();
+                                for _i in 0..(tag - 251) {
+                                    ans.push(read_verification_type_info(r)?);
+                                }
+                                ans
                             },
-                            252..=254 => StackMapFrame::AppendFrame {
-                                offset_delta: r.next16()?,
-                                locals: {
-                                    let mut ans = vec!();
-                                    for _i in 0..(tag - 251) {
-                                        ans.push(read_verification_type_info(r)?);
-                                    }
-                                    ans
-                                },
+                        },
+                        255 => StackMapFrame::FullFrame {
+                            offset_delta: r.next16()?,
+                            locals: {
+                                let mut ans = vec!();
+                                for _i in 0..r.next16()? {
+                                    ans.push(read_verification_type_info(r)?);
+                                }
+                                ans
                             },
-                            255 => StackMapFrame::FullFrame {
-                                offset_delta: r.next16()?,
-                                locals: {
-                                    let mut ans = vec!();
-                                    for _i in 0..r.next16()? {
-                                        ans.push(read_verification_type_info(r)?);
-                                    }
-                                    ans
-                                },
-                                stack: {
-                                    let mut ans = vec!();
-                                    for _i in 0..r.next16()? {
-                                        ans.push(read_verification_type_info(r)?);
-                                    }
-                                    ans
-                                },
+                            stack: {
+                                let mut ans = vec!();
+                                for _i in 0..r.next16()? {
+                                    ans.push(read_verification_type_info(r)?);
+                                }
+                                ans
                             },
-                            _ => return malformed("Invalid stackmapframe tag")
-                        });
-                    }
-                    ans
+                        },
+                        _ => return Err(ClassParseError::InvalidStackMapFrameTag {
+                            offset: r.dist() - 1,
+                            path: format!("entries[{}]", i),
+                            tag,
+                        })
+                    });
                 }
-            },
-            "Exceptions" => Exceptions {
-                exception_index_table: {
-                    let num = r.next16()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        ans.push(r.next16()?);
-                    }
-                    ans
+                ans
+            }
+        },
+        "Exceptions" => Exceptions {
+            exception_index_table: {
+                let num = r.next16()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for _i in 0..num {
+                    ans.push(r.next16()?);
                 }
-            },
-            "InnerClasses" => InnerClasses {
-                classes: {
-                    let num = r.next16()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        ans.push(InnerClassInfo {
-                            inner_class_info_index: r.next16()?.into(),
-                            outer_class_info_index: r.next16()?.into(),
-                            inner_name_index: r.next16()?.into(),
-                            inner_class_access_flags: r.next16()?,
-                        })
-                    }
-                    ans
+                ans
+            }
+        },
+        "InnerClasses" => InnerClasses {
+            classes: {
+                let num = r.next16()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for _i in 0..num {
+                    ans.push(InnerClassInfo {
+                        inner_class_info_index: r.next16()?.into(),
+                        outer_class_info_index: r.next16()?.into(),
+                        inner_name_index: r.next16()?.into(),
+                        inner_class_access_flags: r.next16()?,
+                    })
                 }
-            },
-            "EnclosingMethod" => EnclosingMethod {
-                class_index: r.next16()?.into(),
-                method_index: r.next16()?.into(),
-            },
-            "Synthetic" => Synthetic,
-            "Signature" => Signature {
-                signature_index: r.next16()?.into()
-            },
-            "SourceFile" => SourceFile {
-                sourcefile_index: r.next16()?.into()
-            },
-            "SourceDebugExtension" => SourceDebugExtenson {
-                debug_extension: {
-                    let mut ans = Vec::with_capacity(r.next16()? as usize);
-                    for _i in 0..attribute_length {
-                        ans.push(r.next8()?)
-                    }
-                    ans
+                ans
+            }
+        },
+        "EnclosingMethod" => EnclosingMethod {
+            class_index: r.next16()?.into(),
+            method_index: r.next16()?.into(),
+        },
+        "Synthetic" => Synthetic,
+        "Signature" => Signature {
+            signature_index: r.next16()?.into()
+        },
+        "SourceFile" => SourceFile {
+            sourcefile_index: r.next16()?.into()
+        },
+        "SourceDebugExtension" => SourceDebugExtenson {
+            debug_extension: {
+                let mut ans = Vec::with_capacity(r.capacity(attribute_length));
+                for _i in 0..attribute_length {
+                    ans.push(r.next8()?)
                 }
-            },
-            "LineNumberTable" => LineNumberTable {
-                line_number_table: {
-                    let len = r.next16()?;
-                    let mut ans = Vec::with_capacity(len as usize);
-                    for _i in 0..len {
-                        ans.push(LineNumberTableEntry {
-                            start_pc: r.next16()?,
-                            line_number: r.next16()?,
-                        })
-                    }
-                    ans
+                ans
+            }
+        },
+        "LineNumberTable" => LineNumberTable {
+            line_number_table: {
+                let len = r.next16()?;
+                let mut ans = Vec::with_capacity(len as usize);
+                for _i in 0..len {
+                    ans.push(LineNumberTableEntry {
+                        start_pc: r.next16()?,
+                        line_number: r.next16()?,
+                    })
                 }
-            },
-            "LocalVariableTable" => LocalVariableTable {
-                local_variable_table: {
-                    let len = r.next16()?;
-                    let mut ans = Vec::with_capacity(len as usize);
-                    for _i in 0..len {
-                        ans.push(LocalVariableTableEntry {
-                            start_pc: r.next16()?,
-                            length: r.next16()?,
-                            name_index: r.next16()?.into(),
-                            descriptor_index: r.next16()?.into(),
-                            index: r.next16()?,
-                        })
-                    }
-                    ans
+                ans
+            }
+        },
+        "LocalVariableTable" => LocalVariableTable {
+            local_variable_table: {
+                let len = r.next16()?;
+                let mut ans = Vec::with_capacity(len as usize);
+                for _i in 0..len {
+                    ans.push(LocalVariableTableEntry {
+                        start_pc: r.next16()?,
+                        length: r.next16()?,
+                        name_index: r.next16()?.into(),
+                        descriptor_index: r.next16()?.into(),
+                        index: r.next16()?,
+                    })
                 }
-            },
-            "LocalVariableTypeTable" => LocalVariableTypeTable {
-                local_variable_type_table: {
-                    let len = r.next16()?;
-                    let mut ans = Vec::with_capacity(len as usize);
-                    for _i in 0..len {
-                        ans.push(LocalVariableTypeTableEntry {
-                            start_pc: r.next16()?,
-                            length: r.next16()?,
-                            name_index: r.next16()?.into(),
-                            signature_index: r.next16()?.into(),
-                            index: r.next16()?,
-                        })
-                    }
-                    ans
+                ans
+            }
+        },
+        "LocalVariableTypeTable" => LocalVariableTypeTable {
+            local_variable_type_table: {
+                let len = r.next16()?;
+                let mut ans = Vec::with_capacity(len as usize);
+                for _i in 0..len {
+                    ans.push(LocalVariableTypeTableEntry {
+                        start_pc: r.next16()?,
+                        length: r.next16()?,
+                        name_index: r.next16()?.into(),
+                        signature_index: r.next16()?.into(),
+                        index: r.next16()?,
+                    })
                 }
-            },
-            "Deprecated" => Deprecated,
-            "RuntimeVisibleAnnotations" => RuntimeVisibleAnnotations {
-                annotations: read_annotations(r)?
-            },
-            "RuntimeInvisibleAnnotations" => RuntimeInvisibleAnnotations {
-                annotations: read_annotations(r)?
-            },
-            "RuntimeVisibleParameterAnnotations" => RuntimeVisibleParameterAnnotations {
-                parameter_annotations: {
-                    let num = r.next8()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        ans.push(read_annotations(r)?);
-                    }
-                    ans
+                ans
+            }
+        },
+        "Deprecated" => Deprecated,
+        "RuntimeVisibleAnnotations" => RuntimeVisibleAnnotations {
+            annotations: read_annotations(r)?
+        },
+        "RuntimeInvisibleAnnotations" => RuntimeInvisibleAnnotations {
+            annotations: read_annotations(r)?
+        },
+        "RuntimeVisibleParameterAnnotations" => RuntimeVisibleParameterAnnotations {
+            parameter_annotations: {
+                let num = r.next8()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for i in 0..num {
+                    ans.push(read_annotations(r).map_err(|e| e.within(&format!("parameter_annotations[{}]", i)))?);
                 }
-            },
-            "RuntimeInvisibleParameterAnnotations" => RuntimeInvisibleParameterAnnotations {
-                parameter_annotations: {
-                    let num = r.next8()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        ans.push(read_annotations(r)?);
-                    }
-                    ans
+                ans
+            }
+        },
+        "RuntimeInvisibleParameterAnnotations" => RuntimeInvisibleParameterAnnotations {
+            parameter_annotations: {
+                let num = r.next8()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for i in 0..num {
+                    ans.push(read_annotations(r).map_err(|e| e.within(&format!("parameter_annotations[{}]", i)))?);
                 }
-            },
-            "RuntimeVisibleTypeAnnotations" => RuntimeVisibleTypeAnnotations {
-                annotations: read_type_annotations(r)?
-            },
-            "RuntimeInvisibleTypeAnnotations" => RuntimeInvisibleTypeAnnotations {
-                annotations: read_type_annotations(r)?
-            },
-            "AnnotationDefault" => AnnotationDefault {
-                default_value: read_element_value(r)?
-            },
-            "BootstrapMethods" => BootstrapMethods {
-                bootstrap_methods: {
-                    let num = r.next16()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        ans.push(BootstrapMethodsEntry {
-                            bootstrap_method_ref: r.next16()?.into(),
-                            bootstrap_arguments: {
-                                let numb = r.next16()?;
-                                let mut bas = Vec::with_capacity(numb as usize);
-                                for _j in 0..numb {
-                                    bas.push(r.next16()?.into());
-                                }
-                                bas
-                            },
-                        });
-                    }
-                    ans
+                ans
+            }
+        },
+        "RuntimeVisibleTypeAnnotations" => RuntimeVisibleTypeAnnotations {
+            annotations: read_type_annotations(r)?
+        },
+        "RuntimeInvisibleTypeAnnotations" => RuntimeInvisibleTypeAnnotations {
+            annotations: read_type_annotations(r)?
+        },
+        "AnnotationDefault" => AnnotationDefault {
+            default_value: read_element_value(r)?
+        },
+        "BootstrapMethods" => BootstrapMethods {
+            bootstrap_methods: {
+                let num = r.next16()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for _i in 0..num {
+                    ans.push(BootstrapMethodsEntry {
+                        bootstrap_method_ref: r.next16()?.into(),
+                        bootstrap_arguments: {
+                            let numb = r.next16()?;
+                            let mut bas = Vec::with_capacity(numb as usize);
+                            for _j in 0..numb {
+                                bas.push(r.next16()?.into());
+                            }
+                            bas
+                        },
+                    });
                 }
-            },
-            "MethodParameters" => MethodParameters {
-                parameters: {
-                    let num = r.next16()?;
-                    let mut ans = Vec::with_capacity(num as usize);
-                    for _i in 0..num {
-                        ans.push(MethodParameterEntry {
-                            name_index: r.next16()?.into(),
-                            access_flags: r.next16()?,
-                        });
-                    }
-                    ans
+                ans
+            }
+        },
+        "MethodParameters" => MethodParameters {
+            parameters: {
+                let num = r.next8()?;
+                let mut ans = Vec::with_capacity(num as usize);
+                for _i in 0..num {
+                    ans.push(MethodParameterEntry {
+                        name_index: r.next16()?.into(),
+                        access_flags: r.next16()?,
+                    });
                 }
-            },
-            _ => return malformed("invalid attribute name")
-        });
-    }
-    Ok(ans)
+                ans
+            }
+        },
+        //JVM specification §4.7.1: attributes that aren't recognized must be silently ignored
+        _ => Unknown {
+            name_index,
+            info: {
+                let mut ans = Vec::with_capacity(r.capacity(attribute_length));
+                for _i in 0..attribute_length {
+                    ans.push(r.next8()?)
+                }
+                ans
+            }
+        }
+    })
 }
 
-fn read_type_annotations(r: &mut JavaClassReader) -> io::Result<Vec<TypeAnnotation>> {
+fn read_type_annotations(r: &mut JavaClassReader) -> Result<Vec<TypeAnnotation>, ClassParseError> {
     let len = r.next16()?;
     let mut ans = Vec::with_capacity(len as usize);
-    for _i in 0..len {
-        ans.push(read_type_annotation(r)?);
+    for i in 0..len {
+        ans.push(read_type_annotation(r).map_err(|e| e.within(&format!("annotations[{}]", i)))?);
     }
     Ok(ans)
 }
 
-fn read_type_annotation(r: &mut JavaClassReader) -> io::Result<TypeAnnotation> {
+fn read_type_annotation(r: &mut JavaClassReader) -> Result<TypeAnnotation, ClassParseError> {
     let target_type = r.next8()?;
     let target_info = match target_type {
         0x00 | 0x01 => TargetInfo::TypeParameterTarget { type_parameter_index: r.next8()? },
@@ -535,7 +624,7 @@ fn read_type_annotation(r: &mut JavaClassReader) -> io::Result<TypeAnnotation> {
         0x40 | 0x41 => TargetInfo::LocalVarTarget {
             table: {
                 let len = r.next16()?;
-                let mut ans = Vec::with_capacity(r.next16()? as usize);
+                let mut ans = Vec::with_capacity(len as usize);
                 for _i in 0..len {
                     ans.push(LocalVarTagetTableEntry {
                         start_pc: r.next16()?,
@@ -549,7 +638,7 @@ fn read_type_annotation(r: &mut JavaClassReader) -> io::Result<TypeAnnotation> {
         0x42 => TargetInfo::CatchTarget { exception_table_index: r.next16()? },
         0x43 | 0x44 | 0x45 | 0x46 => TargetInfo::OffsetTarget { offset: r.next16()? },
         0x47 | 0x48 | 0x49 | 0x4A | 0x4B => TargetInfo::TypeArgumentTarget { offset: r.next16()?, type_argument_index: r.next8()? },
-        _ => return malformed("Bad target info tag")
+        _ => return Err(ClassParseError::InvalidTargetType { offset: r.dist() - 1, path: String::new(), target_type })
     };
     let target_path = TypePath {
         path: {
@@ -567,13 +656,14 @@ fn read_type_annotation(r: &mut JavaClassReader) -> io::Result<TypeAnnotation> {
     let type_index = r.next16()?.into();
     let num_ev = r.next16()?;
     let mut element_value_pairs = Vec::with_capacity(num_ev as usize);
-    for _i in 0..num_ev {
+    for i in 0..num_ev {
         element_value_pairs.push(ElementValuePair {
             element_name_index: r.next16()?.into(),
-            value: read_element_value(r)?,
+            value: read_element_value(r).map_err(|e| e.within(&format!("element_value_pairs[{}]", i)))?,
         });
     }
     Ok(TypeAnnotation {
+        target_type,
         target_info,
         target_path,
         type_index,
@@ -581,23 +671,23 @@ fn read_type_annotation(r: &mut JavaClassReader) -> io::Result<TypeAnnotation> {
     })
 }
 
-fn read_annotations(r: &mut JavaClassReader) -> io::Result<Vec<Annotation>> {
+fn read_annotations(r: &mut JavaClassReader) -> Result<Vec<Annotation>, ClassParseError> {
     let len = r.next16()?;
     let mut ans = Vec::with_capacity(len as usize);
-    for _i in 0..len {
-        ans.push(read_annotation(r)?);
+    for i in 0..len {
+        ans.push(read_annotation(r).map_err(|e| e.within(&format!("annotations[{}]", i)))?);
     }
     Ok(ans)
 }
 
-fn read_annotation(r: &mut JavaClassReader) -> io::Result<Annotation> {
+fn read_annotation(r: &mut JavaClassReader) -> Result<Annotation, ClassParseError> {
     let type_index = r.next16()?.into();
     let len = r.next16()?;
     let mut ans = Vec::with_capacity(len as usize);
-    for _i in 0..len {
+    for i in 0..len {
         ans.push(ElementValuePair {
             element_name_index: r.next16()?.into(),
-            value: read_element_value(r)?,
+            value: read_element_value(r).map_err(|e| e.within(&format!("element_value_pairs[{}]", i)))?,
         })
     }
     Ok(Annotation {
@@ -606,29 +696,29 @@ fn read_annotation(r: &mut JavaClassReader) -> io::Result<Annotation> {
     })
 }
 
-fn read_element_value(r: &mut JavaClassReader) -> io::Result<ElementValue> {
+fn read_element_value(r: &mut JavaClassReader) -> Result<ElementValue, ClassParseError> {
     let tag = r.next8()?;
     Ok(match tag as char {
-        'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 's' => ElementValue::ConstValueIndex(r.next16()?.into()),
+        'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 's' => ElementValue::ConstValueIndex { tag, index: r.next16()?.into() },
         'e' => ElementValue::EnumConstValue {
             type_name_index: r.next16()?.into(),
             const_name_index: r.next16()?.into(),
         },
         'c' => ElementValue::ClassInfoIndex(r.next16()?.into()),
-        '@' => ElementValue::AnnotationValue(read_annotation(r)?),
-        '[' => ElementValue::ArrayValue({
+        '@' => ElementValue::AnnotationValue(r.nested(read_annotation)?),
+        '[' => ElementValue::ArrayValue(r.nested(|r| {
             let num = r.next16()?;
-            let mut ans = Vec::with_capacity(num as usize);
-            for _i in 0..num {
-                ans.push(read_element_value(r)?);
+            let mut ans = Vec::with_capacity(r.capacity(num as u32));
+            for i in 0..num {
+                ans.push(read_element_value(r).map_err(|e| e.within(&format!("values[{}]", i)))?);
             }
-            ans
-        }),
-        _ => return malformed("invalid elementvalue tag")
+            Ok(ans)
+        })?),
+        _ => return Err(ClassParseError::InvalidElementValueTag { offset: r.dist() - 1, path: String::new(), tag })
     })
 }
 
-fn read_verification_type_info(r: &mut JavaClassReader) -> io::Result<VerificationTypeInfo> {
+fn read_verification_type_info(r: &mut JavaClassReader) -> Result<VerificationTypeInfo, ClassParseError> {
     let tag = r.next8()?;
     Ok(match tag {
         0 => VerificationTypeInfo::Top,
@@ -640,21 +730,117 @@ fn read_verification_type_info(r: &mut JavaClassReader) -> io::Result<Verificati
         8 => VerificationTypeInfo::UninitializedVariable { offset: r.next16()? },
         4 => VerificationTypeInfo::Long,
         3 => VerificationTypeInfo::Double,
-        _ => return malformed("Bad verification type info number")
+        _ => return Err(ClassParseError::InvalidVerificationTypeTag { offset: r.dist() - 1, path: String::new(), tag })
     })
 }
 
-fn malformed<T>(err: &str) -> io::Result<T> {
-    Err(io::Error::new(io::ErrorKind::InvalidData, format!("Malformed class file: {}", err)))
+/// Where and how a class file failed to parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassParseError {
+    /// the file ended in the middle of a structure
+    UnexpectedEof { offset: u32, path: String },
+    /// the file didn't start with `0xCAFEBABE`
+    WrongMagic { offset: u32, path: String, magic: u32 },
+    /// a constant pool entry had a tag the JVM specification doesn't define
+    InvalidConstantTag { offset: u32, path: String, tag: u8 },
+    /// an attribute's name index didn't point to a Utf8 constant
+    InvalidAttributeName { offset: u32, path: String, name_index: CPIndex },
+    /// an attribute's contents took up more or less than its declared length.
+    /// Contents that run past it are read as far as they go, so `actual` is only a lower bound if they fail to parse.
+    AttributeLengthMismatch { offset: u32, path: String, declared: u32, actual: u32 },
+    /// `wide` was followed by an opcode that can't be widened
+    InvalidOpcode { offset: u32, path: String, opcode: u8 },
+    InvalidStackMapFrameTag { offset: u32, path: String, tag: u8 },
+    InvalidVerificationTypeTag { offset: u32, path: String, tag: u8 },
+    InvalidTargetType { offset: u32, path: String, target_type: u8 },
+    InvalidElementValueTag { offset: u32, path: String, tag: u8 },
+    /// attributes or annotation values were nested more than `MAX_NESTING` deep
+    NestedTooDeeply { offset: u32, path: String },
 }
-fn malformed_inner(err: &str) -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidData, format!("Malformed class file: {}", err))
+
+impl ClassParseError {
+    /// The position in the file where the offending structure starts
+    pub fn offset(&self) -> u32 {
+        use self::ClassParseError::*;
+        match *self {
+            UnexpectedEof { offset, .. } | WrongMagic { offset, .. } | InvalidConstantTag { offset, .. } |
+            InvalidAttributeName { offset, .. } | AttributeLengthMismatch { offset, .. } | InvalidOpcode { offset, .. } |
+            InvalidStackMapFrameTag { offset, .. } | InvalidVerificationTypeTag { offset, .. } |
+            InvalidTargetType { offset, .. } | InvalidElementValueTag { offset, .. } | NestedTooDeeply { offset, .. } => offset,
+        }
+    }
+
+    /// The structure that was being read, such as `methods[3].Code.code@pc 17`
+    pub fn path(&self) -> &str {
+        use self::ClassParseError::*;
+        match self {
+            UnexpectedEof { path, .. } | WrongMagic { path, .. } | InvalidConstantTag { path, .. } |
+            InvalidAttributeName { path, .. } | AttributeLengthMismatch { path, .. } | InvalidOpcode { path, .. } |
+            InvalidStackMapFrameTag { path, .. } | InvalidVerificationTypeTag { path, .. } |
+            InvalidTargetType { path, .. } | InvalidElementValueTag { path, .. } | NestedTooDeeply { path, .. } => path,
+        }
+    }
+
+    /// prefixes the path with the structure containing the one that failed
+    fn within(mut self, segment: &str) -> ClassParseError {
+        use self::ClassParseError::*;
+        match &mut self {
+            UnexpectedEof { path, .. } | WrongMagic { path, .. } | InvalidConstantTag { path, .. } |
+            InvalidAttributeName { path, .. } | AttributeLengthMismatch { path, .. } | InvalidOpcode { path, .. } |
+            InvalidStackMapFrameTag { path, .. } | InvalidVerificationTypeTag { path, .. } |
+            InvalidTargetType { path, .. } | InvalidElementValueTag { path, .. } | NestedTooDeeply { path, .. } => {
+                *path = if path.is_empty() { segment.to_owned() } else { format!("{}.{}", segment, path) };
+            }
+        }
+        self
+    }
 }
 
+impl fmt::Display for ClassParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ClassParseError::*;
+        write!(f, "Malformed class file: ")?;
+        match self {
+            UnexpectedEof { .. } => write!(f, "Reached eof early")?,
+            WrongMagic { magic, .. } => write!(f, "Wrong magic number {:#010x}", magic)?,
+            InvalidConstantTag { tag, .. } => write!(f, "Invalid constant pool tag {}", tag)?,
+            InvalidAttributeName { name_index, .. } => write!(f, "Attribute name #{} was not Utf8", name_index)?,
+            AttributeLengthMismatch { declared, actual, .. } =>
+                write!(f, "Attribute length {} did not match its contents ({} bytes)", declared, actual)?,
+            InvalidOpcode { opcode, .. } => write!(f, "Invalid bytecode {:#04x}", opcode)?,
+            InvalidStackMapFrameTag { tag, .. } => write!(f, "Invalid stackmapframe tag {}", tag)?,
+            InvalidVerificationTypeTag { tag, .. } => write!(f, "Bad verification type info number {}", tag)?,
+            InvalidTargetType { target_type, .. } => write!(f, "Bad target info tag {:#04x}", target_type)?,
+            InvalidElementValueTag { tag, .. } => write!(f, "Invalid element value tag {:?}", *tag as char)?,
+            NestedTooDeeply { .. } => write!(f, "Structures nested more than {} deep", MAX_NESTING)?,
+        }
+        if !self.path().is_empty() {
+            write!(f, " in {}", self.path())?;
+        }
+        write!(f, " at offset {:#x}", self.offset())
+    }
+}
+
+impl Error for ClassParseError {}
+
+impl From<ClassParseError> for io::Error {
+    /// wraps the error as `InvalidData`, so the original can be recovered with `get_ref` and `downcast_ref`
+    fn from(e: ClassParseError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// How deeply Code attributes and annotation values may contain themselves,
+/// so hostile input can't overflow the stack
+pub const MAX_NESTING: u32 = 64;
+
 /// an abstraction for reading bytes of a .class
 pub struct JavaClassReader {
     buffer: Vec<u8>,
     dist: u32,
+    depth: u32,
+    /// reads stop here: the end of the buffer, or of the attribute being read
+    end: u32,
 }
 
 impl JavaClassReader {
@@ -664,36 +850,181 @@ impl JavaClassReader {
     fn new(file_name: &str) -> io::Result<JavaClassReader> {
         let mut buffer = Vec::with_capacity(::std::fs::metadata(file_name)?.len() as usize);
         File::open(file_name)?.read_to_end(&mut buffer)?;
-        Ok(JavaClassReader { buffer, dist: 0 })
+        Ok(JavaClassReader::from_buffer(buffer))
     }
     /// creates a JavaClassReader by using a Rust struct implementing Read
     fn new_from_reader<T: Read>(mut reader: T) -> io::Result<JavaClassReader> {
         let mut buffer = vec!();
         reader.read_to_end(&mut buffer)?;
-        Ok(JavaClassReader { buffer, dist: 0 })
+        Ok(JavaClassReader::from_buffer(buffer))
     }
     /// creates a JavaClassReader by using a Vec of bytes
-    fn new_from_bytes(bytes: Vec<u8>) -> io::Result<JavaClassReader> {
-        Ok(JavaClassReader { buffer: bytes, dist: 0 })
+    pub(crate) fn new_from_bytes(bytes: Vec<u8>) -> io::Result<JavaClassReader> {
+        Ok(JavaClassReader::from_buffer(bytes))
+    }
+    fn from_buffer(buffer: Vec<u8>) -> JavaClassReader {
+        let end = buffer.len().min(u32::MAX as usize) as u32;
+        JavaClassReader { buffer, dist: 0, depth: 0, end }
     }
-    pub fn next8(&mut self) -> io::Result<u8> {
-        if self.dist as usize >= self.buffer.len() {
-            return malformed("Reached eof early");
+    pub fn next8(&mut self) -> Result<u8, ClassParseError> {
+        if self.dist >= self.end {
+            return Err(ClassParseError::UnexpectedEof { offset: self.dist, path: String::new() });
         }
         let ans = self.buffer[self.dist as usize];
         self.dist += 1;
         Ok(ans)
     }
-    pub fn next16(&mut self) -> io::Result<u16> {
+    pub fn next16(&mut self) -> Result<u16, ClassParseError> {
         Ok(build_u16!(self.next8()?, self.next8()?))
     }
-    pub fn next32(&mut self) -> io::Result<u32> {
+    pub fn next32(&mut self) -> Result<u32, ClassParseError> {
         Ok(build_u32!(self.next8()?, self.next8()?, self.next8()?, self.next8()?))
     }
-    pub fn next64(&mut self) -> io::Result<u64> {
+    pub fn next64(&mut self) -> Result<u64, ClassParseError> {
         Ok(((self.next32()? as u64) << 32) | (self.next32()? as u64))
     }
     pub fn dist(&self) -> u32 {
         self.dist
     }
-}
\ No newline at end of file
+    /// How many of `count` items to preallocate room for, since each takes at least a byte of what's left
+    fn capacity(&self, count: u32) -> usize {
+        (count as usize).min(self.end.saturating_sub(self.dist) as usize)
+    }
+    /// reads a structure that declares its length as `len`, so it can't read past that
+    fn limited<T, F: FnOnce(&mut JavaClassReader) -> Result<T, ClassParseError>>(&mut self, len: u32, read: F) -> Result<T, ClassParseError> {
+        let end = self.end;
+        self.end = end.min(self.dist.saturating_add(len));
+        let ans = read(self);
+        self.end = end;
+        ans
+    }
+    /// reads a structure that can contain itself, failing once they're nested `MAX_NESTING` deep
+    fn nested<T, F: FnOnce(&mut JavaClassReader) -> Result<T, ClassParseError>>(&mut self, read: F) -> Result<T, ClassParseError> {
+        if self.depth >= MAX_NESTING {
+            return Err(ClassParseError::NestedTooDeeply { offset: self.dist, path: String::new() });
+        }
+        self.depth += 1;
+        let ans = read(self);
+        self.depth -= 1;
+        ans
+    }
+    /// the error for the instruction at `offset` that `to_opcode` couldn't read
+    fn invalid_instruction(&self, offset: u32) -> ClassParseError {
+        if self.dist >= self.end {
+            ClassParseError::UnexpectedEof { offset: self.dist, path: String::new() }
+        } else {
+            ClassParseError::InvalidOpcode { offset, path: String::new(), opcode: self.buffer[offset as usize] }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builders::cp::CPBuilder;
+    use methods::MethodInfo;
+    use opcodes::Opcode;
+
+    /// a class with one method, whose code has a `LineNumberTable`, followed by `SourceFile` and `Deprecated`
+    fn class_bytes() -> Vec<u8> {
+        let mut cp = CPBuilder::new();
+        let mut class = JavaClass::empty();
+        class.major_version = 52;
+        class.this_class = cp.add_class("T".to_owned());
+        class.super_class = cp.add_class("java/lang/Object".to_owned());
+        let line_number_table = vec![LineNumberTableEntry { start_pc: 0, line_number: 1 }];
+        let code = Code { max_stack: 0, max_locals: 0, code: vec![Opcode::return_], exception_table: vec![], attributes: vec![LineNumberTable { line_number_table }] };
+        class.methods.push(MethodInfo { access_flags: 0x0008, name_index: cp.add_utf8("m".to_owned()), descriptor_index: cp.add_utf8("()V".to_owned()), attributes: vec![code] });
+        class.attributes = vec![SourceFile { sourcefile_index: cp.add_utf8("T.java".to_owned()) }, Deprecated];
+        class.constant_pool = cp.build();
+        class.to_bytes()
+    }
+
+    /// parses `bytes` with the big-endian length at `at` replaced by `length`
+    fn parse_with_length(mut bytes: Vec<u8>, at: usize, length: u32) -> Result<JavaClass, ClassParseError> {
+        bytes[at..at + 4].copy_from_slice(&length.to_be_bytes());
+        JavaClass::parse(bytes)
+    }
+
+    #[test]
+    fn attributes_are_read_within_their_length() {
+        let bytes = class_bytes();
+        let len = bytes.len();
+        assert!(JavaClass::parse(bytes.clone()).is_ok());
+        //`Deprecated` takes the last 6 bytes, and `SourceFile` the 8 before it
+        let source_file = len - 8;
+        for declared in [1, 3] {
+            match parse_with_length(bytes.clone(), source_file - 4, declared) {
+                Err(ClassParseError::AttributeLengthMismatch { offset, path, declared: d, actual }) => {
+                    assert_eq!((offset, path.as_str(), d, actual), (source_file as u32, "SourceFile", declared, 2));
+                }
+                r => panic!("expected a length mismatch, got {:?}", r.map(|_| ())),
+            }
+        }
+        //the line number table ends where the class attributes, and their count, begin
+        let line_numbers = len - 14 - 2 - 6;
+        match parse_with_length(bytes, line_numbers - 4, 4) {
+            Err(ClassParseError::AttributeLengthMismatch { offset, path, declared, actual }) => {
+                assert_eq!((offset, declared, actual), (line_numbers as u32, 4, 6));
+                assert!(path.ends_with("Code.LineNumberTable"), "{}", path);
+            }
+            r => panic!("expected a length mismatch, got {:?}", r.map(|_| ())),
+        }
+        //a count that runs past the length is reported at the attribute too, not wherever reading it ends up failing
+        for (count, actual) in [(2, 10), (0xffff, len - line_numbers)] {
+            let mut bytes = class_bytes();
+            bytes[line_numbers..line_numbers + 2].copy_from_slice(&(count as u16).to_be_bytes());
+            match JavaClass::parse(bytes) {
+                Err(ClassParseError::AttributeLengthMismatch { offset, declared, actual: a, .. }) => {
+                    assert_eq!((offset, declared, a), (line_numbers as u32, 6, actual as u32));
+                }
+                r => panic!("expected a length mismatch, got {:?}", r.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn modified_utf8_round_trips() {
+        let cases: &[(&str, &[u8])] = &[
+            ("", b""),
+            ("java/lang/Object", b"java/lang/Object"),
+            //NUL takes two bytes so the encoding never contains a zero byte
+            ("a\0b", &[0x61, 0xc0, 0x80, 0x62]),
+            ("\u{e9}\u{20ac}", &[0xc3, 0xa9, 0xe2, 0x82, 0xac]),
+            //supplementary characters are a surrogate pair of three bytes each, not one four byte sequence
+            ("\u{1f600}", &[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]),
+            ("x\u{10ffff}\0", &[0x78, 0xed, 0xaf, 0xbf, 0xed, 0xbf, 0xbf, 0xc0, 0x80]),
+        ];
+        for (s, bytes) in cases {
+            assert_eq!(write_string(s), *bytes, "encoding {:?}", s);
+            assert_eq!(read_string(bytes), *s, "decoding {:?}", s);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_utf8_is_readable_and_lossless() {
+        let utf8 = |bytes: Vec<u8>| CPInfo::Utf8 { length: bytes.len() as u16, bytes };
+        let mut class = JavaClass::empty();
+        class.constant_pool = ConstantPool::new_with_info(vec![
+            utf8(write_string("a\0b\u{1f600}")),
+            //an unpaired surrogate, which would become U+FFFD as a string
+            utf8(vec![0xed, 0xa0, 0xbd]),
+            CPInfo::Long { bytes: 1 },
+            CPInfo::LongDoubleDummy,
+        ]);
+        let json = class.to_json();
+        assert!(json.contains(r#"{"Utf8":{"length":10,"bytes":"a\u0000b😀"}}"#), "{}", json);
+        assert!(json.contains(r#"{"Utf8":{"length":3,"bytes":[237,160,189]}}"#), "{}", json);
+        assert!(json.contains(r#"{"Long":{"bytes":1}},"LongDoubleDummy""#), "{}", json);
+        let back = JavaClass::from_json(&json).unwrap();
+        assert_eq!(back.constant_pool.items(), class.constant_pool.items());
+    }
+
+    #[test]
+    fn invalid_modified_utf8_is_replaced() {
+        //a standard UTF-8 four byte sequence and an unpaired surrogate
+        assert_eq!(read_string(&[0xf0, 0x9f, 0x98, 0x80]), "\u{fffd}\u{fffd}\u{fffd}\u{fffd}");
+        assert_eq!(read_string(&[0x61, 0xed, 0xa0, 0xbd]), "a\u{fffd}");
+    }
+}
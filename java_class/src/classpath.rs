@@ -0,0 +1,410 @@
+use class::JavaClass;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use zip::ZipArchive;
+
+/// A place classes and resources can be loaded from
+#[derive(Debug)]
+pub enum ClassPathEntry {
+    /// a directory laid out by package, e.g. `java/lang/Object.class`
+    Directory(PathBuf),
+    /// a jar or zip file.
+    /// `multi_release` is true if the manifest has `Multi-Release: true`,
+    /// in which case entries under `META-INF/versions/N/` override the base entries for Java N and up.
+    Zip { path: PathBuf, archive: Mutex<ZipArchive<File>>, multi_release: bool },
+}
+
+impl ClassPathEntry {
+    /// Opens `path` as a directory if it is one, and as a zip otherwise
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ClassPathEntry> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            Ok(ClassPathEntry::Directory(path.to_path_buf()))
+        } else {
+            let archive = ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+            let mut ans = ClassPathEntry::Zip { path: path.to_path_buf(), archive: Mutex::new(archive), multi_release: false };
+            if let Some(manifest) = ans.read("META-INF/MANIFEST.MF")? {
+                if let ClassPathEntry::Zip { multi_release, .. } = &mut ans {
+                    *multi_release = is_multi_release(&manifest);
+                }
+            }
+            Ok(ans)
+        }
+    }
+
+    /// Returns the path of the directory or zip file
+    pub fn path(&self) -> &Path {
+        match self {
+            ClassPathEntry::Directory(path) => path,
+            ClassPathEntry::Zip { path, .. } => path,
+        }
+    }
+
+    /// Reads a resource from this entry, returning `None` if it isn't there.
+    /// Resource names always use '/' as the separator, e.g. `java/lang/Object.class`.
+    pub fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            ClassPathEntry::Directory(dir) => {
+                let mut p = dir.clone();
+                for part in name.split('/') {
+                    p.push(part);
+                }
+                if !p.is_file() {
+                    return Ok(None);
+                }
+                fs::read(p).map(Some)
+            }
+            ClassPathEntry::Zip { archive, .. } => {
+                let mut archive = archive.lock().unwrap();
+                let mut file = match archive.by_name(name) {
+                    Ok(f) => f,
+                    Err(_) => return Ok(None)
+                };
+                let mut ans = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut ans)?;
+                Ok(Some(ans))
+            }
+        }
+    }
+
+    /// Returns true for a multi-release jar
+    pub fn is_multi_release(&self) -> bool {
+        match self {
+            ClassPathEntry::Zip { multi_release, .. } => *multi_release,
+            _ => false
+        }
+    }
+
+    /// Lists the versions of a resource in this entry, in ascending order.
+    /// `None` is the base version and `Some(n)` is the one under `META-INF/versions/n/`.
+    /// Versioned entries are only listed for multi-release jars.
+    pub fn versions(&self, name: &str) -> io::Result<Vec<Option<u16>>> {
+        let mut ans = vec!();
+        for n in self.resource_names()? {
+            if n == name {
+                ans.push(None);
+            } else if self.is_multi_release() {
+                if let Some((v, rest)) = split_versioned(&n) {
+                    if rest == name {
+                        ans.push(Some(v));
+                    }
+                }
+            }
+        }
+        ans.sort();
+        Ok(ans)
+    }
+
+    /// Maps the name of each resource visible to Java `release` to the name it's stored under.
+    /// With no release, or outside of a multi-release jar, everything maps to itself.
+    fn resolve_names(&self, release: Option<u16>) -> io::Result<HashMap<String, String>> {
+        let names = self.resource_names()?;
+        let mut chosen: HashMap<String, (u16, String)> = HashMap::new();
+        for n in names {
+            let (version, name) = match split_versioned(&n) {
+                Some((v, rest)) if self.is_multi_release() => match release {
+                    Some(r) if v <= r => (v, rest.to_owned()),
+                    _ => continue
+                },
+                _ => (0, n.clone())
+            };
+            let slot = chosen.entry(name).or_insert_with(|| (version, n.clone()));
+            if version >= slot.0 {
+                *slot = (version, n);
+            }
+        }
+        Ok(chosen.into_iter().map(|(k, (_, v))| (k, v)).collect())
+    }
+
+    /// Lists the name of every resource in this entry, excluding directories
+    pub fn resource_names(&self) -> io::Result<Vec<String>> {
+        let mut ans = vec!();
+        match self {
+            ClassPathEntry::Directory(dir) => list_dir(dir, "", &mut ans)?,
+            ClassPathEntry::Zip { archive, .. } => {
+                let archive = archive.lock().unwrap();
+                ans.extend(archive.file_names().filter(|n| !n.ends_with('/')).map(str::to_owned));
+            }
+        }
+        Ok(ans)
+    }
+}
+
+/// Splits `META-INF/versions/N/name` into `N` and `name`
+fn split_versioned(resource: &str) -> Option<(u16, &str)> {
+    let rest = resource.strip_prefix("META-INF/versions/")?;
+    let slash = rest.find('/')?;
+    let version = rest[..slash].parse().ok()?;
+    Some((version, &rest[slash + 1..]))
+}
+
+fn is_multi_release(manifest: &[u8]) -> bool {
+    String::from_utf8_lossy(manifest).lines().any(|l| {
+        let mut parts = l.splitn(2, ':');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        key.eq_ignore_ascii_case("Multi-Release") && value.eq_ignore_ascii_case("true")
+    })
+}
+
+fn list_dir(dir: &Path, prefix: &str, out: &mut Vec<String>) -> io::Result<()> {
+    for e in fs::read_dir(dir)? {
+        let e = e?;
+        let name = match e.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue
+        };
+        let full = format!("{}{}", prefix, name);
+        if e.file_type()?.is_dir() {
+            list_dir(&e.path(), &(full + "/"), out)?;
+        } else {
+            out.push(full);
+        }
+    }
+    Ok(())
+}
+
+/// A classpath entry and the versions of a resource it has, as returned by `ClassPathEntry::versions`
+pub type EntryVersions<'a> = (&'a ClassPathEntry, Vec<Option<u16>>);
+
+/// An ordered list of directories and zips to search for classes, like the `-classpath` option of `java`.
+/// When more than one entry has a resource, the earliest entry wins.
+/// Multi-release jars are resolved for the Java release set with `set_release`;
+/// with no release set only their base entries are used, as on Java 8.
+#[derive(Debug, Default)]
+pub struct ClassPath {
+    entries: Vec<ClassPathEntry>,
+    release: Option<u16>,
+    /// resource name -> (index of the entry it's loaded from, name it's stored under). Built on first use.
+    index: RwLock<Option<HashMap<String, (usize, String)>>>,
+}
+
+impl ClassPath {
+    pub fn new() -> ClassPath {
+        ClassPath::default()
+    }
+
+    /// Creates a `ClassPath` from a list of directories and zips
+    pub fn from_paths<I, P>(paths: I) -> io::Result<ClassPath>
+        where I: IntoIterator<Item = P>, P: AsRef<Path> {
+        let mut ans = ClassPath::new();
+        for p in paths {
+            ans.push(p)?;
+        }
+        Ok(ans)
+    }
+
+    /// Parses a classpath string, with entries separated by ':' (or ';' on Windows).
+    /// Empty entries are skipped.
+    pub fn parse(classpath: &str) -> io::Result<ClassPath> {
+        let separator = if cfg!(windows) { ';' } else { ':' };
+        ClassPath::from_paths(classpath.split(separator).filter(|s| !s.is_empty()))
+    }
+
+    /// Adds a directory or zip to the end of the classpath
+    pub fn push<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let entry = ClassPathEntry::open(path)?;
+        self.push_entry(entry);
+        Ok(())
+    }
+
+    pub fn push_entry(&mut self, entry: ClassPathEntry) {
+        self.entries.push(entry);
+        self.refresh();
+    }
+
+    /// Sets the Java release (e.g. 11) multi-release jars are resolved for
+    pub fn set_release(&mut self, release: Option<u16>) {
+        self.release = release;
+        self.refresh();
+    }
+
+    pub fn release(&self) -> Option<u16> {
+        self.release
+    }
+
+    pub fn entries(&self) -> &[ClassPathEntry] {
+        &self.entries
+    }
+
+    /// Drops the cached name index, e.g. after files are added to a directory entry
+    pub fn refresh(&self) {
+        *self.index.write().unwrap() = None;
+    }
+
+    fn with_index<T, F: FnOnce(&HashMap<String, (usize, String)>) -> T>(&self, f: F) -> io::Result<T> {
+        if let Some(index) = &*self.index.read().unwrap() {
+            return Ok(f(index));
+        }
+        let mut index = HashMap::new();
+        for (i, e) in self.entries.iter().enumerate() {
+            for (name, stored) in e.resolve_names(self.release)? {
+                index.entry(name).or_insert((i, stored));
+            }
+        }
+        let ans = f(&index);
+        *self.index.write().unwrap() = Some(index);
+        Ok(ans)
+    }
+
+    /// Returns the entry a resource would be loaded from, and the name it's stored under there.
+    /// The names differ when a multi-release jar supplies a versioned copy.
+    pub fn entry_of(&self, name: &str) -> io::Result<Option<(&ClassPathEntry, String)>> {
+        let found = self.with_index(|index| index.get(name).cloned())?;
+        Ok(found.map(|(i, stored)| (&self.entries[i], stored)))
+    }
+
+    /// Reads a resource, e.g. `META-INF/MANIFEST.MF`
+    pub fn find_resource(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.entry_of(name)? {
+            Some((e, stored)) => e.read(&stored),
+            None => Ok(None)
+        }
+    }
+
+    /// Reads the bytes of a class file given the class's binary name, e.g. `java/lang/Object`.
+    /// '.' is accepted as the package separator as well.
+    pub fn find_class_bytes(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.find_resource(&class_resource(name))
+    }
+
+    /// Loads a class given its binary name
+    pub fn find_class(&self, name: &str) -> io::Result<Option<JavaClass>> {
+        match self.find_class_bytes(name)? {
+            Some(bytes) => JavaClass::new_from_bytes(bytes).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    pub fn contains_class(&self, name: &str) -> io::Result<bool> {
+        let name = class_resource(name);
+        self.with_index(|index| index.contains_key(&name))
+    }
+
+    /// Lists every version of a class on the classpath, by entry, in classpath order.
+    /// Entries that don't have the class are left out.
+    pub fn class_versions(&self, name: &str) -> io::Result<Vec<EntryVersions<'_>>> {
+        let resource = class_resource(name);
+        let mut ans = vec!();
+        for e in &self.entries {
+            let versions = e.versions(&resource)?;
+            if !versions.is_empty() {
+                ans.push((e, versions));
+            }
+        }
+        Ok(ans)
+    }
+
+    /// Lists every resource on the classpath, sorted
+    pub fn resource_names(&self) -> io::Result<Vec<String>> {
+        let mut ans = self.with_index(|index| index.keys().cloned().collect::<Vec<_>>())?;
+        ans.sort();
+        Ok(ans)
+    }
+
+    /// Lists the binary name of every class on the classpath, sorted
+    pub fn class_names(&self) -> io::Result<Vec<String>> {
+        let mut ans = self.with_index(|index| {
+            index.keys().filter_map(|n| class_name_of(n)).collect::<Vec<_>>()
+        })?;
+        ans.sort();
+        Ok(ans)
+    }
+
+    /// Iterates over every class on the classpath in name order, loading each one as it's reached
+    pub fn classes<'a>(&'a self) -> io::Result<impl Iterator<Item = (String, io::Result<JavaClass>)> + 'a> {
+        Ok(self.class_names()?.into_iter().map(move |name| {
+            let class = self.find_class(&name).and_then(|c| c.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.clone())));
+            (name, class)
+        }))
+    }
+}
+
+/// Turns a binary class name into the name of its class file
+pub fn class_resource(name: &str) -> String {
+    name.replace('.', "/") + ".class"
+}
+
+/// Turns the name of a class file into the binary name of the class.
+/// Returns `None` for other resources, including `module-info.class` and `package-info.class`
+/// and anything under `META-INF/`.
+pub fn class_name_of(resource: &str) -> Option<String> {
+    let name = resource.strip_suffix(".class")?;
+    if resource.starts_with("META-INF/") || name.ends_with("module-info") || name.ends_with("package-info") {
+        return None;
+    }
+    Some(name.to_owned())
+}
+
+fn zip_error(e: ::zip::result::ZipError) -> io::Error {
+    match e {
+        ::zip::result::ZipError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+    use zip::CompressionMethod;
+
+    /// Writes a jar with the given files to a temporary file, returning its path
+    fn write_jar(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("java_class_{}_{}.jar", name, std::process::id()));
+        let mut jar = ZipWriter::new(File::create(&path).unwrap());
+        for (name, contents) in files {
+            jar.start_file(*name, FileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
+            jar.write_all(contents.as_bytes()).unwrap();
+        }
+        jar.finish().unwrap();
+        path
+    }
+
+    fn read(cp: &ClassPath, name: &str) -> Option<String> {
+        cp.find_resource(name).unwrap().map(|b| String::from_utf8(b).unwrap())
+    }
+
+    #[test]
+    fn multi_release_jars_pick_the_newest_version_up_to_the_release() {
+        let files = [
+            ("META-INF/MANIFEST.MF", "Manifest-Version: 1.0\r\nmulti-release: TRUE\r\n"),
+            ("a/A.class", "base"),
+            ("a/B.class", "base"),
+            ("META-INF/versions/9/a/A.class", "9"),
+            ("META-INF/versions/11/a/A.class", "11"),
+            ("META-INF/versions/11/a/C.class", "11"),
+        ];
+        let path = write_jar("multi_release", &files);
+        let mut cp = ClassPath::from_paths([&path]).unwrap();
+        assert!(cp.entries()[0].is_multi_release());
+        assert_eq!(cp.entries()[0].versions("a/A.class").unwrap(), vec![None, Some(9), Some(11)]);
+        for &(release, a, c) in &[(None, "base", None), (Some(8), "base", None), (Some(9), "9", None),
+                                  (Some(10), "9", None), (Some(11), "11", Some("11")), (Some(17), "11", Some("11"))] {
+            cp.set_release(release);
+            assert_eq!(read(&cp, "a/A.class").as_deref(), Some(a), "release {:?}", release);
+            assert_eq!(read(&cp, "a/B.class").as_deref(), Some("base"), "release {:?}", release);
+            assert_eq!(read(&cp, "a/C.class").as_deref(), c, "release {:?}", release);
+        }
+        assert_eq!(cp.entry_of("a/A.class").unwrap().unwrap().1, "META-INF/versions/11/a/A.class");
+        assert_eq!(cp.class_names().unwrap(), vec!["a/A", "a/B", "a/C"]);
+
+        //without the manifest attribute the versioned entries are just resources under META-INF
+        let path2 = write_jar("single_release", &files[1..]);
+        let mut cp = ClassPath::from_paths([&path2]).unwrap();
+        cp.set_release(Some(11));
+        assert!(!cp.entries()[0].is_multi_release());
+        assert_eq!(cp.entries()[0].versions("a/A.class").unwrap(), vec![None]);
+        assert_eq!(read(&cp, "a/A.class").as_deref(), Some("base"));
+        assert_eq!(read(&cp, "a/C.class"), None);
+        assert_eq!(cp.class_names().unwrap(), vec!["a/A", "a/B"]);
+        fs::remove_file(path).unwrap();
+        fs::remove_file(path2).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+use attributes::*;
+use builders::cp::CPBuilder;
+use bytecode_tools::instruction_offsets;
+use cp::ConstantPool;
+use malformed;
+use opcode_info::Flow;
+use std::io;
+use symbolic::{from_symbolic, to_symbolic, SymOpcode};
+
+/// An exception handler whose range and handler are instruction indices.
+/// `end` is exclusive, and may equal the number of instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionHandler {
+    pub start: usize,
+    pub end: usize,
+    pub handler: usize,
+    /// binary name of the caught class, or `None` for a handler that catches everything (e.g. `finally`)
+    pub catch_type: Option<String>,
+}
+
+/// A method body in symbolic form.
+/// Every position in the body is an instruction index rather than a bytecode offset:
+/// the exception table, and the pcs in the `LineNumberTable`, `LocalVariableTable`,
+/// `LocalVariableTypeTable`, `StackMapTable` and type annotation attributes.
+/// Instructions can therefore be inserted, removed or resized without breaking the tables,
+/// as long as the indices are shifted along with them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBody {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub instructions: Vec<SymOpcode>,
+    pub exception_table: Vec<ExceptionHandler>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl CodeBody {
+    /// Converts a `Code` attribute to a `CodeBody`
+    /// # Parameters:
+    /// * attr: the `Code` attribute
+    /// * cp: the constant pool of the class the attribute belongs to
+    /// * bootstrap_methods: the class's `BootstrapMethods`, used to resolve `invokedynamic`
+    pub fn from_attribute(attr: &Attribute, cp: &ConstantPool, bootstrap_methods: &[BootstrapMethodsEntry]) -> io::Result<CodeBody> {
+        let (max_stack, max_locals, code, exception_table, attributes) = match attr {
+            Attribute::Code { max_stack, max_locals, code, exception_table, attributes } => (*max_stack, *max_locals, code, exception_table, attributes),
+            _ => return Err(malformed("Expected a Code attribute"))
+        };
+        let offsets = instruction_offsets(code);
+        let end = offsets.len() - 1;
+        let index_of = |pc: u32| -> io::Result<u32> {
+            offsets.binary_search(&pc).map(|i| i as u32).map_err(|_| malformed("pc is not the start of an instruction"))
+        };
+        //javac sometimes ends local variable ranges past the end of the code
+        let end_index_of = |pc: u32| if pc > offsets[end] { Ok(end as u32) } else { index_of(pc) };
+        let mut handlers = Vec::with_capacity(exception_table.len());
+        for e in exception_table {
+            handlers.push(ExceptionHandler {
+                start: index_of(e.start_pc as u32)? as usize,
+                end: index_of(e.end_pc as u32)? as usize,
+                handler: index_of(e.handler_pc as u32)? as usize,
+                catch_type: if e.catch_type.as_u16() == 0 {
+                    None
+                } else {
+                    Some(cp.get_class_name(e.catch_type).ok_or_else(|| malformed("catch_type did not point to Class"))?)
+                },
+            });
+        }
+        let mut attributes = attributes.clone();
+        remap_positions(&mut attributes, index_of, end_index_of)?;
+        Ok(CodeBody {
+            max_stack,
+            max_locals,
+            instructions: to_symbolic(code, cp, bootstrap_methods)?,
+            exception_table: handlers,
+            attributes,
+        })
+    }
+
+    /// Converts the body back to a `Code` attribute, interning constants through `cp`
+    /// and appending any new bootstrap methods to `bootstrap_methods`
+    pub fn to_attribute(&self, cp: &mut CPBuilder, bootstrap_methods: &mut Vec<BootstrapMethodsEntry>) -> io::Result<Attribute> {
+        let code = from_symbolic(&self.instructions, cp, bootstrap_methods)?;
+        let offsets = instruction_offsets(&code);
+        if offsets[code.len()] > u16::MAX as u32 {
+            return Err(malformed("Code is longer than 65535 bytes"));
+        }
+        let pc_of = |index: u32| -> io::Result<u32> {
+            offsets.get(index as usize).cloned().ok_or_else(|| malformed("Instruction index out of range"))
+        };
+        let mut exception_table = Vec::with_capacity(self.exception_table.len());
+        for e in &self.exception_table {
+            exception_table.push(ExceptionTableEntry {
+                start_pc: pc_of(e.start as u32)? as u16,
+                end_pc: pc_of(e.end as u32)? as u16,
+                handler_pc: pc_of(e.handler as u32)? as u16,
+                catch_type: match &e.catch_type {
+                    Some(name) => cp.add_class(name.clone()),
+                    None => 0.into()
+                },
+            });
+        }
+        let mut attributes = self.attributes.clone();
+        remap_positions(&mut attributes, pc_of, pc_of)?;
+        Ok(Attribute::Code {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code,
+            exception_table,
+            attributes,
+        })
+    }
+
+    /// Computes the deepest the operand stack gets, following every path from the start and every handler
+    pub fn compute_max_stack(&self) -> io::Result<u16> {
+        let code = &self.instructions;
+        let mut depths: Vec<Option<u32>> = vec![None; code.len()];
+        let mut work = vec![];
+        reach(&mut depths, &mut work, 0, 0)?;
+        for e in &self.exception_table {
+            reach(&mut depths, &mut work, e.handler, 1)?;
+        }
+        let mut max = 0;
+        while let Some(i) = work.pop() {
+            let depth = depths[i].unwrap_or(0);
+            let effect = code[i].stack_effect()?;
+            let after = depth.checked_sub(effect.pop).ok_or_else(|| malformed("Operand stack underflow"))? + effect.push;
+            max = max.max(depth).max(after);
+            match code[i].flow() {
+                Flow::Next => reach(&mut depths, &mut work, i + 1, after)?,
+                Flow::Subroutine => {
+                    //the subroutine pops its return address, leaving the stack as it was at the `jsr`
+                    reach(&mut depths, &mut work, i + 1, depth)?;
+                    for t in targets(&code[i]) {
+                        reach(&mut depths, &mut work, t, after)?;
+                    }
+                }
+                flow => {
+                    if flow == Flow::ConditionalBranch {
+                        reach(&mut depths, &mut work, i + 1, after)?;
+                    }
+                    for t in targets(&code[i]) {
+                        reach(&mut depths, &mut work, t, after)?;
+                    }
+                }
+            }
+        }
+        if max > u16::MAX as u32 {
+            return Err(malformed("max_stack is too large"));
+        }
+        Ok(max as u16)
+    }
+}
+
+/// Rewrites every bytecode position held by the attributes of a `Code` attribute with `map`.
+/// Ranges are given as a start and a length, so both ends of the range are mapped, the end with `map_end`.
+pub fn remap_positions<F, G>(attributes: &mut [Attribute], map: F, map_end: G) -> io::Result<()>
+    where F: Fn(u32) -> io::Result<u32>, G: Fn(u32) -> io::Result<u32> {
+    let range = |start: &mut u16, length: &mut u16| -> io::Result<()> {
+        let new_start = map(*start as u32)?;
+        let new_end = map_end(*start as u32 + *length as u32)?;
+        *start = new_start as u16;
+        *length = (new_end - new_start) as u16;
+        Ok(())
+    };
+    for attr in attributes.iter_mut() {
+        match attr {
+            Attribute::LineNumberTable { line_number_table } => {
+                for l in line_number_table {
+                    l.start_pc = map(l.start_pc as u32)? as u16;
+                }
+            }
+            Attribute::LocalVariableTable { local_variable_table } => {
+                for l in local_variable_table {
+                    range(&mut l.start_pc, &mut l.length)?;
+                }
+            }
+            Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+                for l in local_variable_type_table {
+                    range(&mut l.start_pc, &mut l.length)?;
+                }
+            }
+            Attribute::StackMapTable { entries } => {
+                let mut last: Option<(u32, u32)> = None; //(old position, new position)
+                for frame in entries.iter_mut() {
+                    let delta = offset_delta(frame) as u32;
+                    let pos = match last {
+                        Some((old, _)) => old + delta + 1,
+                        None => delta
+                    };
+                    let new_pos = map(pos)?;
+                    let new_delta = match last {
+                        Some((_, new_last)) => new_pos.checked_sub(new_last + 1).ok_or_else(|| malformed("Stack map frames out of order"))?,
+                        None => new_pos
+                    };
+                    remap_frame_types(frame, &map)?;
+                    *frame = with_offset_delta(frame, new_delta as u16);
+                    last = Some((pos, new_pos));
+                }
+            }
+            Attribute::RuntimeVisibleTypeAnnotations { annotations } |
+            Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+                for a in annotations {
+                    match &mut a.target_info {
+                        TargetInfo::LocalVarTarget { table } => {
+                            for e in table {
+                                range(&mut e.start_pc, &mut e.length)?;
+                            }
+                        }
+                        TargetInfo::OffsetTarget { offset } |
+                        TargetInfo::TypeArgumentTarget { offset, .. } => {
+                            *offset = map(*offset as u32)? as u16;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn remap_frame_types<F: Fn(u32) -> io::Result<u32>>(frame: &mut StackMapFrame, map: &F) -> io::Result<()> {
+    let types: Vec<&mut VerificationTypeInfo> = match frame {
+        StackMapFrame::SameLocals1Item { stack, .. } |
+        StackMapFrame::SameLocals1ItemExtended { stack, .. } => vec![stack],
+        StackMapFrame::AppendFrame { locals, .. } => locals.iter_mut().collect(),
+        StackMapFrame::FullFrame { locals, stack, .. } => locals.iter_mut().chain(stack.iter_mut()).collect(),
+        _ => vec![]
+    };
+    for t in types {
+        //the offset of an uninitialized type is the position of the `new` that created it
+        if let VerificationTypeInfo::UninitializedVariable { offset } = t {
+            *offset = map(*offset as u32)? as u16;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the `offset_delta` of a stack map frame
+pub fn offset_delta(frame: &StackMapFrame) -> u16 {
+    match frame {
+        StackMapFrame::SameFrame { offset_delta } |
+        StackMapFrame::SameLocals1Item { offset_delta, .. } => *offset_delta as u16,
+        StackMapFrame::SameLocals1ItemExtended { offset_delta, .. } |
+        StackMapFrame::ChopFrame { offset_delta, .. } |
+        StackMapFrame::SameFrameExtended { offset_delta } |
+        StackMapFrame::AppendFrame { offset_delta, .. } |
+        StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta,
+    }
+}
+
+/// Returns a copy of `frame` with a new `offset_delta`, switching between the short and extended
+/// forms of `SameFrame` and `SameLocals1Item` as needed to fit the delta
+pub fn with_offset_delta(frame: &StackMapFrame, delta: u16) -> StackMapFrame {
+    match frame {
+        StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => {
+            if delta <= 63 {
+                StackMapFrame::SameFrame { offset_delta: delta as u8 }
+            } else {
+                StackMapFrame::SameFrameExtended { offset_delta: delta }
+            }
+        }
+        StackMapFrame::SameLocals1Item { stack, .. } | StackMapFrame::SameLocals1ItemExtended { stack, .. } => {
+            if delta <= 63 {
+                StackMapFrame::SameLocals1Item { offset_delta: delta as u8, stack: stack.clone() }
+            } else {
+                StackMapFrame::SameLocals1ItemExtended { offset_delta: delta, stack: stack.clone() }
+            }
+        }
+        StackMapFrame::ChopFrame { absent_locals, .. } => StackMapFrame::ChopFrame { absent_locals: *absent_locals, offset_delta: delta },
+        StackMapFrame::AppendFrame { locals, .. } => StackMapFrame::AppendFrame { offset_delta: delta, locals: locals.clone() },
+        StackMapFrame::FullFrame { locals, stack, .. } => StackMapFrame::FullFrame { offset_delta: delta, locals: locals.clone(), stack: stack.clone() },
+    }
+}
+
+fn reach(depths: &mut [Option<u32>], work: &mut Vec<usize>, i: usize, depth: u32) -> io::Result<()> {
+    match depths.get_mut(i) {
+        Some(d @ None) => {
+            *d = Some(depth);
+            work.push(i);
+            Ok(())
+        }
+        Some(Some(_)) => Ok(()),
+        None => Err(malformed("Code can continue past its end"))
+    }
+}
+
+fn targets(op: &SymOpcode) -> Vec<usize> {
+    match op {
+        SymOpcode::Branch { target, .. } => vec![*target],
+        SymOpcode::TableSwitch { default, targets, .. } => targets.iter().chain(Some(default)).cloned().collect(),
+        SymOpcode::LookupSwitch { default, pairs } => pairs.iter().map(|p| p.1).chain(Some(*default)).collect(),
+        _ => vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcodes::Opcode::*;
+
+    fn code(attributes: Vec<Attribute>) -> Attribute {
+        //iconst_0 and return are 1 byte each, so the code is 2 bytes long
+        Attribute::Code { max_stack: 1, max_locals: 1, code: vec![iconst_0, return_], exception_table: vec![], attributes }
+    }
+
+    fn local(start_pc: u16, length: u16) -> Attribute {
+        Attribute::LocalVariableTable { local_variable_table: vec![LocalVariableTableEntry {
+            start_pc, length, name_index: 1.into(), descriptor_index: 2.into(), index: 0,
+        }] }
+    }
+
+    #[test]
+    fn only_local_variable_ranges_may_end_past_the_code() {
+        let cp = ConstantPool::new_with_info(vec![]);
+        let body = CodeBody::from_attribute(&code(vec![local(0, 5)]), &cp, &[]).unwrap();
+        assert_eq!(body.attributes, vec![local(0, 2)]);
+        assert!(CodeBody::from_attribute(&code(vec![local(3, 1)]), &cp, &[]).is_err());
+        let line = Attribute::LineNumberTable { line_number_table: vec![LineNumberTableEntry { start_pc: 3, line_number: 1 }] };
+        assert!(CodeBody::from_attribute(&code(vec![line]), &cp, &[]).is_err());
+        let handler = ExceptionTableEntry { start_pc: 0, end_pc: 3, handler_pc: 1, catch_type: 0.into() };
+        let attr = Attribute::Code { max_stack: 1, max_locals: 1, code: vec![iconst_0, return_], exception_table: vec![handler], attributes: vec![] };
+        assert!(CodeBody::from_attribute(&attr, &cp, &[]).is_err());
+    }
+
+    #[test]
+    fn code_must_fit_in_65535_bytes() {
+        let mut body = CodeBody { max_stack: 0, max_locals: 0, instructions: vec![SymOpcode::Plain(nop); 65_535], exception_table: vec![], attributes: vec![] };
+        body.instructions.push(SymOpcode::Plain(return_));
+        assert!(body.to_attribute(&mut CPBuilder::new(), &mut vec![]).is_err());
+        body.instructions.remove(0);
+        match body.to_attribute(&mut CPBuilder::new(), &mut vec![]).unwrap() {
+            Attribute::Code { code, .. } => assert_eq!(code.len(), 65_535),
+            a => panic!("expected a Code attribute, got {:?}", a)
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use attributes::*;
+use builders::cp::CPBuilder;
+use class::JavaClass;
+use cp::{CPIndex, ConstantPool};
+use cp_info::CPInfo;
+use malformed;
+use opcodes::Opcode;
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+
+/// How `compact` orders the constants it keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// keep the constants in their original relative order
+    Original,
+    /// merge duplicate constants and sort the rest by kind and contents,
+    /// so the pool only depends on what the class refers to and not on how it was built.
+    /// Constants loaded by `ldc` come first, so their indices still fit in a byte.
+    Sorted,
+}
+
+impl JavaClass {
+    /// Drops the constants nothing refers to and renumbers the rest; see `compact`
+    pub fn compact_constant_pool(&mut self, order: Order) -> io::Result<()> {
+        compact(self, order)
+    }
+}
+
+/// Rebuilds the constant pool of `class` with only the constants it refers to,
+/// directly or through other constants, and rewrites every index into the pool.
+/// Bootstrap methods no `invokedynamic` constant refers to are dropped as well.
+/// The names of the attributes are kept, so `to_bytes` doesn't append them again.
+///
+/// Fails on an undecoded attribute other than `NestHost`, `NestMembers`, `PermittedSubclasses` and `Record`.
+/// If an error is returned the class is left unchanged.
+pub fn compact(class: &mut JavaClass, order: Order) -> io::Result<()> {
+    let (cp, names) = with_attribute_names(class);
+    let bootstrap_methods = class.bootstrap_methods().to_vec();
+    let mut live = vec![false; cp.len() as usize];
+    let mut used_bootstrap = vec![false; bootstrap_methods.len()];
+    //the name of `BootstrapMethods` is only kept if the attribute is
+    let mut pending: Vec<CPIndex> = names.iter().filter(|(name, _)| *name != "BootstrapMethods").map(|(_, i)| *i).collect();
+    Walker { cp: &cp, f: &mut |i: &mut CPIndex| {
+        pending.push(*i);
+        Ok(())
+    } }.class(class)?;
+    while let Some(i) = pending.pop() {
+        let c = constant(&cp, i)?;
+        if !mem::replace(&mut live[i.as_u16() as usize], true) {
+            pending.extend(references(&cp, c)?);
+            if let CPInfo::InvokeDynamic { bootstrap_method_attr_index, .. } = *c {
+                let b = bootstrap_method_attr_index.as_u16() as usize;
+                let method = bootstrap_methods.get(b).ok_or_else(|| malformed("InvokeDynamic referred to a missing bootstrap method"))?;
+                if !mem::replace(&mut used_bootstrap[b], true) {
+                    pending.push(method.bootstrap_method_ref);
+                    pending.extend_from_slice(&method.bootstrap_arguments);
+                    pending.extend(names.iter().filter(|(name, _)| *name == "BootstrapMethods").map(|(_, i)| *i));
+                }
+            }
+        }
+    }
+    let mut bootstrap_map = Vec::with_capacity(bootstrap_methods.len());
+    let mut next_bootstrap = 0u16;
+    for used in &used_bootstrap {
+        bootstrap_map.push(next_bootstrap);
+        next_bootstrap += *used as u16;
+    }
+    let ldc = ldc_targets(class);
+
+    //the constants that end up in the new pool, as lists of old indices merged into one constant
+    let mut groups: Vec<Vec<u16>> = Vec::new();
+    match order {
+        Order::Original => groups.extend((1..cp.len()).filter(|i| live[*i as usize]).map(|i| vec![i])),
+        Order::Sorted => {
+            let mut keys = HashMap::new();
+            let mut by_key: HashMap<Vec<u8>, Vec<u16>> = HashMap::new();
+            for i in (1..cp.len()).filter(|i| live[*i as usize]) {
+                by_key.entry(sort_key(&cp, i.into(), &mut keys)).or_default().push(i);
+            }
+            let mut sorted: Vec<(bool, Vec<u8>, Vec<u16>)> = by_key.into_iter()
+                .map(|(key, group)| (!group.iter().any(|i| ldc.contains(&(*i).into())), key, group))
+                .collect();
+            sorted.sort();
+            groups.extend(sorted.into_iter().map(|(_, _, group)| group));
+        }
+    }
+
+    let mut map = vec![0u16; cp.len() as usize];
+    let mut next = 1u32;
+    for group in &groups {
+        if next > u16::MAX as u32 {
+            return Err(malformed("Compacted constant pool is too large"));
+        }
+        for i in group {
+            map[*i as usize] = next as u16;
+        }
+        next += match cp[group[0].into()] {
+            CPInfo::Long { .. } | CPInfo::Double { .. } => 2,
+            _ => 1
+        };
+    }
+    let remap = |i: CPIndex| -> CPIndex { map[i.as_u16() as usize].into() };
+    if ldc.iter().any(|i| remap(*i).as_u16() > u8::MAX as u16) {
+        return Err(malformed("Constant loaded by ldc no longer fits in a byte"));
+    }
+
+    let mut items = Vec::with_capacity(next as usize);
+    for group in &groups {
+        let c = match renumber(&cp[group[0].into()], &remap) {
+            CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => CPInfo::InvokeDynamic {
+                bootstrap_method_attr_index: bootstrap_map[bootstrap_method_attr_index.as_u16() as usize].into(),
+                name_and_type_index,
+            },
+            c => c,
+        };
+        let wide = matches!(c, CPInfo::Long { .. } | CPInfo::Double { .. });
+        items.push(c);
+        if wide {
+            items.push(CPInfo::LongDoubleDummy);
+        }
+    }
+    Walker { cp: &cp, f: &mut |i: &mut CPIndex| {
+        *i = remap(*i);
+        Ok(())
+    } }.class(class)?;
+    for a in &mut class.attributes {
+        if let Attribute::BootstrapMethods { bootstrap_methods } = a {
+            let mut used = used_bootstrap.iter();
+            bootstrap_methods.retain(|_| *used.next().unwrap_or(&false));
+            for b in bootstrap_methods {
+                b.bootstrap_method_ref = remap(b.bootstrap_method_ref);
+                for arg in &mut b.bootstrap_arguments {
+                    *arg = remap(*arg);
+                }
+            }
+        }
+    }
+    class.attributes.retain(|a| !matches!(a, Attribute::BootstrapMethods { bootstrap_methods } if bootstrap_methods.is_empty()));
+    class.constant_pool = ConstantPool::new_with_info(items);
+    Ok(())
+}
+
+/// The constant pool of `class` with the names of its decoded attributes added,
+/// and those names with their indices
+fn with_attribute_names(class: &JavaClass) -> (ConstantPool, Vec<(&'static str, CPIndex)>) {
+    fn add(attributes: &[Attribute], cp: &mut CPBuilder, names: &mut Vec<(&'static str, CPIndex)>) {
+        for a in attributes {
+            if let Some(name) = a.name() {
+                names.push((name, cp.add_utf8(name.to_owned())));
+            }
+            if let Attribute::Code { attributes, .. } = a {
+                add(attributes, cp, names);
+            }
+        }
+    }
+    let mut cp = CPBuilder::from_constant_pool(&class.constant_pool);
+    let mut names = Vec::new();
+    add(&class.attributes, &mut cp, &mut names);
+    for f in &class.fields {
+        add(&f.attributes, &mut cp, &mut names);
+    }
+    for m in &class.methods {
+        add(&m.attributes, &mut cp, &mut names);
+    }
+    (cp.build(), names)
+}
+
+/// The constants loaded by `ldc`, whose index is a single byte
+fn ldc_targets(class: &JavaClass) -> Vec<CPIndex> {
+    let mut ans = Vec::new();
+    for m in &class.methods {
+        for a in &m.attributes {
+            if let Attribute::Code { code, .. } = a {
+                ans.extend(code.iter().filter_map(|op| match op {
+                    Opcode::ldc { index } => Some(*index),
+                    _ => None
+                }));
+            }
+        }
+    }
+    ans
+}
+
+fn constant(cp: &ConstantPool, index: CPIndex) -> io::Result<&CPInfo> {
+    match cp.get(index) {
+        Some(CPInfo::LongDoubleDummy) => Err(malformed("Constant pool index pointed to the second slot of a Long or Double")),
+        Some(c) => Ok(c),
+        None => Err(malformed("Constant pool index out of range")),
+    }
+}
+
+/// The constants `c` refers to, after checking that each is of the kind the reference needs
+fn references(cp: &ConstantPool, c: &CPInfo) -> io::Result<Vec<CPIndex>> {
+    let expect = |i: CPIndex, ok: fn(&CPInfo) -> bool, kind: &str| -> io::Result<CPIndex> {
+        if ok(constant(cp, i)?) {
+            Ok(i)
+        } else {
+            Err(malformed(&format!("Constant pool index did not point to {}", kind)))
+        }
+    };
+    let utf8 = |c: &CPInfo| matches!(c, CPInfo::Utf8 { .. });
+    let class = |c: &CPInfo| matches!(c, CPInfo::Class { .. });
+    let name_and_type = |c: &CPInfo| matches!(c, CPInfo::NameAndType { .. });
+    let member = |c: &CPInfo| matches!(c, CPInfo::Fieldref { .. } | CPInfo::Methodref { .. } | CPInfo::InterfaceMethodref { .. });
+    Ok(match *c {
+        CPInfo::Class { name_index } => vec![expect(name_index, utf8, "Utf8")?],
+        CPInfo::String { string_index } => vec![expect(string_index, utf8, "Utf8")?],
+        CPInfo::MethodType { descriptor_index } => vec![expect(descriptor_index, utf8, "Utf8")?],
+        CPInfo::NameAndType { name_index, descriptor_index } => vec![expect(name_index, utf8, "Utf8")?, expect(descriptor_index, utf8, "Utf8")?],
+        CPInfo::Fieldref { class_index, name_and_type_index } |
+        CPInfo::Methodref { class_index, name_and_type_index } |
+        CPInfo::InterfaceMethodref { class_index, name_and_type_index } => {
+            vec![expect(class_index, class, "Class")?, expect(name_and_type_index, name_and_type, "NameAndType")?]
+        }
+        CPInfo::MethodHandle { reference_index, .. } => vec![expect(reference_index, member, "a member reference")?],
+        //the bootstrap method index is into the BootstrapMethods attribute, not the pool
+        CPInfo::InvokeDynamic { name_and_type_index, .. } => vec![expect(name_and_type_index, name_and_type, "NameAndType")?],
+        _ => vec![]
+    })
+}
+
+/// A key that identifies a constant by its tag and contents, with the constants it refers to
+/// replaced by their own keys. Equal keys mean the constants are interchangeable.
+fn sort_key(cp: &ConstantPool, index: CPIndex, keys: &mut HashMap<u16, Vec<u8>>) -> Vec<u8> {
+    if let Some(key) = keys.get(&index.as_u16()) {
+        return key.clone();
+    }
+    let c = &cp[index];
+    let mut key = vec![c.tag()];
+    let mut nested = |i: CPIndex, key: &mut Vec<u8>| {
+        let k = sort_key(cp, i, keys);
+        key.extend_from_slice(&(k.len() as u32).to_be_bytes());
+        key.extend_from_slice(&k);
+    };
+    match *c {
+        CPInfo::Utf8 { ref bytes, .. } => key.extend_from_slice(bytes),
+        CPInfo::Integer { bytes } | CPInfo::Float { bytes } => key.extend_from_slice(&bytes.to_be_bytes()),
+        CPInfo::Long { bytes } | CPInfo::Double { bytes } => key.extend_from_slice(&bytes.to_be_bytes()),
+        CPInfo::Class { name_index: i } | CPInfo::String { string_index: i } | CPInfo::MethodType { descriptor_index: i } => nested(i, &mut key),
+        CPInfo::NameAndType { name_index: a, descriptor_index: b } |
+        CPInfo::Fieldref { class_index: a, name_and_type_index: b } |
+        CPInfo::Methodref { class_index: a, name_and_type_index: b } |
+        CPInfo::InterfaceMethodref { class_index: a, name_and_type_index: b } => {
+            nested(a, &mut key);
+            nested(b, &mut key);
+        }
+        CPInfo::MethodHandle { reference_kind, reference_index } => {
+            key.push(reference_kind);
+            nested(reference_index, &mut key);
+        }
+        CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            key.extend_from_slice(&bootstrap_method_attr_index.as_u16().to_be_bytes());
+            nested(name_and_type_index, &mut key);
+        }
+        CPInfo::LongDoubleDummy => {}
+    }
+    keys.insert(index.as_u16(), key.clone());
+    key
+}
+
+/// `c` with the constants it refers to renumbered by `map`
+fn renumber(c: &CPInfo, map: &dyn Fn(CPIndex) -> CPIndex) -> CPInfo {
+    match *c {
+        CPInfo::Class { name_index } => CPInfo::Class { name_index: map(name_index) },
+        CPInfo::String { string_index } => CPInfo::String { string_index: map(string_index) },
+        CPInfo::MethodType { descriptor_index } => CPInfo::MethodType { descriptor_index: map(descriptor_index) },
+        CPInfo::NameAndType { name_index, descriptor_index } => CPInfo::NameAndType { name_index: map(name_index), descriptor_index: map(descriptor_index) },
+        CPInfo::Fieldref { class_index, name_and_type_index } => CPInfo::Fieldref { class_index: map(class_index), name_and_type_index: map(name_and_type_index) },
+        CPInfo::Methodref { class_index, name_and_type_index } => CPInfo::Methodref { class_index: map(class_index), name_and_type_index: map(name_and_type_index) },
+        CPInfo::InterfaceMethodref { class_index, name_and_type_index } => {
+            CPInfo::InterfaceMethodref { class_index: map(class_index), name_and_type_index: map(name_and_type_index) }
+        }
+        CPInfo::MethodHandle { reference_kind, reference_index } => CPInfo::MethodHandle { reference_kind, reference_index: map(reference_index) },
+        CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index: map(name_and_type_index) }
+        }
+        ref c => c.clone(),
+    }
+}
+
+/// Calls `f` on every constant pool index held by a class, outside the pool itself.
+/// `cp` is the pool the indices currently point into, which is needed to decode `Record` attributes.
+struct Walker<'a> {
+    cp: &'a ConstantPool,
+    f: &'a mut dyn FnMut(&mut CPIndex) -> io::Result<()>,
+}
+
+impl<'a> Walker<'a> {
+    fn index(&mut self, i: &mut CPIndex) -> io::Result<()> {
+        (self.f)(i)
+    }
+
+    /// an index that may be 0
+    fn optional(&mut self, i: &mut CPIndex) -> io::Result<()> {
+        if i.as_u16() == 0 {
+            Ok(())
+        } else {
+            (self.f)(i)
+        }
+    }
+
+    fn class(&mut self, class: &mut JavaClass) -> io::Result<()> {
+        self.index(&mut class.this_class)?;
+        self.optional(&mut class.super_class)?;
+        for i in &mut class.interfaces {
+            self.index(i)?;
+        }
+        for f in &mut class.fields {
+            self.index(&mut f.name_index)?;
+            self.index(&mut f.descriptor_index)?;
+            self.attributes(&mut f.attributes)?;
+        }
+        for m in &mut class.methods {
+            self.index(&mut m.name_index)?;
+            self.index(&mut m.descriptor_index)?;
+            self.attributes(&mut m.attributes)?;
+        }
+        self.attributes(&mut class.attributes)
+    }
+
+    fn attributes(&mut self, attributes: &mut [Attribute]) -> io::Result<()> {
+        for a in attributes {
+            self.attribute(a)?;
+        }
+        Ok(())
+    }
+
+    fn attribute(&mut self, a: &mut Attribute) -> io::Result<()> {
+        match a {
+            Attribute::ConstantValue { constantvalue_index } => self.index(constantvalue_index)?,
+            Attribute::Code { code, exception_table, attributes, .. } => {
+                for op in code {
+                    self.opcode(op)?;
+                }
+                for e in exception_table {
+                    self.optional(&mut e.catch_type)?;
+                }
+                self.attributes(attributes)?;
+            }
+            Attribute::StackMapTable { entries } => for frame in entries {
+                self.frame(frame)?;
+            },
+            Attribute::Exceptions { exception_index_table } => for e in exception_index_table {
+                let mut i = CPIndex::from(*e);
+                self.index(&mut i)?;
+                *e = i.as_u16();
+            },
+            Attribute::InnerClasses { classes } => for c in classes {
+                self.index(&mut c.inner_class_info_index)?;
+                self.optional(&mut c.outer_class_info_index)?;
+                self.optional(&mut c.inner_name_index)?;
+            },
+            Attribute::EnclosingMethod { class_index, method_index } => {
+                self.index(class_index)?;
+                self.optional(method_index)?;
+            }
+            Attribute::Signature { signature_index } => self.index(signature_index)?,
+            Attribute::SourceFile { sourcefile_index } => self.index(sourcefile_index)?,
+            Attribute::LocalVariableTable { local_variable_table } => for l in local_variable_table {
+                self.index(&mut l.name_index)?;
+                self.index(&mut l.descriptor_index)?;
+            },
+            Attribute::LocalVariableTypeTable { local_variable_type_table } => for l in local_variable_type_table {
+                self.index(&mut l.name_index)?;
+                self.index(&mut l.signature_index)?;
+            },
+            Attribute::RuntimeVisibleAnnotations { annotations } |
+            Attribute::RuntimeInvisibleAnnotations { annotations } => for an in annotations {
+                self.annotation(an)?;
+            },
+            Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } |
+            Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => for an in parameter_annotations.iter_mut().flatten() {
+                self.annotation(an)?;
+            },
+            Attribute::RuntimeVisibleTypeAnnotations { annotations } |
+            Attribute::RuntimeInvisibleTypeAnnotations { annotations } => for an in annotations {
+                self.index(&mut an.type_index)?;
+                self.pairs(&mut an.element_value_pairs)?;
+            },
+            Attribute::AnnotationDefault { default_value } => self.element_value(default_value)?,
+            //only the used bootstrap methods are kept, which `compact` works out from the constant pool
+            Attribute::BootstrapMethods { .. } => {}
+            Attribute::MethodParameters { parameters } => for p in parameters {
+                self.optional(&mut p.name_index)?;
+            },
+            Attribute::Unknown { name_index, info } => {
+                let name = self.cp.get_utf8(*name_index).ok_or_else(|| malformed("Attribute name did not point to Utf8"))?;
+                self.index(name_index)?;
+                *info = self.unknown(&name, info)?;
+            }
+            Attribute::Synthetic | Attribute::Deprecated | Attribute::SourceDebugExtenson { .. } | Attribute::LineNumberTable { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn opcode(&mut self, op: &mut Opcode) -> io::Result<()> {
+        use opcodes::Opcode::*;
+        match op {
+            anewarray { index } | checkcast { index } | getfield { index } | getstatic { index } |
+            instanceof { index } | invokedynamic { index } | invokeinterface { index, .. } | invokespecial { index } |
+            invokestatic { index } | invokevirtual { index } | ldc { index } | ldc_w { index } | ldc2_w { index } |
+            multianewarray { index, .. } | new { index } | putfield { index } | putstatic { index } => self.index(index),
+            _ => Ok(())
+        }
+    }
+
+    fn frame(&mut self, frame: &mut StackMapFrame) -> io::Result<()> {
+        let types: Vec<&mut VerificationTypeInfo> = match frame {
+            StackMapFrame::SameLocals1Item { stack, .. } |
+            StackMapFrame::SameLocals1ItemExtended { stack, .. } => vec![stack],
+            StackMapFrame::AppendFrame { locals, .. } => locals.iter_mut().collect(),
+            StackMapFrame::FullFrame { locals, stack, .. } => locals.iter_mut().chain(stack.iter_mut()).collect(),
+            _ => vec![]
+        };
+        for t in types {
+            if let VerificationTypeInfo::Object { cpool_index } = t {
+                self.index(cpool_index)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn annotation(&mut self, a: &mut Annotation) -> io::Result<()> {
+        self.index(&mut a.type_index)?;
+        self.pairs(&mut a.element_value_pairs)
+    }
+
+    fn pairs(&mut self, pairs: &mut [ElementValuePair]) -> io::Result<()> {
+        for p in pairs {
+            self.index(&mut p.element_name_index)?;
+            self.element_value(&mut p.value)?;
+        }
+        Ok(())
+    }
+
+    fn element_value(&mut self, v: &mut ElementValue) -> io::Result<()> {
+        match v {
+            ElementValue::ConstValueIndex { index, .. } => self.index(index),
+            ElementValue::EnumConstValue { type_name_index, const_name_index } => {
+                self.index(type_name_index)?;
+                self.index(const_name_index)
+            }
+            ElementValue::ClassInfoIndex(i) => self.index(i),
+            ElementValue::AnnotationValue(a) => self.annotation(a),
+            ElementValue::ArrayValue(values) => {
+                for v in values {
+                    self.element_value(v)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// the contents of an undecoded attribute, with the indices in it passed through `f`
+    fn unknown(&mut self, name: &str, info: &[u8]) -> io::Result<Vec<u8>> {
+        let mut contents = UndecodedContents::read(name, info, self.cp)?
+            .ok_or_else(|| malformed(&format!("Attribute {} may refer to the constant pool in ways that can't be found", name)))?;
+        match &mut contents {
+            UndecodedContents::NestHost(i) => self.index(i)?,
+            UndecodedContents::ClassList(classes) => for i in classes {
+                self.index(i)?;
+            },
+            UndecodedContents::Record(components) => for c in components {
+                self.index(&mut c.name_index)?;
+                self.index(&mut c.descriptor_index)?;
+                for (name_index, a) in &mut c.attributes {
+                    self.index(name_index)?;
+                    self.attribute(a)?;
+                }
+            },
+        }
+        Ok(contents.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use check::check_format;
+    use code::CodeBody;
+    use diff::{diff, DiffLine};
+    use opcodes::Opcode::*;
+    use symbolic::{BootstrapMethod, Constant, MethodHandle, SymOpcode};
+    use test_classes::{member, ClassSpec};
+
+    fn lambda(name: &str) -> SymOpcode {
+        let metafactory = member("java/lang/invoke/LambdaMetafactory", "metafactory",
+                                 "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;");
+        SymOpcode::InvokeDynamic {
+            bootstrap: BootstrapMethod {
+                handle: MethodHandle { reference_kind: 6, member: metafactory },
+                arguments: vec![Constant::MethodHandle(MethodHandle { reference_kind: 6, member: member("p/A", name, "()V") })],
+            },
+            name: "run".to_owned(),
+            descriptor: "()Ljava/lang/Runnable;".to_owned(),
+        }
+    }
+
+    /// A class whose pool mixes one and two slot constants, with `first` added before anything the class refers to.
+    /// Its last method is the only user of the second bootstrap method.
+    fn class<F: FnOnce(&mut CPBuilder)>(first: F) -> JavaClass {
+        ClassSpec::new(0x0021, "p/A", Some("java/lang/Object"))
+            .attribute(|cp| {
+                first(cp);
+                Attribute::Synthetic
+            })
+            .field(0x0019, "D", "D").field_attribute(|cp| Attribute::ConstantValue { constantvalue_index: cp.add_double_f64(2.5) })
+            .method(0x0009, "run", "()J", vec![
+                SymOpcode::Ldc(Constant::Long(-1)),
+                SymOpcode::Ldc(Constant::Double(0.5)),
+                SymOpcode::Ldc(Constant::String("text".to_owned())),
+                SymOpcode::Ldc(Constant::Integer(100_000)),
+                lambda("a"),
+                SymOpcode::Plain(pop),
+                SymOpcode::Plain(pop),
+                SymOpcode::Plain(pop2),
+                SymOpcode::Plain(lreturn),
+            ])
+            .method(0x0009, "unused", "()V", vec![lambda("b"), SymOpcode::Plain(pop), SymOpcode::Plain(return_)])
+            .build()
+    }
+
+    fn instructions(class: &JavaClass) -> Vec<Vec<SymOpcode>> {
+        class.methods.iter().flat_map(|m| &m.attributes).map(|a| {
+            CodeBody::from_attribute(a, &class.constant_pool, class.bootstrap_methods()).unwrap().instructions
+        }).collect()
+    }
+
+    /// Checks that the compacted class reads back the same, and that its members refer to the same things as the original's.
+    /// Returns the lines of the class attributes that changed.
+    fn check_compacted(original: &JavaClass, compacted: &JavaClass) -> Vec<DiffLine> {
+        assert_eq!(check_format(compacted), vec![]);
+        let parsed = JavaClass::new_from_bytes(compacted.to_bytes()).unwrap();
+        assert_eq!(parsed.constant_pool.items(), compacted.constant_pool.items());
+        assert_eq!(instructions(&parsed), instructions(original));
+        let d = diff(original, &parsed).unwrap();
+        assert!(d.header.is_empty() && d.methods_changed.is_empty() && d.fields_changed.is_empty(), "{}", d.to_text());
+        d.attributes.into_iter().filter(|l| !matches!(l, DiffLine::Same(_))).collect()
+    }
+
+    #[test]
+    fn long_and_double_take_two_slots() {
+        for &order in &[Order::Original, Order::Sorted] {
+            let original = class(|_| {});
+            let mut compacted = class(|_| {});
+            compacted.compact_constant_pool(order).unwrap();
+            let items = compacted.constant_pool.items();
+            for (i, c) in items.iter().enumerate() {
+                let wide = matches!(c, CPInfo::Long { .. } | CPInfo::Double { .. });
+                assert_eq!(wide, items.get(i + 1) == Some(&CPInfo::LongDoubleDummy), "{:?} constant {}", order, i + 1);
+            }
+            assert_eq!(items.iter().filter(|c| **c == CPInfo::LongDoubleDummy).count(), 3);
+            assert_eq!(check_compacted(&original, &compacted), vec![]);
+        }
+    }
+
+    #[test]
+    fn unused_constants_and_bootstrap_methods_are_dropped() {
+        let unused = |cp: &mut CPBuilder| {
+            cp.add_string("unused".to_owned());
+            cp.add_long(42);
+        };
+        let mut original = class(unused);
+        original.methods.pop();
+        let mut compacted = class(unused);
+        compacted.methods.pop();
+        compacted.compact_constant_pool(Order::Original).unwrap();
+
+        let describe = |class: &JavaClass| -> Vec<String> {
+            (1..class.constant_pool.len()).filter_map(|i| class.constant_pool.describe(i.into())).collect()
+        };
+        assert!(describe(&original).contains(&"Long 42".to_owned()));
+        assert!(!describe(&compacted).iter().any(|c| c.contains("unused") || c == "Long 42" || c.contains("p/A.b")));
+        assert_eq!(original.bootstrap_methods().len(), 2);
+        assert_eq!(compacted.bootstrap_methods().len(), 1);
+        assert_eq!(check_compacted(&original, &compacted), vec![
+            DiffLine::Removed("BootstrapMethods: #1 java/lang/invoke/LambdaMetafactory.metafactory:\
+                               (Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite; \
+                               (methodhandle 6 p/A.b:()V)".to_owned()),
+        ]);
+    }
+
+    /// Sorting gives the same pool whatever order the constants were added in, and sorting again changes nothing
+    #[test]
+    fn sorted_order_is_stable() {
+        let mut a = class(|_| {});
+        let mut b = class(|cp| {
+            cp.add_string("text".to_owned());
+            cp.add_double_f64(0.5);
+            cp.add_utf8("()J".to_owned());
+            cp.add_methodref("p/A".to_owned(), "b".to_owned(), "()V".to_owned());
+        });
+        assert_ne!(a.constant_pool.items(), b.constant_pool.items());
+        a.compact_constant_pool(Order::Sorted).unwrap();
+        b.compact_constant_pool(Order::Sorted).unwrap();
+        assert_eq!(a.constant_pool.items(), b.constant_pool.items());
+        assert_eq!(a.to_bytes(), b.to_bytes());
+
+        let before = a.to_bytes();
+        a.compact_constant_pool(Order::Sorted).unwrap();
+        assert_eq!(a.to_bytes(), before);
+
+        //the constants loaded by ldc come first
+        assert_eq!(a.constant_pool.describe(1.into()), Some("Integer 100000".to_owned()));
+        assert_eq!(a.constant_pool.describe(2.into()), Some("String \"text\"".to_owned()));
+    }
+}
@@ -0,0 +1,590 @@
+use attributes::Attribute;
+use class::{AccessFlags as ClassFlags, JavaClass};
+use classpath::ClassPath;
+use fields::AccessFlags as FieldFlags;
+use hierarchy::{ClassNode, Hierarchy};
+use json;
+use malformed;
+use methods::AccessFlags as MethodFlags;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use symbolic::{resolve_constant, Constant};
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// binary compatible
+    Info,
+    /// binary compatible, but existing clients may behave differently or fail when the member is used
+    /// (e.g. a changed constant that clients have inlined, or a new abstract method)
+    Warning,
+    /// breaks binary compatibility: existing clients may fail to link
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// What changed. The JLS sections are those of chapter 13 ("Binary Compatibility").
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChangeKind {
+    ClassRemoved,
+    ClassAdded,
+    /// a public class became non-public (§13.4.3)
+    ClassAccessNarrowed,
+    /// §13.4.2
+    ClassMadeFinal,
+    /// §13.4.1
+    ClassMadeAbstract,
+    /// a class became an interface or the other way around (§13.4.1)
+    ClassKindChanged,
+    /// a superclass or superinterface is no longer a supertype (§13.4.4)
+    SupertypeRemoved,
+    FieldRemoved,
+    FieldAdded,
+    /// the field's type changed (§13.4.8)
+    FieldTypeChanged,
+    FieldAccessNarrowed,
+    /// §13.4.10
+    FieldStaticChanged,
+    /// a non-final field became final (§13.4.9)
+    FieldMadeFinal,
+    /// the value of a constant variable changed, or it stopped being a constant (§13.4.9)
+    ConstantValueChanged,
+    MethodRemoved,
+    MethodAdded,
+    /// the method is gone, but a method with the same name and a different descriptor exists (§13.4.14)
+    MethodDescriptorChanged,
+    MethodAccessNarrowed,
+    /// §13.4.19
+    MethodStaticChanged,
+    /// §13.4.17
+    MethodMadeFinal,
+    /// §13.4.16
+    MethodMadeAbstract,
+    /// an abstract method was added to a class or interface that can be extended (§13.4.16, §13.5.3)
+    AbstractMethodAdded,
+    /// a default method was added to an interface (§13.5.6)
+    DefaultMethodAdded,
+    /// a default method lost its body (§13.5.6)
+    DefaultMethodMadeAbstract,
+    /// a default method was removed (§13.5.6)
+    DefaultMethodRemoved,
+}
+
+impl ChangeKind {
+    pub fn name(self) -> &'static str {
+        use self::ChangeKind::*;
+        match self {
+            ClassRemoved => "class-removed",
+            ClassAdded => "class-added",
+            ClassAccessNarrowed => "class-access-narrowed",
+            ClassMadeFinal => "class-made-final",
+            ClassMadeAbstract => "class-made-abstract",
+            ClassKindChanged => "class-kind-changed",
+            SupertypeRemoved => "supertype-removed",
+            FieldRemoved => "field-removed",
+            FieldAdded => "field-added",
+            FieldTypeChanged => "field-type-changed",
+            FieldAccessNarrowed => "field-access-narrowed",
+            FieldStaticChanged => "field-static-changed",
+            FieldMadeFinal => "field-made-final",
+            ConstantValueChanged => "constant-value-changed",
+            MethodRemoved => "method-removed",
+            MethodAdded => "method-added",
+            MethodDescriptorChanged => "method-descriptor-changed",
+            MethodAccessNarrowed => "method-access-narrowed",
+            MethodStaticChanged => "method-static-changed",
+            MethodMadeFinal => "method-made-final",
+            MethodMadeAbstract => "method-made-abstract",
+            AbstractMethodAdded => "abstract-method-added",
+            DefaultMethodAdded => "default-method-added",
+            DefaultMethodMadeAbstract => "default-method-made-abstract",
+            DefaultMethodRemoved => "default-method-removed",
+        }
+    }
+}
+
+/// A single difference between the two versions
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    pub severity: Severity,
+    pub kind: ChangeKind,
+    /// binary name of the class
+    pub class: String,
+    /// name and descriptor of the field or method, e.g. `size()I`, or `None` for class-level findings
+    pub member: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.member {
+            Some(m) => write!(f, "{}: {}.{}: {}", self.severity.name(), self.class, m, self.message),
+            None => write!(f, "{}: {}: {}", self.severity.name(), self.class, self.message),
+        }
+    }
+}
+
+/// The findings of a comparison, most severe first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Returns the most severe finding's severity, or `None` if nothing changed
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|f| f.severity).max()
+    }
+
+    /// Returns true if no finding breaks binary compatibility
+    pub fn is_compatible(&self) -> bool {
+        self.max_severity() != Some(Severity::Error)
+    }
+
+    /// Formats the findings as a JSON array of `{"severity", "kind", "class", "member", "message"}` objects
+    pub fn to_json(&self) -> String {
+        json::array(self.findings.iter().map(|f| json::object(&[
+            ("severity", json::string(f.severity.name())),
+            ("kind", json::string(f.kind.name())),
+            ("class", json::string(&f.class)),
+            ("member", f.member.as_ref().map_or_else(|| "null".to_owned(), |m| json::string(m))),
+            ("message", json::string(&f.message)),
+        ])))
+    }
+}
+
+struct FieldView {
+    descriptor: String,
+    flags: FieldFlags,
+    constant: Option<Constant>,
+}
+
+struct ClassView {
+    flags: ClassFlags,
+    fields: BTreeMap<String, FieldView>,
+    /// (name, descriptor) -> access flags
+    methods: BTreeMap<(String, String), MethodFlags>,
+}
+
+impl ClassView {
+    fn new(class: &JavaClass) -> io::Result<ClassView> {
+        let cp = &class.constant_pool;
+        let utf8 = |i| cp.get_utf8(i).ok_or_else(|| malformed("Index did not point to Utf8"));
+        let mut fields = BTreeMap::new();
+        for f in &class.fields {
+            let mut constant = None;
+            for a in &f.attributes {
+                if let Attribute::ConstantValue { constantvalue_index } = a {
+                    constant = Some(resolve_constant(cp, *constantvalue_index)?);
+                }
+            }
+            fields.insert(utf8(f.name_index)?, FieldView { descriptor: utf8(f.descriptor_index)?, flags: f.flags(), constant });
+        }
+        let mut methods = BTreeMap::new();
+        for m in &class.methods {
+            methods.insert((utf8(m.name_index)?, utf8(m.descriptor_index)?), m.flags());
+        }
+        Ok(ClassView { flags: class.flags(), fields, methods })
+    }
+
+    fn is(&self, flag: ClassFlags) -> bool {
+        self.flags.contains(flag)
+    }
+}
+
+/// One version of a library
+struct Version {
+    classes: HashMap<String, ClassView>,
+    hierarchy: Hierarchy,
+}
+
+impl Version {
+    fn new(classes: &[JavaClass]) -> io::Result<Version> {
+        let mut ans = Version { classes: HashMap::new(), hierarchy: Hierarchy::new() };
+        for c in classes {
+            let node = ClassNode::from_class(c)?;
+            ans.classes.insert(node.name.clone(), ClassView::new(c)?);
+            ans.hierarchy.add(node);
+        }
+        Ok(ans)
+    }
+
+    /// Finds a method declared by `class` or inherited from one of its supertypes, ignoring private methods
+    fn find_method(&self, class: &str, name: &str, descriptor: &str) -> Option<MethodFlags> {
+        let key = (name.to_owned(), descriptor.to_owned());
+        ::std::iter::once(class.to_owned()).chain(self.hierarchy.supertypes(class))
+            .filter_map(|c| self.classes.get(&c)?.methods.get(&key).cloned())
+            .find(|flags| !flags.contains(MethodFlags::PRIVATE))
+    }
+
+    /// Finds a field declared by `class` or inherited from one of its supertypes
+    fn find_field(&self, class: &str, name: &str) -> Option<&FieldView> {
+        ::std::iter::once(class.to_owned()).chain(self.hierarchy.supertypes(class))
+            .filter_map(|c| self.classes.get(&c)?.fields.get(name))
+            .next()
+    }
+}
+
+/// Compares two versions of a library. Only the API visible outside the library is checked:
+/// public classes, and their public and protected members. Synthetic members are ignored.
+pub fn check(old: &[JavaClass], new: &[JavaClass]) -> io::Result<Report> {
+    let old = Version::new(old)?;
+    let new = Version::new(new)?;
+    let mut findings = vec!();
+    let mut names: Vec<&String> = old.classes.keys().collect();
+    names.sort();
+    for name in names {
+        let o = &old.classes[name];
+        if !o.is(ClassFlags::PUBLIC) {
+            continue;
+        }
+        match new.classes.get(name) {
+            Some(n) => check_class(name, o, n, &old, &new, &mut findings),
+            None => findings.push(finding(Severity::Error, ChangeKind::ClassRemoved, name, None, "public class was removed".to_owned())),
+        }
+    }
+    for (name, n) in &new.classes {
+        if n.is(ClassFlags::PUBLIC) && !old.classes.get(name).is_some_and(|o| o.is(ClassFlags::PUBLIC)) {
+            findings.push(finding(Severity::Info, ChangeKind::ClassAdded, name, None, "public class was added".to_owned()));
+        }
+    }
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| a.cmp(b)));
+    Ok(Report { findings })
+}
+
+/// Compares every class on two classpaths
+pub fn check_classpaths(old: &ClassPath, new: &ClassPath) -> io::Result<Report> {
+    let load = |cp: &ClassPath| -> io::Result<Vec<JavaClass>> { cp.classes()?.map(|(_, c)| c).collect() };
+    check(&load(old)?, &load(new)?)
+}
+
+fn finding(severity: Severity, kind: ChangeKind, class: &str, member: Option<String>, message: String) -> Finding {
+    Finding { severity, kind, class: class.to_owned(), member, message }
+}
+
+fn check_class(name: &str, o: &ClassView, n: &ClassView, old: &Version, new: &Version, out: &mut Vec<Finding>) {
+    use self::ChangeKind::*;
+    use self::Severity::*;
+    let mut push = |severity, kind, member: Option<String>, message: String| out.push(finding(severity, kind, name, member, message));
+    if !n.is(ClassFlags::PUBLIC) {
+        push(Error, ClassAccessNarrowed, None, "class is no longer public".to_owned());
+        return;
+    }
+    if o.is(ClassFlags::INTERFACE) != n.is(ClassFlags::INTERFACE) {
+        let (from, to) = if o.is(ClassFlags::INTERFACE) { ("an interface", "a class") } else { ("a class", "an interface") };
+        push(Error, ClassKindChanged, None, format!("changed from {} to {}", from, to));
+        return;
+    }
+    let is_interface = o.is(ClassFlags::INTERFACE);
+    if !o.is(ClassFlags::FINAL) && n.is(ClassFlags::FINAL) {
+        push(Error, ClassMadeFinal, None, "class was made final".to_owned());
+    }
+    if !is_interface && !o.is(ClassFlags::ABSTRACT) && n.is(ClassFlags::ABSTRACT) {
+        push(Error, ClassMadeAbstract, None, "class was made abstract".to_owned());
+    }
+    let new_supers = new.hierarchy.supertypes(name);
+    for s in old.hierarchy.supertypes(name) {
+        if !new_supers.contains(&s) {
+            push(Error, SupertypeRemoved, None, format!("{} is no longer a supertype", s));
+        }
+    }
+    //a class that can't be subclassed outside the library can't be broken by new abstract methods or final methods
+    let extensible = !n.is(ClassFlags::FINAL);
+
+    for (fname, of) in &o.fields {
+        if !Access::from(of.flags).visible() || of.flags.contains(FieldFlags::SYNTHETIC) {
+            continue;
+        }
+        let member = Some(format!("{}:{}", fname, of.descriptor));
+        let nf = match n.fields.get(fname).or_else(|| new.find_field(name, fname)) {
+            Some(f) => f,
+            None => {
+                push(Error, FieldRemoved, member, "field was removed".to_owned());
+                continue;
+            }
+        };
+        if nf.descriptor != of.descriptor {
+            push(Error, FieldTypeChanged, member.clone(), format!("type changed from {} to {}", of.descriptor, nf.descriptor));
+        }
+        let (oa, na) = (Access::from(of.flags), Access::from(nf.flags));
+        if na < oa {
+            push(Error, FieldAccessNarrowed, member.clone(), format!("access narrowed from {} to {}", oa, na));
+        }
+        if of.flags.contains(FieldFlags::STATIC) != nf.flags.contains(FieldFlags::STATIC) {
+            push(Error, FieldStaticChanged, member.clone(), if nf.flags.contains(FieldFlags::STATIC) { "field was made static" } else { "field is no longer static" }.to_owned());
+        }
+        if !of.flags.contains(FieldFlags::FINAL) && nf.flags.contains(FieldFlags::FINAL) {
+            push(Error, FieldMadeFinal, member.clone(), "field was made final".to_owned());
+        }
+        if let Some(oc) = &of.constant {
+            match &nf.constant {
+                Some(nc) if nc == oc => {}
+                Some(nc) => push(Warning, ConstantValueChanged, member, format!("constant changed from {} to {}; clients compiled against the old value still use it", constant_string(oc), constant_string(nc))),
+                None => push(Warning, ConstantValueChanged, member, format!("no longer a constant; clients compiled against the old value {} still use it", constant_string(oc))),
+            }
+        }
+    }
+    for (fname, nf) in &n.fields {
+        if Access::from(nf.flags).visible() && !nf.flags.contains(FieldFlags::SYNTHETIC) && !o.fields.get(fname).is_some_and(|f| Access::from(f.flags).visible()) {
+            push(Info, FieldAdded, Some(format!("{}:{}", fname, nf.descriptor)), "field was added".to_owned());
+        }
+    }
+
+    for ((mname, desc), &oflags) in &o.methods {
+        if !Access::from(oflags).visible() || oflags.contains(MethodFlags::SYNTHETIC) || mname == "<clinit>" {
+            continue;
+        }
+        let member = Some(format!("{}{}", mname, desc));
+        let nflags = match n.methods.get(&(mname.clone(), desc.clone())).cloned().or_else(|| new.find_method(name, mname, desc)) {
+            Some(f) => f,
+            None => {
+                if is_interface && !oflags.intersects(MethodFlags::ABSTRACT | MethodFlags::STATIC) {
+                    push(Error, DefaultMethodRemoved, member, "default method was removed".to_owned());
+                } else if let Some((_, other)) = n.methods.keys().find(|(m, _)| m == mname) {
+                    push(Error, MethodDescriptorChanged, member, format!("descriptor changed to {}", other));
+                } else {
+                    push(Error, MethodRemoved, member, "method was removed".to_owned());
+                }
+                continue;
+            }
+        };
+        let (oa, na) = (Access::from(oflags), Access::from(nflags));
+        if na < oa {
+            push(Error, MethodAccessNarrowed, member.clone(), format!("access narrowed from {} to {}", oa, na));
+        }
+        if oflags.contains(MethodFlags::STATIC) != nflags.contains(MethodFlags::STATIC) {
+            push(Error, MethodStaticChanged, member.clone(), if nflags.contains(MethodFlags::STATIC) { "method was made static" } else { "method is no longer static" }.to_owned());
+        }
+        if extensible && !oflags.intersects(MethodFlags::FINAL | MethodFlags::STATIC) && nflags.contains(MethodFlags::FINAL) {
+            push(Error, MethodMadeFinal, member.clone(), "method was made final".to_owned());
+        }
+        if !oflags.contains(MethodFlags::ABSTRACT) && nflags.contains(MethodFlags::ABSTRACT) {
+            if is_interface {
+                push(Error, DefaultMethodMadeAbstract, member, "default method is now abstract".to_owned());
+            } else {
+                push(Error, MethodMadeAbstract, member, "method was made abstract".to_owned());
+            }
+        }
+    }
+    for ((mname, desc), &nflags) in &n.methods {
+        if !Access::from(nflags).visible() || nflags.contains(MethodFlags::SYNTHETIC) || mname == "<clinit>" {
+            continue;
+        }
+        let visible = |f: MethodFlags| Access::from(f).visible();
+        if o.methods.get(&(mname.clone(), desc.clone())).cloned().is_some_and(visible) || old.find_method(name, mname, desc).is_some_and(visible) {
+            continue;
+        }
+        let member = Some(format!("{}{}", mname, desc));
+        if nflags.contains(MethodFlags::ABSTRACT) && extensible {
+            push(Warning, AbstractMethodAdded, member, "abstract method was added; existing implementations will throw AbstractMethodError when it's called".to_owned());
+        } else if is_interface && !nflags.contains(MethodFlags::STATIC) {
+            push(Warning, DefaultMethodAdded, member, "default method was added; it may conflict with a default method from another interface".to_owned());
+        } else {
+            push(Info, MethodAdded, member, "method was added".to_owned());
+        }
+    }
+}
+
+/// Where a member can be used from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Access {
+    Private,
+    Package,
+    Protected,
+    Public,
+}
+
+impl Access {
+    fn new(public: bool, protected: bool, private: bool) -> Access {
+        if public {
+            Access::Public
+        } else if protected {
+            Access::Protected
+        } else if private {
+            Access::Private
+        } else {
+            Access::Package
+        }
+    }
+
+    /// true for public and protected members
+    fn visible(self) -> bool {
+        self >= Access::Protected
+    }
+}
+
+impl From<FieldFlags> for Access {
+    fn from(f: FieldFlags) -> Access {
+        Access::new(f.contains(FieldFlags::PUBLIC), f.contains(FieldFlags::PROTECTED), f.contains(FieldFlags::PRIVATE))
+    }
+}
+
+impl From<MethodFlags> for Access {
+    fn from(f: MethodFlags) -> Access {
+        Access::new(f.contains(MethodFlags::PUBLIC), f.contains(MethodFlags::PROTECTED), f.contains(MethodFlags::PRIVATE))
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Access::Public => "public",
+            Access::Protected => "protected",
+            Access::Package => "package-private",
+            Access::Private => "private",
+        })
+    }
+}
+
+fn constant_string(c: &Constant) -> String {
+    match c {
+        Constant::Integer(v) => v.to_string(),
+        Constant::Float(v) => format!("{}f", v),
+        Constant::Long(v) => format!("{}L", v),
+        Constant::Double(v) => v.to_string(),
+        Constant::String(s) => format!("{:?}", s),
+        c => format!("{:?}", c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_classes::ClassSpec;
+
+    fn class(access_flags: u16, name: &str) -> ClassSpec {
+        ClassSpec::new(access_flags, name, Some("java/lang/Object"))
+    }
+
+    fn constant(spec: ClassSpec, value: u32) -> ClassSpec {
+        spec.field(0x0019, "K", "I").field_attribute(|cp| Attribute::ConstantValue { constantvalue_index: cp.add_integer(value) })
+    }
+
+    fn old() -> Vec<JavaClass> {
+        vec![
+            class(0x0021, "p/Removed").build(),
+            class(0x0021, "p/Hidden").build(),
+            class(0x0021, "p/Kind").build(),
+            class(0x0021, "p/Final").build(),
+            class(0x0021, "p/Abstract").build(),
+            class(0x0021, "p/Base").build(),
+            ClassSpec::new(0x0021, "p/Sub", Some("p/Base")).build(),
+            constant(class(0x0021, "p/Fields"), 1)
+                .field(0x0001, "gone", "I")
+                .field(0x0001, "type", "I")
+                .field(0x0001, "narrow", "I")
+                .field(0x0001, "stat", "I")
+                .field(0x0001, "fin", "I")
+                .field(0x0002, "secret", "I")
+                .build(),
+            class(0x0421, "p/Methods")
+                .method(0x0001, "gone", "()V", vec![])
+                .method(0x0001, "desc", "(I)V", vec![])
+                .method(0x0001, "narrow", "()V", vec![])
+                .method(0x0001, "stat", "()V", vec![])
+                .method(0x0001, "fin", "()V", vec![])
+                .method(0x0001, "abs", "()V", vec![])
+                .method(0x0002, "secret", "()V", vec![])
+                .build(),
+            class(0x0601, "p/Iface")
+                .method(0x0001, "def", "()V", vec![])
+                .method(0x0001, "defAbs", "()V", vec![])
+                .build(),
+        ]
+    }
+
+    fn new() -> Vec<JavaClass> {
+        vec![
+            class(0x0021, "p/Added").build(),
+            class(0x0020, "p/Hidden").build(),
+            class(0x0601, "p/Kind").build(),
+            class(0x0031, "p/Final").build(),
+            class(0x0421, "p/Abstract").build(),
+            class(0x0021, "p/Base").build(),
+            class(0x0021, "p/Sub").build(),
+            constant(class(0x0021, "p/Fields"), 2)
+                .field(0x0001, "type", "J")
+                .field(0x0004, "narrow", "I")
+                .field(0x0009, "stat", "I")
+                .field(0x0011, "fin", "I")
+                .field(0x0001, "added", "I")
+                .build(),
+            class(0x0421, "p/Methods")
+                .method(0x0001, "desc", "(J)V", vec![])
+                .method(0x0004, "narrow", "()V", vec![])
+                .method(0x0009, "stat", "()V", vec![])
+                .method(0x0011, "fin", "()V", vec![])
+                .method(0x0401, "abs", "()V", vec![])
+                .method(0x0401, "newAbs", "()V", vec![])
+                .method(0x0001, "added", "()V", vec![])
+                .build(),
+            class(0x0601, "p/Iface")
+                .method(0x0401, "defAbs", "()V", vec![])
+                .method(0x0001, "newDef", "()V", vec![])
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn every_kind_of_change_is_reported() {
+        use self::ChangeKind::*;
+        use self::Severity::*;
+        let report = check(&old(), &new()).unwrap();
+        let found: Vec<(Severity, ChangeKind, &str, Option<&str>)> = report.findings.iter()
+            .map(|f| (f.severity, f.kind, f.class.as_str(), f.member.as_deref()))
+            .collect();
+        assert_eq!(found, vec![
+            (Error, ClassRemoved, "p/Removed", None),
+            (Error, ClassAccessNarrowed, "p/Hidden", None),
+            (Error, ClassMadeFinal, "p/Final", None),
+            (Error, ClassMadeAbstract, "p/Abstract", None),
+            (Error, ClassKindChanged, "p/Kind", None),
+            (Error, SupertypeRemoved, "p/Sub", None),
+            (Error, FieldRemoved, "p/Fields", Some("gone:I")),
+            (Error, FieldTypeChanged, "p/Fields", Some("type:I")),
+            (Error, FieldAccessNarrowed, "p/Fields", Some("narrow:I")),
+            (Error, FieldStaticChanged, "p/Fields", Some("stat:I")),
+            (Error, FieldMadeFinal, "p/Fields", Some("fin:I")),
+            (Error, MethodRemoved, "p/Methods", Some("gone()V")),
+            (Error, MethodDescriptorChanged, "p/Methods", Some("desc(I)V")),
+            (Error, MethodAccessNarrowed, "p/Methods", Some("narrow()V")),
+            (Error, MethodStaticChanged, "p/Methods", Some("stat()V")),
+            (Error, MethodMadeFinal, "p/Methods", Some("fin()V")),
+            (Error, MethodMadeAbstract, "p/Methods", Some("abs()V")),
+            (Error, DefaultMethodMadeAbstract, "p/Iface", Some("defAbs()V")),
+            (Error, DefaultMethodRemoved, "p/Iface", Some("def()V")),
+            (Warning, ConstantValueChanged, "p/Fields", Some("K:I")),
+            (Warning, AbstractMethodAdded, "p/Methods", Some("newAbs()V")),
+            (Warning, DefaultMethodAdded, "p/Iface", Some("newDef()V")),
+            (Info, ClassAdded, "p/Added", None),
+            (Info, FieldAdded, "p/Fields", Some("added:I")),
+            (Info, MethodAdded, "p/Methods", Some("added()V")),
+            //the method with the new descriptor is new as far as clients are concerned
+            (Info, MethodAdded, "p/Methods", Some("desc(J)V")),
+        ]);
+        assert!(!report.is_compatible());
+        let sub = report.findings.iter().find(|f| f.kind == SupertypeRemoved).unwrap();
+        assert_eq!(sub.to_string(), "error: p/Sub: p/Base is no longer a supertype");
+    }
+
+    #[test]
+    fn additions_are_compatible() {
+        assert_eq!(check(&old(), &old()).unwrap(), Report::default());
+        let mut new = old();
+        new.push(class(0x0021, "p/Added").build());
+        let report = check(&old(), &new).unwrap();
+        assert!(report.is_compatible());
+        assert_eq!(report.max_severity(), Some(Severity::Info));
+        assert_eq!(report.to_json(), r#"[{"severity":"info","kind":"class-added","class":"p/Added","member":null,"message":"public class was added"}]"#);
+    }
+}
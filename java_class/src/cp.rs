@@ -1,14 +1,20 @@
+use class::read_string;
 use cp_info::CPInfo;
-use std::{fmt::{Debug, Display}, ops::Index};
+use malformed;
+use std::{fmt::{Debug, Display}, io, ops::Index};
 
 /// A struct representing the constant pool of a class file.
 /// 1-indexed (to emulate the Java constant pool)
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct ConstantPool {
     cp: Vec<CPInfo>
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct CPIndex {
     ind: u16
 }
@@ -68,13 +74,106 @@ impl ConstantPool {
     pub fn items(&self) -> &Vec<CPInfo> {
         &self.cp
     }
+
+    /// Returns the constant at `index`, or `None` if the index is 0 or out of range
+    pub fn get(&self, index: CPIndex) -> Option<&CPInfo> {
+        if index.ind == 0 {
+            return None;
+        }
+        self.cp.get(index.ind as usize - 1)
+    }
+
+    /// Returns the string held by the `Utf8` constant at `index`
+    pub fn get_utf8(&self, index: CPIndex) -> Option<String> {
+        match self.get(index)? {
+            CPInfo::Utf8 { bytes, .. } => Some(read_string(bytes)),
+            _ => None
+        }
+    }
+
+    /// Returns the binary name held by the `Class` constant at `index`
+    pub fn get_class_name(&self, index: CPIndex) -> Option<String> {
+        match self.get(index)? {
+            CPInfo::Class { name_index } => self.get_utf8(*name_index),
+            _ => None
+        }
+    }
+
+    /// Like `get_utf8`, but a malformed class file error if `index` doesn't point to a `Utf8` constant
+    pub(crate) fn utf8(&self, index: CPIndex) -> io::Result<String> {
+        self.get_utf8(index).ok_or_else(|| malformed(&format!("Constant pool index {} did not point to Utf8", index)))
+    }
+
+    /// Like `get_class_name`, but a malformed class file error if `index` doesn't point to a `Class` constant
+    pub(crate) fn class_name(&self, index: CPIndex) -> io::Result<String> {
+        self.get_class_name(index).ok_or_else(|| malformed(&format!("Constant pool index {} did not point to Class", index)))
+    }
+
+    /// Returns the name and descriptor held by the `NameAndType` constant at `index`
+    pub fn get_name_and_type(&self, index: CPIndex) -> Option<(String, String)> {
+        match self.get(index)? {
+            CPInfo::NameAndType { name_index, descriptor_index } => {
+                Some((self.get_utf8(*name_index)?, self.get_utf8(*descriptor_index)?))
+            }
+            _ => None
+        }
+    }
+
+    /// Returns the owner class, name and descriptor of the `Fieldref`, `Methodref`
+    /// or `InterfaceMethodref` constant at `index`
+    pub fn get_member_ref(&self, index: CPIndex) -> Option<(String, String, String)> {
+        match self.get(index)? {
+            CPInfo::Fieldref { class_index, name_and_type_index } |
+            CPInfo::Methodref { class_index, name_and_type_index } |
+            CPInfo::InterfaceMethodref { class_index, name_and_type_index } => {
+                let (name, descriptor) = self.get_name_and_type(*name_and_type_index)?;
+                Some((self.get_class_name(*class_index)?, name, descriptor))
+            }
+            _ => None
+        }
+    }
+
+    /// Describes the constant at `index` without any constant pool indices, e.g. `Class java/lang/Object`.
+    /// Returns `None` for the unusable slot after a `Long` or `Double`
+    pub fn describe(&self, index: CPIndex) -> Option<String> {
+        let nat = |i: CPIndex| self.get_name_and_type(i).map_or_else(|| "?".to_owned(), |(n, d)| format!("{}:{}", n, d));
+        let class = |i: CPIndex| self.get_class_name(i).unwrap_or_else(|| "?".to_owned());
+        let utf8 = |i: CPIndex| self.get_utf8(i).unwrap_or_else(|| "?".to_owned());
+        Some(match self.get(index)? {
+            CPInfo::Utf8 { bytes, .. } => format!("Utf8 {:?}", read_string(bytes)),
+            CPInfo::Integer { bytes } => format!("Integer {}", *bytes as i32),
+            CPInfo::Float { bytes } => format!("Float {:?}", f32::from_bits(*bytes)),
+            CPInfo::Long { bytes } => format!("Long {}", *bytes as i64),
+            CPInfo::Double { bytes } => format!("Double {:?}", f64::from_bits(*bytes)),
+            CPInfo::Class { name_index } => format!("Class {}", utf8(*name_index)),
+            CPInfo::String { string_index } => format!("String {:?}", utf8(*string_index)),
+            CPInfo::Fieldref { class_index, name_and_type_index } => format!("Fieldref {}.{}", class(*class_index), nat(*name_and_type_index)),
+            CPInfo::Methodref { class_index, name_and_type_index } => format!("Methodref {}.{}", class(*class_index), nat(*name_and_type_index)),
+            CPInfo::InterfaceMethodref { class_index, name_and_type_index } => format!("InterfaceMethodref {}.{}", class(*class_index), nat(*name_and_type_index)),
+            CPInfo::NameAndType { name_index, descriptor_index } => format!("NameAndType {}:{}", utf8(*name_index), utf8(*descriptor_index)),
+            CPInfo::MethodHandle { reference_kind, reference_index } => match self.get_member_ref(*reference_index) {
+                Some((owner, name, desc)) => format!("MethodHandle {} {}.{}:{}", reference_kind, owner, name, desc),
+                None => format!("MethodHandle {} ?", reference_kind),
+            },
+            CPInfo::MethodType { descriptor_index } => format!("MethodType {}", utf8(*descriptor_index)),
+            CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                format!("InvokeDynamic #{} {}", bootstrap_method_attr_index.as_u16(), nat(*name_and_type_index))
+            }
+            CPInfo::LongDoubleDummy => return None,
+        })
+    }
 }
 
 impl Index<CPIndex> for ConstantPool {
     type Output = CPInfo;
 
+    /// # Panics
+    /// If `index` is 0 or past the end of the pool; use `get` for indices read from untrusted input
     fn index(&self, index: CPIndex) -> &CPInfo {
-        &self.cp[index.ind as usize - 1]
+        match self.get(index) {
+            Some(c) => c,
+            None => panic!("Constant pool index {} is out of range for a pool of length {}", index, self.len()),
+        }
     }
 }
 
@@ -1,4 +1,6 @@
 use crate::cp::CPIndex;
+#[cfg(feature = "serde")]
+use class::{read_string, write_string};
 
 macro_rules! push_u16 {
     ($num:expr, $vec:expr) => {
@@ -34,7 +36,8 @@ macro_rules! push_u64 {
 
 /// enum containing all JVM cp_info structs
 /// for more information refer to the [JVM specification](https://docs.oracle.com/javase/specs/jvms/se8/html/index.html)
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CPInfo {
     Class { name_index: CPIndex },
     //name_index
@@ -57,7 +60,8 @@ pub enum CPInfo {
     LongDoubleDummy,
     NameAndType { name_index: CPIndex, descriptor_index: CPIndex },
     //name_index, descriptor_index
-    Utf8 { length: u16, bytes: Vec<u8> },
+    /// `bytes` are serialized as the string they decode to, or as an array of bytes if that wouldn't round trip
+    Utf8 { length: u16, #[cfg_attr(feature = "serde", serde(with = "utf8_bytes"))] bytes: Vec<u8> },
     //length, bytes
     MethodHandle { reference_kind: u8, reference_index: CPIndex },
     //reference_kind, reference_index
@@ -131,7 +135,7 @@ impl CPInfo {
             },
             CPInfo::Utf8 { length, bytes } => {
                 push_u16!(*length, ans);
-                ans.copy_from_slice(bytes);
+                ans.extend_from_slice(bytes);
             },
             CPInfo::MethodHandle { reference_kind, reference_index } => {
                 ans.push(*reference_kind);
@@ -148,4 +152,53 @@ impl CPInfo {
         }
         ans
     }
-}
\ No newline at end of file
+}
+
+/// Serializes modified UTF-8 as a string when it's valid, so JSON exports are readable.
+/// Anything else, such as an unpaired surrogate or a malformed sequence, is kept as an array of bytes.
+#[cfg(feature = "serde")]
+mod utf8_bytes {
+    use super::{read_string, write_string};
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        let string = read_string(bytes);
+        if write_string(&string) == bytes {
+            s.serialize_str(&string)
+        } else {
+            s.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        d.deserialize_any(Utf8Visitor)
+    }
+
+    struct Utf8Visitor;
+
+    impl<'de> Visitor<'de> for Utf8Visitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a string or an array of bytes")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            Ok(write_string(v))
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut ans = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element()? {
+                ans.push(b);
+            }
+            Ok(ans)
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use attributes::*;
+use bytecode_tools::instruction_offsets;
+use class::{read_string, JavaClass};
+use cp::ConstantPool;
+use malformed;
+use resolved::Code;
+use std::io;
+use std::ops::Range;
+
+/// A local variable, from a `LocalVariableTable` entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVariable {
+    pub name: String,
+    /// field descriptor of the variable's type
+    pub descriptor: String,
+    /// generic signature, from the matching `LocalVariableTypeTable` entry
+    pub signature: Option<String>,
+    /// the local variable slot
+    pub index: u16,
+    /// the pcs where the variable has a value
+    pub range: Range<u32>,
+}
+
+/// The `LineNumberTable` entries in `attributes` (the attributes of a `Code` attribute),
+/// merged and sorted by pc
+pub fn line_numbers(attributes: &[Attribute]) -> Vec<LineNumberTableEntry> {
+    let mut ans = Vec::new();
+    for a in attributes {
+        if let Attribute::LineNumberTable { line_number_table } = a {
+            ans.extend(line_number_table.iter().cloned());
+        }
+    }
+    ans.sort_by_key(|e| e.start_pc);
+    ans
+}
+
+/// The source line of the instruction at `pc`
+pub fn line_at(attributes: &[Attribute], pc: u32) -> Option<u16> {
+    line_numbers(attributes).iter().rev().find(|e| e.start_pc as u32 <= pc).map(|e| e.line_number)
+}
+
+/// The pc ranges whose instructions come from `line`, in pc order.
+/// `code_length` is the length of the bytecode, which ends the last range.
+pub fn pc_ranges(attributes: &[Attribute], code_length: u32, line: u16) -> Vec<Range<u32>> {
+    let table = line_numbers(attributes);
+    let mut ans: Vec<Range<u32>> = Vec::new();
+    for (i, e) in table.iter().enumerate() {
+        if e.line_number != line {
+            continue;
+        }
+        let start = e.start_pc as u32;
+        let end = table[i + 1..].iter().map(|n| n.start_pc as u32).find(|pc| *pc > start).unwrap_or(code_length);
+        match ans.last_mut() {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ => ans.push(start..end),
+        }
+    }
+    ans
+}
+
+/// The distinct lines in the `LineNumberTable`s, in ascending order
+pub fn lines(attributes: &[Attribute]) -> Vec<u16> {
+    let mut ans: Vec<u16> = line_numbers(attributes).iter().map(|e| e.line_number).collect();
+    ans.sort_unstable();
+    ans.dedup();
+    ans
+}
+
+/// The local variables in `attributes` that have a value at `pc`, ordered by slot
+pub fn locals_at(attributes: &[Attribute], cp: &ConstantPool, pc: u32) -> io::Result<Vec<LocalVariable>> {
+    let mut ans: Vec<LocalVariable> = local_variables(attributes, cp)?.into_iter().filter(|v| v.range.contains(&pc)).collect();
+    ans.sort_by_key(|v| v.index);
+    Ok(ans)
+}
+
+/// Every entry of the `LocalVariableTable`s in `attributes`, with its signature from the `LocalVariableTypeTable`s
+pub fn local_variables(attributes: &[Attribute], cp: &ConstantPool) -> io::Result<Vec<LocalVariable>> {
+    let mut ans = Vec::new();
+    for a in attributes {
+        if let Attribute::LocalVariableTable { local_variable_table } = a {
+            for e in local_variable_table {
+                ans.push(LocalVariable {
+                    name: cp.utf8(e.name_index)?,
+                    descriptor: cp.utf8(e.descriptor_index)?,
+                    signature: None,
+                    index: e.index,
+                    range: e.start_pc as u32..e.start_pc as u32 + e.length as u32,
+                });
+            }
+        }
+    }
+    for a in attributes {
+        if let Attribute::LocalVariableTypeTable { local_variable_type_table } = a {
+            for e in local_variable_type_table {
+                //§4.7.14: an entry describes the same variable as the LocalVariableTable entry with the same range and slot
+                let range = e.start_pc as u32..e.start_pc as u32 + e.length as u32;
+                if let Some(v) = ans.iter_mut().find(|v| v.index == e.index && v.range == range) {
+                    v.signature = Some(cp.utf8(e.signature_index)?);
+                }
+            }
+        }
+    }
+    Ok(ans)
+}
+
+impl<'a> Code<'a> {
+    /// The source line of the instruction at `pc`
+    pub fn line_at(&self, pc: u32) -> Option<u16> {
+        line_at(self.attributes, pc)
+    }
+
+    /// The pc ranges whose instructions come from `line`
+    pub fn pc_ranges(&self, line: u16) -> Vec<Range<u32>> {
+        let length = *instruction_offsets(self.code).last().unwrap_or(&0);
+        pc_ranges(self.attributes, length, line)
+    }
+
+    /// The distinct source lines of the code
+    pub fn lines(&self) -> Vec<u16> {
+        lines(self.attributes)
+    }
+
+    /// The local variables that have a value at `pc`
+    pub fn locals_at(&self, cp: &ConstantPool, pc: u32) -> io::Result<Vec<LocalVariable>> {
+        locals_at(self.attributes, cp, pc)
+    }
+}
+
+impl JavaClass {
+    /// The file name in the `SourceFile` attribute, e.g. `String.java`
+    pub fn source_file(&self) -> io::Result<Option<String>> {
+        for a in &self.attributes {
+            if let Attribute::SourceFile { sourcefile_index } = a {
+                return self.constant_pool.utf8(*sourcefile_index).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The `SourceDebugExtension` attribute, parsed as an SMAP.
+    /// Returns `None` if the class has no such attribute.
+    pub fn source_map(&self) -> io::Result<Option<SourceMap>> {
+        for a in &self.attributes {
+            if let Attribute::SourceDebugExtenson { debug_extension } = a {
+                return SourceMap::parse(&read_string(debug_extension)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A JSR-45 source map, which maps the lines of a class's `LineNumberTable`s
+/// to lines of the files it was generated from, e.g. JSP pages or inlined Kotlin functions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    /// the name of the generated source file
+    pub output_file: String,
+    pub default_stratum: String,
+    pub strata: Vec<Stratum>,
+}
+
+/// One stratum (source language level) of a `SourceMap`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stratum {
+    pub id: String,
+    pub files: Vec<SourceMapFile>,
+    pub lines: Vec<LineMapping>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMapFile {
+    pub id: u32,
+    pub name: String,
+    /// the path of the file, if the SMAP gave one
+    pub path: Option<String>,
+}
+
+/// A line section entry: input lines `input_start..input_start + repeat` map,
+/// in order, to `output_increment` output lines each, starting at `output_start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineMapping {
+    pub input_start: u32,
+    pub file_id: u32,
+    pub repeat: u32,
+    pub output_start: u32,
+    pub output_increment: u32,
+}
+
+/// A line of a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine<'a> {
+    pub file: &'a SourceMapFile,
+    pub line: u32,
+}
+
+impl SourceMap {
+    pub fn parse(smap: &str) -> io::Result<SourceMap> {
+        let mut lines = smap.lines().map(|l| l.trim_end_matches('\r'));
+        if lines.next() != Some("SMAP") {
+            return Err(malformed("SMAP does not start with SMAP"));
+        }
+        let output_file = lines.next().ok_or_else(|| malformed("SMAP has no output file name"))?.to_owned();
+        let default_stratum = lines.next().ok_or_else(|| malformed("SMAP has no default stratum"))?.to_owned();
+        let mut strata: Vec<Stratum> = Vec::new();
+        let mut section = "";
+        let mut file_id = 0;
+        while let Some(line) = lines.next() {
+            if line.starts_with('*') {
+                let mut parts = line.split_whitespace();
+                section = parts.next().unwrap_or("");
+                match section {
+                    "*S" => {
+                        let id = parts.next().ok_or_else(|| malformed("SMAP stratum has no id"))?;
+                        strata.push(Stratum { id: id.to_owned(), files: Vec::new(), lines: Vec::new() });
+                        file_id = 0;
+                    }
+                    //embedded SMAPs should have been resolved by the tool that installed this one
+                    "*O" | "*C" => return Err(malformed("Embedded SMAPs are not supported")),
+                    _ => {}
+                }
+                continue;
+            }
+            let stratum = match (section, strata.last_mut()) {
+                ("*F", Some(s)) | ("*L", Some(s)) => s,
+                //vendor and unknown sections are ignored.
+                //Some compilers (e.g. kotlinc) end every stratum with *E, so it doesn't end the SMAP
+                _ => continue,
+            };
+            if section == "*F" {
+                let (with_path, entry) = match line.strip_prefix("+ ") {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let mut parts = entry.trim().splitn(2, ' ');
+                let id = parse_number(parts.next())?;
+                let name = parts.next().ok_or_else(|| malformed("SMAP file entry has no name"))?.trim().to_owned();
+                let path = if with_path {
+                    Some(lines.next().ok_or_else(|| malformed("SMAP file entry has no path"))?.to_owned())
+                } else {
+                    None
+                };
+                stratum.files.push(SourceMapFile { id, name, path });
+            } else {
+                let (input, output) = line.split_once(':').ok_or_else(|| malformed("SMAP line entry has no ':'"))?;
+                let (input, repeat) = match input.split_once(',') {
+                    Some((input, repeat)) => (input, parse_number(Some(repeat))?),
+                    None => (input, 1),
+                };
+                let input_start = match input.split_once('#') {
+                    Some((start, id)) => {
+                        file_id = parse_number(Some(id))?;
+                        parse_number(Some(start))?
+                    }
+                    None => parse_number(Some(input))?,
+                };
+                let (output_start, output_increment) = match output.split_once(',') {
+                    Some((start, increment)) => (parse_number(Some(start))?, parse_number(Some(increment))?),
+                    None => (parse_number(Some(output))?, 1),
+                };
+                stratum.lines.push(LineMapping { input_start, file_id, repeat, output_start, output_increment });
+            }
+        }
+        Ok(SourceMap { output_file, default_stratum, strata })
+    }
+
+    pub fn stratum(&self, id: &str) -> Option<&Stratum> {
+        self.strata.iter().find(|s| s.id == id)
+    }
+
+    /// Maps a line of the class (as found in a `LineNumberTable`) to a source line in `stratum`,
+    /// or in the default stratum if `stratum` is `None`
+    pub fn map_line(&self, stratum: Option<&str>, line: u32) -> Option<SourceLine<'_>> {
+        self.stratum(stratum.unwrap_or(&self.default_stratum))?.map_line(line)
+    }
+}
+
+impl Stratum {
+    pub fn file(&self, id: u32) -> Option<&SourceMapFile> {
+        self.files.iter().find(|f| f.id == id)
+    }
+
+    /// Maps a line of the class to a source line, using the first line entry that covers it
+    pub fn map_line(&self, line: u32) -> Option<SourceLine<'_>> {
+        for m in &self.lines {
+            //an increment of 0 maps every input line to the same output line
+            let step = m.output_increment.max(1) as u64;
+            let span = if m.output_increment == 0 { 1 } else { m.repeat as u64 * step };
+            let output = line as u64;
+            if output >= m.output_start as u64 && output < m.output_start as u64 + span {
+                let i = if m.output_increment == 0 { 0 } else { (output - m.output_start as u64) / step };
+                return Some(SourceLine { file: self.file(m.file_id)?, line: m.input_start.checked_add(i as u32)? });
+            }
+        }
+        None
+    }
+}
+
+fn parse_number(s: Option<&str>) -> io::Result<u32> {
+    s.and_then(|s| s.trim().parse().ok()).ok_or_else(|| malformed("Bad number in SMAP"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two strata in the style of kotlinc, each ending with `*E`
+    const SMAP: &str = "SMAP\r\nFoo.kt\r\nKotlin\r\n\
+        *S Kotlin\r\n*F\r\n+ 1 Foo.kt\r\ncom/example/Foo.kt\r\n+ 2 Inline.kt\r\ncom/example/Inline.kt\r\n3 Other.kt\r\n\
+        *L\r\n1#1,10:1\r\n5#2,3:20,2\r\n8:30\r\n2#3,2:40,0\r\n*E\r\n\
+        1#1:99\r\n\
+        *S KotlinDebug\r\n*F\r\n+ 1 Foo.kt\r\ncom/example/Foo.kt\r\n*L\r\n12:50\r\n4#1:20,6\r\n*E\r\n";
+
+    fn file(id: u32, name: &str, path: Option<&str>) -> SourceMapFile {
+        SourceMapFile { id, name: name.to_owned(), path: path.map(str::to_owned) }
+    }
+
+    fn mapping(input_start: u32, file_id: u32, repeat: u32, output_start: u32, output_increment: u32) -> LineMapping {
+        LineMapping { input_start, file_id, repeat, output_start, output_increment }
+    }
+
+    #[test]
+    fn parses_strata() {
+        let smap = SourceMap::parse(SMAP).unwrap();
+        assert_eq!(smap.output_file, "Foo.kt");
+        assert_eq!(smap.default_stratum, "Kotlin");
+        assert_eq!(smap.strata, vec![
+            Stratum {
+                id: "Kotlin".to_owned(),
+                files: vec![file(1, "Foo.kt", Some("com/example/Foo.kt")), file(2, "Inline.kt", Some("com/example/Inline.kt")), file(3, "Other.kt", None)],
+                //a line entry without #n uses the file of the one before it
+                lines: vec![mapping(1, 1, 10, 1, 1), mapping(5, 2, 3, 20, 2), mapping(8, 2, 1, 30, 1), mapping(2, 3, 2, 40, 0)],
+            },
+            Stratum {
+                id: "KotlinDebug".to_owned(),
+                files: vec![file(1, "Foo.kt", Some("com/example/Foo.kt"))],
+                //the file id starts over at 0 in each stratum
+                lines: vec![mapping(12, 0, 1, 50, 1), mapping(4, 1, 1, 20, 6)],
+            },
+        ]);
+    }
+
+    #[test]
+    fn maps_lines() {
+        let smap = SourceMap::parse(SMAP).unwrap();
+        let map = |stratum, line| smap.map_line(stratum, line).map(|l| (l.file.name.clone(), l.line));
+        assert_eq!(map(None, 3), Some(("Foo.kt".to_owned(), 3)));
+        assert_eq!(map(None, 11), None);
+        //each of the repeated input lines covers 2 output lines
+        assert_eq!(map(None, 20), Some(("Inline.kt".to_owned(), 5)));
+        assert_eq!(map(None, 23), Some(("Inline.kt".to_owned(), 6)));
+        assert_eq!(map(None, 25), Some(("Inline.kt".to_owned(), 7)));
+        assert_eq!(map(None, 26), None);
+        assert_eq!(map(None, 30), Some(("Inline.kt".to_owned(), 8)));
+        //an increment of 0 maps to the first line
+        assert_eq!(map(None, 40), Some(("Other.kt".to_owned(), 2)));
+        assert_eq!(map(None, 41), None);
+        //the entry after *E isn't part of any stratum
+        assert_eq!(map(None, 99), None);
+        assert_eq!(map(Some("KotlinDebug"), 25), Some(("Foo.kt".to_owned(), 4)));
+        //file 0 isn't declared
+        assert_eq!(map(Some("KotlinDebug"), 50), None);
+        assert_eq!(map(Some("Java"), 3), None);
+    }
+
+    #[test]
+    fn malformed_smaps() {
+        let header = "SMAP\nFoo.java\nJava\n*S Java\n";
+        for (smap, message) in vec![
+            ("".to_owned(), "SMAP does not start with SMAP"),
+            ("JSR45\nFoo.java\nJava\n".to_owned(), "SMAP does not start with SMAP"),
+            ("SMAP\nFoo.java\n".to_owned(), "SMAP has no default stratum"),
+            ("SMAP\nFoo.java\nJava\n*S\n".to_owned(), "SMAP stratum has no id"),
+            (format!("{}*F\n1\n*E\n", header), "SMAP file entry has no name"),
+            (format!("{}*F\nx Foo.java\n*E\n", header), "Bad number in SMAP"),
+            (format!("{}*F\n+ 1 Foo.java\n", header), "SMAP file entry has no path"),
+            (format!("{}*F\n1 Foo.java\n*L\n1 1\n*E\n", header), "SMAP line entry has no ':'"),
+            (format!("{}*F\n1 Foo.java\n*L\n1#:1\n*E\n", header), "Bad number in SMAP"),
+            (format!("{}*F\n1 Foo.java\n*L\n1,-1:1\n*E\n", header), "Bad number in SMAP"),
+            (format!("{}*F\n1 Foo.java\n*L\n1:4294967296\n*E\n", header), "Bad number in SMAP"),
+            (format!("{}*O Jsp\n*C Jsp\n*E\n", header), "Embedded SMAPs are not supported"),
+        ] {
+            let e = SourceMap::parse(&smap).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{:?}", smap);
+            assert_eq!(e.to_string(), format!("Malformed class file: {}", message), "{:?}", smap);
+        }
+    }
+}
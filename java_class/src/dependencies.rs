@@ -0,0 +1,529 @@
+use attributes::{Annotation, Attribute, ElementValue, ElementValuePair};
+use class::JavaClass;
+use classpath::{class_resource, ClassPath};
+use cp::{CPIndex, ConstantPool};
+use cp_info::CPInfo;
+use graph;
+use malformed;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+/// Where in a class file a type reference was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ReferenceSource {
+    /// a `Class` constant, e.g. the target of `new`, `checkcast` or a member reference
+    ConstantPool,
+    /// a field or method descriptor, including those of referenced members and local variables
+    Descriptor,
+    /// a generic `Signature`
+    Signature,
+    /// an annotation's type, or a class, enum or annotation used as one of its values
+    Annotation,
+    /// the `catch_type` of an exception handler
+    ExceptionTable,
+    /// the `Exceptions` attribute (`throws` clause) of a method
+    Throws,
+}
+
+/// Collects every class a class refers to, with where each reference was found.
+/// Array types are reduced to their element type, primitives are left out,
+/// and so is the class itself.
+pub fn type_references(class: &JavaClass) -> io::Result<BTreeMap<String, BTreeSet<ReferenceSource>>> {
+    let cp = &class.constant_pool;
+    let mut refs = References { cp, found: BTreeMap::new() };
+    for item in cp.items() {
+        match item {
+            CPInfo::Class { name_index } => {
+                let name = cp.utf8(*name_index)?;
+                if name.starts_with('[') {
+                    refs.descriptor(&name, ReferenceSource::ConstantPool);
+                } else {
+                    refs.add(name, ReferenceSource::ConstantPool);
+                }
+            }
+            CPInfo::NameAndType { descriptor_index, .. } |
+            CPInfo::MethodType { descriptor_index } => {
+                refs.descriptor(&cp.utf8(*descriptor_index)?, ReferenceSource::Descriptor);
+            }
+            _ => {}
+        }
+    }
+    for f in &class.fields {
+        refs.descriptor(&cp.utf8(f.descriptor_index)?, ReferenceSource::Descriptor);
+        refs.attributes(&f.attributes)?;
+    }
+    for m in &class.methods {
+        refs.descriptor(&cp.utf8(m.descriptor_index)?, ReferenceSource::Descriptor);
+        refs.attributes(&m.attributes)?;
+    }
+    refs.attributes(&class.attributes)?;
+    let this = cp.get_class_name(class.this_class).ok_or_else(|| malformed("this_class did not point to Class"))?;
+    refs.found.remove(&this);
+    Ok(refs.found)
+}
+
+struct References<'a> {
+    cp: &'a ConstantPool,
+    found: BTreeMap<String, BTreeSet<ReferenceSource>>,
+}
+
+impl<'a> References<'a> {
+    fn add(&mut self, name: String, source: ReferenceSource) {
+        self.found.entry(name).or_default().insert(source);
+    }
+
+    fn descriptor(&mut self, descriptor: &str, source: ReferenceSource) {
+        for name in signature_classes(descriptor) {
+            self.add(name, source);
+        }
+    }
+
+    fn class(&mut self, index: CPIndex, source: ReferenceSource) -> io::Result<()> {
+        let name = self.cp.get_class_name(index).ok_or_else(|| malformed("Class index did not point to Class"))?;
+        if name.starts_with('[') {
+            self.descriptor(&name, source);
+        } else {
+            self.add(name, source);
+        }
+        Ok(())
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) -> io::Result<()> {
+        for a in attributes {
+            match a {
+                Attribute::Code { exception_table, attributes, .. } => {
+                    for e in exception_table {
+                        if e.catch_type.as_u16() != 0 {
+                            self.class(e.catch_type, ReferenceSource::ExceptionTable)?;
+                        }
+                    }
+                    self.attributes(attributes)?;
+                }
+                Attribute::Exceptions { exception_index_table } => {
+                    for i in exception_index_table {
+                        self.class((*i).into(), ReferenceSource::Throws)?;
+                    }
+                }
+                Attribute::Signature { signature_index } => {
+                    self.descriptor(&self.cp.utf8(*signature_index)?, ReferenceSource::Signature);
+                }
+                Attribute::LocalVariableTable { local_variable_table } => {
+                    for l in local_variable_table {
+                        self.descriptor(&self.cp.utf8(l.descriptor_index)?, ReferenceSource::Descriptor);
+                    }
+                }
+                Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+                    for l in local_variable_type_table {
+                        self.descriptor(&self.cp.utf8(l.signature_index)?, ReferenceSource::Signature);
+                    }
+                }
+                Attribute::RuntimeVisibleAnnotations { annotations } |
+                Attribute::RuntimeInvisibleAnnotations { annotations } => {
+                    for an in annotations {
+                        self.annotation(an)?;
+                    }
+                }
+                Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } |
+                Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+                    for an in parameter_annotations.iter().flatten() {
+                        self.annotation(an)?;
+                    }
+                }
+                Attribute::RuntimeVisibleTypeAnnotations { annotations } |
+                Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+                    for an in annotations {
+                        self.descriptor(&self.cp.utf8(an.type_index)?, ReferenceSource::Annotation);
+                        self.element_value_pairs(&an.element_value_pairs)?;
+                    }
+                }
+                Attribute::AnnotationDefault { default_value } => self.element_value(default_value)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn annotation(&mut self, an: &Annotation) -> io::Result<()> {
+        self.descriptor(&self.cp.utf8(an.type_index)?, ReferenceSource::Annotation);
+        self.element_value_pairs(&an.element_value_pairs)
+    }
+
+    fn element_value_pairs(&mut self, pairs: &[ElementValuePair]) -> io::Result<()> {
+        for p in pairs {
+            self.element_value(&p.value)?;
+        }
+        Ok(())
+    }
+
+    fn element_value(&mut self, value: &ElementValue) -> io::Result<()> {
+        match value {
+            ElementValue::ConstValueIndex { .. } => {}
+            ElementValue::EnumConstValue { type_name_index, .. } => {
+                self.descriptor(&self.cp.utf8(*type_name_index)?, ReferenceSource::Annotation);
+            }
+            ElementValue::ClassInfoIndex(index) => {
+                self.descriptor(&self.cp.utf8(*index)?, ReferenceSource::Annotation);
+            }
+            ElementValue::AnnotationValue(an) => self.annotation(an)?,
+            ElementValue::ArrayValue(values) => {
+                for v in values {
+                    self.element_value(v)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the binary names of the classes named in a field or method descriptor,
+/// or in a class, field or method signature. Inner classes of parameterized types
+/// (`Lp/Outer<TT;>.Inner;`) are named as `p/Outer$Inner`, along with the outer class. Stops at the first malformed part.
+pub fn signature_classes(signature: &str) -> Vec<String> {
+    let s = signature.as_bytes();
+    let mut ans = vec!();
+    let mut i = 0;
+    let _ = parse_signature(s, &mut i, &mut ans);
+    ans
+}
+
+fn parse_signature(s: &[u8], i: &mut usize, out: &mut Vec<String>) -> Option<()> {
+    if *s.get(*i)? == b'<' {
+        //type parameters: `<T:Ljava/lang/Object;U::Ljava/lang/Comparable<TU;>;>`
+        *i += 1;
+        while *s.get(*i)? != b'>' {
+            while *s.get(*i)? != b':' {
+                *i += 1;
+            }
+            //the class bound may be empty, the interface bounds each start with ':'
+            while s.get(*i) == Some(&b':') {
+                *i += 1;
+                if *s.get(*i)? != b':' && s[*i] != b'>' && !is_type_start(s[*i]) {
+                    return None;
+                }
+                if is_type_start(s[*i]) {
+                    parse_type(s, i, out)?;
+                }
+            }
+        }
+        *i += 1;
+    }
+    if s.get(*i) == Some(&b'(') {
+        *i += 1;
+        while *s.get(*i)? != b')' {
+            parse_type(s, i, out)?;
+        }
+        *i += 1;
+        parse_type(s, i, out)?;
+        while s.get(*i) == Some(&b'^') {
+            *i += 1;
+            parse_type(s, i, out)?;
+        }
+        return Some(());
+    }
+    while *i < s.len() {
+        parse_type(s, i, out)?;
+    }
+    Some(())
+}
+
+fn is_type_start(c: u8) -> bool {
+    c == b'L' || c == b'T' || c == b'['
+}
+
+fn parse_type(s: &[u8], i: &mut usize, out: &mut Vec<String>) -> Option<()> {
+    match *s.get(*i)? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b'V' => *i += 1,
+        b'[' => {
+            *i += 1;
+            parse_type(s, i, out)?;
+        }
+        b'T' => {
+            while *s.get(*i)? != b';' {
+                *i += 1;
+            }
+            *i += 1;
+        }
+        b'L' => {
+            *i += 1;
+            let mut name = String::new();
+            loop {
+                let start = *i;
+                while !matches!(*s.get(*i)?, b'<' | b'.' | b';') {
+                    *i += 1;
+                }
+                name.push_str(&String::from_utf8_lossy(&s[start..*i]));
+                if s[*i] == b'<' {
+                    *i += 1;
+                    while *s.get(*i)? != b'>' {
+                        match s[*i] {
+                            b'*' => *i += 1,
+                            b'+' | b'-' => {
+                                *i += 1;
+                                parse_type(s, i, out)?;
+                            }
+                            _ => parse_type(s, i, out)?
+                        }
+                    }
+                    *i += 1;
+                }
+                match *s.get(*i)? {
+                    b'.' => {
+                        *i += 1;
+                        out.push(name.clone());
+                        name.push('$');
+                    }
+                    b';' => {
+                        *i += 1;
+                        break;
+                    }
+                    _ => return None
+                }
+            }
+            out.push(name);
+        }
+        _ => return None
+    }
+    Some(())
+}
+
+/// A directed graph between classes, packages or archives
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    pub nodes: BTreeSet<String>,
+    /// node -> nodes it depends on
+    pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    fn add_edge(&mut self, from: &str, to: &str) {
+        self.nodes.insert(from.to_owned());
+        self.nodes.insert(to.to_owned());
+        self.edges.entry(from.to_owned()).or_default().insert(to.to_owned());
+    }
+
+    pub fn dependencies(&self, node: &str) -> BTreeSet<String> {
+        self.edges.get(node).cloned().unwrap_or_default()
+    }
+
+    /// Returns the nodes that depend on `node`
+    pub fn dependents(&self, node: &str) -> BTreeSet<String> {
+        self.edges.iter().filter(|(_, to)| to.contains(node)).map(|(from, _)| from.clone()).collect()
+    }
+
+    /// Returns every cycle, as the sorted nodes of each strongly connected component
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let nodes: Vec<&str> = self.nodes.iter().map(String::as_str).collect();
+        graph::cycles(&nodes, |n| self.edges.get(n).into_iter().flatten().map(String::as_str).collect())
+    }
+
+    /// Returns every edge whose source is `from` or inside it, and whose target is `to` or inside it.
+    /// A node is inside a prefix if it continues it after a '/', so `com/foo` covers `com/foo/bar` but not `com/foobar`.
+    /// On a package graph, `find_edges("com/app/model", "com/app/ui")` finds forbidden layer dependencies.
+    pub fn find_edges(&self, from: &str, to: &str) -> Vec<(String, String)> {
+        let mut ans = vec!();
+        for (f, targets) in &self.edges {
+            if !within(f, from) {
+                continue;
+            }
+            for t in targets {
+                if within(t, to) {
+                    ans.push((f.clone(), t.clone()));
+                }
+            }
+        }
+        ans
+    }
+}
+
+/// Returns true if `node` is `prefix`, or is under it when both are split on '/'. Every node is under "".
+fn within(node: &str, prefix: &str) -> bool {
+    match node.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false
+    }
+}
+
+/// The type references of a set of classes, and the archive or directory each class came from
+#[derive(Debug, Clone, Default)]
+pub struct Dependencies {
+    references: BTreeMap<String, BTreeSet<String>>,
+    origins: BTreeMap<String, String>,
+}
+
+impl Dependencies {
+    pub fn new() -> Dependencies {
+        Dependencies::default()
+    }
+
+    /// Analyzes every class on a classpath, recording the entry each came from
+    pub fn from_classpath(cp: &ClassPath) -> io::Result<Dependencies> {
+        let mut ans = Dependencies::new();
+        for (name, class) in cp.classes()? {
+            let origin = cp.entry_of(&class_resource(&name))?.map(|(e, _)| e.path().display().to_string());
+            ans.add_class(&class?, origin)?;
+        }
+        Ok(ans)
+    }
+
+    /// Adds a class, replacing any class with the same name.
+    /// `origin` names the jar or directory it came from, and is used for the archive graph.
+    pub fn add_class(&mut self, class: &JavaClass, origin: Option<String>) -> io::Result<()> {
+        let name = class.constant_pool.get_class_name(class.this_class).ok_or_else(|| malformed("this_class did not point to Class"))?;
+        let refs = type_references(class)?.into_keys().collect();
+        self.references.insert(name.clone(), refs);
+        match origin {
+            Some(o) => { self.origins.insert(name, o); }
+            None => { self.origins.remove(&name); }
+        }
+        Ok(())
+    }
+
+    /// Returns the classes a class refers to
+    pub fn references(&self, class: &str) -> Option<&BTreeSet<String>> {
+        self.references.get(class)
+    }
+
+    /// Returns the dependencies between classes, including classes that were never added
+    pub fn class_graph(&self) -> DependencyGraph {
+        let mut ans = DependencyGraph::default();
+        for (from, targets) in &self.references {
+            ans.nodes.insert(from.clone());
+            for to in targets {
+                ans.add_edge(from, to);
+            }
+        }
+        ans
+    }
+
+    /// Returns the dependencies between packages. A package's dependencies on itself are left out.
+    pub fn package_graph(&self) -> DependencyGraph {
+        let mut ans = DependencyGraph::default();
+        for (from, targets) in &self.references {
+            let from = package_of(from);
+            ans.nodes.insert(from.to_owned());
+            for to in targets {
+                let to = package_of(to);
+                if from != to {
+                    ans.add_edge(from, to);
+                }
+            }
+        }
+        ans
+    }
+
+    /// Returns the dependencies between archives. Classes without an origin, and references
+    /// to classes that were never added, are left out.
+    pub fn archive_graph(&self) -> DependencyGraph {
+        let mut ans = DependencyGraph::default();
+        for (from, targets) in &self.references {
+            let from = match self.origins.get(from) {
+                Some(o) => o,
+                None => continue
+            };
+            ans.nodes.insert(from.clone());
+            for to in targets {
+                if let Some(to) = self.origins.get(to) {
+                    if from != to {
+                        ans.add_edge(from, to);
+                    }
+                }
+            }
+        }
+        ans
+    }
+
+    /// Returns each referenced class that was never added, with the classes that refer to it.
+    /// To leave out the platform, analyze it too or filter out names starting with `java/`.
+    pub fn unresolved(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut ans: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (from, targets) in &self.references {
+            for to in targets {
+                if !self.references.contains_key(to) {
+                    ans.entry(to.clone()).or_default().insert(from.clone());
+                }
+            }
+        }
+        ans
+    }
+}
+
+/// Returns the package of a binary class name, e.g. `java/lang` for `java/lang/Object`,
+/// or "" for the unnamed package
+pub fn package_of(class: &str) -> &str {
+    match class.rfind('/') {
+        Some(i) => &class[..i],
+        None => ""
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcodes::Opcode::*;
+    use symbolic::SymOpcode;
+    use test_classes::ClassSpec;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    /// `com/foo/A` refers to `com/foobar/B`, which extends it
+    fn classes() -> (JavaClass, JavaClass) {
+        let a = ClassSpec::new(0x0021, "com/foo/A", Some("java/lang/Object"))
+            .field(0x0002, "list", "Ljava/util/List;")
+            .method(0x0001, "m", "(Lcom/foobar/B;)[Lcom/foo/sub/C;", vec![
+                SymOpcode::Plain(aconst_null),
+                SymOpcode::CheckCast("com/other/E".to_owned()),
+                SymOpcode::Plain(areturn),
+            ])
+            .attribute(|cp| Attribute::Signature { signature_index: cp.add_utf8("Ljava/lang/Object;Ljava/lang/Comparable<Lcom/foo/G;>;".to_owned()) })
+            .build();
+        let b = ClassSpec::new(0x0021, "com/foobar/B", Some("com/foo/A")).build();
+        (a, b)
+    }
+
+    #[test]
+    fn references_are_found_with_their_source() {
+        use self::ReferenceSource::*;
+        let refs = type_references(&classes().0).unwrap();
+        let expected: Vec<(&str, Vec<ReferenceSource>)> = vec![
+            ("com/foo/G", vec![Signature]),
+            ("com/foo/sub/C", vec![Descriptor]),
+            ("com/foobar/B", vec![Descriptor]),
+            ("com/other/E", vec![ConstantPool]),
+            ("java/lang/Comparable", vec![Signature]),
+            ("java/lang/Object", vec![ConstantPool, Signature]),
+            ("java/util/List", vec![Descriptor]),
+        ];
+        let expected: BTreeMap<String, BTreeSet<ReferenceSource>> = expected.into_iter()
+            .map(|(n, s)| (n.to_owned(), s.into_iter().collect())).collect();
+        assert_eq!(refs, expected);
+        assert_eq!(signature_classes("Lp/Outer<TT;>.Inner;"), strings(&["p/Outer", "p/Outer$Inner"]));
+        assert_eq!(signature_classes("(I[[Ljava/lang/String;)V"), strings(&["java/lang/String"]));
+    }
+
+    #[test]
+    fn graphs_and_edges_respect_package_boundaries() {
+        let (a, b) = classes();
+        let mut deps = Dependencies::new();
+        deps.add_class(&a, Some("a.jar".to_owned())).unwrap();
+        deps.add_class(&b, Some("b.jar".to_owned())).unwrap();
+        let packages = deps.package_graph();
+        assert_eq!(packages.dependencies("com/foo"), strings(&["com/foo/sub", "com/foobar", "com/other", "java/lang", "java/util"]).into_iter().collect());
+        assert_eq!(packages.dependents("com/foo"), strings(&["com/foobar"]).into_iter().collect());
+        assert_eq!(packages.cycles(), vec![strings(&["com/foo", "com/foobar"])]);
+
+        let edge = |from: &str, to: &str| (from.to_owned(), to.to_owned());
+        assert_eq!(packages.find_edges("com/foo", "com/foo"), vec![edge("com/foo", "com/foo/sub")]);
+        assert_eq!(packages.find_edges("com/foo", "com/foobar"), vec![edge("com/foo", "com/foobar")]);
+        assert_eq!(packages.find_edges("com/foobar", "com/"), vec![edge("com/foobar", "com/foo")]);
+        assert_eq!(packages.find_edges("com/fo", ""), vec![]);
+        assert_eq!(packages.find_edges("", "java"), vec![edge("com/foo", "java/lang"), edge("com/foo", "java/util")]);
+
+        let archives = deps.archive_graph();
+        assert_eq!(archives.cycles(), vec![strings(&["a.jar", "b.jar"])]);
+        assert_eq!(deps.unresolved()["java/lang/Object"], strings(&["com/foo/A"]).into_iter().collect());
+        assert!(!deps.unresolved().contains_key("com/foo/A"));
+    }
+}
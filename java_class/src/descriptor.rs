@@ -0,0 +1,162 @@
+use malformed;
+use std::fmt;
+use std::io;
+
+/// A field type, parsed from a field descriptor (§4.3.2)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// a class or interface, by binary name
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> io::Result<FieldType> {
+        let mut i = 0;
+        match FieldType::read(descriptor, &mut i) {
+            Some(t) if i == descriptor.len() => Ok(t),
+            _ => Err(malformed(&format!("Bad field descriptor {}", descriptor)))
+        }
+    }
+
+    /// Reads the field type starting at byte `i` of `s`, leaving `i` just past it
+    fn read(s: &str, i: &mut usize) -> Option<FieldType> {
+        let c = *s.as_bytes().get(*i)?;
+        *i += 1;
+        Some(match c {
+            b'B' => FieldType::Byte,
+            b'C' => FieldType::Char,
+            b'D' => FieldType::Double,
+            b'F' => FieldType::Float,
+            b'I' => FieldType::Int,
+            b'J' => FieldType::Long,
+            b'S' => FieldType::Short,
+            b'Z' => FieldType::Boolean,
+            b'L' => {
+                let end = *i + s[*i..].find(';')?;
+                if end == *i {
+                    return None;
+                }
+                let name = s[*i..end].to_owned();
+                *i = end + 1;
+                FieldType::Object(name)
+            }
+            b'[' => FieldType::Array(Box::new(FieldType::read(s, i)?)),
+            _ => return None
+        })
+    }
+
+    /// The number of local variable slots a value of this type takes
+    pub fn slots(&self) -> u32 {
+        match self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1
+        }
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        !matches!(self, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    /// The type as it is written in Java, e.g. `java.lang.String[]`
+    pub fn java_name(&self) -> String {
+        match self {
+            FieldType::Byte => "byte".to_owned(),
+            FieldType::Char => "char".to_owned(),
+            FieldType::Double => "double".to_owned(),
+            FieldType::Float => "float".to_owned(),
+            FieldType::Int => "int".to_owned(),
+            FieldType::Long => "long".to_owned(),
+            FieldType::Short => "short".to_owned(),
+            FieldType::Boolean => "boolean".to_owned(),
+            FieldType::Object(name) => name.replace('/', "."),
+            FieldType::Array(inner) => inner.java_name() + "[]",
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    /// Formats the type as a descriptor
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(name) => write!(f, "L{};", name),
+            FieldType::Array(inner) => write!(f, "[{}", inner),
+        }
+    }
+}
+
+/// A parsed method descriptor (§4.3.3)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    /// `None` for `void`
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> io::Result<MethodDescriptor> {
+        MethodDescriptor::read(descriptor).ok_or_else(|| malformed(&format!("Bad method descriptor {}", descriptor)))
+    }
+
+    fn read(s: &str) -> Option<MethodDescriptor> {
+        if !s.starts_with('(') {
+            return None;
+        }
+        let mut i = 1;
+        let mut parameters = Vec::new();
+        while s.as_bytes().get(i) != Some(&b')') {
+            parameters.push(FieldType::read(s, &mut i)?);
+        }
+        i += 1;
+        let return_type = if &s[i..] == "V" {
+            None
+        } else {
+            let t = FieldType::read(s, &mut i)?;
+            if i != s.len() {
+                return None;
+            }
+            Some(t)
+        };
+        Some(MethodDescriptor { parameters, return_type })
+    }
+
+    /// The number of local variable slots taken by the arguments, not counting `this`
+    pub fn arg_slots(&self) -> u32 {
+        self.parameters.iter().map(FieldType::slots).sum()
+    }
+
+    /// The return type as it is written in Java
+    pub fn java_return_type(&self) -> String {
+        self.return_type.as_ref().map(FieldType::java_name).unwrap_or_else(|| "void".to_owned())
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    /// Formats the descriptor in its class file form, e.g. `(ILjava/lang/String;)V`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for p in &self.parameters {
+            write!(f, "{}", p)?;
+        }
+        match &self.return_type {
+            Some(t) => write!(f, "){}", t),
+            None => write!(f, ")V"),
+        }
+    }
+}
@@ -0,0 +1,631 @@
+use attributes::*;
+use class::JavaClass;
+use code::CodeBody;
+use cp::{CPIndex, ConstantPool};
+use json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use symbolic::{resolve_bootstrap_method, resolve_constant, Constant, SymOpcode};
+
+/// One line of a line-by-line comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A value that differs between the two classes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub what: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// The differences between two fields or two methods with the same name and descriptor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemberDiff {
+    pub name: String,
+    pub descriptor: String,
+    pub changes: Vec<Change>,
+    /// the member's attributes, resolved to text, if they differ.
+    /// For methods this includes the exception table and the attributes of the `Code` attribute.
+    pub attributes: Vec<DiffLine>,
+    /// the instructions in symbolic form, if they differ. Branch targets are instruction indices.
+    pub code: Vec<DiffLine>,
+}
+
+/// The differences between two class files.
+/// Everything is compared by value, so classes whose constant pools are merely ordered differently are equal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassDiff {
+    pub header: Vec<Change>,
+    pub constants_removed: Vec<String>,
+    pub constants_added: Vec<String>,
+    /// fields and methods are named as `name descriptor`
+    pub fields_removed: Vec<String>,
+    pub fields_added: Vec<String>,
+    pub fields_changed: Vec<MemberDiff>,
+    pub methods_removed: Vec<String>,
+    pub methods_added: Vec<String>,
+    pub methods_changed: Vec<MemberDiff>,
+    /// the class's attributes, resolved to text, if they differ
+    pub attributes: Vec<DiffLine>,
+}
+
+impl ClassDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == ClassDiff::default()
+    }
+
+    /// Formats the diff for people, in the style of a unified diff.
+    /// Unchanged lines are only shown near changed ones.
+    pub fn to_text(&self) -> String {
+        let mut ans = String::new();
+        for c in &self.header {
+            let _ = writeln!(ans, "{}: {} -> {}", c.what, c.old, c.new);
+        }
+        for c in &self.constants_removed {
+            let _ = writeln!(ans, "- constant {}", c);
+        }
+        for c in &self.constants_added {
+            let _ = writeln!(ans, "+ constant {}", c);
+        }
+        write_lines(&mut ans, "", &self.attributes);
+        for (kind, removed, added, changed) in &[("field", &self.fields_removed, &self.fields_added, &self.fields_changed),
+                                                 ("method", &self.methods_removed, &self.methods_added, &self.methods_changed)] {
+            for m in removed.iter() {
+                let _ = writeln!(ans, "- {} {}", kind, m);
+            }
+            for m in added.iter() {
+                let _ = writeln!(ans, "+ {} {}", kind, m);
+            }
+            for m in changed.iter() {
+                let _ = writeln!(ans, "{} {} {}:", kind, m.name, m.descriptor);
+                for c in &m.changes {
+                    let _ = writeln!(ans, "    {}: {} -> {}", c.what, c.old, c.new);
+                }
+                write_lines(&mut ans, "    ", &m.attributes);
+                write_lines(&mut ans, "    ", &m.code);
+            }
+        }
+        ans
+    }
+
+    /// Formats the diff as JSON, with the same fields as `ClassDiff`.
+    /// Lines are objects with an `op` of "same", "removed" or "added", and the `text` of the line.
+    pub fn to_json(&self) -> String {
+        let strings = |v: &[String]| json::array(v.iter().map(|s| json::string(s)));
+        let changes = |v: &[Change]| json::array(v.iter().map(|c| json::object(&[
+            ("what", json::string(&c.what)),
+            ("old", json::string(&c.old)),
+            ("new", json::string(&c.new)),
+        ])));
+        let members = |v: &[MemberDiff]| json::array(v.iter().map(|m| json::object(&[
+            ("name", json::string(&m.name)),
+            ("descriptor", json::string(&m.descriptor)),
+            ("changes", changes(&m.changes)),
+            ("attributes", lines_json(&m.attributes)),
+            ("code", lines_json(&m.code)),
+        ])));
+        json::object(&[
+            ("header", changes(&self.header)),
+            ("constants_removed", strings(&self.constants_removed)),
+            ("constants_added", strings(&self.constants_added)),
+            ("fields_removed", strings(&self.fields_removed)),
+            ("fields_added", strings(&self.fields_added)),
+            ("fields_changed", members(&self.fields_changed)),
+            ("methods_removed", strings(&self.methods_removed)),
+            ("methods_added", strings(&self.methods_added)),
+            ("methods_changed", members(&self.methods_changed)),
+            ("attributes", lines_json(&self.attributes)),
+        ])
+    }
+}
+
+fn lines_json(lines: &[DiffLine]) -> String {
+    json::array(lines.iter().map(|l| {
+        let (op, text) = match l {
+            DiffLine::Same(t) => ("same", t),
+            DiffLine::Removed(t) => ("removed", t),
+            DiffLine::Added(t) => ("added", t),
+        };
+        json::object(&[("op", json::string(op)), ("text", json::string(text))])
+    }))
+}
+
+/// how many unchanged lines to show around a change
+const CONTEXT: usize = 2;
+
+fn write_lines(out: &mut String, indent: &str, lines: &[DiffLine]) {
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, l)| !matches!(l, DiffLine::Same(_))).map(|(i, _)| i).collect();
+    let near = |i: usize| changed.iter().any(|&c| c.abs_diff(i) <= CONTEXT);
+    let mut skipped = false;
+    for (i, l) in lines.iter().enumerate() {
+        match l {
+            DiffLine::Removed(t) => { let _ = writeln!(out, "{}- {}", indent, t); }
+            DiffLine::Added(t) => { let _ = writeln!(out, "{}+ {}", indent, t); }
+            DiffLine::Same(t) if near(i) => { let _ = writeln!(out, "{}  {}", indent, t); }
+            DiffLine::Same(_) => {
+                if !skipped {
+                    let _ = writeln!(out, "{}  ...", indent);
+                }
+                skipped = true;
+                continue;
+            }
+        }
+        skipped = false;
+    }
+}
+
+/// Compares two classes
+pub fn diff(old: &JavaClass, new: &JavaClass) -> io::Result<ClassDiff> {
+    let (ocp, ncp) = (&old.constant_pool, &new.constant_pool);
+    let mut ans = ClassDiff::default();
+
+    let header = |c: &JavaClass| -> io::Result<Vec<(&'static str, String)>> {
+        let cp = &c.constant_pool;
+        Ok(vec![
+            ("version", format!("{}.{}", c.major_version, c.minor_version)),
+            ("access flags", format!("{:#06x}", c.access_flags)),
+            ("this class", cp.class_name(c.this_class)?),
+            ("super class", if c.super_class.as_u16() == 0 { "none".to_owned() } else { cp.class_name(c.super_class)? }),
+            ("interfaces", c.interfaces.iter().map(|i| cp.class_name(*i)).collect::<io::Result<Vec<_>>>()?.join(", ")),
+        ])
+    };
+    for ((what, o), (_, n)) in header(old)?.into_iter().zip(header(new)?) {
+        if o != n {
+            ans.header.push(Change { what: what.to_owned(), old: o, new: n });
+        }
+    }
+
+    let (oc, nc) = (constant_counts(ocp), constant_counts(ncp));
+    for (c, count) in &oc {
+        for _ in *nc.get(c).unwrap_or(&0)..*count {
+            ans.constants_removed.push(c.clone());
+        }
+    }
+    for (c, count) in &nc {
+        for _ in *oc.get(c).unwrap_or(&0)..*count {
+            ans.constants_added.push(c.clone());
+        }
+    }
+
+    ans.attributes = diff_lines(&attributes_text(&old.attributes, ocp, old.bootstrap_methods())?, &attributes_text(&new.attributes, ncp, new.bootstrap_methods())?);
+
+    let (of, nf) = (members(old, &old.fields.iter().map(|f| (f.access_flags, f.name_index, f.descriptor_index, &f.attributes)).collect::<Vec<_>>())?,
+                    members(new, &new.fields.iter().map(|f| (f.access_flags, f.name_index, f.descriptor_index, &f.attributes)).collect::<Vec<_>>())?);
+    diff_members(&of, &nf, old, new, &mut ans.fields_removed, &mut ans.fields_added, &mut ans.fields_changed)?;
+    let (om, nm) = (members(old, &old.methods.iter().map(|m| (m.access_flags, m.name_index, m.descriptor_index, &m.attributes)).collect::<Vec<_>>())?,
+                    members(new, &new.methods.iter().map(|m| (m.access_flags, m.name_index, m.descriptor_index, &m.attributes)).collect::<Vec<_>>())?);
+    diff_members(&om, &nm, old, new, &mut ans.methods_removed, &mut ans.methods_added, &mut ans.methods_changed)?;
+    Ok(ans)
+}
+
+type Members<'a> = BTreeMap<(String, String), (u16, &'a Vec<Attribute>)>;
+
+/// Keys fields or methods, given as (access flags, name index, descriptor index, attributes), by name and descriptor
+fn members<'a>(class: &JavaClass, members: &[(u16, CPIndex, CPIndex, &'a Vec<Attribute>)]) -> io::Result<Members<'a>> {
+    let cp = &class.constant_pool;
+    members.iter().map(|&(flags, name, descriptor, attributes)| Ok(((cp.utf8(name)?, cp.utf8(descriptor)?), (flags, attributes)))).collect()
+}
+
+fn diff_members(old: &Members, new: &Members, oclass: &JavaClass, nclass: &JavaClass,
+                removed: &mut Vec<String>, added: &mut Vec<String>, changed: &mut Vec<MemberDiff>) -> io::Result<()> {
+    for (key, (oflags, oattrs)) in old {
+        let (nflags, nattrs) = match new.get(key) {
+            Some(n) => n,
+            None => {
+                removed.push(format!("{} {}", key.0, key.1));
+                continue;
+            }
+        };
+        let mut m = MemberDiff { name: key.0.clone(), descriptor: key.1.clone(), ..MemberDiff::default() };
+        if oflags != nflags {
+            m.changes.push(Change { what: "access flags".to_owned(), old: format!("{:#06x}", oflags), new: format!("{:#06x}", nflags) });
+        }
+        let (ocode, oattrs) = split_code(oattrs, oclass)?;
+        let (ncode, nattrs) = split_code(nattrs, nclass)?;
+        for (what, o, n) in &[("max stack", ocode.as_ref().map(|c| c.max_stack), ncode.as_ref().map(|c| c.max_stack)),
+                              ("max locals", ocode.as_ref().map(|c| c.max_locals), ncode.as_ref().map(|c| c.max_locals))] {
+            if o != n {
+                m.changes.push(Change { what: what.to_string(), old: option_text(o), new: option_text(n) });
+            }
+        }
+        let mut olines = attributes_text(&oattrs, &oclass.constant_pool, oclass.bootstrap_methods())?;
+        let mut nlines = attributes_text(&nattrs, &nclass.constant_pool, nclass.bootstrap_methods())?;
+        olines.extend(code_attributes_text(&ocode, oclass)?);
+        nlines.extend(code_attributes_text(&ncode, nclass)?);
+        m.attributes = diff_lines(&olines, &nlines);
+        let instructions = |c: &Option<CodeBody>| -> Vec<String> {
+            c.iter().flat_map(|c| c.instructions.iter().map(instruction_text)).collect()
+        };
+        m.code = diff_lines(&instructions(&ocode), &instructions(&ncode));
+        if !m.changes.is_empty() || !m.attributes.is_empty() || !m.code.is_empty() {
+            changed.push(m);
+        }
+    }
+    for key in new.keys() {
+        if !old.contains_key(key) {
+            added.push(format!("{} {}", key.0, key.1));
+        }
+    }
+    Ok(())
+}
+
+fn option_text<T: ToString>(o: &Option<T>) -> String {
+    o.as_ref().map_or_else(|| "none".to_owned(), T::to_string)
+}
+
+/// Splits out a method's `Code` attribute as a `CodeBody`
+fn split_code(attributes: &[Attribute], class: &JavaClass) -> io::Result<(Option<CodeBody>, Vec<Attribute>)> {
+    let mut code = None;
+    let mut rest = vec!();
+    for a in attributes {
+        if let Attribute::Code { .. } = a {
+            code = Some(CodeBody::from_attribute(a, &class.constant_pool, class.bootstrap_methods())?);
+        } else {
+            rest.push(a.clone());
+        }
+    }
+    Ok((code, rest))
+}
+
+fn code_attributes_text(code: &Option<CodeBody>, class: &JavaClass) -> io::Result<Vec<String>> {
+    let mut ans = vec!();
+    if let Some(code) = code {
+        for e in &code.exception_table {
+            ans.push(format!("Code.exception_table: [{}, {}) -> {} catch {}", e.start, e.end, e.handler, e.catch_type.as_ref().map_or("any", String::as_str)));
+        }
+        for line in attributes_text(&code.attributes, &class.constant_pool, class.bootstrap_methods())? {
+            ans.push(format!("Code.{}", line));
+        }
+    }
+    Ok(ans)
+}
+
+/// Returns a line-by-line comparison of `old` and `new`, or an empty list if they're equal
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    if old == new {
+        return vec!();
+    }
+    //strip the common ends first, which keeps the table small for typical edits
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    let mut ans: Vec<DiffLine> = old[..prefix].iter().cloned().map(DiffLine::Same).collect();
+    if a.len().saturating_mul(b.len()) > 25_000_000 {
+        //too big to align; report a replacement
+        ans.extend(a.iter().cloned().map(DiffLine::Removed));
+        ans.extend(b.iter().cloned().map(DiffLine::Added));
+    } else {
+        //longest common subsequence
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ans.push(DiffLine::Same(a[i].clone()));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ans.push(DiffLine::Removed(a[i].clone()));
+                i += 1;
+            } else {
+                ans.push(DiffLine::Added(b[j].clone()));
+                j += 1;
+            }
+        }
+    }
+    ans.extend(old[old.len() - suffix..].iter().cloned().map(DiffLine::Same));
+    ans
+}
+
+fn constant_counts(cp: &ConstantPool) -> BTreeMap<String, usize> {
+    let mut ans = BTreeMap::new();
+    for i in 1..cp.len() {
+        if let Some(text) = cp.describe(i.into()) {
+            *ans.entry(text).or_insert(0) += 1;
+        }
+    }
+    ans
+}
+
+/// Formats an instruction like `javap -c` does, but with instruction indices as branch targets
+pub(crate) fn instruction_text(op: &SymOpcode) -> String {
+    let member = |m: &::symbolic::MemberRef| format!("{}.{}:{}", m.owner, m.name, m.descriptor);
+    match op {
+        SymOpcode::Plain(op) => op.to_string(),
+        SymOpcode::Ldc(c) => format!("ldc {}", constant_value_text(c)),
+        SymOpcode::GetStatic(m) => format!("getstatic {}", member(m)),
+        SymOpcode::PutStatic(m) => format!("putstatic {}", member(m)),
+        SymOpcode::GetField(m) => format!("getfield {}", member(m)),
+        SymOpcode::PutField(m) => format!("putfield {}", member(m)),
+        SymOpcode::InvokeVirtual(m) => format!("invokevirtual {}", member(m)),
+        SymOpcode::InvokeSpecial(m) => format!("invokespecial {}", member(m)),
+        SymOpcode::InvokeStatic(m) => format!("invokestatic {}", member(m)),
+        SymOpcode::InvokeInterface(m) => format!("invokeinterface {}", member(m)),
+        SymOpcode::InvokeDynamic { bootstrap, name, descriptor } => {
+            let args: Vec<String> = bootstrap.arguments.iter().map(constant_value_text).collect();
+            format!("invokedynamic {}:{} bootstrap {} ({})", name, descriptor, member(&bootstrap.handle.member), args.join(", "))
+        }
+        SymOpcode::New(c) => format!("new {}", c),
+        SymOpcode::ANewArray(c) => format!("anewarray {}", c),
+        SymOpcode::CheckCast(c) => format!("checkcast {}", c),
+        SymOpcode::InstanceOf(c) => format!("instanceof {}", c),
+        SymOpcode::MultiANewArray { class, dimensions } => format!("multianewarray {} {}", class, dimensions),
+        SymOpcode::Branch { kind, target } => {
+            let name = format!("{:?}", kind).to_lowercase().replace("ificmp", "if_icmp").replace("ifacmp", "if_acmp");
+            format!("{} -> {}", name, target)
+        }
+        SymOpcode::TableSwitch { default, low, targets } => {
+            let cases: Vec<String> = targets.iter().enumerate().map(|(i, t)| format!("{}: {}", *low as i64 + i as i64, t)).collect();
+            format!("tableswitch {{{}, default: {}}}", cases.join(", "), default)
+        }
+        SymOpcode::LookupSwitch { default, pairs } => {
+            let cases: Vec<String> = pairs.iter().map(|(k, t)| format!("{}: {}", k, t)).collect();
+            format!("lookupswitch {{{}, default: {}}}", cases.join(", "), default)
+        }
+    }
+}
+
+fn constant_value_text(c: &Constant) -> String {
+    match c {
+        Constant::Integer(v) => v.to_string(),
+        Constant::Float(v) => format!("{:?}f", v),
+        Constant::Long(v) => format!("{}L", v),
+        Constant::Double(v) => format!("{:?}", v),
+        Constant::String(s) => format!("{:?}", s),
+        Constant::Class(s) => format!("class {}", s),
+        Constant::MethodType(s) => format!("methodtype {}", s),
+        Constant::MethodHandle(h) => format!("methodhandle {} {}.{}:{}", h.reference_kind, h.member.owner, h.member.name, h.member.descriptor),
+    }
+}
+
+/// Describes attributes as lines of text without any constant pool indices.
+/// Every line starts with the name of the attribute it describes, and attributes are sorted by name
+/// since their order in the class file doesn't matter.
+pub fn attributes_text(attributes: &[Attribute], cp: &ConstantPool, bootstrap_methods: &[BootstrapMethodsEntry]) -> io::Result<Vec<String>> {
+    let mut named = attributes.iter().map(|a| Ok((match a {
+        Attribute::Unknown { name_index, .. } => cp.utf8(*name_index)?,
+        a => a.name().unwrap_or("?").to_owned(),
+    }, a))).collect::<io::Result<Vec<_>>>()?;
+    named.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut ans = vec!();
+    for (name, a) in named {
+        let mut push = |text: String| ans.push(format!("{}: {}", name, text));
+        match a {
+            Attribute::ConstantValue { constantvalue_index } => push(constant_value_text(&resolve_constant(cp, *constantvalue_index)?)),
+            Attribute::Code { .. } => push("(code)".to_owned()),
+            Attribute::StackMapTable { entries } => {
+                for f in entries {
+                    push(frame_text(f, cp));
+                }
+            }
+            Attribute::Exceptions { exception_index_table } => {
+                for i in exception_index_table {
+                    push(cp.class_name((*i).into())?);
+                }
+            }
+            Attribute::InnerClasses { classes } => {
+                for c in classes {
+                    push(format!("{} outer {} name {} flags {:#06x}",
+                                 optional(cp, c.inner_class_info_index, ConstantPool::class_name)?,
+                                 optional(cp, c.outer_class_info_index, ConstantPool::class_name)?,
+                                 optional(cp, c.inner_name_index, ConstantPool::utf8)?,
+                                 c.inner_class_access_flags));
+                }
+            }
+            Attribute::EnclosingMethod { class_index, method_index } => {
+                let method = cp.get_name_and_type(*method_index).map_or_else(|| "none".to_owned(), |(n, d)| format!("{}{}", n, d));
+                push(format!("{} {}", cp.class_name(*class_index)?, method));
+            }
+            Attribute::Synthetic | Attribute::Deprecated => push("present".to_owned()),
+            Attribute::Signature { signature_index } => push(cp.utf8(*signature_index)?),
+            Attribute::SourceFile { sourcefile_index } => push(cp.utf8(*sourcefile_index)?),
+            Attribute::SourceDebugExtenson { debug_extension } => push(format!("{:?}", String::from_utf8_lossy(debug_extension))),
+            Attribute::LineNumberTable { line_number_table } => {
+                for l in line_number_table {
+                    push(format!("{} line {}", l.start_pc, l.line_number));
+                }
+            }
+            Attribute::LocalVariableTable { local_variable_table } => {
+                for l in local_variable_table {
+                    push(format!("[{}, +{}) slot {} {} {}", l.start_pc, l.length, l.index, cp.utf8(l.name_index)?, cp.utf8(l.descriptor_index)?));
+                }
+            }
+            Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+                for l in local_variable_type_table {
+                    push(format!("[{}, +{}) slot {} {} {}", l.start_pc, l.length, l.index, cp.utf8(l.name_index)?, cp.utf8(l.signature_index)?));
+                }
+            }
+            Attribute::RuntimeVisibleAnnotations { annotations } |
+            Attribute::RuntimeInvisibleAnnotations { annotations } => {
+                for an in annotations {
+                    push(annotation_text(an, cp)?);
+                }
+            }
+            Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } |
+            Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+                for (i, p) in parameter_annotations.iter().enumerate() {
+                    for an in p {
+                        push(format!("parameter {} {}", i, annotation_text(an, cp)?));
+                    }
+                }
+            }
+            Attribute::RuntimeVisibleTypeAnnotations { annotations } |
+            Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+                for an in annotations {
+                    push(format!("{:#04x} {:?} {:?} @{}({})", an.target_type, an.target_info, an.target_path.path,
+                                 cp.utf8(an.type_index)?, pairs_text(&an.element_value_pairs, cp)?));
+                }
+            }
+            Attribute::AnnotationDefault { default_value } => push(element_value_text(default_value, cp)?),
+            Attribute::BootstrapMethods { .. } => {
+                for (i, b) in bootstrap_methods.iter().enumerate() {
+                    let b = resolve_bootstrap_method(cp, b)?;
+                    let args: Vec<String> = b.arguments.iter().map(constant_value_text).collect();
+                    push(format!("#{} {}.{}:{} ({})", i, b.handle.member.owner, b.handle.member.name, b.handle.member.descriptor, args.join(", ")));
+                }
+            }
+            Attribute::MethodParameters { parameters } => {
+                for p in parameters {
+                    push(format!("{} flags {:#06x}", optional(cp, p.name_index, ConstantPool::utf8)?, p.access_flags));
+                }
+            }
+            Attribute::Unknown { info, .. } => match UndecodedContents::read(&name, info, cp) {
+                Ok(Some(contents)) => {
+                    for line in undecoded_text(&contents, cp, bootstrap_methods)? {
+                        push(line);
+                    }
+                }
+                _ => {
+                    let hex: String = info.iter().take(64).map(|b| format!("{:02x}", b)).collect();
+                    push(format!("{} bytes {}{}", info.len(), hex, if info.len() > 64 { "..." } else { "" }));
+                }
+            },
+        }
+    }
+    Ok(ans)
+}
+
+/// Describes the contents of an attribute this crate doesn't decode, with its constant pool indices resolved
+fn undecoded_text(contents: &UndecodedContents, cp: &ConstantPool, bootstrap_methods: &[BootstrapMethodsEntry]) -> io::Result<Vec<String>> {
+    let mut ans = vec!();
+    match contents {
+        UndecodedContents::NestHost(i) => ans.push(cp.class_name(*i)?),
+        UndecodedContents::ClassList(classes) => for i in classes {
+            ans.push(cp.class_name(*i)?);
+        },
+        UndecodedContents::Record(components) => for c in components {
+            let component = format!("{} {}", cp.utf8(c.name_index)?, cp.utf8(c.descriptor_index)?);
+            let attributes: Vec<Attribute> = c.attributes.iter().map(|(_, a)| a.clone()).collect();
+            ans.push(component.clone());
+            for line in attributes_text(&attributes, cp, bootstrap_methods)? {
+                ans.push(format!("{} {}", component, line));
+            }
+        },
+    }
+    Ok(ans)
+}
+
+fn optional(cp: &ConstantPool, index: CPIndex, f: fn(&ConstantPool, CPIndex) -> io::Result<String>) -> io::Result<String> {
+    if index.as_u16() == 0 {
+        Ok("none".to_owned())
+    } else {
+        f(cp, index)
+    }
+}
+
+fn frame_text(frame: &StackMapFrame, cp: &ConstantPool) -> String {
+    let types = |v: &[VerificationTypeInfo]| v.iter().map(|t| match t {
+        VerificationTypeInfo::Object { cpool_index } => cp.get_class_name(*cpool_index).unwrap_or_else(|| "?".to_owned()),
+        t => format!("{:?}", t),
+    }).collect::<Vec<_>>().join(", ");
+    let delta = ::code::offset_delta(frame);
+    match frame {
+        StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => format!("+{} same", delta),
+        StackMapFrame::SameLocals1Item { stack, .. } |
+        StackMapFrame::SameLocals1ItemExtended { stack, .. } => format!("+{} same locals, stack [{}]", delta, types(::std::slice::from_ref(stack))),
+        StackMapFrame::ChopFrame { absent_locals, .. } => format!("+{} chop {}", delta, absent_locals),
+        StackMapFrame::AppendFrame { locals, .. } => format!("+{} append [{}]", delta, types(locals)),
+        StackMapFrame::FullFrame { locals, stack, .. } => format!("+{} full locals [{}] stack [{}]", delta, types(locals), types(stack)),
+    }
+}
+
+fn annotation_text(an: &Annotation, cp: &ConstantPool) -> io::Result<String> {
+    Ok(format!("@{}({})", cp.utf8(an.type_index)?, pairs_text(&an.element_value_pairs, cp)?))
+}
+
+fn pairs_text(pairs: &[ElementValuePair], cp: &ConstantPool) -> io::Result<String> {
+    let pairs = pairs.iter().map(|p| Ok(format!("{}={}", cp.utf8(p.element_name_index)?, element_value_text(&p.value, cp)?))).collect::<io::Result<Vec<_>>>()?;
+    Ok(pairs.join(", "))
+}
+
+fn element_value_text(v: &ElementValue, cp: &ConstantPool) -> io::Result<String> {
+    Ok(match v {
+        ElementValue::ConstValueIndex { tag: b's', index } => format!("s:{:?}", cp.utf8(*index)?),
+        ElementValue::ConstValueIndex { tag, index } => format!("{}:{}", *tag as char, constant_value_text(&resolve_constant(cp, *index)?)),
+        ElementValue::EnumConstValue { type_name_index, const_name_index } => format!("{}.{}", cp.utf8(*type_name_index)?, cp.utf8(*const_name_index)?),
+        ElementValue::ClassInfoIndex(i) => format!("class {}", cp.utf8(*i)?),
+        ElementValue::AnnotationValue(an) => annotation_text(an, cp)?,
+        ElementValue::ArrayValue(values) => {
+            let values = values.iter().map(|v| element_value_text(v, cp)).collect::<io::Result<Vec<_>>>()?;
+            format!("{{{}}}", values.join(", "))
+        }
+    })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compact::Order;
+    use opcodes::Opcode::*;
+    use test_classes::ClassSpec;
+
+    /// A record with a `Signature` on its component, a nest, and constants that sorting moves around.
+    /// It's written and read back so its constant pool holds the attribute names, as a compiled class's would.
+    fn record() -> JavaClass {
+        let class = ClassSpec::new(0x0031, "p/Point", Some("java/lang/Record"))
+            .field(0x0012, "x", "Ljava/util/List;")
+            .method(0x0001, "sum", "()J", vec![SymOpcode::Ldc(Constant::Long(7)), SymOpcode::Ldc(Constant::String("hi".to_owned())),
+                                               SymOpcode::Plain(pop), SymOpcode::Plain(lreturn)])
+            .attribute(|cp| {
+                let component = RecordComponent {
+                    name_index: cp.add_utf8("x".to_owned()),
+                    descriptor_index: cp.add_utf8("Ljava/util/List;".to_owned()),
+                    attributes: vec![(cp.add_utf8("Signature".to_owned()),
+                                      Attribute::Signature { signature_index: cp.add_utf8("Ljava/util/List<Ljava/lang/String;>;".to_owned()) })],
+                };
+                Attribute::Unknown { name_index: cp.add_utf8("Record".to_owned()), info: UndecodedContents::Record(vec![component]).to_bytes() }
+            })
+            .attribute(|cp| {
+                let classes = vec![cp.add_class("p/Point$A".to_owned()), cp.add_class("p/Point$B".to_owned())];
+                Attribute::Unknown { name_index: cp.add_utf8("NestMembers".to_owned()), info: UndecodedContents::ClassList(classes).to_bytes() }
+            })
+            .build();
+        JavaClass::new_from_bytes(class.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn compacting_changes_nothing() {
+        let original = record();
+        let mut compacted = record();
+        compacted.compact_constant_pool(Order::Sorted).unwrap();
+        //the indices inside the record really did move, so comparing raw bytes would report a change
+        assert_ne!(original.attributes, compacted.attributes);
+
+        let d = diff(&original, &compacted).unwrap();
+        assert!(d.is_empty(), "{}", d.to_text());
+        assert_eq!(constant_counts(&original.constant_pool), constant_counts(&compacted.constant_pool));
+    }
+
+    #[test]
+    fn record_components_are_resolved() {
+        let class = record();
+        let lines = attributes_text(&class.attributes, &class.constant_pool, class.bootstrap_methods()).unwrap();
+        assert_eq!(lines, vec![
+            "NestMembers: p/Point$A",
+            "NestMembers: p/Point$B",
+            "Record: x Ljava/util/List;",
+            "Record: x Ljava/util/List; Signature: Ljava/util/List<Ljava/lang/String;>;",
+        ]);
+    }
+
+    #[test]
+    fn counts_every_constant() {
+        let class = record();
+        let counts = constant_counts(&class.constant_pool);
+        //attribute names are added last when writing the class
+        assert_eq!(counts.get("Utf8 \"Code\""), Some(&1));
+        assert_eq!(counts.values().sum::<usize>(), class.constant_pool.len() as usize - 2);
+    }
+}
@@ -5,6 +5,7 @@ use crate::cp::CPIndex;
 /// struct representing the FieldInfo struct
 /// for more information refer to the [JVM specification](https://docs.oracle.com/javase/specs/jvms/se8/html/index.html)
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FieldInfo {
     pub access_flags: u16,
     pub name_index: CPIndex,
@@ -12,14 +13,23 @@ pub struct FieldInfo {
     pub attributes: Vec<Attribute>,
 }
 
-pub enum AccessFlags {
-    Public = 0x0001,
-    Private = 0x0002,
-    Protected = 0x0004,
-    Static = 0x0008,
-    Final = 0x0010,
-    Volatile = 0x0040,
-    Transient = 0x0080,
-    Synthetic = 0x1000,
-    Enum = 0x4000,
+impl FieldInfo {
+    pub fn flags(&self) -> AccessFlags {
+        AccessFlags::from_bits(self.access_flags)
+    }
+}
+
+access_flags! {
+    /// The access flags of a field
+    pub struct AccessFlags {
+        PUBLIC = 0x0001,
+        PRIVATE = 0x0002,
+        PROTECTED = 0x0004,
+        STATIC = 0x0008,
+        FINAL = 0x0010,
+        VOLATILE = 0x0040,
+        TRANSIENT = 0x0080,
+        SYNTHETIC = 0x1000,
+        ENUM = 0x4000,
+    }
 }
\ No newline at end of file
@@ -0,0 +1,351 @@
+use attributes::*;
+use builders::cp::CPBuilder;
+use class::JavaClass;
+use code::{offset_delta, with_offset_delta};
+use cp::ConstantPool;
+use descriptor::{FieldType, MethodDescriptor};
+use malformed;
+use methods::{AccessFlags, MethodInfo};
+use std::convert::TryInto;
+use std::fmt;
+use std::io;
+
+/// A verification type with its class resolved
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor, before the superclass constructor has been called
+    UninitializedThis,
+    /// a class, interface or array type, by the name in its `Class` constant,
+    /// e.g. `java/lang/String` or `[I`
+    Object(String),
+    /// an object created by the `new` at this offset, whose constructor hasn't been called yet
+    Uninitialized(u32),
+}
+
+impl FrameType {
+    pub fn from_info(info: &VerificationTypeInfo, cp: &ConstantPool) -> io::Result<FrameType> {
+        Ok(match info {
+            VerificationTypeInfo::Top => FrameType::Top,
+            VerificationTypeInfo::Integer => FrameType::Integer,
+            VerificationTypeInfo::Float => FrameType::Float,
+            VerificationTypeInfo::Long => FrameType::Long,
+            VerificationTypeInfo::Double => FrameType::Double,
+            VerificationTypeInfo::Null => FrameType::Null,
+            VerificationTypeInfo::UninitializedThis => FrameType::UninitializedThis,
+            VerificationTypeInfo::Object { cpool_index } => {
+                FrameType::Object(cp.get_class_name(*cpool_index).ok_or_else(|| malformed("Stack map Object did not point to Class"))?)
+            }
+            VerificationTypeInfo::UninitializedVariable { offset } => FrameType::Uninitialized(*offset as u32),
+        })
+    }
+
+    /// Converts the type back to its class file form, adding its class to `cp` if needed
+    pub fn to_info(&self, cp: &mut CPBuilder) -> io::Result<VerificationTypeInfo> {
+        Ok(match self {
+            FrameType::Top => VerificationTypeInfo::Top,
+            FrameType::Integer => VerificationTypeInfo::Integer,
+            FrameType::Float => VerificationTypeInfo::Float,
+            FrameType::Long => VerificationTypeInfo::Long,
+            FrameType::Double => VerificationTypeInfo::Double,
+            FrameType::Null => VerificationTypeInfo::Null,
+            FrameType::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+            FrameType::Object(name) => VerificationTypeInfo::Object { cpool_index: cp.add_class(name.clone()) },
+            FrameType::Uninitialized(offset) => VerificationTypeInfo::UninitializedVariable {
+                offset: (*offset).try_into().map_err(|_| malformed("Uninitialized offset is past the end of the code"))?,
+            },
+        })
+    }
+
+    /// The type a value of field type `t` has in a frame
+    pub fn of_field_type(t: &FieldType) -> FrameType {
+        match t {
+            FieldType::Byte | FieldType::Char | FieldType::Short | FieldType::Boolean | FieldType::Int => FrameType::Integer,
+            FieldType::Float => FrameType::Float,
+            FieldType::Long => FrameType::Long,
+            FieldType::Double => FrameType::Double,
+            FieldType::Object(name) => FrameType::Object(name.clone()),
+            FieldType::Array(_) => FrameType::Object(t.to_string()),
+        }
+    }
+
+    /// The number of local variable or operand stack slots the type takes
+    pub fn slots(&self) -> u32 {
+        match self {
+            FrameType::Long | FrameType::Double => 2,
+            _ => 1
+        }
+    }
+}
+
+impl fmt::Display for FrameType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameType::Top => write!(f, "top"),
+            FrameType::Integer => write!(f, "int"),
+            FrameType::Float => write!(f, "float"),
+            FrameType::Long => write!(f, "long"),
+            FrameType::Double => write!(f, "double"),
+            FrameType::Null => write!(f, "null"),
+            FrameType::UninitializedThis => write!(f, "uninitializedThis"),
+            FrameType::Object(name) => write!(f, "{}", name),
+            FrameType::Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+        }
+    }
+}
+
+/// The full state of the locals and operand stack at a bytecode offset
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub offset: u32,
+    /// the locals, one entry per variable as in the class file:
+    /// `Long` and `Double` take two slots but only one entry
+    pub locals: Vec<FrameType>,
+    /// the operand stack, from bottom to top
+    pub stack: Vec<FrameType>,
+}
+
+impl Frame {
+    /// The implicit frame at offset 0 of a method (§4.10.1.6):
+    /// `this` (unless the method is static) followed by the parameters
+    /// # Parameters:
+    /// * class: binary name of the class the method belongs to
+    /// * name: the method's name, which matters for constructors
+    /// * descriptor: the method's descriptor
+    pub fn initial(class: &str, name: &str, descriptor: &str, is_static: bool) -> io::Result<Frame> {
+        let descriptor = MethodDescriptor::parse(descriptor)?;
+        let mut locals = Vec::with_capacity(descriptor.parameters.len() + 1);
+        if !is_static {
+            locals.push(if name == "<init>" && class != "java/lang/Object" {
+                FrameType::UninitializedThis
+            } else {
+                FrameType::Object(class.to_owned())
+            });
+        }
+        locals.extend(descriptor.parameters.iter().map(FrameType::of_field_type));
+        Ok(Frame { offset: 0, locals, stack: Vec::new() })
+    }
+
+    /// The type of each local variable slot, with `Top` in the second slot of a `Long` or `Double`
+    pub fn local_slots(&self) -> Vec<FrameType> {
+        let mut ans = Vec::with_capacity(self.locals.len());
+        for l in &self.locals {
+            ans.push(l.clone());
+            if l.slots() == 2 {
+                ans.push(FrameType::Top);
+            }
+        }
+        ans
+    }
+
+    /// Sets the type of local variable slot `slot`, padding the locals with `Top` up to it.
+    /// A `Long` or `Double` also takes the slot after it.
+    pub fn set_local(&mut self, slot: u16, t: FrameType) {
+        let mut slots = self.local_slots();
+        let (slot, end) = (slot as usize, slot as usize + t.slots() as usize);
+        if slots.len() < end {
+            slots.resize(end, FrameType::Top);
+        }
+        //a two slot type whose second half is overwritten is gone
+        if slot > 0 && slots[slot - 1].slots() == 2 {
+            slots[slot - 1] = FrameType::Top;
+        }
+        for s in &mut slots[slot + 1..end] {
+            *s = FrameType::Top;
+        }
+        slots[slot] = t;
+        let mut locals = Vec::with_capacity(slots.len());
+        let mut i = 0;
+        while i < slots.len() {
+            locals.push(slots[i].clone());
+            i += slots[i].slots() as usize;
+        }
+        while locals.last() == Some(&FrameType::Top) {
+            locals.pop();
+        }
+        self.locals = locals;
+    }
+
+    /// The number of operand stack slots in use
+    pub fn stack_depth(&self) -> u32 {
+        self.stack.iter().map(FrameType::slots).sum()
+    }
+}
+
+impl fmt::Display for Frame {
+    /// Formats the frame as e.g. `12: locals [java/lang/String, int] stack [long]`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |types: &[FrameType]| types.iter().map(FrameType::to_string).collect::<Vec<String>>().join(", ");
+        write!(f, "{}: locals [{}] stack [{}]", self.offset, list(&self.locals), list(&self.stack))
+    }
+}
+
+/// Expands the entries of a `StackMapTable` into frames at absolute offsets.
+/// `initial` is the method's implicit first frame (see `Frame::initial`), which isn't included in the result.
+pub fn expand(initial: &Frame, entries: &[StackMapFrame], cp: &ConstantPool) -> io::Result<Vec<Frame>> {
+    let types = |v: &[VerificationTypeInfo]| v.iter().map(|t| FrameType::from_info(t, cp)).collect::<io::Result<Vec<_>>>();
+    let mut ans: Vec<Frame> = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let (prev_locals, offset) = match ans.last() {
+            Some(prev) => (&prev.locals, prev.offset + offset_delta(entry) as u32 + 1),
+            None => (&initial.locals, offset_delta(entry) as u32),
+        };
+        let (locals, stack) = match entry {
+            StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => (prev_locals.clone(), vec![]),
+            StackMapFrame::SameLocals1Item { stack, .. } |
+            StackMapFrame::SameLocals1ItemExtended { stack, .. } => (prev_locals.clone(), vec![FrameType::from_info(stack, cp)?]),
+            StackMapFrame::ChopFrame { absent_locals, .. } => {
+                let kept = prev_locals.len().checked_sub(*absent_locals as usize)
+                    .ok_or_else(|| malformed(&format!("Stack map frame {} chops more locals than there are", i)))?;
+                (prev_locals[..kept].to_vec(), vec![])
+            }
+            StackMapFrame::AppendFrame { locals, .. } => {
+                let mut all = prev_locals.clone();
+                all.extend(types(locals)?);
+                (all, vec![])
+            }
+            StackMapFrame::FullFrame { locals, stack, .. } => (types(locals)?, types(stack)?),
+        };
+        ans.push(Frame { offset, locals, stack });
+    }
+    Ok(ans)
+}
+
+/// Compresses frames into `StackMapTable` entries, picking the smallest encoding for each.
+/// `frames` must be sorted by offset with no two at the same offset, and `initial` is the method's implicit first frame.
+/// Classes named by the frames are added to `cp`.
+pub fn compress(initial: &Frame, frames: &[Frame], cp: &mut CPBuilder) -> io::Result<Vec<StackMapFrame>> {
+    let mut ans = Vec::with_capacity(frames.len());
+    let mut prev: Option<&Frame> = None;
+    for frame in frames {
+        let delta = match prev {
+            Some(p) if frame.offset > p.offset => frame.offset - p.offset - 1,
+            Some(_) => return Err(malformed("Stack map frames are not in increasing offset order")),
+            None => frame.offset,
+        };
+        let offset_delta: u16 = delta.try_into().map_err(|_| malformed("Stack map frame offset is past the end of the code"))?;
+        let prev_locals = prev.map_or(&initial.locals, |p| &p.locals);
+        let (n, m) = (prev_locals.len(), frame.locals.len());
+        let same_locals = frame.locals == *prev_locals;
+        let mut types = |v: &[FrameType]| v.iter().map(|t| t.to_info(cp)).collect::<io::Result<Vec<_>>>();
+        let entry = if same_locals && frame.stack.is_empty() {
+            with_offset_delta(&StackMapFrame::SameFrame { offset_delta: 0 }, offset_delta)
+        } else if same_locals && frame.stack.len() == 1 {
+            let stack = types(&frame.stack)?.remove(0);
+            with_offset_delta(&StackMapFrame::SameLocals1Item { offset_delta: 0, stack }, offset_delta)
+        } else if frame.stack.is_empty() && m < n && n - m <= 3 && prev_locals[..m] == frame.locals[..] {
+            StackMapFrame::ChopFrame { absent_locals: (n - m) as u8, offset_delta }
+        } else if frame.stack.is_empty() && m > n && m - n <= 3 && frame.locals[..n] == prev_locals[..] {
+            StackMapFrame::AppendFrame { offset_delta, locals: types(&frame.locals[n..])? }
+        } else {
+            StackMapFrame::FullFrame { offset_delta, locals: types(&frame.locals)?, stack: types(&frame.stack)? }
+        };
+        ans.push(entry);
+        prev = Some(frame);
+    }
+    Ok(ans)
+}
+
+impl JavaClass {
+    /// The implicit frame at the start of `method`, which must belong to this class
+    pub fn initial_frame(&self, method: &MethodInfo) -> io::Result<Frame> {
+        let cp = &self.constant_pool;
+        let class = cp.get_class_name(self.this_class).ok_or_else(|| malformed("this_class did not point to Class"))?;
+        let name = cp.get_utf8(method.name_index).ok_or_else(|| malformed("Method name did not point to Utf8"))?;
+        let descriptor = cp.get_utf8(method.descriptor_index).ok_or_else(|| malformed("Method descriptor did not point to Utf8"))?;
+        Frame::initial(&class, &name, &descriptor, method.flags().contains(AccessFlags::STATIC))
+    }
+
+    /// The expanded `StackMapTable` of `method`, which must belong to this class.
+    /// Empty if the method has no code or no `StackMapTable`.
+    pub fn stack_map_frames(&self, method: &MethodInfo) -> io::Result<Vec<Frame>> {
+        for a in &method.attributes {
+            if let Attribute::Code { attributes, .. } = a {
+                for a in attributes {
+                    if let Attribute::StackMapTable { entries } = a {
+                        return expand(&self.initial_frame(method)?, entries, &self.constant_pool);
+                    }
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(offset: u32, locals: Vec<FrameType>, stack: Vec<FrameType>) -> Frame {
+        Frame { offset, locals, stack }
+    }
+
+    #[test]
+    fn compress_picks_smallest_entries_and_expand_reverses_it() {
+        use self::FrameType::*;
+        let initial = Frame::initial("A", "m", "(I)V", false).unwrap();
+        let this = Object("A".to_owned());
+        assert_eq!(initial.locals, vec![this.clone(), Integer]);
+        let new = Uninitialized(12);
+        let frames = vec![
+            frame(5, vec![this.clone(), Integer, Long, Object("java/lang/String".to_owned())], vec![]),
+            frame(9, vec![this.clone(), Integer], vec![]),
+            frame(20, vec![this.clone(), Integer], vec![new.clone(), new.clone()]),
+            frame(30, vec![this.clone(), Integer], vec![new.clone()]),
+            frame(100, vec![this.clone(), Integer], vec![]),
+            frame(101, vec![this.clone()], vec![]),
+            frame(102, vec![Object("[I".to_owned()), Integer, Top, Double], vec![]),
+        ];
+        let mut cp = CPBuilder::new();
+        let entries = compress(&initial, &frames, &mut cp).unwrap();
+        //the classes are already in the pool, so this just looks up their indices
+        let len = cp.len();
+        let mut class = |name: &str| cp.add_class(name.to_owned());
+        let (a, string, int_array) = (class("A"), class("java/lang/String"), class("[I"));
+        assert_eq!(cp.len(), len);
+        let uninit = VerificationTypeInfo::UninitializedVariable { offset: 12 };
+        assert_eq!(entries, vec![
+            StackMapFrame::AppendFrame {
+                offset_delta: 5,
+                locals: vec![VerificationTypeInfo::Long, VerificationTypeInfo::Object { cpool_index: string }],
+            },
+            StackMapFrame::ChopFrame { absent_locals: 2, offset_delta: 3 },
+            StackMapFrame::FullFrame {
+                offset_delta: 10,
+                locals: vec![VerificationTypeInfo::Object { cpool_index: a }, VerificationTypeInfo::Integer],
+                stack: vec![uninit.clone(), uninit.clone()],
+            },
+            StackMapFrame::SameLocals1Item { offset_delta: 9, stack: uninit },
+            StackMapFrame::SameFrameExtended { offset_delta: 69 },
+            StackMapFrame::ChopFrame { absent_locals: 1, offset_delta: 0 },
+            StackMapFrame::FullFrame {
+                offset_delta: 0,
+                locals: vec![
+                    VerificationTypeInfo::Object { cpool_index: int_array },
+                    VerificationTypeInfo::Integer,
+                    VerificationTypeInfo::Top,
+                    VerificationTypeInfo::Double,
+                ],
+                stack: vec![],
+            },
+        ]);
+        assert_eq!(expand(&initial, &entries, &cp.build()).unwrap(), frames);
+    }
+
+    #[test]
+    fn bad_frames_are_rejected() {
+        let initial = Frame::initial("A", "m", "()V", true).unwrap();
+        let mut cp = CPBuilder::new();
+        let unordered = [frame(4, vec![], vec![]), frame(4, vec![FrameType::Integer], vec![])];
+        assert!(compress(&initial, &unordered, &mut cp).is_err());
+        let far = [frame(0, vec![], vec![FrameType::Uninitialized(70_000)])];
+        assert!(compress(&initial, &far, &mut cp).is_err());
+        let chop = [StackMapFrame::ChopFrame { absent_locals: 1, offset_delta: 0 }];
+        assert!(expand(&initial, &chop, &cp.build()).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+//! Graph algorithms shared by the analyses
+
+use std::collections::{HashMap, HashSet};
+
+/// Finds every cycle in a directed graph, as the sorted names of the nodes in each strongly connected component.
+/// A single node is only a cycle if it has an edge to itself. Successors that aren't in `nodes` are ignored.
+pub(crate) fn cycles<'a, F>(nodes: &[&'a str], successors: F) -> Vec<Vec<String>>
+    where F: Fn(&'a str) -> Vec<&'a str> {
+    //Tarjan's algorithm, done iteratively so deep graphs can't overflow the stack
+    let known: HashSet<&str> = nodes.iter().cloned().collect();
+    let mut sorted = nodes.to_vec();
+    sorted.sort();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut low: HashMap<&str, usize> = HashMap::new();
+    let mut on_stack = HashSet::new();
+    let mut stack = vec!();
+    let mut ans = vec!();
+    for root in sorted {
+        if index.contains_key(root) {
+            continue;
+        }
+        //(node, its successors, index of the next successor to look at)
+        let mut work: Vec<(&str, Vec<&str>, usize)> = vec!();
+        let succ: Vec<&str> = successors(root).into_iter().filter(|s| known.contains(s)).collect();
+        let i = index.len();
+        index.insert(root, i);
+        low.insert(root, i);
+        stack.push(root);
+        on_stack.insert(root);
+        work.push((root, succ, 0));
+        while let Some((v, succ, next)) = work.last_mut() {
+            let v = *v;
+            if let Some(&w) = succ.get(*next) {
+                *next += 1;
+                if !index.contains_key(w) {
+                    let i = index.len();
+                    index.insert(w, i);
+                    low.insert(w, i);
+                    stack.push(w);
+                    on_stack.insert(w);
+                    let succ = successors(w).into_iter().filter(|s| known.contains(s)).collect();
+                    work.push((w, succ, 0));
+                } else if on_stack.contains(w) {
+                    let l = low[v].min(index[w]);
+                    low.insert(v, l);
+                }
+                continue;
+            }
+            let self_loop = succ.contains(&v);
+            work.pop();
+            if let Some((parent, _, _)) = work.last() {
+                let l = low[parent].min(low[v]);
+                low.insert(parent, l);
+            }
+            if low[v] == index[v] {
+                let mut component = vec!();
+                while let Some(w) = stack.pop() {
+                    on_stack.remove(w);
+                    component.push(w.to_owned());
+                    if w == v {
+                        break;
+                    }
+                }
+                if component.len() > 1 || self_loop {
+                    component.sort();
+                    ans.push(component);
+                }
+            }
+        }
+    }
+    ans.sort();
+    ans
+}
@@ -0,0 +1,274 @@
+use class::JavaClass;
+use classpath::ClassPath;
+use graph;
+use malformed;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io;
+
+/// A class's place in the hierarchy: its name, direct superclass and direct superinterfaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassNode {
+    pub name: String,
+    /// `None` only for `java/lang/Object` (and `module-info`)
+    pub super_name: Option<String>,
+    pub interfaces: Vec<String>,
+    pub is_interface: bool,
+}
+
+impl ClassNode {
+    pub fn from_class(class: &JavaClass) -> io::Result<ClassNode> {
+        let cp = &class.constant_pool;
+        let name_of = |i| cp.get_class_name(i).ok_or_else(|| malformed("Class index did not point to Class"));
+        Ok(ClassNode {
+            name: name_of(class.this_class)?,
+            super_name: if class.super_class.as_u16() == 0 { None } else { Some(name_of(class.super_class)?) },
+            interfaces: class.interfaces.iter().map(|i| name_of(*i)).collect::<io::Result<_>>()?,
+            is_interface: class.is_interface(),
+        })
+    }
+
+    /// The direct superclass followed by the direct superinterfaces
+    pub fn supertypes(&self) -> impl Iterator<Item = &String> {
+        self.super_name.iter().chain(self.interfaces.iter())
+    }
+}
+
+/// An index of the subtype relation between a set of classes.
+/// Classes can be added, replaced and removed at any time; queries always reflect the current set.
+/// Supertypes that were never added are "missing", and are treated as having no supertypes themselves.
+/// All queries terminate even if the hierarchy has cycles.
+#[derive(Debug, Clone, Default)]
+pub struct Hierarchy {
+    nodes: HashMap<String, ClassNode>,
+    /// name -> names of the classes that directly extend or implement it. Includes missing names.
+    subtypes: HashMap<String, BTreeSet<String>>,
+}
+
+impl Hierarchy {
+    pub fn new() -> Hierarchy {
+        Hierarchy::default()
+    }
+
+    /// Indexes every class on a classpath
+    pub fn from_classpath(cp: &ClassPath) -> io::Result<Hierarchy> {
+        let mut ans = Hierarchy::new();
+        for (_, class) in cp.classes()? {
+            ans.add_class(&class?)?;
+        }
+        Ok(ans)
+    }
+
+    pub fn add_class(&mut self, class: &JavaClass) -> io::Result<()> {
+        self.add(ClassNode::from_class(class)?);
+        Ok(())
+    }
+
+    /// Adds a class, replacing any class with the same name
+    pub fn add(&mut self, node: ClassNode) {
+        self.remove(&node.name);
+        for s in node.supertypes() {
+            self.subtypes.entry(s.clone()).or_default().insert(node.name.clone());
+        }
+        self.nodes.insert(node.name.clone(), node);
+    }
+
+    /// Removes a class. Classes that extend or implement it are kept, and it becomes missing if they exist.
+    pub fn remove(&mut self, name: &str) -> Option<ClassNode> {
+        let node = self.nodes.remove(name)?;
+        for s in node.supertypes() {
+            let now_empty = match self.subtypes.get_mut(s) {
+                Some(set) => {
+                    set.remove(name);
+                    set.is_empty()
+                }
+                None => false
+            };
+            if now_empty {
+                self.subtypes.remove(s);
+            }
+        }
+        Some(node)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ClassNode> {
+        self.nodes.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.nodes.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterates over every class in the index, in no particular order
+    pub fn classes(&self) -> impl Iterator<Item = &ClassNode> {
+        self.nodes.values()
+    }
+
+    /// Returns the classes and interfaces that directly extend or implement `name`
+    pub fn direct_subtypes(&self, name: &str) -> BTreeSet<String> {
+        self.subtypes.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns every class and interface that extends or implements `name`, directly or not
+    pub fn subtypes(&self, name: &str) -> BTreeSet<String> {
+        self.walk_down(name, |_, _| true)
+    }
+
+    /// Returns every class that extends `name`, directly or not
+    pub fn subclasses(&self, name: &str) -> BTreeSet<String> {
+        self.walk_down(name, |parent, child| child.super_name.as_deref() == Some(parent))
+    }
+
+    /// Returns every non-interface class that implements the interface `name`,
+    /// either directly, through a superinterface, or by inheriting it from a superclass
+    pub fn implementors(&self, name: &str) -> BTreeSet<String> {
+        self.subtypes(name).into_iter().filter(|n| self.nodes.get(n).is_some_and(|n| !n.is_interface)).collect()
+    }
+
+    /// Follows the subtype edges down from `name` that `follow(parent, child)` accepts
+    fn walk_down<F: Fn(&str, &ClassNode) -> bool>(&self, name: &str, follow: F) -> BTreeSet<String> {
+        let mut ans = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(name.to_owned());
+        while let Some(parent) = queue.pop_front() {
+            for child in self.subtypes.get(&parent).into_iter().flatten() {
+                let node = &self.nodes[child];
+                if follow(&parent, node) && child != name && ans.insert(child.clone()) {
+                    queue.push_back(child.clone());
+                }
+            }
+        }
+        ans
+    }
+
+    /// Returns every supertype of `name`, nearest first: superclasses and interfaces, direct or not.
+    /// Missing supertypes are included, but their own supertypes are unknown.
+    pub fn supertypes(&self, name: &str) -> Vec<String> {
+        let mut ans = vec!();
+        let mut seen = HashSet::new();
+        seen.insert(name.to_owned());
+        let mut queue = VecDeque::new();
+        queue.push_back(name.to_owned());
+        while let Some(curr) = queue.pop_front() {
+            if let Some(node) = self.nodes.get(&curr) {
+                for s in node.supertypes() {
+                    if seen.insert(s.clone()) {
+                        ans.push(s.clone());
+                        queue.push_back(s.clone());
+                    }
+                }
+            }
+        }
+        ans
+    }
+
+    /// Returns the superclass chain of `name`, starting with its direct superclass.
+    /// Stops at the first missing class, or before repeating a class if the chain is cyclic.
+    pub fn superclasses(&self, name: &str) -> Vec<String> {
+        let mut ans = vec!();
+        let mut seen = HashSet::new();
+        seen.insert(name);
+        let mut curr = self.nodes.get(name);
+        while let Some(s) = curr.and_then(|n| n.super_name.as_ref()) {
+            if !seen.insert(s) {
+                break;
+            }
+            ans.push(s.clone());
+            curr = self.nodes.get(s);
+        }
+        ans
+    }
+
+    /// Returns true if `name` is `expected`, or extends or implements it directly or not
+    pub fn is_subtype_of(&self, name: &str, expected: &str) -> bool {
+        name == expected || self.supertypes(name).iter().any(|s| s == expected)
+    }
+
+    /// Returns every class that is extended or implemented by a class in the index, but isn't in it
+    pub fn missing(&self) -> BTreeSet<String> {
+        self.subtypes.keys().filter(|n| !self.nodes.contains_key(*n)).cloned().collect()
+    }
+
+    /// Returns the supertypes of `name` that aren't in the index
+    pub fn missing_supertypes(&self, name: &str) -> BTreeSet<String> {
+        self.supertypes(name).into_iter().filter(|n| !self.nodes.contains_key(n)).collect()
+    }
+
+    /// Returns every cycle in the hierarchy (e.g. `A extends B`, `B extends A`),
+    /// as the sorted names of the classes in it. Such classes can never be loaded.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let names: Vec<&str> = self.nodes.keys().map(String::as_str).collect();
+        graph::cycles(&names, |n| self.nodes[n].supertypes().map(String::as_str).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_classes::ClassSpec;
+
+    fn node(name: &str, super_name: Option<&str>, interfaces: &[&str], is_interface: bool) -> ClassNode {
+        ClassNode {
+            name: name.to_owned(),
+            super_name: super_name.map(str::to_owned),
+            interfaces: interfaces.iter().map(|i| i.to_string()).collect(),
+            is_interface,
+        }
+    }
+
+    fn names(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn queries_follow_classes_and_interfaces() {
+        let mut h = Hierarchy::new();
+        h.add_class(&ClassSpec::new(0x0601, "I", Some("java/lang/Object")).build()).unwrap();
+        h.add_class(&ClassSpec::new(0x0601, "J", Some("java/lang/Object")).implements(&["I"]).build()).unwrap();
+        h.add_class(&ClassSpec::new(0x0021, "A", Some("java/lang/Object")).implements(&["J"]).build()).unwrap();
+        h.add_class(&ClassSpec::new(0x0021, "B", Some("A")).build()).unwrap();
+        assert_eq!(h.get("J"), Some(&node("J", Some("java/lang/Object"), &["I"], true)));
+        assert_eq!(h.subtypes("I"), names(&["A", "B", "J"]));
+        assert_eq!(h.implementors("I"), names(&["A", "B"]));
+        assert_eq!(h.subclasses("A"), names(&["B"]));
+        assert_eq!(h.supertypes("B"), vec!["A", "java/lang/Object", "J", "I"]);
+        assert_eq!(h.superclasses("B"), vec!["A", "java/lang/Object"]);
+        assert!(h.is_subtype_of("B", "I") && !h.is_subtype_of("I", "B"));
+        assert_eq!(h.missing(), names(&["java/lang/Object"]));
+        assert!(h.cycles().is_empty());
+
+        //A becomes missing, but B still extends it
+        h.remove("A");
+        assert_eq!(h.implementors("I"), BTreeSet::new());
+        assert_eq!(h.missing_supertypes("B"), names(&["A"]));
+    }
+
+    #[test]
+    fn cycles_are_found_and_queries_terminate() {
+        let mut h = Hierarchy::new();
+        h.add(node("A", Some("B"), &[], false));
+        h.add(node("B", Some("C"), &[], false));
+        h.add(node("C", Some("A"), &[], false));
+        h.add(node("D", Some("A"), &[], false));
+        h.add(node("Self", Some("Self"), &[], false));
+        h.add(node("I", None, &["J"], true));
+        h.add(node("J", None, &["I"], true));
+        assert_eq!(h.cycles(), vec![vec!["A", "B", "C"], vec!["I", "J"], vec!["Self"]]);
+        assert_eq!(h.superclasses("D"), vec!["A", "B", "C"]);
+        assert_eq!(h.supertypes("A"), vec!["B", "C"]);
+        assert_eq!(h.subtypes("A"), names(&["B", "C", "D"]));
+        assert_eq!(h.subclasses("Self"), BTreeSet::new());
+        assert!(h.is_subtype_of("I", "J") && h.is_subtype_of("J", "I"));
+
+        //breaking the cycle removes it
+        h.add(node("C", Some("java/lang/Object"), &[], false));
+        assert_eq!(h.cycles(), vec![vec!["I", "J"], vec!["Self"]]);
+    }
+}
@@ -0,0 +1,451 @@
+use attributes::*;
+use builders::cp::CPBuilder;
+use class::JavaClass;
+use code::{remap_positions, CodeBody, ExceptionHandler};
+use cp::ConstantPool;
+use frames::{compress, expand, Frame, FrameType};
+use opcode_info::Flow;
+use opcodes::Opcode;
+use std::io;
+use symbolic::SymOpcode;
+
+/// Inserts probes into the code of one method.
+/// Every position in the method (branch targets, the exception table, `LineNumberTable`, `LocalVariableTable`
+/// and the stack map frames) is shifted along with the instructions, and `max_stack` is recomputed by `finish`.
+///
+/// Inserted code must run straight through: it can't branch, return or throw, and it has to leave the
+/// operand stack as it found it. It can use the locals given out by `add_local`.
+#[derive(Debug, Clone)]
+pub struct MethodEditor {
+    body: CodeBody,
+    initial: Frame,
+    /// the stack map frames, at instruction indices
+    frames: Vec<Frame>,
+    /// true if the class file version requires a `StackMapTable`
+    stack_map: bool,
+    /// the first instruction the handler added by `on_throw` covers
+    covered_start: usize,
+    added_locals: Vec<(u16, FrameType)>,
+    throw_probe: Option<Vec<SymOpcode>>,
+}
+
+impl MethodEditor {
+    /// Starts editing a method body
+    /// # Parameters:
+    /// * body: the method's code
+    /// * initial: the method's implicit first frame (see `Frame::initial`)
+    /// * major_version: the major version of the class file the method belongs to
+    /// * cp: the constant pool the stack map frames in `body` refer to
+    pub fn new(mut body: CodeBody, initial: Frame, major_version: u16, cp: &ConstantPool) -> io::Result<MethodEditor> {
+        let frames = match body.attributes.iter().position(|a| matches!(a, Attribute::StackMapTable { .. })) {
+            Some(i) => match body.attributes.remove(i) {
+                Attribute::StackMapTable { entries } => expand(&initial, &entries, cp)?,
+                _ => vec![]
+            },
+            None => vec![]
+        };
+        //in a constructor, nothing can catch exceptions until `this` has been initialized
+        let covered_start = if initial.locals.first() == Some(&FrameType::UninitializedThis) {
+            this_initialized(&body.instructions).map_or(body.instructions.len(), |i| i + 1)
+        } else {
+            0
+        };
+        Ok(MethodEditor {
+            body,
+            initial,
+            frames,
+            stack_map: major_version >= 50,
+            covered_start,
+            added_locals: vec![],
+            throw_probe: None,
+        })
+    }
+
+    pub fn instructions(&self) -> &[SymOpcode] {
+        &self.body.instructions
+    }
+
+    /// The stack map frames, with offsets that are instruction indices
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Allocates a new local variable of type `t` that probes can use across the whole method,
+    /// e.g. to hold a start time from the entry probe for the exit probes. Returns its slot.
+    /// The entry probe must store to it, since every frame after the entry claims it holds a `t`.
+    pub fn add_local(&mut self, t: FrameType) -> io::Result<u16> {
+        let slot = self.body.max_locals;
+        self.body.max_locals = slot.checked_add(t.slots() as u16).ok_or_else(|| invalid("Too many locals"))?;
+        for f in &mut self.frames {
+            f.set_local(slot, t.clone());
+        }
+        self.added_locals.push((slot, t));
+        Ok(slot)
+    }
+
+    /// Inserts `code` before the instruction at `index`.
+    /// Branches and exception handlers that targeted the instruction run `code` first,
+    /// and `code` gets the instruction's line number.
+    pub fn insert_before(&mut self, index: usize, code: Vec<SymOpcode>) -> io::Result<()> {
+        if index >= self.body.instructions.len() {
+            return Err(invalid("Instruction index out of range"));
+        }
+        check_straight(&code)?;
+        self.splice(index, 0, code, true)
+    }
+
+    /// Inserts `code` after the instruction at `index`, which has to be able to continue with the next instruction.
+    /// `code` runs only when the instruction does so: branches to the next instruction skip it.
+    pub fn insert_after(&mut self, index: usize, code: Vec<SymOpcode>) -> io::Result<()> {
+        match self.body.instructions.get(index).map(SymOpcode::flow) {
+            Some(Flow::Next) | Some(Flow::ConditionalBranch) => {}
+            Some(_) => return Err(invalid("Code can't be inserted after an instruction that never continues to the next one")),
+            None => return Err(invalid("Instruction index out of range")),
+        }
+        check_straight(&code)?;
+        self.splice(index + 1, 0, code, false)
+    }
+
+    /// Replaces the instruction at `index` with `code`. Branches to the instruction go to the start of `code`.
+    /// The last instruction of `code` may return or throw if the replaced instruction did too.
+    /// A `new` whose uninitialized object appears in a stack map frame can't be replaced.
+    pub fn replace(&mut self, index: usize, code: Vec<SymOpcode>) -> io::Result<()> {
+        let flow = match self.body.instructions.get(index) {
+            Some(op) => op.flow(),
+            None => return Err(invalid("Instruction index out of range")),
+        };
+        match flow {
+            Flow::Next => check_straight(&code)?,
+            Flow::Return | Flow::Throw => {
+                match code.split_last() {
+                    Some((last, rest)) if last.flow() == flow => check_straight(rest)?,
+                    _ => return Err(invalid("A return or athrow has to be replaced by code that ends the same way")),
+                }
+            }
+            _ => return Err(invalid("Branch instructions can't be replaced")),
+        }
+        self.splice(index, 1, code, true)
+    }
+
+    /// Inserts `code` at the start of the method. Branches back to the first instruction don't run it again.
+    /// In a constructor `code` runs before the superclass constructor, so it can't use `this`.
+    pub fn on_entry(&mut self, code: Vec<SymOpcode>) -> io::Result<()> {
+        check_straight(&code)?;
+        self.splice(0, 0, code, false)
+    }
+
+    /// Inserts the code returned by `probe` before every return instruction.
+    /// `probe` is given the return instruction, and the value being returned (if any) is on top of the stack.
+    pub fn on_return<F: FnMut(&Opcode) -> Vec<SymOpcode>>(&mut self, mut probe: F) -> io::Result<()> {
+        let returns: Vec<(usize, Vec<SymOpcode>)> = self.body.instructions.iter().enumerate().filter_map(|(i, op)| match op {
+            SymOpcode::Plain(op) if op.flow() == Flow::Return => Some((i, probe(op))),
+            _ => None
+        }).collect();
+        for (i, code) in returns.into_iter().rev() {
+            self.insert_before(i, code)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `code` whenever the method completes by throwing an exception, with the exception on top of the stack.
+    /// `code` is placed in a handler for every exception, which rethrows once `code` is done.
+    /// The handler covers the whole method except the entry probes and, in a constructor, the code
+    /// up to the superclass constructor call. It is added by `finish`, after every other handler,
+    /// and can only use the locals given out by `add_local`.
+    pub fn on_throw(&mut self, code: Vec<SymOpcode>) -> io::Result<()> {
+        check_straight(&code)?;
+        self.throw_probe = Some(code);
+        Ok(())
+    }
+
+    /// Inserts the code returned by `probe` before every `getfield`, `putfield`, `getstatic` and `putstatic`,
+    /// where the instruction's operands are on the stack. Returning `None` leaves the instruction alone.
+    pub fn on_field_access<F: FnMut(&SymOpcode) -> Option<Vec<SymOpcode>>>(&mut self, mut probe: F) -> io::Result<()> {
+        self.insert_before_each(|op| match op {
+            SymOpcode::GetField(_) | SymOpcode::PutField(_) | SymOpcode::GetStatic(_) | SymOpcode::PutStatic(_) => probe(op),
+            _ => None
+        })
+    }
+
+    /// Wraps every call site `wrap` returns code for: the first half runs before the call, with the arguments
+    /// on the stack, and the second half after it, with the returned value (if any) on the stack.
+    /// Exceptions thrown by the call skip the second half.
+    pub fn wrap_calls<F>(&mut self, mut wrap: F) -> io::Result<()>
+        where F: FnMut(&SymOpcode) -> Option<(Vec<SymOpcode>, Vec<SymOpcode>)> {
+        let mut calls = vec![];
+        for (i, op) in self.body.instructions.iter().enumerate() {
+            match op {
+                SymOpcode::InvokeVirtual(_) | SymOpcode::InvokeSpecial(_) | SymOpcode::InvokeStatic(_) |
+                SymOpcode::InvokeInterface(_) | SymOpcode::InvokeDynamic { .. } => if let Some(w) = wrap(op) {
+                    calls.push((i, w));
+                },
+                _ => {}
+            }
+        }
+        for (i, (before, after)) in calls.into_iter().rev() {
+            self.insert_after(i, after)?;
+            self.insert_before(i, before)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts the code returned by `probe` at the start of every line in the `LineNumberTable`.
+    /// `probe` is given the line number.
+    pub fn on_line<F: FnMut(u16) -> Vec<SymOpcode>>(&mut self, mut probe: F) -> io::Result<()> {
+        let mut starts: Vec<(usize, u16)> = vec![];
+        for a in &self.body.attributes {
+            if let Attribute::LineNumberTable { line_number_table } = a {
+                starts.extend(line_number_table.iter().map(|l| (l.start_pc as usize, l.line_number)));
+            }
+        }
+        //several entries can start at the same instruction; the first one wins
+        starts.sort_by_key(|s| s.0);
+        starts.dedup_by_key(|s| s.0);
+        let probes: Vec<(usize, Vec<SymOpcode>)> = starts.into_iter()
+            .filter(|s| s.0 < self.body.instructions.len())
+            .map(|(i, line)| (i, probe(line)))
+            .collect();
+        for (i, code) in probes.into_iter().rev() {
+            self.insert_before(i, code)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts the code returned by `probe` before every instruction it returns code for
+    pub fn insert_before_each<F: FnMut(&SymOpcode) -> Option<Vec<SymOpcode>>>(&mut self, mut probe: F) -> io::Result<()> {
+        let probes: Vec<(usize, Vec<SymOpcode>)> = self.body.instructions.iter().enumerate()
+            .filter_map(|(i, op)| probe(op).map(|code| (i, code)))
+            .collect();
+        for (i, code) in probes.into_iter().rev() {
+            self.insert_before(i, code)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes editing, adding the `on_throw` handler, recomputing `max_stack` and
+    /// rebuilding the `StackMapTable`, whose classes are added to `cp`
+    pub fn finish(mut self, cp: &mut CPBuilder) -> io::Result<CodeBody> {
+        if let Some(probe) = self.throw_probe.take() {
+            let handler = self.body.instructions.len();
+            if self.covered_start < handler {
+                self.body.instructions.extend(probe);
+                self.body.instructions.push(SymOpcode::Plain(Opcode::athrow));
+                self.body.exception_table.push(ExceptionHandler { start: self.covered_start, end: handler, handler, catch_type: None });
+                let mut frame = Frame { offset: handler as u32, locals: vec![], stack: vec![FrameType::Object("java/lang/Throwable".to_owned())] };
+                for (slot, t) in &self.added_locals {
+                    frame.set_local(*slot, t.clone());
+                }
+                self.frames.push(frame);
+            }
+        }
+        self.body.max_stack = self.body.max_stack.max(self.body.compute_max_stack()?);
+        if self.stack_map && !self.frames.is_empty() {
+            let entries = compress(&self.initial, &self.frames, cp)?;
+            self.body.attributes.push(Attribute::StackMapTable { entries });
+        }
+        Ok(self.body)
+    }
+
+    /// Replaces `remove` instructions at `index` with `code`, moving every position after them.
+    /// If `retarget` is set, positions of the instruction at `index` move to the start of `code`, otherwise past it.
+    fn splice(&mut self, index: usize, remove: usize, code: Vec<SymOpcode>, retarget: bool) -> io::Result<()> {
+        let added = code.len();
+        if added == 0 && remove == 0 {
+            return Ok(());
+        }
+        let moved = |p: usize| p + added - remove;
+        let map = |p: usize| if p < index || (p == index && retarget) { p } else { moved(p) };
+        //the end of a range stays before inserted code unless the range covers a replaced instruction
+        let map_end = |p: usize| if p <= index { p } else { moved(p) };
+        for op in &mut self.body.instructions {
+            match op {
+                SymOpcode::Branch { target, .. } => *target = map(*target),
+                SymOpcode::TableSwitch { default, targets, .. } => {
+                    *default = map(*default);
+                    for t in targets {
+                        *t = map(*t);
+                    }
+                }
+                SymOpcode::LookupSwitch { default, pairs } => {
+                    *default = map(*default);
+                    for p in pairs {
+                        p.1 = map(p.1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for e in &mut self.body.exception_table {
+            e.start = map(e.start);
+            e.end = map_end(e.end);
+            e.handler = map(e.handler);
+        }
+        let map_pos = |p: u32| Ok(map(p as usize) as u32);
+        remap_positions(&mut self.body.attributes, map_pos, map_pos)?;
+        for f in &mut self.frames {
+            f.offset = map(f.offset as usize) as u32;
+            for t in f.locals.iter_mut().chain(f.stack.iter_mut()) {
+                //an uninitialized type belongs to the `new` instruction itself, not to whatever runs before it
+                if let FrameType::Uninitialized(offset) = t {
+                    let o = *offset as usize;
+                    if remove > 0 && o == index {
+                        return Err(invalid("The new instruction of an uninitialized object can't be replaced"));
+                    }
+                    if o >= index {
+                        *offset = moved(o) as u32;
+                    }
+                }
+            }
+        }
+        self.covered_start = map(self.covered_start);
+        self.body.instructions.splice(index..index + remove, code);
+        Ok(())
+    }
+}
+
+impl JavaClass {
+    /// Edits the code of `self.methods[method]` with `edit`, then writes the code back and adds any new constants
+    /// and bootstrap methods to the class. Does nothing if the method has no code.
+    pub fn instrument_method<F>(&mut self, method: usize, edit: F) -> io::Result<()>
+        where F: FnOnce(&mut MethodEditor) -> io::Result<()> {
+        let m = self.methods.get(method).ok_or_else(|| invalid("Method index out of range"))?;
+        let code = match m.attributes.iter().position(|a| matches!(a, Attribute::Code { .. })) {
+            Some(i) => i,
+            None => return Ok(())
+        };
+        let mut bootstrap_methods = self.bootstrap_methods().to_vec();
+        let body = CodeBody::from_attribute(&m.attributes[code], &self.constant_pool, &bootstrap_methods)?;
+        let mut editor = MethodEditor::new(body, self.initial_frame(m)?, self.major_version, &self.constant_pool)?;
+        edit(&mut editor)?;
+        let mut cp = CPBuilder::from_constant_pool(&self.constant_pool);
+        let bootstrap_count = bootstrap_methods.len();
+        let attr = editor.finish(&mut cp)?.to_attribute(&mut cp, &mut bootstrap_methods)?;
+        self.methods[method].attributes[code] = attr;
+        if bootstrap_methods.len() != bootstrap_count {
+            match self.attributes.iter_mut().find(|a| matches!(a, Attribute::BootstrapMethods { .. })) {
+                Some(a) => *a = Attribute::BootstrapMethods { bootstrap_methods },
+                None => self.attributes.push(Attribute::BootstrapMethods { bootstrap_methods }),
+            }
+        }
+        self.constant_pool = cp.build();
+        Ok(())
+    }
+}
+
+/// Finds the call to the superclass (or another) constructor that initializes `this` in a constructor,
+/// skipping calls that initialize objects from earlier `new` instructions, the way javac nests them
+fn this_initialized(code: &[SymOpcode]) -> Option<usize> {
+    let mut pending = 0;
+    for (i, op) in code.iter().enumerate() {
+        match op {
+            SymOpcode::New(_) => pending += 1,
+            SymOpcode::InvokeSpecial(m) if m.name == "<init>" => {
+                if pending == 0 {
+                    return Some(i);
+                }
+                pending -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn check_straight(code: &[SymOpcode]) -> io::Result<()> {
+    match code.iter().find(|op| op.flow() != Flow::Next) {
+        Some(op) => Err(invalid(&format!("Inserted code can't branch, return or throw, but has {:?}", op))),
+        None => Ok(())
+    }
+}
+
+fn invalid(err: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid instrumentation: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcodes::Opcode::*;
+    use symbolic::BranchKind;
+
+    fn lines(body: &[Attribute]) -> Vec<(u16, u16)> {
+        body.iter().filter_map(|a| match a {
+            Attribute::LineNumberTable { line_number_table } => Some(line_number_table.iter().map(|l| (l.start_pc, l.line_number))),
+            _ => None
+        }).flatten().collect()
+    }
+
+    #[test]
+    fn edits_move_targets_handlers_lines_and_frames() {
+        let mut cp = CPBuilder::new();
+        let throwable = cp.add_class("java/lang/Throwable".to_owned());
+        let cp = cp.build();
+        //static void m(int x) { if (x != 0) try { 1; } catch (Throwable t) {} }
+        let body = CodeBody {
+            max_stack: 1,
+            max_locals: 1,
+            instructions: vec![
+                SymOpcode::Plain(iload_0),
+                SymOpcode::Branch { kind: BranchKind::Ifeq, target: 4 },
+                SymOpcode::Plain(iconst_1),
+                SymOpcode::Plain(pop),
+                SymOpcode::Plain(return_),
+                SymOpcode::Plain(pop),
+                SymOpcode::Plain(return_),
+            ],
+            exception_table: vec![ExceptionHandler { start: 2, end: 4, handler: 5, catch_type: None }],
+            attributes: vec![
+                Attribute::LineNumberTable { line_number_table: [(0, 10), (2, 11), (4, 12), (5, 13)].iter()
+                    .map(|&(start_pc, line_number)| LineNumberTableEntry { start_pc, line_number }).collect() },
+                Attribute::StackMapTable { entries: vec![
+                    StackMapFrame::SameFrame { offset_delta: 4 },
+                    StackMapFrame::SameLocals1Item { offset_delta: 0, stack: VerificationTypeInfo::Object { cpool_index: throwable } },
+                ] },
+            ],
+        };
+        let initial = Frame::initial("A", "m", "(I)V", true).unwrap();
+        let mut editor = MethodEditor::new(body, initial, 52, &cp).unwrap();
+        //the branch target gets the nop, but the end of the try block stays before it
+        editor.insert_before(4, vec![SymOpcode::Plain(nop)]).unwrap();
+        //code at the start of the try block is covered by it
+        editor.insert_before(2, vec![SymOpcode::Plain(iconst_0), SymOpcode::Plain(pop)]).unwrap();
+        editor.on_entry(vec![SymOpcode::Plain(nop)]).unwrap();
+        let mut cp = CPBuilder::from_constant_pool(&cp);
+        let body = editor.finish(&mut cp).unwrap();
+
+        assert_eq!(body.instructions, vec![
+            SymOpcode::Plain(nop),
+            SymOpcode::Plain(iload_0),
+            SymOpcode::Branch { kind: BranchKind::Ifeq, target: 7 },
+            SymOpcode::Plain(iconst_0),
+            SymOpcode::Plain(pop),
+            SymOpcode::Plain(iconst_1),
+            SymOpcode::Plain(pop),
+            SymOpcode::Plain(nop),
+            SymOpcode::Plain(return_),
+            SymOpcode::Plain(pop),
+            SymOpcode::Plain(return_),
+        ]);
+        assert_eq!(body.exception_table, vec![ExceptionHandler { start: 3, end: 7, handler: 9, catch_type: None }]);
+        assert_eq!(lines(&body.attributes), vec![(1, 10), (3, 11), (7, 12), (9, 13)]);
+        assert_eq!(body.attributes[1], Attribute::StackMapTable { entries: vec![
+            StackMapFrame::SameFrame { offset_delta: 7 },
+            StackMapFrame::SameLocals1Item { offset_delta: 1, stack: VerificationTypeInfo::Object { cpool_index: throwable } },
+        ] });
+
+        //in bytecode the ifeq is 3 bytes long and everything else 1
+        match body.to_attribute(&mut cp, &mut vec![]).unwrap() {
+            Attribute::Code { code, exception_table, attributes, .. } => {
+                assert_eq!(code[2], ifeq { branch: 7 });
+                let e = &exception_table[0];
+                assert_eq!((e.start_pc, e.end_pc, e.handler_pc), (5, 9, 11));
+                assert_eq!(lines(&attributes), vec![(1, 10), (5, 11), (9, 12), (11, 13)]);
+                assert_eq!(attributes[1], Attribute::StackMapTable { entries: vec![
+                    StackMapFrame::SameFrame { offset_delta: 9 },
+                    StackMapFrame::SameLocals1Item { offset_delta: 1, stack: VerificationTypeInfo::Object { cpool_index: throwable } },
+                ] });
+            }
+            a => panic!("expected a Code attribute, got {:?}", a)
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use class::{write_string, AccessFlags as ClassFlags, JavaClass};
+use descriptor::{FieldType, MethodDescriptor};
+use malformed;
+use methods::AccessFlags as MethodFlags;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::io;
+
+/// Options for `jni_bindings`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingOptions {
+    /// The path of the module that defines `JNIEnv`, `jvalue` and the other JNI types, which has to work from any module:
+    /// `::jni_sys` for the stock definitions, or e.g. `::jni` for the `jni` module of this project's JVM
+    pub jni_path: String,
+    /// Also wrap private and package-private methods and constructors
+    pub non_public: bool,
+}
+
+impl Default for BindingOptions {
+    fn default() -> BindingOptions {
+        BindingOptions { jni_path: "::jni_sys".to_owned(), non_public: false }
+    }
+}
+
+/// Generates Rust source with a wrapper for each class, a `Copy` struct holding a `jobject`, whose functions
+/// call the class's methods and constructors through a `JNIEnv`.
+/// Arguments and results are mapped from the descriptors: primitives become Rust primitives, objects of the
+/// given classes become their wrappers, and other objects stay JNI references (`jobject`, `jstring`, ...).
+/// The class and every method ID are looked up once and cached. A Java exception is cleared and returned as the `Err`.
+///
+/// The output also defines a private `jni_support` module, so it should be included once per module.
+/// Overloads get the types of their parameters appended to their names, e.g. `value_of_int` and `value_of_string`.
+pub fn jni_bindings(classes: &[JavaClass], options: &BindingOptions) -> io::Result<String> {
+    let jni = &options.jni_path;
+    let mut wrappers: HashMap<String, String> = HashMap::new();
+    let mut names = Vec::with_capacity(classes.len());
+    for class in classes {
+        let name = class.constant_pool.get_class_name(class.this_class)
+            .ok_or_else(|| malformed("Class Pool index did not point to Utf8"))?;
+        let wrapper = type_name(&name);
+        if let Some((other, _)) = wrappers.iter().find(|(_, w)| **w == wrapper) {
+            return Err(invalid(&format!("{} and {} would both be wrapped as {}", other, name, wrapper)));
+        }
+        wrappers.insert(name.clone(), wrapper);
+        names.push(name);
+    }
+    let mut ans = String::new();
+    let _ = writeln!(ans, "// Generated by java_class. Every function needs the JNIEnv of a thread attached to the JVM.");
+    ans += &SUPPORT.replace("JNI::", &format!("{}::", jni));
+    for (class, name) in classes.iter().zip(&names) {
+        class_bindings(&mut ans, class, name, &wrappers, options)?;
+    }
+    Ok(ans)
+}
+
+/// Looks up classes and method IDs and turns pending exceptions into errors
+const SUPPORT: &str = r#"
+#[allow(dead_code)]
+mod jni_support {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Clears the pending exception, if there is one, and returns it
+    pub unsafe fn check(env: *mut JNI::JNIEnv) -> Result<(), JNI::jthrowable> {
+        if ((**env).ExceptionCheck.unwrap())(env) != 0 {
+            let e = ((**env).ExceptionOccurred.unwrap())(env);
+            ((**env).ExceptionClear.unwrap())(env);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Looks up a class by its binary name, keeping a global reference to it in `cache`
+    pub unsafe fn class(env: *mut JNI::JNIEnv, cache: &AtomicUsize, name: &[u8]) -> Result<JNI::jclass, JNI::jthrowable> {
+        let cached = cache.load(Ordering::Acquire);
+        if cached != 0 {
+            return Ok(cached as JNI::jclass);
+        }
+        let local = ((**env).FindClass.unwrap())(env, name.as_ptr() as *const _);
+        check(env)?;
+        let global = ((**env).NewGlobalRef.unwrap())(env, local);
+        ((**env).DeleteLocalRef.unwrap())(env, local);
+        match cache.compare_exchange(0, global as usize, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => Ok(global),
+            Err(other) => {
+                //another thread got there first
+                ((**env).DeleteGlobalRef.unwrap())(env, global);
+                Ok(other as JNI::jclass)
+            }
+        }
+    }
+
+    /// Looks up a method, keeping its ID in `cache`
+    pub unsafe fn method(env: *mut JNI::JNIEnv, cache: &AtomicUsize, class: JNI::jclass, name: &[u8], sig: &[u8], is_static: bool)
+        -> Result<JNI::jmethodID, JNI::jthrowable> {
+        let cached = cache.load(Ordering::Acquire);
+        if cached != 0 {
+            return Ok(cached as JNI::jmethodID);
+        }
+        let id = if is_static {
+            ((**env).GetStaticMethodID.unwrap())(env, class, name.as_ptr() as *const _, sig.as_ptr() as *const _)
+        } else {
+            ((**env).GetMethodID.unwrap())(env, class, name.as_ptr() as *const _, sig.as_ptr() as *const _)
+        };
+        check(env)?;
+        cache.store(id as usize, Ordering::Release);
+        Ok(id)
+    }
+}
+"#;
+
+fn class_bindings(out: &mut String, class: &JavaClass, name: &str, wrappers: &HashMap<String, String>, options: &BindingOptions)
+    -> io::Result<()> {
+    let jni = &options.jni_path;
+    let wrapper = &wrappers[name];
+    let cp = &class.constant_pool;
+    let flags = class.flags();
+    let can_construct = !flags.contains(ClassFlags::ABSTRACT) && !flags.contains(ClassFlags::INTERFACE);
+    //(Rust name, Java name, descriptor, flags)
+    let mut methods = vec![];
+    for m in &class.methods {
+        let m_flags = m.flags();
+        let java_name = cp.get_utf8(m.name_index).ok_or_else(|| malformed("Constant pool index did not point to Utf8"))?;
+        let descriptor = cp.get_utf8(m.descriptor_index).ok_or_else(|| malformed("Constant pool index did not point to Utf8"))?;
+        if java_name == "<clinit>" || (java_name == "<init>" && !can_construct)
+            || m_flags.contains(MethodFlags::SYNTHETIC) || m_flags.contains(MethodFlags::BRIDGE)
+            || !(options.non_public || m_flags.contains(MethodFlags::PUBLIC)) {
+            continue;
+        }
+        let base = if java_name == "<init>" { "new".to_owned() } else { snake_case(&java_name) };
+        methods.push((base, java_name, MethodDescriptor::parse(&descriptor)?, m_flags));
+    }
+    //overloads are told apart by their parameter types, and anything still clashing gets a number
+    let mut overloads: HashMap<String, usize> = HashMap::new();
+    for m in &methods {
+        *overloads.entry(m.0.clone()).or_insert(0) += 1;
+    }
+    let mut used: HashSet<String> = ["class".to_owned()].iter().cloned().collect();
+    for m in &mut methods {
+        if overloads[&m.0] > 1 && !m.2.parameters.is_empty() {
+            let types: Vec<String> = m.2.parameters.iter().map(type_word).collect();
+            m.0 = format!("{}_{}", m.0, types.join("_"));
+        }
+        let mut rust_name = m.0.clone();
+        let mut n = 2;
+        while !used.insert(rust_name.clone()) {
+            rust_name = format!("{}_{}", m.0, n);
+            n += 1;
+        }
+        m.0 = rust_name;
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "/// A reference to a `{}`", name.replace('/', "."));
+    let _ = writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq)]");
+    let _ = writeln!(out, "#[repr(transparent)]");
+    let _ = writeln!(out, "#[allow(clippy::upper_case_acronyms)]");
+    let _ = writeln!(out, "pub struct {}(pub {}::jobject);", wrapper, jni);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#[allow(dead_code, clippy::missing_safety_doc, clippy::too_many_arguments)]");
+    let _ = writeln!(out, "impl {} {{", wrapper);
+    let _ = writeln!(out, "    pub const CLASS_NAME: &'static str = {:?};", name);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "    /// A global reference to the class, looked up on first use");
+    let _ = writeln!(out, "    pub unsafe fn class(env: *mut {0}::JNIEnv) -> Result<{0}::jclass, {0}::jthrowable> {{", jni);
+    let _ = writeln!(out, "        static CLASS: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);");
+    let _ = writeln!(out, "        jni_support::class(env, &CLASS, {})", c_string(name));
+    let _ = writeln!(out, "    }}");
+    for (rust_name, java_name, descriptor, m_flags) in &methods {
+        let is_static = m_flags.contains(MethodFlags::STATIC);
+        let is_constructor = java_name == "<init>";
+        let mut params = vec![format!("env: *mut {}::JNIEnv", jni)];
+        if !is_static && !is_constructor {
+            params.insert(0, "self".to_owned());
+        }
+        let mut args = vec![];
+        for (i, p) in descriptor.parameters.iter().enumerate() {
+            params.push(format!("arg{}: {}", i, rust_type(p, wrappers, jni)));
+            args.push(format!("{}::jvalue {{ {} }}", jni, jvalue_field(p, &format!("arg{}", i), wrappers)));
+        }
+        let result = if is_constructor {
+            wrapper.clone()
+        } else {
+            descriptor.return_type.as_ref().map_or_else(|| "()".to_owned(), |t| rust_type(t, wrappers, jni))
+        };
+        let call = if is_constructor {
+            "NewObjectA".to_owned()
+        } else {
+            format!("Call{}{}MethodA", if is_static { "Static" } else { "" }, call_kind(descriptor.return_type.as_ref()))
+        };
+        let receiver = if is_static || is_constructor { "class" } else { "self.0" };
+        let _ = writeln!(out);
+        let _ = writeln!(out, "    /// `{}`", java_signature(name, java_name, descriptor, *m_flags));
+        let _ = writeln!(out, "    pub unsafe fn {}({}) -> Result<{}, {}::jthrowable> {{", rust_name, params.join(", "), result, jni);
+        let _ = writeln!(out, "        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);");
+        let _ = writeln!(out, "        let class = Self::class(env)?;");
+        let _ = writeln!(out, "        let id = jni_support::method(env, &ID, class, {}, {}, {})?;",
+                         c_string(java_name), c_string(&descriptor.to_string()), is_static);
+        if args.is_empty() {
+            let _ = writeln!(out, "        let args: [{}::jvalue; 0] = [];", jni);
+        } else {
+            let _ = writeln!(out, "        let args = [{}];", args.join(", "));
+        }
+        let value = if is_constructor {
+            format!("{}(ans)", wrapper)
+        } else {
+            match &descriptor.return_type {
+                None => "()".to_owned(),
+                Some(FieldType::Boolean) => "ans != 0".to_owned(),
+                Some(FieldType::Object(n)) if wrappers.contains_key(n) => format!("{}(ans)", wrappers[n]),
+                Some(_) => "ans".to_owned(),
+            }
+        };
+        let binding = if value == "()" { "" } else { "let ans = " };
+        let _ = writeln!(out, "        {}((**env).{}.unwrap())(env, {}, id, args.as_ptr());", binding, call, receiver);
+        let _ = writeln!(out, "        jni_support::check(env)?;");
+        let _ = writeln!(out, "        Ok({})", value);
+        let _ = writeln!(out, "    }}");
+    }
+    let _ = writeln!(out, "}}");
+    Ok(())
+}
+
+/// The wrapper's name: the class's simple name, with nested class names run together, e.g. `MapEntry`
+fn type_name(class: &str) -> String {
+    let simple = class.rsplit('/').next().unwrap_or(class);
+    let mut ans: String = simple.split('$').filter(|s| !s.is_empty()).map(|s| {
+        let mut chars = s.chars();
+        chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+    }).collect();
+    ans = ans.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if !ans.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        ans.insert(0, 'J');
+    }
+    ans
+}
+
+/// Converts a Java name such as `getURLPath` to `get_url_path`, avoiding Rust keywords
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut ans = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let after_lower = i > 0 && (chars[i - 1].is_ascii_lowercase() || chars[i - 1].is_ascii_digit());
+            let acronym_end = i > 0 && chars[i - 1].is_ascii_uppercase() && chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if after_lower || acronym_end {
+                ans.push('_');
+            }
+            ans.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            ans.push(c);
+        } else {
+            ans.push('_');
+        }
+    }
+    if ans.is_empty() || ans.starts_with(|c: char| c.is_ascii_digit()) {
+        ans.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ans.as_str()) {
+        ans.push('_');
+    }
+    ans
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else", "enum", "extern", "false",
+    "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv",
+    "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe",
+    "unsized", "use", "virtual", "where", "while", "yield", "abstract", "become", "gen",
+];
+
+/// A parameter type as a word in the name of an overload
+fn type_word(t: &FieldType) -> String {
+    match t {
+        FieldType::Object(name) => snake_case(name.rsplit(['/', '$']).next().unwrap_or(name)).trim_matches('_').to_owned(),
+        FieldType::Array(inner) => type_word(inner) + "_array",
+        t => t.java_name(),
+    }
+}
+
+fn rust_type(t: &FieldType, wrappers: &HashMap<String, String>, jni: &str) -> String {
+    match t {
+        FieldType::Boolean => "bool".to_owned(),
+        FieldType::Byte => "i8".to_owned(),
+        FieldType::Char => "u16".to_owned(),
+        FieldType::Short => "i16".to_owned(),
+        FieldType::Int => "i32".to_owned(),
+        FieldType::Long => "i64".to_owned(),
+        FieldType::Float => "f32".to_owned(),
+        FieldType::Double => "f64".to_owned(),
+        FieldType::Object(name) => match wrappers.get(name) {
+            Some(w) => w.clone(),
+            None => format!("{}::{}", jni, match name.as_str() {
+                "java/lang/String" => "jstring",
+                "java/lang/Class" => "jclass",
+                "java/lang/Throwable" => "jthrowable",
+                _ => "jobject",
+            }),
+        },
+        FieldType::Array(inner) => format!("{}::{}", jni, match **inner {
+            FieldType::Boolean => "jbooleanArray",
+            FieldType::Byte => "jbyteArray",
+            FieldType::Char => "jcharArray",
+            FieldType::Short => "jshortArray",
+            FieldType::Int => "jintArray",
+            FieldType::Long => "jlongArray",
+            FieldType::Float => "jfloatArray",
+            FieldType::Double => "jdoubleArray",
+            _ => "jobjectArray",
+        }),
+    }
+}
+
+/// The `jvalue` initializer for an argument
+fn jvalue_field(t: &FieldType, arg: &str, wrappers: &HashMap<String, String>) -> String {
+    match t {
+        FieldType::Boolean => format!("z: {} as u8", arg),
+        FieldType::Byte => format!("b: {}", arg),
+        FieldType::Char => format!("c: {}", arg),
+        FieldType::Short => format!("s: {}", arg),
+        FieldType::Int => format!("i: {}", arg),
+        FieldType::Long => format!("j: {}", arg),
+        FieldType::Float => format!("f: {}", arg),
+        FieldType::Double => format!("d: {}", arg),
+        FieldType::Object(name) if wrappers.contains_key(name) => format!("l: {}.0", arg),
+        _ => format!("l: {}", arg),
+    }
+}
+
+/// The part of the `Call*MethodA` function name that depends on the return type
+fn call_kind(t: Option<&FieldType>) -> &'static str {
+    match t {
+        None => "Void",
+        Some(FieldType::Boolean) => "Boolean",
+        Some(FieldType::Byte) => "Byte",
+        Some(FieldType::Char) => "Char",
+        Some(FieldType::Short) => "Short",
+        Some(FieldType::Int) => "Int",
+        Some(FieldType::Long) => "Long",
+        Some(FieldType::Float) => "Float",
+        Some(FieldType::Double) => "Double",
+        Some(_) => "Object",
+    }
+}
+
+/// The method's declaration as it is written in Java, e.g. `public static int parseInt(java.lang.String)`
+pub(crate) fn java_signature(class: &str, name: &str, descriptor: &MethodDescriptor, flags: MethodFlags) -> String {
+    let mut ans = String::new();
+    for (flag, word) in &[(MethodFlags::PUBLIC, "public "), (MethodFlags::PROTECTED, "protected "), (MethodFlags::PRIVATE, "private "),
+                          (MethodFlags::STATIC, "static "), (MethodFlags::FINAL, "final "), (MethodFlags::SYNCHRONIZED, "synchronized "),
+                          (MethodFlags::NATIVE, "native "), (MethodFlags::ABSTRACT, "abstract ")] {
+        if flags.contains(*flag) {
+            ans += word;
+        }
+    }
+    if name == "<init>" {
+        ans += class.rsplit(['/', '$']).next().unwrap_or(class);
+    } else {
+        ans += &descriptor.java_return_type();
+        ans.push(' ');
+        ans += name;
+    }
+    let params: Vec<String> = descriptor.parameters.iter().map(FieldType::java_name).collect();
+    ans + "(" + &params.join(", ") + ")"
+}
+
+/// A byte string literal holding `s` in modified UTF-8, with the terminating nul
+fn c_string(s: &str) -> String {
+    let mut ans = "b\"".to_owned();
+    for b in write_string(s) {
+        match b {
+            b'"' | b'\\' => { ans.push('\\'); ans.push(b as char); }
+            0x20..=0x7e => ans.push(b as char),
+            _ => { let _ = write!(ans, "\\x{:02x}", b); }
+        }
+    }
+    ans + "\\0\""
+}
+
+fn invalid(err: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid bindings: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_classes::ClassSpec;
+
+    fn point() -> JavaClass {
+        ClassSpec::new(0x0021, "p/Point", Some("java/lang/Object"))
+            .method(0x0001, "<init>", "(II)V", vec![])
+            .method(0x0001, "getX", "()I", vec![])
+            .method(0x0009, "valueOf", "(Ljava/lang/String;)Lp/Point;", vec![])
+            .method(0x0009, "valueOf", "([I)Lp/Point;", vec![])
+            .method(0x0001, "equals", "(Ljava/lang/Object;)Z", vec![])
+            .method(0x0001, "move", "(JD)V", vec![])
+            .method(0x0002, "helper", "()V", vec![])
+            .method(0x1041, "equals", "(Lp/Point;)Z", vec![])
+            .method(0x0008, "<clinit>", "()V", vec![])
+            .build()
+    }
+
+    /// The bindings of `point()`, after the support module
+    const POINT: &str = r#"
+/// A reference to a `p.Point`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct Point(pub ::jni_sys::jobject);
+
+#[allow(dead_code, clippy::missing_safety_doc, clippy::too_many_arguments)]
+impl Point {
+    pub const CLASS_NAME: &'static str = "p/Point";
+
+    /// A global reference to the class, looked up on first use
+    pub unsafe fn class(env: *mut ::jni_sys::JNIEnv) -> Result<::jni_sys::jclass, ::jni_sys::jthrowable> {
+        static CLASS: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        jni_support::class(env, &CLASS, b"p/Point\0")
+    }
+
+    /// `public Point(int, int)`
+    pub unsafe fn new(env: *mut ::jni_sys::JNIEnv, arg0: i32, arg1: i32) -> Result<Point, ::jni_sys::jthrowable> {
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let class = Self::class(env)?;
+        let id = jni_support::method(env, &ID, class, b"<init>\0", b"(II)V\0", false)?;
+        let args = [::jni_sys::jvalue { i: arg0 }, ::jni_sys::jvalue { i: arg1 }];
+        let ans = ((**env).NewObjectA.unwrap())(env, class, id, args.as_ptr());
+        jni_support::check(env)?;
+        Ok(Point(ans))
+    }
+
+    /// `public int getX()`
+    pub unsafe fn get_x(self, env: *mut ::jni_sys::JNIEnv) -> Result<i32, ::jni_sys::jthrowable> {
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let class = Self::class(env)?;
+        let id = jni_support::method(env, &ID, class, b"getX\0", b"()I\0", false)?;
+        let args: [::jni_sys::jvalue; 0] = [];
+        let ans = ((**env).CallIntMethodA.unwrap())(env, self.0, id, args.as_ptr());
+        jni_support::check(env)?;
+        Ok(ans)
+    }
+
+    /// `public static p.Point valueOf(java.lang.String)`
+    pub unsafe fn value_of_string(env: *mut ::jni_sys::JNIEnv, arg0: ::jni_sys::jstring) -> Result<Point, ::jni_sys::jthrowable> {
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let class = Self::class(env)?;
+        let id = jni_support::method(env, &ID, class, b"valueOf\0", b"(Ljava/lang/String;)Lp/Point;\0", true)?;
+        let args = [::jni_sys::jvalue { l: arg0 }];
+        let ans = ((**env).CallStaticObjectMethodA.unwrap())(env, class, id, args.as_ptr());
+        jni_support::check(env)?;
+        Ok(Point(ans))
+    }
+
+    /// `public static p.Point valueOf(int[])`
+    pub unsafe fn value_of_int_array(env: *mut ::jni_sys::JNIEnv, arg0: ::jni_sys::jintArray) -> Result<Point, ::jni_sys::jthrowable> {
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let class = Self::class(env)?;
+        let id = jni_support::method(env, &ID, class, b"valueOf\0", b"([I)Lp/Point;\0", true)?;
+        let args = [::jni_sys::jvalue { l: arg0 }];
+        let ans = ((**env).CallStaticObjectMethodA.unwrap())(env, class, id, args.as_ptr());
+        jni_support::check(env)?;
+        Ok(Point(ans))
+    }
+
+    /// `public boolean equals(java.lang.Object)`
+    pub unsafe fn equals(self, env: *mut ::jni_sys::JNIEnv, arg0: ::jni_sys::jobject) -> Result<bool, ::jni_sys::jthrowable> {
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let class = Self::class(env)?;
+        let id = jni_support::method(env, &ID, class, b"equals\0", b"(Ljava/lang/Object;)Z\0", false)?;
+        let args = [::jni_sys::jvalue { l: arg0 }];
+        let ans = ((**env).CallBooleanMethodA.unwrap())(env, self.0, id, args.as_ptr());
+        jni_support::check(env)?;
+        Ok(ans != 0)
+    }
+
+    /// `public void move(long, double)`
+    pub unsafe fn move_(self, env: *mut ::jni_sys::JNIEnv, arg0: i64, arg1: f64) -> Result<(), ::jni_sys::jthrowable> {
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let class = Self::class(env)?;
+        let id = jni_support::method(env, &ID, class, b"move\0", b"(JD)V\0", false)?;
+        let args = [::jni_sys::jvalue { j: arg0 }, ::jni_sys::jvalue { d: arg1 }];
+        ((**env).CallVoidMethodA.unwrap())(env, self.0, id, args.as_ptr());
+        jni_support::check(env)?;
+        Ok(())
+    }
+}
+"#;
+
+    #[test]
+    fn bindings_of_a_class() {
+        let out = jni_bindings(&[point()], &BindingOptions::default()).unwrap();
+        assert!(out.starts_with("// Generated by java_class."));
+        let (support, point) = out.split_at(out.find("\n/// A reference to").unwrap());
+        assert!(support.contains("pub unsafe fn check(env: *mut ::jni_sys::JNIEnv)"));
+        assert!(!support.contains("JNI::"));
+        assert_eq!(point, POINT);
+    }
+
+    #[test]
+    fn options() {
+        let options = BindingOptions { jni_path: "::jni".to_owned(), non_public: true };
+        let out = jni_bindings(&[point()], &options).unwrap();
+        assert!(out.contains("pub struct Point(pub ::jni::jobject);"));
+        assert!(!out.contains("jni_sys"));
+        //private methods are wrapped, but synthetic ones and the class initializer still aren't
+        assert!(out.contains("    /// `private void helper()`\n    pub unsafe fn helper(self, env: *mut ::jni::JNIEnv) -> Result<(), ::jni::jthrowable> {"));
+        assert_eq!(out.matches("fn equals").count(), 1);
+        assert!(!out.contains("clinit"));
+    }
+
+    #[test]
+    fn classes_refer_to_each_others_wrappers() {
+        let line = ClassSpec::new(0x0021, "p/Line", Some("java/lang/Object"))
+            .method(0x0001, "start", "()Lp/Point;", vec![])
+            .method(0x0001, "setStart", "(Lp/Point;)V", vec![])
+            .build();
+        let out = jni_bindings(&[point(), line], &BindingOptions::default()).unwrap();
+        assert!(out.contains("pub unsafe fn start(self, env: *mut ::jni_sys::JNIEnv) -> Result<Point, ::jni_sys::jthrowable> {"));
+        assert!(out.contains("pub unsafe fn set_start(self, env: *mut ::jni_sys::JNIEnv, arg0: Point) -> Result<(), ::jni_sys::jthrowable> {"));
+        assert!(out.contains("let args = [::jni_sys::jvalue { l: arg0.0 }];"));
+
+        let other = ClassSpec::new(0x0021, "q/Point", Some("java/lang/Object")).build();
+        let e = jni_bindings(&[point(), other], &BindingOptions::default()).unwrap_err();
+        assert_eq!(e.to_string(), "Invalid bindings: p/Point and q/Point would both be wrapped as Point");
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(snake_case("getURLPath"), "get_url_path");
+        assert_eq!(snake_case("toString"), "to_string");
+        assert_eq!(snake_case("x2Y"), "x2_y");
+        assert_eq!(snake_case("type"), "type_");
+        assert_eq!(snake_case("1st"), "_1st");
+        assert_eq!(type_name("java/util/Map$Entry"), "MapEntry");
+        assert_eq!(type_name("p/Outer$1"), "Outer1");
+        assert_eq!(type_name("p/$1"), "J1");
+        assert_eq!(c_string("a\"\0é"), "b\"a\\\"\\xc0\\x80\\xc3\\xa9\\0\"");
+    }
+}
@@ -0,0 +1,347 @@
+use attributes::Attribute;
+use class::JavaClass;
+use descriptor::{FieldType, MethodDescriptor};
+use fields::AccessFlags as FieldFlags;
+use hierarchy::Hierarchy;
+use jni_bindings::java_signature;
+use malformed;
+use methods::AccessFlags as MethodFlags;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::io;
+use symbolic::{resolve_constant, Constant};
+
+/// A native method, ready to be written out
+struct Native {
+    name: String,
+    descriptor: String,
+    parsed: MethodDescriptor,
+    flags: MethodFlags,
+    /// the name of the function that implements it
+    function: String,
+}
+
+/// Generates the C header that `javah` (or `javac -h`) would, declaring a JNI function for every native method
+/// and defining the class's primitive constants.
+/// Parameters and results of a class that extends `Throwable` are `jthrowable`s, which is only known for
+/// `java/lang/Throwable` itself and, if `hierarchy` is given, the classes that it knows extend it.
+/// Floating point constants are written as `Double.toString` writes them from Java 19 on; older versions
+/// sometimes printed more digits.
+/// The header is conventionally named after the include guard, e.g. `my_pkg_Outer_Inner.h`.
+pub fn jni_header(class: &JavaClass, hierarchy: Option<&Hierarchy>) -> io::Result<String> {
+    let name = class_name(class)?;
+    let c_name = header_name(class, &name)?;
+    let mut ans = String::new();
+    let _ = writeln!(ans, "/* DO NOT EDIT THIS FILE - it is machine generated */");
+    let _ = writeln!(ans, "#include <jni.h>");
+    let _ = writeln!(ans, "/* Header for class {} */", c_name);
+    let _ = writeln!(ans);
+    let _ = writeln!(ans, "#ifndef _Included_{}", c_name);
+    let _ = writeln!(ans, "#define _Included_{}", c_name);
+    let _ = writeln!(ans, "#ifdef __cplusplus");
+    let _ = writeln!(ans, "extern \"C\" {{");
+    let _ = writeln!(ans, "#endif");
+    for (field, value) in constants(class)? {
+        let _ = writeln!(ans, "#undef {}_{}", c_name, field);
+        let _ = writeln!(ans, "#define {}_{} {}", c_name, field, value);
+    }
+    for m in natives(class, &name)? {
+        let mut params = vec!["JNIEnv *", if m.flags.contains(MethodFlags::STATIC) { "jclass" } else { "jobject" }];
+        params.extend(m.parsed.parameters.iter().map(|p| jni_type(p, hierarchy)));
+        let _ = writeln!(ans, "/*");
+        let _ = writeln!(ans, " * Class:     {}", c_name);
+        let _ = writeln!(ans, " * Method:    {}", stub_name(&m.name));
+        let _ = writeln!(ans, " * Signature: {}", m.descriptor);
+        let _ = writeln!(ans, " */");
+        let _ = writeln!(ans, "JNIEXPORT {} JNICALL {}", m.parsed.return_type.as_ref().map_or("void", |t| jni_type(t, hierarchy)), m.function);
+        let _ = writeln!(ans, "  ({});", params.join(", "));
+        let _ = writeln!(ans);
+    }
+    let _ = writeln!(ans, "#ifdef __cplusplus");
+    let _ = writeln!(ans, "}}");
+    let _ = writeln!(ans, "#endif");
+    let _ = writeln!(ans, "#endif");
+    Ok(ans)
+}
+
+/// Generates a Rust `extern "C"` function for every native method, with the name the JVM links it by,
+/// whose body is `unimplemented!()`. The types come from the module at `jni_path`, e.g. `::jni` for `jvm::jni`.
+/// `hierarchy` is used as in `jni_header`.
+pub fn jni_stubs(class: &JavaClass, hierarchy: Option<&Hierarchy>, jni_path: &str) -> io::Result<String> {
+    let name = class_name(class)?;
+    let mut ans = String::new();
+    let _ = writeln!(ans, "// Native methods of {}", name.replace('/', "."));
+    for m in natives(class, &name)? {
+        let mut params = vec![
+            format!("_env: *mut {}::JNIEnv", jni_path),
+            if m.flags.contains(MethodFlags::STATIC) { format!("_class: {}::jclass", jni_path) } else { format!("_this: {}::jobject", jni_path) },
+        ];
+        for (i, p) in m.parsed.parameters.iter().enumerate() {
+            params.push(format!("_arg{}: {}::{}", i, jni_path, jni_type(p, hierarchy)));
+        }
+        let result = match &m.parsed.return_type {
+            Some(t) => format!(" -> {}::{}", jni_path, jni_type(t, hierarchy)),
+            None => String::new(),
+        };
+        let _ = writeln!(ans);
+        let _ = writeln!(ans, "/// `{}`", java_signature(&name, &m.name, &m.parsed, m.flags));
+        let _ = writeln!(ans, "#[no_mangle]");
+        let _ = writeln!(ans, "#[allow(non_snake_case)]");
+        let _ = writeln!(ans, "pub extern \"C\" fn {}({}){} {{", m.function, params.join(", "), result);
+        let _ = writeln!(ans, "    unimplemented!(\"{}.{}\")", name.replace('/', ".").escape_default(), m.name.escape_default());
+        let _ = writeln!(ans, "}}");
+    }
+    Ok(ans)
+}
+
+/// The name of the JNI function that implements a native method (§2.2 of the JNI specification).
+/// `descriptor` is only needed for overloaded native methods, and adds the mangled argument types to the name.
+pub fn jni_function_name(class: &str, method: &str, descriptor: Option<&str>) -> String {
+    let mut ans = format!("Java_{}_{}", mangle(class), mangle(method));
+    if let Some(descriptor) = descriptor {
+        let args = descriptor.trim_start_matches('(').split(')').next().unwrap_or("");
+        ans += "__";
+        ans += &mangle(args);
+    }
+    ans
+}
+
+/// Escapes a name for a JNI function name: `/` becomes `_`, and `_`, `;`, `[` and anything that isn't
+/// an ASCII letter or digit become `_1`, `_2`, `_3` and `_0xxxx`
+pub fn mangle(name: &str) -> String {
+    let mut ans = String::with_capacity(name.len());
+    for unit in name.encode_utf16() {
+        match unit {
+            0x30..=0x39 | 0x41..=0x5a | 0x61..=0x7a => ans.push(unit as u8 as char),
+            0x2f => ans.push('_'),
+            0x5f => ans += "_1",
+            0x3b => ans += "_2",
+            0x5b => ans += "_3",
+            _ => { let _ = write!(ans, "_0{:04x}", unit); }
+        }
+    }
+    ans
+}
+
+/// The class's name as it appears in the header, e.g. `my_pkg_Outer_Inner`.
+/// Like `javac -h`, this is the name of the class in source, so a `$` that separates a nested class
+/// becomes `_` and one that is part of a name becomes `__`. The nesting is read from the `InnerClasses` attribute;
+/// local and anonymous classes, which `javac -h` writes no header for, are named as if they weren't nested.
+fn header_name(class: &JavaClass, name: &str) -> io::Result<String> {
+    let cp = &class.constant_pool;
+    let mut outers = HashMap::new();
+    for a in &class.attributes {
+        if let Attribute::InnerClasses { classes } = a {
+            for c in classes.iter().filter(|c| c.outer_class_info_index.as_u16() != 0 && c.inner_name_index.as_u16() != 0) {
+                let inner = cp.get_class_name(c.inner_class_info_index).ok_or_else(|| malformed("Constant pool index did not point to Class"))?;
+                let outer = cp.get_class_name(c.outer_class_info_index).ok_or_else(|| malformed("Constant pool index did not point to Class"))?;
+                let simple = cp.get_utf8(c.inner_name_index).ok_or_else(|| malformed("Constant pool index did not point to Utf8"))?;
+                outers.insert(inner, (outer, simple));
+            }
+        }
+    }
+    //the simple names from the innermost class out, then the top level class
+    let mut parts = vec![];
+    let mut current = name.to_owned();
+    while let Some((outer, simple)) = outers.get(&current) {
+        if parts.len() > outers.len() {
+            return Err(malformed("InnerClasses nests a class inside itself"));
+        }
+        parts.push(simple.replace('$', "__"));
+        current = outer.clone();
+    }
+    parts.push(current.replace('$', "__").replace('/', "_"));
+    parts.reverse();
+    Ok(stub_name(&parts.join("_")))
+}
+
+/// Escapes everything but ASCII letters, digits and `_` as `_0xxxx`, as the comments and constants of a header do
+fn stub_name(name: &str) -> String {
+    let mut ans = String::with_capacity(name.len());
+    for unit in name.encode_utf16() {
+        match unit {
+            0x30..=0x39 | 0x41..=0x5a | 0x61..=0x7a | 0x5f => ans.push(unit as u8 as char),
+            _ => { let _ = write!(ans, "_0{:04x}", unit); }
+        }
+    }
+    ans
+}
+
+fn class_name(class: &JavaClass) -> io::Result<String> {
+    class.constant_pool.get_class_name(class.this_class).ok_or_else(|| malformed("Class Pool index did not point to Utf8"))
+}
+
+/// The native methods, in class file order. Overloaded native methods get the long form of the function name.
+fn natives(class: &JavaClass, name: &str) -> io::Result<Vec<Native>> {
+    let cp = &class.constant_pool;
+    let mut ans = vec![];
+    for m in class.methods.iter().filter(|m| m.is_native()) {
+        let method = cp.get_utf8(m.name_index).ok_or_else(|| malformed("Constant pool index did not point to Utf8"))?;
+        let descriptor = cp.get_utf8(m.descriptor_index).ok_or_else(|| malformed("Constant pool index did not point to Utf8"))?;
+        let parsed = MethodDescriptor::parse(&descriptor)?;
+        ans.push(Native { name: method, descriptor, parsed, flags: m.flags(), function: String::new() });
+    }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for m in &ans {
+        *counts.entry(&m.name).or_insert(0) += 1;
+    }
+    let overloaded: Vec<bool> = ans.iter().map(|m| counts[m.name.as_str()] > 1).collect();
+    for (m, overloaded) in ans.iter_mut().zip(overloaded) {
+        m.function = jni_function_name(name, &m.name, if overloaded { Some(&m.descriptor) } else { None });
+    }
+    Ok(ans)
+}
+
+/// The `static final` primitive fields with a constant value, as header names and C literals
+fn constants(class: &JavaClass) -> io::Result<Vec<(String, String)>> {
+    let cp = &class.constant_pool;
+    let mut ans = vec![];
+    for f in &class.fields {
+        let flags = FieldFlags::from_bits(f.access_flags);
+        if !flags.contains(FieldFlags::STATIC) || !flags.contains(FieldFlags::FINAL) {
+            continue;
+        }
+        let index = f.attributes.iter().filter_map(|a| match a {
+            Attribute::ConstantValue { constantvalue_index } => Some(*constantvalue_index),
+            _ => None
+        }).next();
+        let value = match index {
+            Some(index) => match resolve_constant(cp, index)? {
+                Constant::Integer(i) => format!("{}L", i),
+                Constant::Long(l) => format!("{}LL", l),
+                Constant::Float(f) if f.is_nan() => "NaNf".to_owned(),
+                Constant::Float(f) if f.is_infinite() => if f > 0.0 { "Inff" } else { "-Inff" }.to_owned(),
+                Constant::Float(f) => java_decimal(f) + "f",
+                Constant::Double(d) if d.is_nan() => "NaN".to_owned(),
+                Constant::Double(d) if d.is_infinite() => if d > 0.0 { "InfD" } else { "-InfD" }.to_owned(),
+                Constant::Double(d) => java_decimal(d),
+                _ => continue,
+            },
+            None => continue,
+        };
+        let name = cp.get_utf8(f.name_index).ok_or_else(|| malformed("Constant pool index did not point to Utf8"))?;
+        ans.push((stub_name(&name), value));
+    }
+    Ok(ans)
+}
+
+/// Formats a finite number the way Java's `Double.toString` and `Float.toString` do: with the fewest digits that
+/// read back as the same number, but at least two
+fn java_decimal<T: fmt::LowerExp>(x: T) -> String {
+    let mut scientific = format!("{:e}", x);
+    if !scientific.contains('.') {
+        scientific = format!("{:.1e}", x);
+    }
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(m) => ("-", m),
+        None => ("", mantissa),
+    };
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let digits = digits.trim_end_matches('0');
+    let fraction = |s: &str| if s.is_empty() { "0".to_owned() } else { s.to_owned() };
+    if digits.is_empty() {
+        format!("{}0.0", sign)
+    } else if (-3..7).contains(&exponent) {
+        if exponent < 0 {
+            format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits)
+        } else {
+            let point = exponent as usize + 1;
+            let padded = format!("{:0<width$}", digits, width = point);
+            format!("{}{}.{}", sign, &padded[..point], fraction(&padded[point..]))
+        }
+    } else {
+        format!("{}{}.{}E{}", sign, &digits[..1], fraction(&digits[1..]), exponent)
+    }
+}
+
+/// The JNI type of a value, as named in `jni.h`
+fn jni_type(t: &FieldType, hierarchy: Option<&Hierarchy>) -> &'static str {
+    match t {
+        FieldType::Boolean => "jboolean",
+        FieldType::Byte => "jbyte",
+        FieldType::Char => "jchar",
+        FieldType::Short => "jshort",
+        FieldType::Int => "jint",
+        FieldType::Long => "jlong",
+        FieldType::Float => "jfloat",
+        FieldType::Double => "jdouble",
+        FieldType::Object(name) => match name.as_str() {
+            "java/lang/String" => "jstring",
+            "java/lang/Class" => "jclass",
+            "java/lang/Throwable" => "jthrowable",
+            _ if hierarchy.is_some_and(|h| h.is_subtype_of(name, "java/lang/Throwable")) => "jthrowable",
+            _ => "jobject",
+        },
+        FieldType::Array(inner) => match **inner {
+            FieldType::Boolean => "jbooleanArray",
+            FieldType::Byte => "jbyteArray",
+            FieldType::Char => "jcharArray",
+            FieldType::Short => "jshortArray",
+            FieldType::Int => "jintArray",
+            FieldType::Long => "jlongArray",
+            FieldType::Float => "jfloatArray",
+            FieldType::Double => "jdoubleArray",
+            _ => "jobjectArray",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use attributes::InnerClassInfo;
+    use builders::cp::CPBuilder;
+
+    #[test]
+    fn mangle_escapes() {
+        assert_eq!(mangle("java/lang/String"), "java_lang_String");
+        assert_eq!(mangle("my_pkg/a_b"), "my_1pkg_a_1b");
+        assert_eq!(mangle("[Ljava/lang/Object;"), "_3Ljava_lang_Object_2");
+        //escapes are lowercase UTF-16 code units, so a supplementary character is a surrogate pair
+        assert_eq!(mangle("Ünï"), "_000dcn_000ef");
+        assert_eq!(mangle("\u{1f600}"), "_0d83d_0de00");
+        assert_eq!(mangle("Outer$Inner"), "Outer_00024Inner");
+    }
+
+    #[test]
+    fn function_names() {
+        assert_eq!(jni_function_name("p/Q", "f", None), "Java_p_Q_f");
+        assert_eq!(jni_function_name("p/Q", "f", Some("([Ljava/lang/String;I)V")), "Java_p_Q_f___3Ljava_lang_String_2I");
+        assert_eq!(jni_function_name("p/Q", "f", Some("()V")), "Java_p_Q_f__");
+        assert_eq!(jni_function_name("p/q/Ünï_x$In$ner", "f", None), "Java_p_q__000dcn_000ef_1x_00024In_00024ner_f");
+    }
+
+    /// a class called `name` whose `InnerClasses` has `(inner, outer, simple name)` entries
+    fn nested_class(name: &str, entries: &[(&str, &str, &str)]) -> JavaClass {
+        let mut cp = CPBuilder::new();
+        let this_class = cp.add_class(name.to_owned());
+        let classes = entries.iter().map(|(inner, outer, simple)| InnerClassInfo {
+            inner_class_info_index: cp.add_class(inner.to_string()),
+            outer_class_info_index: cp.add_class(outer.to_string()),
+            inner_name_index: cp.add_utf8(simple.to_string()),
+            inner_class_access_flags: 0,
+        }).collect();
+        let mut class = JavaClass::empty();
+        class.constant_pool = cp.build();
+        class.this_class = this_class;
+        class.attributes.push(Attribute::InnerClasses { classes });
+        class
+    }
+
+    #[test]
+    fn header_names() {
+        //what `javac -h` writes for these classes
+        let top = nested_class("p/q/Top$Level", &[]);
+        assert_eq!(header_name(&top, "p/q/Top$Level").unwrap(), "p_q_Top__Level");
+        let deep = "p/q/Ünï_x$In$ner$Deep";
+        let class = nested_class(deep, &[
+            ("p/q/Ünï_x$In$ner", "p/q/Ünï_x", "In$ner"),
+            (deep, "p/q/Ünï_x$In$ner", "Deep"),
+        ]);
+        assert_eq!(header_name(&class, deep).unwrap(), "p_q__000dcn_000ef_x_In__ner_Deep");
+        let cycle = nested_class("A$B", &[("A$B", "A$C", "B"), ("A$C", "A$B", "C")]);
+        assert!(header_name(&cycle, "A$B").is_err());
+    }
+}
@@ -0,0 +1,35 @@
+//! Small helpers for the hand-written JSON exports
+
+use std::fmt::Write;
+
+/// Quotes and escapes a string for use in JSON
+pub(crate) fn string(s: &str) -> String {
+    let mut ans = String::with_capacity(s.len() + 2);
+    ans.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ans.push_str("\\\""),
+            '\\' => ans.push_str("\\\\"),
+            '\n' => ans.push_str("\\n"),
+            '\r' => ans.push_str("\\r"),
+            '\t' => ans.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(ans, "\\u{:04x}", c as u32);
+            }
+            c => ans.push(c)
+        }
+    }
+    ans.push('"');
+    ans
+}
+
+/// Formats a list of already formatted JSON values
+pub(crate) fn array<I: IntoIterator<Item = String>>(items: I) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// Formats an object from keys and already formatted JSON values
+pub(crate) fn object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields.iter().map(|(k, v)| format!("{}:{}", string(k), v)).collect();
+    format!("{{{}}}", fields.join(","))
+}
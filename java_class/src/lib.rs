@@ -1,3 +1,11 @@
+extern crate zip;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use std::io;
 use std::str::Chars;
 
 #[cfg(test)]
@@ -5,6 +13,132 @@ mod tests {
     //TODO: tests?
 }
 
+/// The error for input that reads fine but breaks the class file format, e.g. a dangling constant pool index
+pub(crate) fn malformed(err: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Malformed class file: {}", err))
+}
+
+/// Defines a typed set of access flags, with an associated constant for each flag.
+/// Bits without a name are kept, so converting to and from `u16` is lossless.
+macro_rules! access_flags {
+    ($(#[$attr:meta])* pub struct $name:ident { $($flag:ident = $value:expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        #[cfg_attr(feature = "serde", serde(transparent))]
+        pub struct $name(u16);
+
+        impl $name {
+            $(pub const $flag: $name = $name($value);)*
+            const NAMES: &'static [($name, &'static str)] = &[$(($name::$flag, stringify!($flag))),*];
+
+            pub const fn from_bits(bits: u16) -> $name {
+                $name(bits)
+            }
+
+            pub const fn bits(self) -> u16 {
+                self.0
+            }
+
+            pub const fn empty() -> $name {
+                $name(0)
+            }
+
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Whether every flag in `other` is set
+            pub const fn contains(self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Whether any flag in `other` is set
+            pub const fn intersects(self, other: $name) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            pub fn insert(&mut self, other: $name) {
+                self.0 |= other.0;
+            }
+
+            pub fn remove(&mut self, other: $name) {
+                self.0 &= !other.0;
+            }
+
+            /// The flag with the given name, e.g. `PUBLIC`
+            pub fn from_name(name: &str) -> Option<$name> {
+                $name::NAMES.iter().find(|(_, n)| *n == name).map(|(f, _)| *f)
+            }
+
+            /// The names of the set flags in bit order, e.g. `["PUBLIC", "FINAL"]`
+            pub fn names(self) -> Vec<&'static str> {
+                $name::NAMES.iter().filter(|(f, _)| self.contains(*f)).map(|(_, n)| *n).collect()
+            }
+        }
+
+        impl ::std::ops::BitOr for $name {
+            type Output = $name;
+            fn bitor(self, other: $name) -> $name {
+                $name(self.0 | other.0)
+            }
+        }
+
+        impl ::std::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: $name) {
+                self.0 |= other.0;
+            }
+        }
+
+        impl ::std::ops::BitAnd for $name {
+            type Output = $name;
+            fn bitand(self, other: $name) -> $name {
+                $name(self.0 & other.0)
+            }
+        }
+
+        /// The flags in `self` that aren't in `other`
+        impl ::std::ops::Sub for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 & !other.0)
+            }
+        }
+
+        impl From<u16> for $name {
+            fn from(bits: u16) -> $name {
+                $name(bits)
+            }
+        }
+
+        impl From<$name> for u16 {
+            fn from(flags: $name) -> u16 {
+                flags.0
+            }
+        }
+
+        impl ::std::fmt::Debug for $name {
+            /// e.g. `AccessFlags(PUBLIC | FINAL | 0x8000)`
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                let mut parts: Vec<::std::string::String> = self.names().iter().map(|n| (*n).to_owned()).collect();
+                let unknown = $name::NAMES.iter().fold(self.0, |bits, (flag, _)| bits & !flag.0);
+                if unknown != 0 {
+                    parts.push(format!("{:#06x}", unknown));
+                }
+                write!(f, "{}({})", stringify!($name), parts.join(" | "))
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            /// The names of the set flags in lower case, e.g. `public final`
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                let names: Vec<::std::string::String> = self.names().iter().map(|n| n.to_lowercase()).collect();
+                write!(f, "{}", names.join(" "))
+            }
+        }
+    };
+}
+
 pub mod cp_info;
 pub mod cp;
 pub mod attributes;
@@ -14,6 +148,34 @@ pub mod methods;
 pub mod fields;
 pub mod class;
 pub mod builders;
+pub mod symbolic;
+pub mod writer;
+pub mod code;
+pub mod visitor;
+pub mod classpath;
+pub mod hierarchy;
+pub mod callgraph;
+pub mod dependencies;
+pub mod compat;
+pub mod diff;
+pub mod shrink;
+pub mod remap;
+pub mod check;
+pub mod annotations;
+pub mod descriptor;
+pub mod resolved;
+pub mod debug_info;
+pub mod compact;
+pub mod opcode_info;
+pub mod frames;
+pub mod instrument;
+pub mod jni_bindings;
+pub mod jni_headers;
+pub mod query;
+mod json;
+mod graph;
+#[cfg(test)]
+mod test_classes;
 
 #[derive(Clone, PartialEq, Eq, Default)]
 pub struct JVMClassName {
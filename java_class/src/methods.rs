@@ -5,6 +5,7 @@ use crate::cp::CPIndex;
 /// struct representing the MethodInfo struct
 /// for more information refer to the [JVM specification](https://docs.oracle.com/javase/specs/jvms/se8/html/index.html)
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MethodInfo {
     pub access_flags: u16,
     pub name_index: CPIndex,
@@ -13,26 +14,33 @@ pub struct MethodInfo {
 }
 
 impl MethodInfo {
+    pub fn flags(&self) -> AccessFlags {
+        AccessFlags::from_bits(self.access_flags)
+    }
+
     pub fn is_native(&self) -> bool {
-        self.access_flags & AccessFlags::Native as u16 != 0
+        self.flags().contains(AccessFlags::NATIVE)
     }
 
     pub fn is_abstract(&self) -> bool {
-        self.access_flags & AccessFlags::Abstract as u16 != 0
+        self.flags().contains(AccessFlags::ABSTRACT)
     }
 }
 
-pub enum AccessFlags {
-    Public = 0x0001,
-    Private = 0x0002,
-    Protected = 0x0004,
-    Static = 0x0008,
-    Final = 0x0010,
-    Synchronized = 0x0020,
-    Bridge = 0x0040,
-    Varargs = 0x0080,
-    Native = 0x0100,
-    Abstract = 0x0400,
-    Strict = 0x0800,
-    Synthetic = 0x1000,
+access_flags! {
+    /// The access flags of a method
+    pub struct AccessFlags {
+        PUBLIC = 0x0001,
+        PRIVATE = 0x0002,
+        PROTECTED = 0x0004,
+        STATIC = 0x0008,
+        FINAL = 0x0010,
+        SYNCHRONIZED = 0x0020,
+        BRIDGE = 0x0040,
+        VARARGS = 0x0080,
+        NATIVE = 0x0100,
+        ABSTRACT = 0x0400,
+        STRICT = 0x0800,
+        SYNTHETIC = 0x1000,
+    }
 }
\ No newline at end of file
@@ -0,0 +1,791 @@
+use cp::{CPIndex, ConstantPool};
+use cp_info::CPInfo;
+use descriptor::{FieldType, MethodDescriptor};
+use malformed;
+use opcodes::Opcode;
+use symbolic::{BranchKind, Constant, MemberRef, SymOpcode};
+use std::fmt;
+use std::convert::{TryFrom, TryInto};
+use std::io;
+use std::str::FromStr;
+
+/// What an instruction does to control flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flow {
+    /// continues with the next instruction
+    Next,
+    /// jumps to its target or continues with the next instruction
+    ConditionalBranch,
+    /// always jumps to its target
+    Goto,
+    /// jumps to one of the targets of a `tableswitch` or `lookupswitch`
+    Switch,
+    /// jumps to a subroutine (`jsr`, `jsr_w`)
+    Subroutine,
+    /// returns from a subroutine (`ret`)
+    SubroutineReturn,
+    /// returns from the method
+    Return,
+    /// throws the exception on top of the stack
+    Throw,
+}
+
+/// The meaning of an operand of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperandKind {
+    /// a local variable slot
+    Local,
+    /// a constant pool index
+    Constant,
+    /// a signed value pushed or added by the instruction
+    Immediate,
+    /// a signed offset from the start of the instruction
+    Branch,
+    /// the element type of `newarray`, e.g. 10 for `T_INT`
+    ArrayType,
+    /// the number of dimensions `multianewarray` creates
+    Dimensions,
+    /// the argument slot count of `invokeinterface`, including the receiver
+    Count,
+    /// the opcode modified by `wide`
+    Opcode,
+}
+
+/// The number of operand stack slots an instruction pops and then pushes.
+/// `long` and `double` values take two slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StackEffect {
+    pub pop: u32,
+    pub push: u32,
+}
+
+impl StackEffect {
+    /// The change in stack depth
+    pub fn delta(&self) -> i32 {
+        self.push as i32 - self.pop as i32
+    }
+}
+
+/// Static information about an opcode
+#[derive(Debug, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    pub opcode: u8,
+    /// the names (as in the fields of the `Opcode` variant) and kinds of the fixed operands, in order.
+    /// The tables of `tableswitch` and `lookupswitch` aren't included.
+    pub operands: &'static [(&'static str, OperandKind)],
+    /// the length in bytes including the operands,
+    /// or `None` for the switches, whose length depends on their position and table
+    pub length: Option<u8>,
+    /// the slots popped, or `None` if that depends on the operands (see `Opcode::stack_effect`)
+    pub pop: Option<u8>,
+    /// the slots pushed, or `None` if that depends on the operands
+    pub push: Option<u8>,
+    pub flow: Flow,
+    /// true if the instruction can complete abruptly with an exception,
+    /// including linkage errors from resolving its constant and `IllegalMonitorStateException` from returns
+    pub can_throw: bool,
+    /// the first class file major version that allows the instruction
+    pub min_major_version: u16,
+    /// the last class file major version that allows the instruction, for `jsr` and `ret`
+    pub max_major_version: Option<u16>,
+}
+
+impl OpcodeInfo {
+    /// Every opcode, ordered by numeric opcode except for `tableswitch`, `lookupswitch` and `wide_iinc`, which come last.
+    /// `wide` appears twice, as `Opcode::wide` and `Opcode::wide_iinc`, and `reserved` stands for 0xcb to 0xfd.
+    pub fn all() -> &'static [OpcodeInfo] {
+        OPCODES
+    }
+
+    pub fn from_byte(opcode: u8) -> Option<&'static OpcodeInfo> {
+        let opcode = if let 0xcb..=0xfd = opcode { 0xcb } else { opcode };
+        OPCODES.iter().find(|i| i.opcode == opcode)
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<&'static OpcodeInfo> {
+        OPCODES.iter().find(|i| i.mnemonic == mnemonic)
+    }
+
+    /// True for instructions that can transfer control somewhere other than the next instruction,
+    /// not counting returns and throws
+    pub fn is_branch(&self) -> bool {
+        matches!(self.flow, Flow::ConditionalBranch | Flow::Goto | Flow::Switch | Flow::Subroutine)
+    }
+
+    pub fn is_return(&self) -> bool {
+        self.flow == Flow::Return
+    }
+
+    /// True for `athrow`
+    pub fn is_throw(&self) -> bool {
+        self.flow == Flow::Throw
+    }
+
+    /// True if execution can continue with the next instruction
+    pub fn falls_through(&self) -> bool {
+        matches!(self.flow, Flow::Next | Flow::ConditionalBranch)
+    }
+
+    /// True if class files of major version `major_version` can contain the instruction
+    pub fn allowed_in(&self, major_version: u16) -> bool {
+        major_version >= self.min_major_version && self.max_major_version.is_none_or(|max| major_version <= max)
+    }
+}
+
+/// Builds the `OpcodeInfo` of one row of `opcodes!`
+macro_rules! opcode_info {
+    ($mnemonic:expr, $byte:expr, $length:expr, [$pop:tt, $push:tt], $flow:ident, $throws:expr, [$($field:ident: $kind:ident),*]) => {
+        OpcodeInfo {
+            mnemonic: $mnemonic,
+            opcode: $byte,
+            operands: &[$((stringify!($field), OperandKind::$kind)),*],
+            length: if $length == 0 { None } else { Some($length) },
+            pop: slots!($pop),
+            push: slots!($push),
+            flow: Flow::$flow,
+            can_throw: $throws,
+            //invokedynamic came with Java 7, which also dropped subroutines
+            min_major_version: if $byte == 0xba { 51 } else { 45 },
+            max_major_version: if $byte == 0xa8 || $byte == 0xa9 || $byte == 0xc9 { Some(50) } else { None },
+        }
+    };
+}
+
+macro_rules! slots {
+    (?) => { None };
+    ($n:expr) => { Some($n) };
+}
+
+/// Defines the opcode table, `Opcode::info`, `Opcode::operands` and the constructor used by `FromStr`.
+/// Each row is `variant { field: OperandKind, .. } => mnemonic, opcode, length, [pop, push], Flow, can_throw;`
+/// with `?` for stack effects that depend on the operands.
+/// The rows after `@special` can't be built from their operands alone, so `FromStr` handles them itself.
+macro_rules! opcodes {
+    ($($variant:ident $({ $($field:ident: $kind:ident),* })? => $mnemonic:expr, $byte:expr, $length:expr, [$pop:tt, $push:tt], $flow:ident, $throws:expr;)*
+     @special $($s_variant:ident { $($s_field:ident: $s_kind:ident),* } => $s_mnemonic:expr, $s_byte:expr, $s_length:expr, [$s_pop:tt, $s_push:tt], $s_flow:ident, $s_throws:expr;)*) => {
+        static OPCODES: &[OpcodeInfo] = &[
+            $(opcode_info!($mnemonic, $byte, $length, [$pop, $push], $flow, $throws, [$($($field: $kind),*)?]),)*
+            $(opcode_info!($s_mnemonic, $s_byte, $s_length, [$s_pop, $s_push], $s_flow, $s_throws, [$($s_field: $s_kind),*]),)*
+        ];
+
+        impl Opcode {
+            /// The static information about this instruction's opcode
+            pub fn info(&self) -> &'static OpcodeInfo {
+                use self::Opcode::*;
+                match self {
+                    $($variant $({ $($field: _,)* .. })? => {
+                        static INFO: OpcodeInfo = opcode_info!($mnemonic, $byte, $length, [$pop, $push], $flow, $throws, [$($($field: $kind),*)?]);
+                        &INFO
+                    })*
+                    $($s_variant { .. } => {
+                        static INFO: OpcodeInfo = opcode_info!($s_mnemonic, $s_byte, $s_length, [$s_pop, $s_push], $s_flow, $s_throws, [$($s_field: $s_kind),*]);
+                        &INFO
+                    })*
+                }
+            }
+
+            /// The values of the operands described by `info().operands`, in order.
+            /// Immediates are sign-extended the way the JVM does, so `bipush` of `0xff` gives -1.
+            pub fn operands(&self) -> Vec<i64> {
+                use self::Opcode::*;
+                match self {
+                    $($variant $({ $($field,)* .. })? => vec![$($($field.to_operand(OperandKind::$kind)),*)?],)*
+                    $($s_variant { $($s_field,)* .. } => vec![$($s_field.to_operand(OperandKind::$s_kind)),*],)*
+                }
+            }
+        }
+
+        /// Builds the instruction `mnemonic` from the values of its operands
+        fn from_operands(mnemonic: &str, values: &[i64]) -> Option<Opcode> {
+            use self::Opcode::*;
+            #[allow(unused_mut, unused_variables)]
+            let mut values = values.iter();
+            let ans = match mnemonic {
+                $($mnemonic => $variant $({ $($field: OperandValue::from_operand(*values.next()?, OperandKind::$kind)?),* })?,)*
+                _ => return None
+            };
+            if values.next().is_some() {
+                return None;
+            }
+            Some(ans)
+        }
+    };
+}
+
+opcodes! {
+    nop => "nop", 0x00, 1, [0, 0], Next, false;
+    aconst_null => "aconst_null", 0x01, 1, [0, 1], Next, false;
+    iconst_m1 => "iconst_m1", 0x02, 1, [0, 1], Next, false;
+    iconst_0 => "iconst_0", 0x03, 1, [0, 1], Next, false;
+    iconst_1 => "iconst_1", 0x04, 1, [0, 1], Next, false;
+    iconst_2 => "iconst_2", 0x05, 1, [0, 1], Next, false;
+    iconst_3 => "iconst_3", 0x06, 1, [0, 1], Next, false;
+    iconst_4 => "iconst_4", 0x07, 1, [0, 1], Next, false;
+    iconst_5 => "iconst_5", 0x08, 1, [0, 1], Next, false;
+    lconst_0 => "lconst_0", 0x09, 1, [0, 2], Next, false;
+    lconst_1 => "lconst_1", 0x0a, 1, [0, 2], Next, false;
+    fconst_0 => "fconst_0", 0x0b, 1, [0, 1], Next, false;
+    fconst_1 => "fconst_1", 0x0c, 1, [0, 1], Next, false;
+    fconst_2 => "fconst_2", 0x0d, 1, [0, 1], Next, false;
+    dconst_0 => "dconst_0", 0x0e, 1, [0, 2], Next, false;
+    dconst_1 => "dconst_1", 0x0f, 1, [0, 2], Next, false;
+    bipush { val: Immediate } => "bipush", 0x10, 2, [0, 1], Next, false;
+    sipush { val: Immediate } => "sipush", 0x11, 3, [0, 1], Next, false;
+    ldc { index: Constant } => "ldc", 0x12, 2, [0, 1], Next, true;
+    ldc_w { index: Constant } => "ldc_w", 0x13, 3, [0, 1], Next, true;
+    ldc2_w { index: Constant } => "ldc2_w", 0x14, 3, [0, 2], Next, true;
+    iload { index: Local } => "iload", 0x15, 2, [0, 1], Next, false;
+    lload { index: Local } => "lload", 0x16, 2, [0, 2], Next, false;
+    fload { index: Local } => "fload", 0x17, 2, [0, 1], Next, false;
+    dload { index: Local } => "dload", 0x18, 2, [0, 2], Next, false;
+    aload { index: Local } => "aload", 0x19, 2, [0, 1], Next, false;
+    iload_0 => "iload_0", 0x1a, 1, [0, 1], Next, false;
+    iload_1 => "iload_1", 0x1b, 1, [0, 1], Next, false;
+    iload_2 => "iload_2", 0x1c, 1, [0, 1], Next, false;
+    iload_3 => "iload_3", 0x1d, 1, [0, 1], Next, false;
+    lload_0 => "lload_0", 0x1e, 1, [0, 2], Next, false;
+    lload_1 => "lload_1", 0x1f, 1, [0, 2], Next, false;
+    lload_2 => "lload_2", 0x20, 1, [0, 2], Next, false;
+    lload_3 => "lload_3", 0x21, 1, [0, 2], Next, false;
+    fload_0 => "fload_0", 0x22, 1, [0, 1], Next, false;
+    fload_1 => "fload_1", 0x23, 1, [0, 1], Next, false;
+    fload_2 => "fload_2", 0x24, 1, [0, 1], Next, false;
+    fload_3 => "fload_3", 0x25, 1, [0, 1], Next, false;
+    dload_0 => "dload_0", 0x26, 1, [0, 2], Next, false;
+    dload_1 => "dload_1", 0x27, 1, [0, 2], Next, false;
+    dload_2 => "dload_2", 0x28, 1, [0, 2], Next, false;
+    dload_3 => "dload_3", 0x29, 1, [0, 2], Next, false;
+    aload_0 => "aload_0", 0x2a, 1, [0, 1], Next, false;
+    aload_1 => "aload_1", 0x2b, 1, [0, 1], Next, false;
+    aload_2 => "aload_2", 0x2c, 1, [0, 1], Next, false;
+    aload_3 => "aload_3", 0x2d, 1, [0, 1], Next, false;
+    iaload => "iaload", 0x2e, 1, [2, 1], Next, true;
+    laload => "laload", 0x2f, 1, [2, 2], Next, true;
+    faload => "faload", 0x30, 1, [2, 1], Next, true;
+    daload => "daload", 0x31, 1, [2, 2], Next, true;
+    aaload => "aaload", 0x32, 1, [2, 1], Next, true;
+    baload => "baload", 0x33, 1, [2, 1], Next, true;
+    caload => "caload", 0x34, 1, [2, 1], Next, true;
+    saload => "saload", 0x35, 1, [2, 1], Next, true;
+    istore { index: Local } => "istore", 0x36, 2, [1, 0], Next, false;
+    lstore { index: Local } => "lstore", 0x37, 2, [2, 0], Next, false;
+    fstore { index: Local } => "fstore", 0x38, 2, [1, 0], Next, false;
+    dstore { index: Local } => "dstore", 0x39, 2, [2, 0], Next, false;
+    astore { index: Local } => "astore", 0x3a, 2, [1, 0], Next, false;
+    istore_0 => "istore_0", 0x3b, 1, [1, 0], Next, false;
+    istore_1 => "istore_1", 0x3c, 1, [1, 0], Next, false;
+    istore_2 => "istore_2", 0x3d, 1, [1, 0], Next, false;
+    istore_3 => "istore_3", 0x3e, 1, [1, 0], Next, false;
+    lstore_0 => "lstore_0", 0x3f, 1, [2, 0], Next, false;
+    lstore_1 => "lstore_1", 0x40, 1, [2, 0], Next, false;
+    lstore_2 => "lstore_2", 0x41, 1, [2, 0], Next, false;
+    lstore_3 => "lstore_3", 0x42, 1, [2, 0], Next, false;
+    fstore_0 => "fstore_0", 0x43, 1, [1, 0], Next, false;
+    fstore_1 => "fstore_1", 0x44, 1, [1, 0], Next, false;
+    fstore_2 => "fstore_2", 0x45, 1, [1, 0], Next, false;
+    fstore_3 => "fstore_3", 0x46, 1, [1, 0], Next, false;
+    dstore_0 => "dstore_0", 0x47, 1, [2, 0], Next, false;
+    dstore_1 => "dstore_1", 0x48, 1, [2, 0], Next, false;
+    dstore_2 => "dstore_2", 0x49, 1, [2, 0], Next, false;
+    dstore_3 => "dstore_3", 0x4a, 1, [2, 0], Next, false;
+    astore_0 => "astore_0", 0x4b, 1, [1, 0], Next, false;
+    astore_1 => "astore_1", 0x4c, 1, [1, 0], Next, false;
+    astore_2 => "astore_2", 0x4d, 1, [1, 0], Next, false;
+    astore_3 => "astore_3", 0x4e, 1, [1, 0], Next, false;
+    iastore => "iastore", 0x4f, 1, [3, 0], Next, true;
+    lastore => "lastore", 0x50, 1, [4, 0], Next, true;
+    fastore => "fastore", 0x51, 1, [3, 0], Next, true;
+    dastore => "dastore", 0x52, 1, [4, 0], Next, true;
+    aastore => "aastore", 0x53, 1, [3, 0], Next, true;
+    bastore => "bastore", 0x54, 1, [3, 0], Next, true;
+    castore => "castore", 0x55, 1, [3, 0], Next, true;
+    sastore => "sastore", 0x56, 1, [3, 0], Next, true;
+    pop => "pop", 0x57, 1, [1, 0], Next, false;
+    pop2 => "pop2", 0x58, 1, [2, 0], Next, false;
+    dup => "dup", 0x59, 1, [1, 2], Next, false;
+    dup_x1 => "dup_x1", 0x5a, 1, [2, 3], Next, false;
+    dup_x2 => "dup_x2", 0x5b, 1, [3, 4], Next, false;
+    dup2 => "dup2", 0x5c, 1, [2, 4], Next, false;
+    dup2_x1 => "dup2_x1", 0x5d, 1, [3, 5], Next, false;
+    dup2_x2 => "dup2_x2", 0x5e, 1, [4, 6], Next, false;
+    swap => "swap", 0x5f, 1, [2, 2], Next, false;
+    iadd => "iadd", 0x60, 1, [2, 1], Next, false;
+    ladd => "ladd", 0x61, 1, [4, 2], Next, false;
+    fadd => "fadd", 0x62, 1, [2, 1], Next, false;
+    dadd => "dadd", 0x63, 1, [4, 2], Next, false;
+    isub => "isub", 0x64, 1, [2, 1], Next, false;
+    lsub => "lsub", 0x65, 1, [4, 2], Next, false;
+    fsub => "fsub", 0x66, 1, [2, 1], Next, false;
+    dsub => "dsub", 0x67, 1, [4, 2], Next, false;
+    imul => "imul", 0x68, 1, [2, 1], Next, false;
+    lmul => "lmul", 0x69, 1, [4, 2], Next, false;
+    fmul => "fmul", 0x6a, 1, [2, 1], Next, false;
+    dmul => "dmul", 0x6b, 1, [4, 2], Next, false;
+    idiv => "idiv", 0x6c, 1, [2, 1], Next, true;
+    ldiv => "ldiv", 0x6d, 1, [4, 2], Next, true;
+    fdiv => "fdiv", 0x6e, 1, [2, 1], Next, false;
+    ddiv => "ddiv", 0x6f, 1, [4, 2], Next, false;
+    irem => "irem", 0x70, 1, [2, 1], Next, true;
+    lrem => "lrem", 0x71, 1, [4, 2], Next, true;
+    frem => "frem", 0x72, 1, [2, 1], Next, false;
+    drem => "drem", 0x73, 1, [4, 2], Next, false;
+    ineg => "ineg", 0x74, 1, [1, 1], Next, false;
+    lneg => "lneg", 0x75, 1, [2, 2], Next, false;
+    fneg => "fneg", 0x76, 1, [1, 1], Next, false;
+    dneg => "dneg", 0x77, 1, [2, 2], Next, false;
+    ishl => "ishl", 0x78, 1, [2, 1], Next, false;
+    lshl => "lshl", 0x79, 1, [3, 2], Next, false;
+    ishr => "ishr", 0x7a, 1, [2, 1], Next, false;
+    lshr => "lshr", 0x7b, 1, [3, 2], Next, false;
+    iushr => "iushr", 0x7c, 1, [2, 1], Next, false;
+    lushr => "lushr", 0x7d, 1, [3, 2], Next, false;
+    iand => "iand", 0x7e, 1, [2, 1], Next, false;
+    land => "land", 0x7f, 1, [4, 2], Next, false;
+    ior => "ior", 0x80, 1, [2, 1], Next, false;
+    lor => "lor", 0x81, 1, [4, 2], Next, false;
+    ixor => "ixor", 0x82, 1, [2, 1], Next, false;
+    lxor => "lxor", 0x83, 1, [4, 2], Next, false;
+    iinc { index: Local, const_: Immediate } => "iinc", 0x84, 3, [0, 0], Next, false;
+    i2l => "i2l", 0x85, 1, [1, 2], Next, false;
+    i2f => "i2f", 0x86, 1, [1, 1], Next, false;
+    i2d => "i2d", 0x87, 1, [1, 2], Next, false;
+    l2i => "l2i", 0x88, 1, [2, 1], Next, false;
+    l2f => "l2f", 0x89, 1, [2, 1], Next, false;
+    l2d => "l2d", 0x8a, 1, [2, 2], Next, false;
+    f2i => "f2i", 0x8b, 1, [1, 1], Next, false;
+    f2l => "f2l", 0x8c, 1, [1, 2], Next, false;
+    f2d => "f2d", 0x8d, 1, [1, 2], Next, false;
+    d2i => "d2i", 0x8e, 1, [2, 1], Next, false;
+    d2l => "d2l", 0x8f, 1, [2, 2], Next, false;
+    d2f => "d2f", 0x90, 1, [2, 1], Next, false;
+    i2b => "i2b", 0x91, 1, [1, 1], Next, false;
+    i2c => "i2c", 0x92, 1, [1, 1], Next, false;
+    i2s => "i2s", 0x93, 1, [1, 1], Next, false;
+    lcmp => "lcmp", 0x94, 1, [4, 1], Next, false;
+    fcmpl => "fcmpl", 0x95, 1, [2, 1], Next, false;
+    fcmpg => "fcmpg", 0x96, 1, [2, 1], Next, false;
+    dcmpl => "dcmpl", 0x97, 1, [4, 1], Next, false;
+    dcmpg => "dcmpg", 0x98, 1, [4, 1], Next, false;
+    ifeq { branch: Branch } => "ifeq", 0x99, 3, [1, 0], ConditionalBranch, false;
+    ifne { branch: Branch } => "ifne", 0x9a, 3, [1, 0], ConditionalBranch, false;
+    iflt { branch: Branch } => "iflt", 0x9b, 3, [1, 0], ConditionalBranch, false;
+    ifge { branch: Branch } => "ifge", 0x9c, 3, [1, 0], ConditionalBranch, false;
+    ifgt { branch: Branch } => "ifgt", 0x9d, 3, [1, 0], ConditionalBranch, false;
+    ifle { branch: Branch } => "ifle", 0x9e, 3, [1, 0], ConditionalBranch, false;
+    if_icmpeq { branch: Branch } => "if_icmpeq", 0x9f, 3, [2, 0], ConditionalBranch, false;
+    if_icmpne { branch: Branch } => "if_icmpne", 0xa0, 3, [2, 0], ConditionalBranch, false;
+    if_icmplt { branch: Branch } => "if_icmplt", 0xa1, 3, [2, 0], ConditionalBranch, false;
+    if_icmpge { branch: Branch } => "if_icmpge", 0xa2, 3, [2, 0], ConditionalBranch, false;
+    if_icmpgt { branch: Branch } => "if_icmpgt", 0xa3, 3, [2, 0], ConditionalBranch, false;
+    if_icmple { branch: Branch } => "if_icmple", 0xa4, 3, [2, 0], ConditionalBranch, false;
+    if_acmpeq { branch: Branch } => "if_acmpeq", 0xa5, 3, [2, 0], ConditionalBranch, false;
+    if_acmpne { branch: Branch } => "if_acmpne", 0xa6, 3, [2, 0], ConditionalBranch, false;
+    goto { branch: Branch } => "goto", 0xa7, 3, [0, 0], Goto, false;
+    jsr { branch: Branch } => "jsr", 0xa8, 3, [0, 1], Subroutine, false;
+    ret { index: Local } => "ret", 0xa9, 2, [0, 0], SubroutineReturn, false;
+    ireturn => "ireturn", 0xac, 1, [1, 0], Return, true;
+    lreturn => "lreturn", 0xad, 1, [2, 0], Return, true;
+    freturn => "freturn", 0xae, 1, [1, 0], Return, true;
+    dreturn => "dreturn", 0xaf, 1, [2, 0], Return, true;
+    areturn => "areturn", 0xb0, 1, [1, 0], Return, true;
+    return_ => "return", 0xb1, 1, [0, 0], Return, true;
+    getstatic { index: Constant } => "getstatic", 0xb2, 3, [?, ?], Next, true;
+    putstatic { index: Constant } => "putstatic", 0xb3, 3, [?, ?], Next, true;
+    getfield { index: Constant } => "getfield", 0xb4, 3, [?, ?], Next, true;
+    putfield { index: Constant } => "putfield", 0xb5, 3, [?, ?], Next, true;
+    invokevirtual { index: Constant } => "invokevirtual", 0xb6, 3, [?, ?], Next, true;
+    invokespecial { index: Constant } => "invokespecial", 0xb7, 3, [?, ?], Next, true;
+    invokestatic { index: Constant } => "invokestatic", 0xb8, 3, [?, ?], Next, true;
+    invokeinterface { index: Constant, count: Count } => "invokeinterface", 0xb9, 5, [?, ?], Next, true;
+    invokedynamic { index: Constant } => "invokedynamic", 0xba, 5, [?, ?], Next, true;
+    new { index: Constant } => "new", 0xbb, 3, [0, 1], Next, true;
+    newarray { atype: ArrayType } => "newarray", 0xbc, 2, [1, 1], Next, true;
+    anewarray { index: Constant } => "anewarray", 0xbd, 3, [1, 1], Next, true;
+    arraylength => "arraylength", 0xbe, 1, [1, 1], Next, true;
+    athrow => "athrow", 0xbf, 1, [1, 0], Throw, true;
+    checkcast { index: Constant } => "checkcast", 0xc0, 3, [1, 1], Next, true;
+    instanceof { index: Constant } => "instanceof", 0xc1, 3, [1, 1], Next, true;
+    monitorenter => "monitorenter", 0xc2, 1, [1, 0], Next, true;
+    monitorexit => "monitorexit", 0xc3, 1, [1, 0], Next, true;
+    wide { opcode: Opcode, index: Local } => "wide", 0xc4, 4, [?, ?], Next, false;
+    multianewarray { index: Constant, dimensions: Dimensions } => "multianewarray", 0xc5, 4, [?, 1], Next, true;
+    ifnull { branch: Branch } => "ifnull", 0xc6, 3, [1, 0], ConditionalBranch, false;
+    ifnonnull { branch: Branch } => "ifnonnull", 0xc7, 3, [1, 0], ConditionalBranch, false;
+    goto_w { branch: Branch } => "goto_w", 0xc8, 5, [0, 0], Goto, false;
+    jsr_w { branch: Branch } => "jsr_w", 0xc9, 5, [0, 1], Subroutine, false;
+    breakpoint => "breakpoint", 0xca, 1, [0, 0], Next, false;
+    reserved => "reserved", 0xcb, 1, [0, 0], Next, false;
+    impdep1 => "impdep1", 0xfe, 1, [0, 0], Next, false;
+    impdep2 => "impdep2", 0xff, 1, [0, 0], Next, false;
+    @special
+    tableswitch { default: Branch, low: Immediate, high: Immediate } => "tableswitch", 0xaa, 0, [1, 0], Switch, false;
+    lookupswitch { default: Branch } => "lookupswitch", 0xab, 0, [1, 0], Switch, false;
+    wide_iinc { index: Local, const_: Immediate } => "wide", 0xc4, 6, [0, 0], Next, false;
+}
+
+/// Conversion between the fields of `Opcode` and operand values
+trait OperandValue: Sized {
+    fn to_operand(&self, kind: OperandKind) -> i64;
+    fn from_operand(value: i64, kind: OperandKind) -> Option<Self>;
+}
+
+impl OperandValue for u8 {
+    fn to_operand(&self, kind: OperandKind) -> i64 {
+        if kind == OperandKind::Immediate { *self as i8 as i64 } else { *self as i64 }
+    }
+
+    fn from_operand(value: i64, kind: OperandKind) -> Option<u8> {
+        if kind == OperandKind::Immediate { i8::from_operand(value, kind).map(|v| v as u8) } else { value.try_into().ok() }
+    }
+}
+
+impl OperandValue for u16 {
+    fn to_operand(&self, kind: OperandKind) -> i64 {
+        if kind == OperandKind::Immediate { *self as i16 as i64 } else { *self as i64 }
+    }
+
+    fn from_operand(value: i64, kind: OperandKind) -> Option<u16> {
+        if kind == OperandKind::Immediate { i16::from_operand(value, kind).map(|v| v as u16) } else { value.try_into().ok() }
+    }
+}
+
+macro_rules! signed_operand {
+    ($($t:ty),*) => {$(
+        impl OperandValue for $t {
+            fn to_operand(&self, _: OperandKind) -> i64 {
+                *self as i64
+            }
+
+            fn from_operand(value: i64, _: OperandKind) -> Option<$t> {
+                value.try_into().ok()
+            }
+        }
+    )*};
+}
+
+signed_operand!(i8, i16, i32);
+
+impl OperandValue for CPIndex {
+    fn to_operand(&self, _: OperandKind) -> i64 {
+        self.as_u16() as i64
+    }
+
+    fn from_operand(value: i64, _: OperandKind) -> Option<CPIndex> {
+        u16::try_from(value).ok().map(CPIndex::from)
+    }
+}
+
+/// The names `newarray` element types are written as, starting from `T_BOOLEAN` (4)
+const ARRAY_TYPES: [&str; 8] = ["boolean", "char", "float", "double", "byte", "short", "int", "long"];
+
+impl Opcode {
+    pub fn mnemonic(&self) -> &'static str {
+        self.info().mnemonic
+    }
+
+    /// Like `info().flow`, but looking through `wide` to a widened `ret`
+    pub fn flow(&self) -> Flow {
+        match self {
+            Opcode::wide { opcode: 0xa9, .. } => Flow::SubroutineReturn,
+            _ => self.info().flow
+        }
+    }
+
+    /// The slots this instruction pops and pushes.
+    /// Field and method instructions look up their descriptor in `cp`, the pool of the class the code belongs to.
+    pub fn stack_effect(&self, cp: &ConstantPool) -> io::Result<StackEffect> {
+        let info = self.info();
+        if let (Some(pop), Some(push)) = (info.pop, info.push) {
+            return Ok(StackEffect { pop: pop as u32, push: push as u32 });
+        }
+        let field = |index: CPIndex| -> io::Result<u32> {
+            let (_, _, descriptor) = cp.get_member_ref(index).ok_or_else(|| malformed("Field instruction did not point to Fieldref"))?;
+            Ok(FieldType::parse(&descriptor)?.slots())
+        };
+        let method = |index: CPIndex| -> io::Result<MethodDescriptor> {
+            let (_, _, descriptor) = cp.get_member_ref(index).ok_or_else(|| malformed("Invoke instruction did not point to a method reference"))?;
+            MethodDescriptor::parse(&descriptor)
+        };
+        let returned = |d: &MethodDescriptor| d.return_type.as_ref().map_or(0, FieldType::slots);
+        Ok(match *self {
+            Opcode::getstatic { index } => StackEffect { pop: 0, push: field(index)? },
+            Opcode::putstatic { index } => StackEffect { pop: field(index)?, push: 0 },
+            Opcode::getfield { index } => StackEffect { pop: 1, push: field(index)? },
+            Opcode::putfield { index } => StackEffect { pop: 1 + field(index)?, push: 0 },
+            Opcode::invokevirtual { index } | Opcode::invokespecial { index } | Opcode::invokeinterface { index, .. } => {
+                let d = method(index)?;
+                StackEffect { pop: 1 + d.arg_slots(), push: returned(&d) }
+            }
+            Opcode::invokestatic { index } => {
+                let d = method(index)?;
+                StackEffect { pop: d.arg_slots(), push: returned(&d) }
+            }
+            Opcode::invokedynamic { index } => {
+                let descriptor = match cp.get(index) {
+                    Some(CPInfo::InvokeDynamic { name_and_type_index, .. }) => cp.get_name_and_type(*name_and_type_index).map(|(_, d)| d),
+                    _ => None
+                };
+                let d = MethodDescriptor::parse(&descriptor.ok_or_else(|| malformed("invokedynamic did not point to InvokeDynamic"))?)?;
+                StackEffect { pop: d.arg_slots(), push: returned(&d) }
+            }
+            Opcode::multianewarray { dimensions, .. } => StackEffect { pop: dimensions as u32, push: 1 },
+            Opcode::wide { opcode, .. } => {
+                let inner = OpcodeInfo::from_byte(opcode).ok_or_else(|| malformed("wide did not modify a load, store or ret"))?;
+                match (inner.pop, inner.push) {
+                    (Some(pop), Some(push)) if inner.operands.first() == Some(&("index", OperandKind::Local)) => StackEffect { pop: pop as u32, push: push as u32 },
+                    _ => return Err(malformed("wide did not modify a load, store or ret"))
+                }
+            }
+            _ => unreachable!("every opcode with a variable stack effect is handled")
+        })
+    }
+}
+
+impl SymOpcode {
+    pub fn flow(&self) -> Flow {
+        match self {
+            SymOpcode::Plain(op) => op.flow(),
+            SymOpcode::Branch { kind: BranchKind::Goto, .. } => Flow::Goto,
+            SymOpcode::Branch { kind: BranchKind::Jsr, .. } => Flow::Subroutine,
+            SymOpcode::Branch { .. } => Flow::ConditionalBranch,
+            SymOpcode::TableSwitch { .. } | SymOpcode::LookupSwitch { .. } => Flow::Switch,
+            SymOpcode::Ldc(_) | SymOpcode::GetStatic(_) | SymOpcode::PutStatic(_) | SymOpcode::GetField(_) | SymOpcode::PutField(_) |
+            SymOpcode::InvokeVirtual(_) | SymOpcode::InvokeSpecial(_) | SymOpcode::InvokeStatic(_) | SymOpcode::InvokeInterface(_) |
+            SymOpcode::InvokeDynamic { .. } | SymOpcode::New(_) | SymOpcode::ANewArray(_) | SymOpcode::CheckCast(_) |
+            SymOpcode::InstanceOf(_) | SymOpcode::MultiANewArray { .. } => Flow::Next,
+        }
+    }
+
+    /// The slots this instruction pops and pushes, taken from the descriptors it carries
+    pub fn stack_effect(&self) -> io::Result<StackEffect> {
+        let field = |m: &MemberRef| -> io::Result<u32> { Ok(FieldType::parse(&m.descriptor)?.slots()) };
+        let returned = |d: &MethodDescriptor| d.return_type.as_ref().map_or(0, FieldType::slots);
+        let invoke = |descriptor: &str, receiver: u32| -> io::Result<StackEffect> {
+            let d = MethodDescriptor::parse(descriptor)?;
+            Ok(StackEffect { pop: receiver + d.arg_slots(), push: returned(&d) })
+        };
+        Ok(match self {
+            //every opcode with a constant pool operand is symbolic, so plain opcodes never need the pool
+            SymOpcode::Plain(op) => op.stack_effect(&ConstantPool::default())?,
+            SymOpcode::Ldc(Constant::Long(_)) | SymOpcode::Ldc(Constant::Double(_)) => StackEffect { pop: 0, push: 2 },
+            SymOpcode::Ldc(_) => StackEffect { pop: 0, push: 1 },
+            SymOpcode::GetStatic(m) => StackEffect { pop: 0, push: field(m)? },
+            SymOpcode::PutStatic(m) => StackEffect { pop: field(m)?, push: 0 },
+            SymOpcode::GetField(m) => StackEffect { pop: 1, push: field(m)? },
+            SymOpcode::PutField(m) => StackEffect { pop: 1 + field(m)?, push: 0 },
+            SymOpcode::InvokeVirtual(m) | SymOpcode::InvokeSpecial(m) | SymOpcode::InvokeInterface(m) => invoke(&m.descriptor, 1)?,
+            SymOpcode::InvokeStatic(m) => invoke(&m.descriptor, 0)?,
+            SymOpcode::InvokeDynamic { descriptor, .. } => invoke(descriptor, 0)?,
+            SymOpcode::New(_) => StackEffect { pop: 0, push: 1 },
+            SymOpcode::ANewArray(_) | SymOpcode::CheckCast(_) | SymOpcode::InstanceOf(_) => StackEffect { pop: 1, push: 1 },
+            SymOpcode::MultiANewArray { dimensions, .. } => StackEffect { pop: *dimensions as u32, push: 1 },
+            SymOpcode::Branch { kind, .. } => StackEffect {
+                pop: match kind {
+                    BranchKind::Goto | BranchKind::Jsr => 0,
+                    BranchKind::Ifeq | BranchKind::Ifne | BranchKind::Iflt | BranchKind::Ifge | BranchKind::Ifgt | BranchKind::Ifle |
+                    BranchKind::Ifnull | BranchKind::Ifnonnull => 1,
+                    _ => 2
+                },
+                push: if *kind == BranchKind::Jsr { 1 } else { 0 },
+            },
+            SymOpcode::TableSwitch { .. } | SymOpcode::LookupSwitch { .. } => StackEffect { pop: 1, push: 0 },
+        })
+    }
+}
+
+impl fmt::Display for Opcode {
+    /// Formats the instruction as its mnemonic followed by its operands, e.g. `iinc 1 -1`, `ldc #4`,
+    /// `newarray int`, `wide iload 300`, `tableswitch 36 0 2 [28, 30, 32]` or `lookupswitch 36 [1: 28, 5: 30]`.
+    /// Branch offsets are relative, as in the class file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Opcode::wide { opcode, index } => return match OpcodeInfo::from_byte(*opcode) {
+                Some(inner) => write!(f, "wide {} {}", inner.mnemonic, index),
+                None => write!(f, "wide {} {}", opcode, index),
+            },
+            Opcode::wide_iinc { index, const_ } => return write!(f, "wide iinc {} {}", index, const_),
+            _ => {}
+        }
+        let info = self.info();
+        write!(f, "{}", info.mnemonic)?;
+        for ((_, kind), value) in info.operands.iter().zip(self.operands()) {
+            match kind {
+                OperandKind::Constant => write!(f, " #{}", value)?,
+                OperandKind::ArrayType if (4..12).contains(&value) => write!(f, " {}", ARRAY_TYPES[value as usize - 4])?,
+                _ => write!(f, " {}", value)?,
+            }
+        }
+        match self {
+            Opcode::tableswitch { jump_offsets, .. } => {
+                let offsets: Vec<String> = jump_offsets.iter().map(i32::to_string).collect();
+                write!(f, " [{}]", offsets.join(", "))
+            }
+            Opcode::lookupswitch { match_offset_pairs, .. } => {
+                let pairs: Vec<String> = match_offset_pairs.iter().map(|(m, o)| format!("{}: {}", m, o)).collect();
+                write!(f, " [{}]", pairs.join(", "))
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+impl FromStr for Opcode {
+    type Err = io::Error;
+
+    /// Parses an instruction in the form written by `Display`.
+    /// Switches are given a padding of 0, since it depends on where they're placed.
+    fn from_str(s: &str) -> io::Result<Opcode> {
+        let (operands, table) = match s.find('[') {
+            Some(i) if s.trim_end().ends_with(']') => (&s[..i], Some(s[i + 1..].trim_end().trim_end_matches(']'))),
+            _ => (s, None),
+        };
+        let mut words = operands.split_whitespace();
+        let mnemonic = words.next().ok_or_else(|| invalid(s))?;
+        let words: Vec<&str> = words.collect();
+        let table: Vec<&str> = match table {
+            Some(t) if !t.trim().is_empty() => t.split(',').map(str::trim).collect(),
+            _ => vec![],
+        };
+        Ok(match (mnemonic, words.as_slice()) {
+            ("tableswitch", [default, low, high]) => Opcode::tableswitch {
+                default: operand(default, s)?,
+                low: operand(low, s)?,
+                high: operand(high, s)?,
+                jump_offsets: table.iter().map(|o| operand(o, s)).collect::<io::Result<_>>()?,
+                padding: 0,
+            },
+            ("lookupswitch", [default]) => Opcode::lookupswitch {
+                default: operand(default, s)?,
+                match_offset_pairs: table.iter().map(|p| {
+                    let (m, o) = p.split_once(':').ok_or_else(|| invalid(s))?;
+                    Ok((operand(m.trim(), s)?, operand(o.trim(), s)?))
+                }).collect::<io::Result<_>>()?,
+                padding: 0,
+            },
+            _ if !table.is_empty() => return Err(invalid(s)),
+            ("wide", ["iinc", index, const_]) => Opcode::wide_iinc { index: operand(index, s)?, const_: operand(const_, s)? },
+            _ => {
+                //the first operand of wide and newarray may be a mnemonic or a type name
+                let values = words.iter().enumerate().map(|(i, w)| match (mnemonic, i) {
+                    ("wide", 0) => OpcodeInfo::from_mnemonic(w).map_or_else(|| operand(w, s), |inner| Ok(inner.opcode as i64)),
+                    ("newarray", 0) => ARRAY_TYPES.iter().position(|t| t == w).map_or_else(|| operand(w, s), |t| Ok(t as i64 + 4)),
+                    _ => operand(w, s)
+                }).collect::<io::Result<Vec<i64>>>()?;
+                from_operands(mnemonic, &values).ok_or_else(|| invalid(s))?
+            }
+        })
+    }
+}
+
+/// Parses one operand of the instruction `s`; constant pool indices may start with `#`
+fn operand<T: TryFrom<i64>>(word: &str, s: &str) -> io::Result<T> {
+    let value: i64 = word.trim_start_matches('#').parse().map_err(|_| invalid(s))?;
+    T::try_from(value).map_err(|_| invalid(s))
+}
+
+fn invalid(s: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid instruction: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builders::cp::CPBuilder;
+    use bytecode_tools::{to_bytecode, to_opcode};
+    use class::JavaClassReader;
+    use symbolic::{from_symbolic, BootstrapMethod, MethodHandle};
+    use test_classes::{interface_method, member};
+
+    #[test]
+    fn every_opcode_has_an_entry() {
+        //0xcb to 0xfd share `reserved`, and `wide` has two entries
+        assert_eq!(OpcodeInfo::all().len(), 256 - 50 + 1);
+        for b in 0..=255u8 {
+            let info = OpcodeInfo::from_byte(b).unwrap_or_else(|| panic!("no entry for {:#04x}", b));
+            assert_eq!(info.opcode, if let 0xcb..=0xfd = b { 0xcb } else { b });
+            assert!(OpcodeInfo::all().iter().any(|i| ::std::ptr::eq(i, info)));
+
+            //decoding the byte gives an instruction with the same information
+            let mut bytes = match b {
+                0xc4 => vec![0xc4, 0x15],
+                _ => vec![b],
+            };
+            bytes.resize(32, 0);
+            let op = to_opcode(&mut JavaClassReader::new_from_bytes(bytes).unwrap(), 0).unwrap();
+            assert_eq!(op.info(), info, "{:#04x}", b);
+            if let Some(length) = info.length {
+                assert_eq!(1 + to_bytecode(op.clone()).1.len(), length as usize, "{}", info.mnemonic);
+            }
+            assert_eq!(op.operands().len(), info.operands.len(), "{}", info.mnemonic);
+        }
+        for info in OpcodeInfo::all() {
+            assert_eq!(OpcodeInfo::from_mnemonic(info.mnemonic).map(|i| i.opcode), Some(info.opcode));
+        }
+        let iinc = Opcode::wide_iinc { index: 300, const_: -1 };
+        assert_eq!(iinc.info().length, Some(6));
+        assert_eq!(iinc.info().pop, Some(0));
+    }
+
+    /// The stack effects of instructions with constant pool operands, which come from their descriptors
+    #[test]
+    fn stack_effects_follow_descriptors() {
+        let lambda = SymOpcode::InvokeDynamic {
+            bootstrap: BootstrapMethod {
+                handle: MethodHandle { reference_kind: 6, member: member("p/A", "bootstrap", "()Ljava/lang/invoke/CallSite;") },
+                arguments: vec![],
+            },
+            name: "run".to_owned(),
+            descriptor: "(JLjava/lang/Object;)Ljava/lang/Runnable;".to_owned(),
+        };
+        let cases = vec![
+            (SymOpcode::GetStatic(member("p/A", "j", "J")), 0, 2),
+            (SymOpcode::PutStatic(member("p/A", "j", "J")), 2, 0),
+            (SymOpcode::GetField(member("p/A", "s", "Ljava/lang/String;")), 1, 1),
+            (SymOpcode::PutField(member("p/A", "d", "D")), 3, 0),
+            (SymOpcode::InvokeVirtual(member("p/A", "m", "(IJLjava/lang/String;)D")), 5, 2),
+            (SymOpcode::InvokeSpecial(member("p/A", "<init>", "()V")), 1, 0),
+            (SymOpcode::InvokeStatic(member("p/A", "s", "([JD)V")), 3, 0),
+            (SymOpcode::InvokeInterface(interface_method("java/util/List", "get", "(I)Ljava/lang/Object;")), 2, 1),
+            (lambda, 3, 1),
+            (SymOpcode::Ldc(Constant::Long(1)), 0, 2),
+            (SymOpcode::Ldc(Constant::Double(1.0)), 0, 2),
+            (SymOpcode::Ldc(Constant::Integer(1)), 0, 1),
+            (SymOpcode::MultiANewArray { class: "[[[I".to_owned(), dimensions: 2 }, 2, 1),
+        ];
+        let mut cp = CPBuilder::new();
+        let mut bootstrap_methods = vec![];
+        let symbolic: Vec<SymOpcode> = cases.iter().map(|c| c.0.clone()).collect();
+        let code = from_symbolic(&symbolic, &mut cp, &mut bootstrap_methods).unwrap();
+        let cp = cp.build();
+        for ((sym, pop, push), op) in cases.iter().zip(&code) {
+            let expected = StackEffect { pop: *pop, push: *push };
+            assert_eq!(sym.stack_effect().unwrap(), expected, "{:?}", sym);
+            assert_eq!(op.stack_effect(&cp).unwrap(), expected, "{}", op);
+            if let Opcode::invokeinterface { count, .. } = op {
+                //the count operand holds the same slots, receiver included
+                assert_eq!(*count as u32, expected.pop);
+            }
+        }
+        assert_eq!(code[9].mnemonic(), "ldc2_w");
+        assert_eq!(code[10].mnemonic(), "ldc2_w");
+        assert_eq!(code[9].info().pop, Some(0));
+        assert_eq!(code[9].info().push, Some(2));
+    }
+
+    #[test]
+    fn wide_takes_the_stack_effect_of_its_opcode() {
+        let cp = ConstantPool::default();
+        let effect = |op: Opcode| op.stack_effect(&cp).map(|e| (e.pop, e.push));
+        assert_eq!(effect(Opcode::wide { opcode: 0x15, index: 300 }).unwrap(), (0, 1));
+        assert_eq!(effect(Opcode::wide { opcode: 0x16, index: 300 }).unwrap(), (0, 2));
+        assert_eq!(effect(Opcode::wide { opcode: 0x37, index: 300 }).unwrap(), (2, 0));
+        assert_eq!(effect(Opcode::wide { opcode: 0x3a, index: 300 }).unwrap(), (1, 0));
+        assert_eq!(effect(Opcode::wide { opcode: 0xa9, index: 300 }).unwrap(), (0, 0));
+        assert_eq!(effect(Opcode::wide_iinc { index: 300, const_: 5 }).unwrap(), (0, 0));
+        assert_eq!(Opcode::wide { opcode: 0xa9, index: 300 }.flow(), Flow::SubroutineReturn);
+        //only loads, stores and ret can be widened
+        assert!(effect(Opcode::wide { opcode: 0x60, index: 300 }).is_err());
+        assert!(effect(Opcode::wide { opcode: 0xcc, index: 300 }).is_err());
+    }
+}
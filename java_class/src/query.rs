@@ -0,0 +1,624 @@
+use attributes::Attribute;
+use bytecode_tools::instruction_offsets;
+use callgraph::MethodId;
+use class::{AccessFlags as ClassFlags, JavaClass};
+use classpath::ClassPath;
+use diff::instruction_text;
+use json;
+use malformed;
+use methods::AccessFlags as MethodFlags;
+use opcodes::Opcode;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::io;
+use std::str::FromStr;
+use symbolic::{to_symbolic, Constant, SymOpcode};
+
+/// HotSpot doesn't JIT compile methods with more bytes of code than this
+const HUGE_METHOD: u32 = 8000;
+
+/// A wildcard pattern over names, where `*` matches any run of characters (including `/`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NamePattern(String);
+
+impl NamePattern {
+    pub fn new(pattern: &str) -> NamePattern {
+        NamePattern(pattern.to_owned())
+    }
+
+    pub fn matches(&self, s: &str) -> bool {
+        let (p, s) = (self.0.as_bytes(), s.as_bytes());
+        let (mut i, mut j) = (0, 0);
+        //the position of the last `*` seen, and the position in `s` it has been tried up to
+        let mut star: Option<(usize, usize)> = None;
+        while j < s.len() {
+            if i < p.len() && p[i] == b'*' {
+                star = Some((i, j));
+                i += 1;
+            } else if i < p.len() && p[i] == s[j] {
+                i += 1;
+                j += 1;
+            } else if let Some((si, sj)) = star {
+                //let the `*` swallow one more character
+                i = si + 1;
+                j = sj + 1;
+                star = Some((si, sj + 1));
+            } else {
+                return false;
+            }
+        }
+        p[i..].iter().all(|c| *c == b'*')
+    }
+}
+
+impl Default for NamePattern {
+    /// Matches everything
+    fn default() -> NamePattern {
+        NamePattern::new("*")
+    }
+}
+
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// What the operand of an instruction has to be
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OperandPattern {
+    #[default]
+    Any,
+    /// a field or method reference. For `invokedynamic` the owner is the bootstrap method's class,
+    /// and the name and descriptor are the call site's.
+    Member { owner: NamePattern, name: NamePattern, descriptor: NamePattern },
+    /// the class operand of `new`, `anewarray`, `checkcast`, `instanceof`, `multianewarray` and `ldc`,
+    /// or the owner of a member
+    Class(NamePattern),
+    /// a string loaded by `ldc`
+    String(NamePattern),
+}
+
+/// Matches instructions by mnemonic and operand, e.g. `invokestatic java/lang/System.exit(I)V`.
+/// As text, the mnemonic comes first and may use `*`, as in `invoke*`. It can be followed by
+/// `owner.name(descriptor)`, `owner.name` or `name(descriptor)` for a member, a class name,
+/// or a string in double quotes.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InstructionPattern {
+    pub mnemonic: NamePattern,
+    pub operand: OperandPattern,
+}
+
+impl InstructionPattern {
+    /// `op` is the instruction as it is in the code, and `sym` is its symbolic form
+    pub fn matches(&self, op: &Opcode, sym: &SymOpcode) -> bool {
+        if !self.mnemonic.matches(op.mnemonic()) {
+            return false;
+        }
+        let member = match sym {
+            SymOpcode::GetStatic(m) | SymOpcode::PutStatic(m) | SymOpcode::GetField(m) | SymOpcode::PutField(m) |
+            SymOpcode::InvokeVirtual(m) | SymOpcode::InvokeSpecial(m) | SymOpcode::InvokeStatic(m) |
+            SymOpcode::InvokeInterface(m) => Some((m.owner.as_str(), m.name.as_str(), m.descriptor.as_str())),
+            SymOpcode::InvokeDynamic { bootstrap, name, descriptor } => Some((bootstrap.handle.member.owner.as_str(), name.as_str(), descriptor.as_str())),
+            _ => None
+        };
+        match &self.operand {
+            OperandPattern::Any => true,
+            OperandPattern::Member { owner, name, descriptor } => match member {
+                Some((o, n, d)) => owner.matches(o) && name.matches(n) && descriptor.matches(d),
+                None => false,
+            },
+            OperandPattern::Class(class) => match sym {
+                SymOpcode::New(c) | SymOpcode::ANewArray(c) | SymOpcode::CheckCast(c) | SymOpcode::InstanceOf(c) |
+                SymOpcode::MultiANewArray { class: c, .. } | SymOpcode::Ldc(Constant::Class(c)) => class.matches(c),
+                _ => member.is_some_and(|(o, _, _)| class.matches(o)),
+            },
+            OperandPattern::String(s) => match sym {
+                SymOpcode::Ldc(Constant::String(v)) => s.matches(v),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl FromStr for InstructionPattern {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<InstructionPattern> {
+        let s = s.trim();
+        let (mnemonic, rest) = match s.find(char::is_whitespace) {
+            Some(i) => (&s[..i], s[i..].trim()),
+            None => (s, ""),
+        };
+        if mnemonic.is_empty() {
+            return Err(invalid("Expected an instruction pattern"));
+        }
+        let operand = if rest.is_empty() {
+            OperandPattern::Any
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.strip_suffix('"') {
+                Some(value) => OperandPattern::String(NamePattern::new(value)),
+                None => return Err(invalid(&format!("Unterminated string in {}", s))),
+            }
+        } else if rest.contains(char::is_whitespace) {
+            return Err(invalid(&format!("Expected one operand in {}", s)));
+        } else {
+            let (member, descriptor) = match rest.find('(') {
+                Some(i) => (&rest[..i], Some(&rest[i..])),
+                None => (rest, None),
+            };
+            match (member.rsplit_once('.'), descriptor) {
+                (Some((owner, name)), _) => OperandPattern::Member {
+                    owner: NamePattern::new(owner),
+                    name: NamePattern::new(name),
+                    descriptor: descriptor.map(NamePattern::new).unwrap_or_default(),
+                },
+                (None, Some(descriptor)) => OperandPattern::Member {
+                    owner: NamePattern::default(),
+                    name: NamePattern::new(member),
+                    descriptor: NamePattern::new(descriptor),
+                },
+                (None, None) => OperandPattern::Class(NamePattern::new(member)),
+            }
+        };
+        Ok(InstructionPattern { mnemonic: NamePattern::new(mnemonic), operand })
+    }
+}
+
+/// The classes a query looks at
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClassFilter {
+    pub name: NamePattern,
+    /// the lowest major version
+    pub min_version: Option<u16>,
+    /// the highest major version
+    pub max_version: Option<u16>,
+    /// flags the class must have
+    pub flags: ClassFlags,
+}
+
+impl ClassFilter {
+    pub fn matches(&self, name: &str, class: &JavaClass) -> bool {
+        self.name.matches(name)
+            && self.min_version.is_none_or(|v| class.major_version >= v)
+            && self.max_version.is_none_or(|v| class.major_version <= v)
+            && class.flags().contains(self.flags)
+    }
+}
+
+/// The methods a query looks at
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MethodFilter {
+    pub name: NamePattern,
+    pub descriptor: NamePattern,
+    /// flags the method must have
+    pub flags: MethodFlags,
+    /// the fewest bytes of code the method can have
+    pub min_code_size: Option<u32>,
+}
+
+/// Finds classes, methods or instructions.
+/// With no method filter and no instruction patterns, the matching classes are reported;
+/// with a method filter but no instruction patterns, the matching methods;
+/// and otherwise every instruction of a matching method that matches one of the patterns.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Query {
+    pub class: ClassFilter,
+    pub method: Option<MethodFilter>,
+    pub instructions: Vec<InstructionPattern>,
+}
+
+/// Something a query found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryMatch {
+    pub class: String,
+    /// `None` when the class itself matched
+    pub method: Option<MethodId>,
+    /// the bytes of code in the method, if it has code
+    pub code_size: Option<u32>,
+    /// the instruction that matched, if any
+    pub instruction: Option<InstructionMatch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionMatch {
+    /// the instruction's index in the method's code
+    pub index: usize,
+    /// the instruction's bytecode offset
+    pub offset: u32,
+    /// the instruction, with its constant pool operands resolved
+    pub text: String,
+}
+
+impl fmt::Display for QueryMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.method, &self.instruction) {
+            (Some(m), Some(i)) => write!(f, "{} @{}: {}", m, i.offset, i.text),
+            (Some(m), None) => match self.code_size {
+                Some(size) => write!(f, "{} ({} bytes of code)", m, size),
+                None => write!(f, "{} (no code)", m),
+            },
+            (None, _) => write!(f, "{}", self.class),
+        }
+    }
+}
+
+impl Query {
+    /// Runs the query over every class on a classpath, in name order
+    pub fn run(&self, cp: &ClassPath) -> io::Result<Vec<QueryMatch>> {
+        let mut ans = vec![];
+        for (_, class) in cp.classes()? {
+            ans.extend(self.run_class(&class?)?);
+        }
+        Ok(ans)
+    }
+
+    /// Runs the query over one class
+    pub fn run_class(&self, class: &JavaClass) -> io::Result<Vec<QueryMatch>> {
+        let cp = &class.constant_pool;
+        let name = cp.get_class_name(class.this_class).ok_or_else(|| malformed("Class Pool index did not point to Utf8"))?;
+        if !self.class.matches(&name, class) {
+            return Ok(vec![]);
+        }
+        let method_filter = match &self.method {
+            Some(f) => f.clone(),
+            None if self.instructions.is_empty() => {
+                return Ok(vec![QueryMatch { class: name, method: None, code_size: None, instruction: None }]);
+            }
+            None => MethodFilter::default(),
+        };
+        let mut ans = vec![];
+        for m in &class.methods {
+            let method_name = cp.get_utf8(m.name_index).ok_or_else(|| malformed("Method name did not point to Utf8"))?;
+            let descriptor = cp.get_utf8(m.descriptor_index).ok_or_else(|| malformed("Method descriptor did not point to Utf8"))?;
+            let code = m.attributes.iter().filter_map(|a| match a {
+                Attribute::Code { code, .. } => Some(code),
+                _ => None
+            }).next();
+            let offsets = code.map(|c| instruction_offsets(c));
+            let code_size = offsets.as_ref().map(|o| o[o.len() - 1]);
+            if !method_filter.name.matches(&method_name) || !method_filter.descriptor.matches(&descriptor)
+                || !m.flags().contains(method_filter.flags) || method_filter.min_code_size.is_some_and(|min| code_size.unwrap_or(0) < min) {
+                continue;
+            }
+            let id = MethodId::new(&name, &method_name, &descriptor);
+            if self.instructions.is_empty() {
+                ans.push(QueryMatch { class: name.clone(), method: Some(id), code_size, instruction: None });
+                continue;
+            }
+            let (code, offsets) = match (code, offsets) {
+                (Some(code), Some(offsets)) => (code, offsets),
+                _ => continue,
+            };
+            for (index, (op, sym)) in code.iter().zip(to_symbolic(code, cp, class.bootstrap_methods())?).enumerate() {
+                if self.instructions.iter().any(|p| p.matches(op, &sym)) {
+                    let instruction = InstructionMatch { index, offset: offsets[index], text: instruction_text(&sym) };
+                    ans.push(QueryMatch { class: name.clone(), method: Some(id.clone()), code_size, instruction: Some(instruction) });
+                }
+            }
+        }
+        Ok(ans)
+    }
+}
+
+/// Formats matches as a JSON array of objects with `class`, and `method`, `code_size`,
+/// `index`, `offset` and `instruction` where they apply
+pub fn matches_to_json(matches: &[QueryMatch]) -> String {
+    json::array(matches.iter().map(|m| {
+        let mut fields = vec![("class", json::string(&m.class))];
+        if let Some(method) = &m.method {
+            fields.push(("method", json::string(&format!("{}{}", method.name, method.descriptor))));
+        }
+        if let Some(size) = m.code_size {
+            fields.push(("code_size", size.to_string()));
+        }
+        if let Some(i) = &m.instruction {
+            fields.push(("index", i.index.to_string()));
+            fields.push(("offset", i.offset.to_string()));
+            fields.push(("instruction", json::string(&i.text)));
+        }
+        json::object(&fields)
+    }))
+}
+
+/// Counts over a set of classes
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    pub classes: usize,
+    pub methods: usize,
+    /// the total bytes of code
+    pub code_size: u64,
+    /// how many classes have each class file version, as (major, minor)
+    pub versions: BTreeMap<(u16, u16), usize>,
+    /// how many times each instruction appears, by mnemonic
+    pub opcodes: BTreeMap<&'static str, u64>,
+    /// the methods with the most code and their sizes, largest first
+    pub largest_methods: Vec<(MethodId, u32)>,
+    /// how many methods have more code than HotSpot will compile
+    pub huge_methods: usize,
+    /// how many of the largest methods to keep
+    outliers: usize,
+}
+
+impl Statistics {
+    /// Starts counting, keeping the `outliers` largest methods
+    pub fn new(outliers: usize) -> Statistics {
+        Statistics { outliers, ..Statistics::default() }
+    }
+
+    /// Counts every class on a classpath
+    pub fn from_classpath(cp: &ClassPath, outliers: usize) -> io::Result<Statistics> {
+        let mut ans = Statistics::new(outliers);
+        for (_, class) in cp.classes()? {
+            ans.add_class(&class?)?;
+        }
+        Ok(ans)
+    }
+
+    pub fn add_class(&mut self, class: &JavaClass) -> io::Result<()> {
+        let cp = &class.constant_pool;
+        let name = cp.get_class_name(class.this_class).ok_or_else(|| malformed("Class Pool index did not point to Utf8"))?;
+        self.classes += 1;
+        *self.versions.entry((class.major_version, class.minor_version)).or_insert(0) += 1;
+        for m in &class.methods {
+            self.methods += 1;
+            let code = match m.attributes.iter().find_map(|a| match a {
+                Attribute::Code { code, .. } => Some(code),
+                _ => None
+            }) {
+                Some(code) => code,
+                None => continue,
+            };
+            for op in code {
+                *self.opcodes.entry(op.mnemonic()).or_insert(0) += 1;
+            }
+            let size = instruction_offsets(code)[code.len()];
+            self.code_size += size as u64;
+            if size > HUGE_METHOD {
+                self.huge_methods += 1;
+            }
+            if self.largest_methods.len() < self.outliers || self.largest_methods.last().is_some_and(|l| l.1 < size) {
+                let method_name = cp.get_utf8(m.name_index).ok_or_else(|| malformed("Method name did not point to Utf8"))?;
+                let descriptor = cp.get_utf8(m.descriptor_index).ok_or_else(|| malformed("Method descriptor did not point to Utf8"))?;
+                let at = self.largest_methods.iter().position(|l| l.1 < size).unwrap_or(self.largest_methods.len());
+                self.largest_methods.insert(at, (MethodId::new(&name, &method_name, &descriptor), size));
+                self.largest_methods.truncate(self.outliers);
+            }
+        }
+        Ok(())
+    }
+
+    /// The instructions by how often they appear, most common first
+    pub fn opcodes_by_count(&self) -> Vec<(&'static str, u64)> {
+        let mut ans: Vec<(&'static str, u64)> = self.opcodes.iter().map(|(m, n)| (*m, *n)).collect();
+        ans.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        ans
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut ans = String::new();
+        let _ = writeln!(ans, "classes: {}", self.classes);
+        let _ = writeln!(ans, "methods: {}", self.methods);
+        let _ = writeln!(ans, "bytes of code: {}", self.code_size);
+        let _ = writeln!(ans, "methods over {} bytes: {}", HUGE_METHOD, self.huge_methods);
+        let _ = writeln!(ans, "class versions:");
+        for ((major, minor), n) in &self.versions {
+            let _ = writeln!(ans, "    {}.{} (Java {}): {}", major, minor, java_release(*major), n);
+        }
+        let _ = writeln!(ans, "instructions:");
+        for (mnemonic, n) in self.opcodes_by_count() {
+            let _ = writeln!(ans, "    {}: {}", mnemonic, n);
+        }
+        let _ = writeln!(ans, "largest methods:");
+        for (m, size) in &self.largest_methods {
+            let _ = writeln!(ans, "    {} {}", size, m);
+        }
+        ans
+    }
+
+    /// Formats the statistics as a JSON object
+    pub fn to_json(&self) -> String {
+        let versions = self.versions.iter().map(|((major, minor), n)| json::object(&[
+            ("major", major.to_string()),
+            ("minor", minor.to_string()),
+            ("classes", n.to_string()),
+        ]));
+        let opcodes = self.opcodes_by_count().into_iter().map(|(m, n)| json::object(&[
+            ("mnemonic", json::string(m)),
+            ("count", n.to_string()),
+        ]));
+        let largest = self.largest_methods.iter().map(|(m, size)| json::object(&[
+            ("class", json::string(&m.owner)),
+            ("method", json::string(&format!("{}{}", m.name, m.descriptor))),
+            ("code_size", size.to_string()),
+        ]));
+        json::object(&[
+            ("classes", self.classes.to_string()),
+            ("methods", self.methods.to_string()),
+            ("code_size", self.code_size.to_string()),
+            ("huge_methods", self.huge_methods.to_string()),
+            ("versions", json::array(versions)),
+            ("opcodes", json::array(opcodes)),
+            ("largest_methods", json::array(largest)),
+        ])
+    }
+}
+
+/// The Java release a class file major version belongs to, e.g. `8` for 52
+fn java_release(major: u16) -> String {
+    match major {
+        0..=45 => "1.1".to_owned(),
+        46..=48 => format!("1.{}", major - 44),
+        _ => (major - 44).to_string(),
+    }
+}
+
+fn invalid(err: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid query: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opcodes::Opcode::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use test_classes::{member, ClassSpec};
+
+    /// Writes `p/Main`, `p/Util` (version 55) and the interface `q/Other` to a temporary directory
+    fn write_classes(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!("java_class_query_{}_{}", name, ::std::process::id()));
+        let main = ClassSpec::new(0x0021, "p/Main", Some("java/lang/Object"))
+            .method(0x0009, "main", "([Ljava/lang/String;)V", vec![
+                SymOpcode::Plain(iconst_0),
+                SymOpcode::InvokeStatic(member("java/lang/System", "exit", "(I)V")),
+                SymOpcode::Ldc(Constant::String("hello".to_owned())),
+                SymOpcode::Plain(pop),
+                SymOpcode::GetStatic(member("java/lang/System", "out", "Ljava/io/PrintStream;")),
+                SymOpcode::Plain(pop),
+                SymOpcode::New("p/Util".to_owned()),
+                SymOpcode::Plain(dup),
+                SymOpcode::InvokeSpecial(member("p/Util", "<init>", "()V")),
+                SymOpcode::Plain(pop),
+                SymOpcode::Plain(return_),
+            ])
+            .build();
+        let mut util = ClassSpec::new(0x0031, "p/Util", Some("java/lang/Object"))
+            .method(0x0001, "<init>", "()V", vec![
+                SymOpcode::Plain(aload_0),
+                SymOpcode::InvokeSpecial(member("java/lang/Object", "<init>", "()V")),
+                SymOpcode::Plain(return_),
+            ])
+            .method(0x0009, "helper", "()V", vec![SymOpcode::Plain(return_)])
+            .build();
+        util.major_version = 55;
+        let other = ClassSpec::new(0x0601, "q/Other", Some("java/lang/Object")).method(0x0401, "run", "()V", vec![]).build();
+        for (name, class) in &[("p/Main", main), ("p/Util", util), ("q/Other", other)] {
+            let path = dir.join(format!("{}.class", name));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, class.to_bytes()).unwrap();
+        }
+        dir
+    }
+
+    fn run(query: &Query, dir: &PathBuf) -> Vec<String> {
+        query.run(&ClassPath::from_paths([dir]).unwrap()).unwrap().iter().map(QueryMatch::to_string).collect()
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<InstructionPattern> {
+        patterns.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn name_patterns() {
+        for &(pattern, s, matches) in &[
+            ("*", "", true), ("*", "java/lang/Object", true), ("java/*", "java/lang/Object", true), ("java/*", "javax/X", false),
+            ("*Exception", "java/io/IOException", true), ("a*b*c", "abxbc", true), ("a*b*c", "acb", false), ("get", "getX", false),
+        ] {
+            assert_eq!(NamePattern::new(pattern).matches(s), matches, "{} {}", pattern, s);
+        }
+    }
+
+    #[test]
+    fn parses_instruction_patterns() {
+        let member = |owner: &str, name: &str, descriptor: &str| OperandPattern::Member {
+            owner: NamePattern::new(owner), name: NamePattern::new(name), descriptor: NamePattern::new(descriptor),
+        };
+        for (text, mnemonic, operand) in [
+            ("invoke*", "invoke*", OperandPattern::Any),
+            ("invokestatic java/lang/System.exit(I)V", "invokestatic", member("java/lang/System", "exit", "(I)V")),
+            ("getstatic java/lang/System.out", "getstatic", member("java/lang/System", "out", "*")),
+            ("invokevirtual toString()*", "invokevirtual", member("*", "toString", "()*")),
+            ("new java/io/*", "new", OperandPattern::Class(NamePattern::new("java/io/*"))),
+            ("  ldc \"a b\"  ", "ldc", OperandPattern::String(NamePattern::new("a b"))),
+        ] {
+            assert_eq!(text.parse::<InstructionPattern>().unwrap(), InstructionPattern { mnemonic: NamePattern::new(mnemonic), operand }, "{}", text);
+        }
+        for text in &["", "ldc \"open", "invokestatic a.b c.d"] {
+            assert!(text.parse::<InstructionPattern>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn finds_classes() {
+        let dir = write_classes("classes");
+        let query = |class: ClassFilter| run(&Query { class, ..Query::default() }, &dir);
+        assert_eq!(query(ClassFilter::default()), vec!["p/Main", "p/Util", "q/Other"]);
+        assert_eq!(query(ClassFilter { name: NamePattern::new("p/*"), ..ClassFilter::default() }), vec!["p/Main", "p/Util"]);
+        assert_eq!(query(ClassFilter { min_version: Some(53), ..ClassFilter::default() }), vec!["p/Util"]);
+        assert_eq!(query(ClassFilter { max_version: Some(52), ..ClassFilter::default() }), vec!["p/Main", "q/Other"]);
+        assert_eq!(query(ClassFilter { flags: ClassFlags::INTERFACE, ..ClassFilter::default() }), vec!["q/Other"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_methods() {
+        let dir = write_classes("methods");
+        let query = |method: MethodFilter| run(&Query { method: Some(method), ..Query::default() }, &dir);
+        assert_eq!(query(MethodFilter::default()), vec![
+            "p/Main.main([Ljava/lang/String;)V (20 bytes of code)",
+            "p/Util.<init>()V (5 bytes of code)",
+            "p/Util.helper()V (1 bytes of code)",
+            "q/Other.run()V (no code)",
+        ]);
+        assert_eq!(query(MethodFilter { flags: MethodFlags::STATIC, ..MethodFilter::default() }), vec![
+            "p/Main.main([Ljava/lang/String;)V (20 bytes of code)",
+            "p/Util.helper()V (1 bytes of code)",
+        ]);
+        assert_eq!(query(MethodFilter { min_code_size: Some(5), ..MethodFilter::default() }), vec![
+            "p/Main.main([Ljava/lang/String;)V (20 bytes of code)",
+            "p/Util.<init>()V (5 bytes of code)",
+        ]);
+        assert_eq!(query(MethodFilter { descriptor: NamePattern::new("()*"), name: NamePattern::new("r*"), ..MethodFilter::default() }),
+                   vec!["q/Other.run()V (no code)"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_instructions() {
+        let dir = write_classes("instructions");
+        let query = |p: &[&str]| run(&Query { instructions: patterns(p), ..Query::default() }, &dir);
+        assert_eq!(query(&["invokestatic java/lang/System.exit(I)V", "ldc \"hel*\""]), vec![
+            "p/Main.main([Ljava/lang/String;)V @1: invokestatic java/lang/System.exit:(I)V",
+            "p/Main.main([Ljava/lang/String;)V @4: ldc \"hello\"",
+        ]);
+        //a class pattern matches the owner of members as well as class operands
+        assert_eq!(query(&["* p/Util"]), vec![
+            "p/Main.main([Ljava/lang/String;)V @11: new p/Util",
+            "p/Main.main([Ljava/lang/String;)V @15: invokespecial p/Util.<init>:()V",
+        ]);
+        assert_eq!(query(&["invokespecial"]), vec![
+            "p/Main.main([Ljava/lang/String;)V @15: invokespecial p/Util.<init>:()V",
+            "p/Util.<init>()V @1: invokespecial java/lang/Object.<init>:()V",
+        ]);
+        assert_eq!(query(&["getstatic *.out"]), vec!["p/Main.main([Ljava/lang/String;)V @7: getstatic java/lang/System.out:Ljava/io/PrintStream;"]);
+
+        //the method filter narrows down where instructions are looked for
+        let matches = Query {
+            class: ClassFilter { name: NamePattern::new("p/Util"), ..ClassFilter::default() },
+            method: Some(MethodFilter { name: NamePattern::new("<init>"), ..MethodFilter::default() }),
+            instructions: patterns(&["*return"]),
+        }.run(&ClassPath::from_paths([&dir]).unwrap()).unwrap();
+        assert_eq!(matches, vec![QueryMatch {
+            class: "p/Util".to_owned(),
+            method: Some(MethodId::new("p/Util", "<init>", "()V")),
+            code_size: Some(5),
+            instruction: Some(InstructionMatch { index: 2, offset: 4, text: "return".to_owned() }),
+        }]);
+        assert_eq!(matches_to_json(&matches),
+                   r#"[{"class":"p/Util","method":"<init>()V","code_size":5,"index":2,"offset":4,"instruction":"return"}]"#);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn statistics() {
+        let dir = write_classes("statistics");
+        let stats = Statistics::from_classpath(&ClassPath::from_paths([&dir]).unwrap(), 2).unwrap();
+        assert_eq!((stats.classes, stats.methods, stats.code_size, stats.huge_methods), (3, 4, 26, 0));
+        assert_eq!(stats.versions.iter().map(|(&v, &n)| (v, n)).collect::<Vec<_>>(), vec![((52, 0), 2), ((55, 0), 1)]);
+        assert_eq!(&stats.opcodes_by_count()[..3], &[("pop", 3), ("return", 3), ("invokespecial", 2)][..]);
+        assert_eq!(stats.largest_methods, vec![(MethodId::new("p/Main", "main", "([Ljava/lang/String;)V"), 20), (MethodId::new("p/Util", "<init>", "()V"), 5)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,823 @@
+use attributes::Attribute;
+use class::JavaClass;
+use code::CodeBody;
+use cp::ConstantPool;
+use hierarchy::{ClassNode, Hierarchy};
+use methods::AccessFlags;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use symbolic::{BootstrapMethod, Constant, MemberRef, MethodHandle, SymOpcode};
+use visitor::{accept_class, ClassHeader, ClassVisitor, ClassWriter, FieldVisitor, MemberHeader, MethodVisitor, RawAttribute, SymbolTable};
+
+/// Says what classes, fields and methods are renamed to. Every method gets names from before the renaming.
+/// By default nothing is renamed, and descriptors and signatures are rewritten with `map_class`.
+pub trait Remapper {
+    /// Maps a binary class name
+    fn map_class(&self, name: &str) -> String {
+        name.to_owned()
+    }
+
+    fn map_field(&self, _owner: &str, name: &str, _descriptor: &str) -> String {
+        name.to_owned()
+    }
+
+    fn map_method(&self, _owner: &str, name: &str, _descriptor: &str) -> String {
+        name.to_owned()
+    }
+
+    /// Maps the name of an annotation element, which is a method of the annotation type without parameters
+    fn map_annotation_element(&self, annotation: &str, name: &str) -> String {
+        let _ = annotation;
+        name.to_owned()
+    }
+
+    fn map_descriptor(&self, descriptor: &str) -> String {
+        remap_signature(descriptor, &|c| self.map_class(c))
+    }
+
+    fn map_signature(&self, signature: &str) -> String {
+        remap_signature(signature, &|c| self.map_class(c))
+    }
+
+    /// Maps the operand of a `Class` constant, which is a class name or an array descriptor
+    fn map_type(&self, name: &str) -> String {
+        if name.starts_with('[') {
+            self.map_descriptor(name)
+        } else {
+            self.map_class(name)
+        }
+    }
+}
+
+/// A `Remapper` that renames nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl Remapper for Identity {}
+
+/// Rewrites the class names in a descriptor or a class, field or method signature.
+/// Inner classes of parameterized types (`Lp/Outer<TT;>.Inner;`) are mapped by their full binary name.
+/// A malformed signature is returned unchanged.
+pub fn remap_signature(signature: &str, map: &dyn Fn(&str) -> String) -> String {
+    let s = signature.as_bytes();
+    let mut out = String::with_capacity(s.len());
+    let mut i = 0;
+    match write_signature(s, &mut i, map, &mut out) {
+        Some(()) => out,
+        None => signature.to_owned(),
+    }
+}
+
+fn write_signature(s: &[u8], i: &mut usize, map: &dyn Fn(&str) -> String, out: &mut String) -> Option<()> {
+    if s.first() == Some(&b'<') {
+        //type parameters: `<T:Ljava/lang/Object;U::Ljava/lang/Comparable<TU;>;>`
+        out.push('<');
+        *i += 1;
+        while *s.get(*i)? != b'>' {
+            let start = *i;
+            while *s.get(*i)? != b':' {
+                *i += 1;
+            }
+            out.push_str(::std::str::from_utf8(&s[start..*i]).ok()?);
+            while s.get(*i) == Some(&b':') {
+                out.push(':');
+                *i += 1;
+                if matches!(s.get(*i)?, b'L' | b'T' | b'[') {
+                    write_type(s, i, map, out)?;
+                }
+            }
+        }
+        out.push('>');
+        *i += 1;
+    }
+    while *i < s.len() {
+        match s[*i] {
+            c @ b'(' | c @ b')' | c @ b'^' => {
+                out.push(c as char);
+                *i += 1;
+            }
+            _ => write_type(s, i, map, out)?,
+        }
+    }
+    Some(())
+}
+
+fn write_type(s: &[u8], i: &mut usize, map: &dyn Fn(&str) -> String, out: &mut String) -> Option<()> {
+    match *s.get(*i)? {
+        c @ b'B' | c @ b'C' | c @ b'D' | c @ b'F' | c @ b'I' | c @ b'J' | c @ b'S' | c @ b'Z' | c @ b'V' => {
+            out.push(c as char);
+            *i += 1;
+        }
+        b'[' => {
+            out.push('[');
+            *i += 1;
+            write_type(s, i, map, out)?;
+        }
+        b'T' => {
+            let start = *i;
+            while *s.get(*i)? != b';' {
+                *i += 1;
+            }
+            *i += 1;
+            out.push_str(::std::str::from_utf8(&s[start..*i]).ok()?);
+        }
+        b'L' => {
+            out.push('L');
+            *i += 1;
+            let mut name = String::new();
+            let mut mapped_outer: Option<String> = None;
+            loop {
+                let start = *i;
+                while !matches!(*s.get(*i)?, b'<' | b'.' | b';') {
+                    *i += 1;
+                }
+                name.push_str(::std::str::from_utf8(&s[start..*i]).ok()?);
+                let mapped = map(&name);
+                match &mapped_outer {
+                    //only the inner class's own name is written after the `.`
+                    Some(outer) => {
+                        let prefix = format!("{}$", outer);
+                        let simple = if mapped.starts_with(&prefix) { &mapped[prefix.len()..] } else { mapped.rsplit('$').next().unwrap_or(&mapped) };
+                        out.push_str(simple);
+                    }
+                    None => out.push_str(&mapped),
+                }
+                if s[*i] == b'<' {
+                    out.push('<');
+                    *i += 1;
+                    while *s.get(*i)? != b'>' {
+                        match s[*i] {
+                            b'*' => {
+                                out.push('*');
+                                *i += 1;
+                            }
+                            c @ b'+' | c @ b'-' => {
+                                out.push(c as char);
+                                *i += 1;
+                                write_type(s, i, map, out)?;
+                            }
+                            _ => write_type(s, i, map, out)?
+                        }
+                    }
+                    out.push('>');
+                    *i += 1;
+                }
+                match *s.get(*i)? {
+                    b'.' => {
+                        out.push('.');
+                        *i += 1;
+                        mapped_outer = Some(mapped);
+                        name.push('$');
+                    }
+                    b';' => {
+                        out.push(';');
+                        *i += 1;
+                        break;
+                    }
+                    _ => return None
+                }
+            }
+        }
+        _ => return None
+    }
+    Some(())
+}
+
+/// Renames everything a method's instructions and exception handlers refer to.
+/// The attributes of the code aren't touched, since they still use the old constant pool.
+pub fn remap_code(code: &mut CodeBody, r: &dyn Remapper) {
+    for h in &mut code.exception_table {
+        if let Some(c) = &mut h.catch_type {
+            *c = r.map_class(c);
+        }
+    }
+    for op in &mut code.instructions {
+        match op {
+            SymOpcode::Ldc(c) => remap_constant(c, r),
+            SymOpcode::GetStatic(m) | SymOpcode::PutStatic(m) | SymOpcode::GetField(m) | SymOpcode::PutField(m) => remap_member(m, true, r),
+            SymOpcode::InvokeVirtual(m) | SymOpcode::InvokeSpecial(m) | SymOpcode::InvokeStatic(m) | SymOpcode::InvokeInterface(m) => remap_member(m, false, r),
+            SymOpcode::InvokeDynamic { bootstrap, name, descriptor } => {
+                *name = remap_dynamic_name(bootstrap, name, descriptor, r);
+                *descriptor = r.map_descriptor(descriptor);
+                remap_bootstrap(bootstrap, r);
+            }
+            SymOpcode::New(c) | SymOpcode::ANewArray(c) | SymOpcode::CheckCast(c) | SymOpcode::InstanceOf(c) |
+            SymOpcode::MultiANewArray { class: c, .. } => *c = r.map_type(c),
+            _ => {}
+        }
+    }
+}
+
+/// Renames the method a lambda implements, which `LambdaMetafactory` gets as the call site's name
+fn remap_dynamic_name(bootstrap: &BootstrapMethod, name: &str, descriptor: &str, r: &dyn Remapper) -> String {
+    let interface = descriptor.rsplit(')').next().and_then(|ret| ret.strip_prefix('L')).and_then(|ret| ret.strip_suffix(';'));
+    match (bootstrap.handle.member.owner.as_str(), interface, bootstrap.arguments.first()) {
+        ("java/lang/invoke/LambdaMetafactory", Some(interface), Some(Constant::MethodType(erased))) => r.map_method(interface, name, erased),
+        _ => name.to_owned(),
+    }
+}
+
+fn remap_bootstrap(b: &mut BootstrapMethod, r: &dyn Remapper) {
+    remap_handle(&mut b.handle, r);
+    for c in &mut b.arguments {
+        remap_constant(c, r);
+    }
+}
+
+fn remap_constant(c: &mut Constant, r: &dyn Remapper) {
+    match c {
+        Constant::Class(name) => *name = r.map_type(name),
+        Constant::MethodType(d) => *d = r.map_descriptor(d),
+        Constant::MethodHandle(h) => remap_handle(h, r),
+        _ => {}
+    }
+}
+
+fn remap_handle(h: &mut MethodHandle, r: &dyn Remapper) {
+    remap_member(&mut h.member, h.reference_kind <= 4, r);
+}
+
+fn remap_member(m: &mut MemberRef, is_field: bool, r: &dyn Remapper) {
+    m.name = if is_field {
+        r.map_field(&m.owner, &m.name, &m.descriptor)
+    } else {
+        r.map_method(&m.owner, &m.name, &m.descriptor)
+    };
+    m.descriptor = r.map_descriptor(&m.descriptor);
+    m.owner = r.map_type(&m.owner);
+}
+
+/// Renames a class and everything it refers to. The result has a new constant pool holding only what it uses.
+pub fn remap_class(class: &JavaClass, r: &dyn Remapper) -> io::Result<JavaClass> {
+    let mut table = SymbolTable::new();
+    let mut writer = ClassWriter::new();
+    let mut error = None;
+    accept_class(class, &mut Remap {
+        next: &mut writer,
+        remapper: r,
+        from: &class.constant_pool,
+        from_bootstrap: class.bootstrap_methods(),
+        class: String::new(),
+        error: &mut error,
+    }, &mut table)?;
+    if let Some(e) = error {
+        return Err(e);
+    }
+    writer.to_class(&mut table)
+}
+
+/// Renames a set of classes with mappings. Mappings of fields and methods also apply to the same member
+/// inherited by or overridden in a subclass, so overriders are renamed along with the method they override.
+pub fn remap_classes(classes: &[JavaClass], mappings: &Mappings) -> io::Result<Vec<JavaClass>> {
+    let mut r = Inheriting { mappings, hierarchy: Hierarchy::new(), private: HashSet::new() };
+    for class in classes {
+        r.hierarchy.add(ClassNode::from_class(class)?);
+        let cp = &class.constant_pool;
+        let name = class.get_name();
+        for m in &class.methods {
+            if m.flags().contains(AccessFlags::PRIVATE) {
+                if let (Some(n), Some(d)) = (cp.get_utf8(m.name_index), cp.get_utf8(m.descriptor_index)) {
+                    r.private.insert((name.clone(), n, d));
+                }
+            }
+        }
+    }
+    classes.iter().map(|c| remap_class(c, &r)).collect()
+}
+
+/// Looks member mappings up in supertypes too
+struct Inheriting<'a> {
+    mappings: &'a Mappings,
+    hierarchy: Hierarchy,
+    /// private methods, which don't inherit their name
+    private: HashSet<(String, String, String)>,
+}
+
+impl<'a> Remapper for Inheriting<'a> {
+    fn map_class(&self, name: &str) -> String {
+        self.mappings.map_class(name)
+    }
+
+    fn map_field(&self, owner: &str, name: &str, descriptor: &str) -> String {
+        ::std::iter::once(owner.to_owned()).chain(self.hierarchy.supertypes(owner))
+            .find_map(|c| self.mappings.field(&c, name, descriptor))
+            .unwrap_or_else(|| name.to_owned())
+    }
+
+    fn map_method(&self, owner: &str, name: &str, descriptor: &str) -> String {
+        if name.starts_with('<') {
+            return name.to_owned();
+        }
+        let key = (owner.to_owned(), name.to_owned(), descriptor.to_owned());
+        if self.private.contains(&key) {
+            return self.mappings.method(owner, name, descriptor).unwrap_or_else(|| name.to_owned());
+        }
+        //a private method in a supertype isn't inherited, so its mapping doesn't carry over
+        let supertypes = self.hierarchy.supertypes(owner).into_iter()
+            .filter(|c| !self.private.contains(&(c.clone(), key.1.clone(), key.2.clone())));
+        ::std::iter::once(key.0.clone()).chain(supertypes)
+            .find_map(|c| self.mappings.method(&c, name, descriptor))
+            .unwrap_or_else(|| name.to_owned())
+    }
+
+    fn map_annotation_element(&self, annotation: &str, name: &str) -> String {
+        self.mappings.map_annotation_element(annotation, name)
+    }
+}
+
+/// Sends a class through with everything renamed, into a `SymbolTable` it wasn't read with
+struct Remap<'a> {
+    next: &'a mut dyn ClassVisitor,
+    remapper: &'a dyn Remapper,
+    from: &'a ConstantPool,
+    from_bootstrap: &'a [::attributes::BootstrapMethodsEntry],
+    /// the old name of the class
+    class: String,
+    error: &'a mut Option<io::Error>,
+}
+
+impl<'a> ClassVisitor for Remap<'a> {
+    fn delegate(&mut self) -> Option<&mut dyn ClassVisitor> {
+        Some(self.next)
+    }
+
+    fn visit_header(&mut self, table: &mut SymbolTable, header: ClassHeader) {
+        let r = self.remapper;
+        self.class = header.name.clone();
+        self.next.visit_header(table, ClassHeader {
+            name: r.map_class(&header.name),
+            super_name: header.super_name.map(|s| r.map_class(&s)),
+            interfaces: header.interfaces.iter().map(|i| r.map_class(i)).collect(),
+            ..header
+        });
+    }
+
+    fn visit_field<'b>(&'b mut self, table: &mut SymbolTable, field: MemberHeader) -> Option<Box<dyn FieldVisitor + 'b>> {
+        let header = MemberHeader {
+            access_flags: field.access_flags,
+            name: self.remapper.map_field(&self.class, &field.name, &field.descriptor),
+            descriptor: self.remapper.map_descriptor(&field.descriptor),
+        };
+        let next = self.next.visit_field(table, header)?;
+        Some(Box::new(RemapMember { next, remapper: self.remapper, from: self.from, from_bootstrap: self.from_bootstrap, class: &self.class, error: &mut *self.error }))
+    }
+
+    fn visit_method<'b>(&'b mut self, table: &mut SymbolTable, method: MemberHeader) -> Option<Box<dyn MethodVisitor + 'b>> {
+        let header = MemberHeader {
+            access_flags: method.access_flags,
+            name: self.remapper.map_method(&self.class, &method.name, &method.descriptor),
+            descriptor: self.remapper.map_descriptor(&method.descriptor),
+        };
+        let next = self.next.visit_method(table, header)?;
+        Some(Box::new(RemapMember { next, remapper: self.remapper, from: self.from, from_bootstrap: self.from_bootstrap, class: &self.class, error: &mut *self.error }))
+    }
+
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        match table.remap_attribute(&attribute, self.from, self.from_bootstrap, self.remapper, &self.class) {
+            Ok(a) => self.next.visit_attribute(table, a),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+    }
+}
+
+struct RemapMember<'a, V: ?Sized> {
+    next: Box<V>,
+    remapper: &'a dyn Remapper,
+    from: &'a ConstantPool,
+    from_bootstrap: &'a [::attributes::BootstrapMethodsEntry],
+    class: &'a str,
+    error: &'a mut Option<io::Error>,
+}
+
+impl<'a, V: ?Sized> RemapMember<'a, V> {
+    fn remap(&mut self, table: &mut SymbolTable, a: &Attribute) -> Option<Attribute> {
+        match table.remap_attribute(a, self.from, self.from_bootstrap, self.remapper, self.class) {
+            Ok(a) => Some(a),
+            Err(e) => {
+                self.error.get_or_insert(e);
+                None
+            }
+        }
+    }
+}
+
+impl<'a, 'b> FieldVisitor for RemapMember<'a, dyn FieldVisitor + 'b> {
+    fn delegate(&mut self) -> Option<&mut dyn FieldVisitor> {
+        Some(&mut *self.next)
+    }
+
+    /// Any attribute can name a class, so none of them can be copied without decoding
+    fn visit_raw(&mut self, _table: &mut SymbolTable, _attributes: &[RawAttribute]) -> bool {
+        false
+    }
+
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        if let Some(a) = self.remap(table, &attribute) {
+            self.next.visit_attribute(table, a);
+        }
+    }
+}
+
+impl<'a, 'b> MethodVisitor for RemapMember<'a, dyn MethodVisitor + 'b> {
+    fn delegate(&mut self) -> Option<&mut dyn MethodVisitor> {
+        Some(&mut *self.next)
+    }
+
+    fn visit_raw(&mut self, _table: &mut SymbolTable, _attributes: &[RawAttribute]) -> bool {
+        false
+    }
+
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        if let Some(a) = self.remap(table, &attribute) {
+            self.next.visit_attribute(table, a);
+        }
+    }
+
+    fn visit_code(&mut self, table: &mut SymbolTable, mut code: CodeBody) {
+        remap_code(&mut code, self.remapper);
+        let mut attributes = vec!();
+        for a in &code.attributes {
+            attributes.extend(self.remap(table, a));
+        }
+        code.attributes = attributes;
+        self.next.visit_code(table, code);
+    }
+}
+
+/// Names of classes, fields and methods before and after renaming.
+/// Member mappings are keyed by the old owner, old name and old descriptor. Field mappings with an empty
+/// descriptor apply to a field with any descriptor, since some formats don't give field types.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mappings {
+    pub classes: HashMap<String, String>,
+    pub fields: HashMap<(String, String, String), String>,
+    pub methods: HashMap<(String, String, String), String>,
+}
+
+impl Mappings {
+    pub fn new() -> Mappings {
+        Mappings::default()
+    }
+
+    /// Looks up a field's new name, if it has one
+    pub fn field(&self, owner: &str, name: &str, descriptor: &str) -> Option<String> {
+        let key = (owner.to_owned(), name.to_owned(), descriptor.to_owned());
+        self.fields.get(&key).or_else(|| self.fields.get(&(key.0, key.1, String::new()))).cloned()
+    }
+
+    /// Looks up a method's new name, if it has one
+    pub fn method(&self, owner: &str, name: &str, descriptor: &str) -> Option<String> {
+        self.methods.get(&(owner.to_owned(), name.to_owned(), descriptor.to_owned())).cloned()
+    }
+
+    /// Returns mappings that undo these ones, such as for deobfuscating with the mappings an obfuscator wrote
+    pub fn reverse(&self) -> Mappings {
+        let mut ans = Mappings::new();
+        for (from, to) in &self.classes {
+            ans.classes.insert(to.clone(), from.clone());
+        }
+        let owner = |o: &str| self.map_class(o);
+        for ((o, n, d), to) in &self.fields {
+            let d = if d.is_empty() { String::new() } else { self.map_descriptor(d) };
+            ans.fields.insert((owner(o), to.clone(), d), n.clone());
+        }
+        for ((o, n, d), to) in &self.methods {
+            ans.methods.insert((owner(o), to.clone(), self.map_descriptor(d)), n.clone());
+        }
+        ans
+    }
+
+    /// Parses a ProGuard or R8 `mapping.txt`, which maps original names to obfuscated ones:
+    /// ```text
+    /// com.example.Foo -> a.a:
+    ///     int count -> a
+    ///     1:4:void add(int,java.lang.String) -> a
+    /// ```
+    pub fn parse_proguard(text: &str) -> io::Result<Mappings> {
+        let mut ans = Mappings::new();
+        let mut class: Option<String> = None;
+        for (n, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (left, right) = match trimmed.find(" -> ") {
+                Some(i) => (trimmed[..i].trim(), trimmed[i + 4..].trim()),
+                None => return Err(bad_mapping(n, line)),
+            };
+            if !line.starts_with(char::is_whitespace) {
+                let from = left.replace('.', "/");
+                let to = right.strip_suffix(':').ok_or_else(|| bad_mapping(n, line))?.replace('.', "/");
+                ans.classes.insert(from.clone(), to);
+                class = Some(from);
+                continue;
+            }
+            let owner = class.clone().ok_or_else(|| bad_mapping(n, line))?;
+            //line numbers from the original source come first
+            let left = left.trim_start_matches(|c: char| c.is_ascii_digit() || c == ':');
+            let (type_, rest) = left.split_once(' ').ok_or_else(|| bad_mapping(n, line))?;
+            let return_type = java_type_descriptor(type_);
+            match rest.find('(') {
+                Some(open) => {
+                    let close = rest.rfind(')').ok_or_else(|| bad_mapping(n, line))?;
+                    let name = &rest[..open];
+                    //methods inlined from another class are named with the class
+                    if name.contains('.') {
+                        continue;
+                    }
+                    let params: String = rest[open + 1..close].split(',').filter(|p| !p.is_empty()).map(java_type_descriptor).collect();
+                    ans.methods.insert((owner, name.to_owned(), format!("({}){}", params, return_type)), right.to_owned());
+                }
+                None => {
+                    ans.fields.insert((owner, rest.to_owned(), return_type), right.to_owned());
+                }
+            }
+        }
+        Ok(ans)
+    }
+
+    /// Parses a Tiny v2 file, mapping from the namespace `from` to the namespace `to`.
+    /// Columns are separated by tabs, which are shown as spaces here:
+    /// ```text
+    /// tiny 2 0 official named
+    /// c a com/example/Foo
+    ///  f I a count
+    ///  m (ILjava/lang/String;)V a add
+    /// ```
+    /// Names that are empty in a namespace are taken from the first one.
+    pub fn parse_tiny(text: &str, from: &str, to: &str) -> io::Result<Mappings> {
+        let mut lines = text.lines().enumerate();
+        let header: Vec<&str> = lines.next().map(|(_, h)| h.split('\t').collect()).unwrap_or_default();
+        if header.len() < 4 || header[0] != "tiny" || header[1] != "2" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a Tiny v2 file"));
+        }
+        let namespaces = &header[3..];
+        let ns = |name: &str| namespaces.iter().position(|n| *n == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("No namespace named {}", name)));
+        let (from, to) = (ns(from)?, ns(to)?);
+        //(names in each namespace, descriptor in the first namespace)
+        let mut classes: Vec<Vec<String>> = vec!();
+        let mut fields: Vec<(usize, String, Vec<String>)> = vec!();
+        let mut methods: Vec<(usize, String, Vec<String>)> = vec!();
+        let names = |parts: &[&str]| -> Vec<String> {
+            let first = parts.first().map_or("", |s| *s);
+            (0..namespaces.len()).map(|i| match parts.get(i) {
+                Some(p) if !p.is_empty() => p.to_string(),
+                _ => first.to_owned(),
+            }).collect()
+        };
+        for (n, line) in lines {
+            let depth = line.len() - line.trim_start_matches('\t').len();
+            let parts: Vec<&str> = line[depth..].split('\t').collect();
+            match (depth, parts.as_slice()) {
+                (0, ["c", rest @ ..]) => classes.push(names(rest)),
+                (1, ["f", desc, rest @ ..]) if !classes.is_empty() => fields.push((classes.len() - 1, desc.to_string(), names(rest))),
+                (1, ["m", desc, rest @ ..]) if !classes.is_empty() => methods.push((classes.len() - 1, desc.to_string(), names(rest))),
+                //comments, parameters, local variables and properties
+                (_, [_, ..]) if depth > 0 || parts[0].is_empty() => {}
+                _ => return Err(bad_mapping(n, line)),
+            }
+        }
+        let mut ans = Mappings::new();
+        //descriptors are written with the first namespace's names
+        let first_to_from: HashMap<String, String> = classes.iter().map(|c| (c[0].clone(), c[from].clone())).collect();
+        let descriptor = |d: &str| remap_signature(d, &|c| first_to_from.get(c).cloned().unwrap_or_else(|| c.to_owned()));
+        for c in &classes {
+            ans.classes.insert(c[from].clone(), c[to].clone());
+        }
+        for (c, desc, names) in fields {
+            ans.fields.insert((classes[c][from].clone(), names[from].clone(), descriptor(&desc)), names[to].clone());
+        }
+        for (c, desc, names) in methods {
+            ans.methods.insert((classes[c][from].clone(), names[from].clone(), descriptor(&desc)), names[to].clone());
+        }
+        ans.remove_unchanged();
+        Ok(ans)
+    }
+
+    /// Parses an SRG file:
+    /// ```text
+    /// CL: a com/example/Foo
+    /// FD: a/a com/example/Foo/count
+    /// MD: a/a (ILjava/lang/String;)V com/example/Foo/add (ILjava/lang/String;)V
+    /// ```
+    pub fn parse_srg(text: &str) -> io::Result<Mappings> {
+        let mut ans = Mappings::new();
+        let split_member = |s: &str| s.rsplit_once('/').map(|(o, n)| (o.to_owned(), n.to_owned()));
+        for (n, line) in text.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                [] | ["PK:", ..] => {}
+                ["CL:", from, to] => {
+                    ans.classes.insert(from.to_string(), to.to_string());
+                }
+                ["FD:", from, to] => {
+                    let ((owner, name), (_, new)) = split_member(from).zip(split_member(to)).ok_or_else(|| bad_mapping(n, line))?;
+                    ans.fields.insert((owner, name, String::new()), new);
+                }
+                ["MD:", from, desc, to, _] => {
+                    let ((owner, name), (_, new)) = split_member(from).zip(split_member(to)).ok_or_else(|| bad_mapping(n, line))?;
+                    ans.methods.insert((owner, name, desc.to_string()), new);
+                }
+                _ => return Err(bad_mapping(n, line)),
+            }
+        }
+        ans.remove_unchanged();
+        Ok(ans)
+    }
+
+    fn remove_unchanged(&mut self) {
+        self.classes.retain(|from, to| from != to);
+        self.fields.retain(|(_, from, _), to| from != to);
+        self.methods.retain(|(_, from, _), to| from != to);
+    }
+
+    /// Rewrites the class and method names in a stack trace, such as one printed by `Throwable.printStackTrace`.
+    /// Frames only name the method, so a frame whose method name was given to several methods lists all of their names.
+    pub fn retrace(&self, trace: &str) -> String {
+        let mut names: HashMap<(String, String), Vec<String>> = HashMap::new();
+        for ((owner, name, _), to) in &self.methods {
+            let list = names.entry((owner.replace('/', "."), to.clone())).or_default();
+            if !list.contains(name) {
+                list.push(name.clone());
+            }
+        }
+        let classes: HashMap<String, String> = self.classes.iter().map(|(f, t)| (t.replace('/', "."), f.replace('/', "."))).collect();
+        let reversed = self.reverse();
+        let mut ans = String::with_capacity(trace.len());
+        for line in trace.lines() {
+            let trimmed = line.trim_start();
+            let frame = trimmed.strip_prefix("at ").and_then(|f| f.split_once('(')).and_then(|(m, rest)| m.rsplit_once('.').map(|(c, m)| (c, m, rest)));
+            match frame {
+                Some((class, method, rest)) => {
+                    let original = reversed.map_class(&class.replace('.', "/")).replace('/', ".");
+                    let mut methods = names.get(&(original.clone(), method.to_owned())).cloned().unwrap_or_else(|| vec!(method.to_owned()));
+                    methods.sort();
+                    ans.push_str(&line[..line.len() - trimmed.len()]);
+                    ans.push_str(&format!("at {}.{}({}", original, methods.join("|"), rest));
+                }
+                //`Exception in thread "main" a.b: message` and `Caused by: a.b: message`
+                None => {
+                    let mut out = line.to_owned();
+                    for word in line.split(|c: char| c.is_whitespace() || c == ':') {
+                        if let Some(original) = classes.get(word) {
+                            out = out.replacen(word, original, 1);
+                        }
+                    }
+                    ans.push_str(&out);
+                }
+            }
+            ans.push('\n');
+        }
+        ans
+    }
+}
+
+impl Remapper for Mappings {
+    /// Maps a class, falling back to its outer class for inner classes without a mapping of their own
+    fn map_class(&self, name: &str) -> String {
+        if let Some(to) = self.classes.get(name) {
+            return to.clone();
+        }
+        match name.rsplit_once('$') {
+            Some((outer, inner)) => format!("{}${}", self.map_class(outer), inner),
+            None => name.to_owned(),
+        }
+    }
+
+    fn map_field(&self, owner: &str, name: &str, descriptor: &str) -> String {
+        self.field(owner, name, descriptor).unwrap_or_else(|| name.to_owned())
+    }
+
+    fn map_method(&self, owner: &str, name: &str, descriptor: &str) -> String {
+        self.method(owner, name, descriptor).unwrap_or_else(|| name.to_owned())
+    }
+
+    fn map_annotation_element(&self, annotation: &str, name: &str) -> String {
+        self.methods.iter()
+            .find(|((o, n, d), _)| o == annotation && n == name && d.starts_with("()"))
+            .map_or_else(|| name.to_owned(), |(_, to)| to.clone())
+    }
+}
+
+/// Turns a Java type as written in source (`java.lang.String[]`) into a descriptor
+fn java_type_descriptor(t: &str) -> String {
+    let dims = t.matches("[]").count();
+    let base = t.trim_end_matches("[]");
+    let base = match base {
+        "void" => "V".to_owned(),
+        "boolean" => "Z".to_owned(),
+        "byte" => "B".to_owned(),
+        "char" => "C".to_owned(),
+        "short" => "S".to_owned(),
+        "int" => "I".to_owned(),
+        "long" => "J".to_owned(),
+        "float" => "F".to_owned(),
+        "double" => "D".to_owned(),
+        class => format!("L{};", class.replace('.', "/")),
+    };
+    "[".repeat(dims) + &base
+}
+
+fn bad_mapping(line: usize, text: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Malformed mapping on line {}: {}", line + 1, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(owner: &str, name: &str, descriptor: &str) -> (String, String, String) {
+        (owner.to_owned(), name.to_owned(), descriptor.to_owned())
+    }
+
+    #[test]
+    fn proguard() {
+        let text = "\
+# compiler: R8
+com.example.Foo -> a.a:
+    int count -> a
+    java.lang.String[] names -> b
+    1:4:void add(int,java.lang.String) -> a
+    5:5:com.example.Foo copy() -> b
+    6:6:void com.example.Bar.inlined():12:12 -> c
+com.example.Foo$Inner -> a.b:
+";
+        let m = Mappings::parse_proguard(text).unwrap();
+        assert_eq!(m.classes.len(), 2);
+        assert_eq!(m.classes["com/example/Foo"], "a/a");
+        assert_eq!(m.classes["com/example/Foo$Inner"], "a/b");
+        assert_eq!(m.fields[&key("com/example/Foo", "count", "I")], "a");
+        assert_eq!(m.fields[&key("com/example/Foo", "names", "[Ljava/lang/String;")], "b");
+        assert_eq!(m.methods.len(), 2);
+        assert_eq!(m.methods[&key("com/example/Foo", "add", "(ILjava/lang/String;)V")], "a");
+        assert_eq!(m.methods[&key("com/example/Foo", "copy", "()Lcom/example/Foo;")], "b");
+        assert!(Mappings::parse_proguard("    int count -> a").is_err());
+        assert!(Mappings::parse_proguard("com.example.Foo a.a:").is_err());
+    }
+
+    #[test]
+    fn tiny() {
+        let text = "tiny\t2\t0\tofficial\tintermediary\tnamed\n\
+                    c\ta\tclass_1\tcom/example/Foo\n\
+                    \tc\tA comment\n\
+                    \tf\tI\ta\tfield_1\tcount\n\
+                    \tm\t(La;)V\tb\tmethod_1\tadd\n\
+                    \t\tp\t1\t\t\tother\n\
+                    \tm\t()V\tc\t\t\n\
+                    c\tb\tclass_2\t\n";
+        let m = Mappings::parse_tiny(text, "official", "named").unwrap();
+        //`b` has no named name and `c` has no names past the first, so they're left out
+        assert_eq!(m.classes.len(), 1);
+        assert_eq!(m.classes["a"], "com/example/Foo");
+        assert_eq!(m.fields[&key("a", "a", "I")], "count");
+        assert_eq!(m.methods.len(), 1);
+        assert_eq!(m.methods[&key("a", "b", "(La;)V")], "add");
+        //descriptors are rewritten into the `from` namespace
+        let m = Mappings::parse_tiny(text, "intermediary", "named").unwrap();
+        assert_eq!(m.methods[&key("class_1", "method_1", "(Lclass_1;)V")], "add");
+        assert!(Mappings::parse_tiny(text, "official", "mojang").is_err());
+        assert!(Mappings::parse_tiny("v1\tofficial\tnamed\n", "official", "named").is_err());
+    }
+
+    #[test]
+    fn srg() {
+        let text = "\
+PK: . net/minecraft/src
+CL: a com/example/Foo
+CL: b b
+FD: a/a com/example/Foo/count
+MD: a/a (ILjava/lang/String;)V com/example/Foo/add (ILjava/lang/String;)V
+MD: a/b ()V com/example/Foo/b ()V
+";
+        let m = Mappings::parse_srg(text).unwrap();
+        assert_eq!(m.classes.len(), 1);
+        assert_eq!(m.classes["a"], "com/example/Foo");
+        //SRG doesn't give field types, so the mapping applies to any descriptor
+        assert_eq!(m.field("a", "a", "J").as_deref(), Some("count"));
+        assert_eq!(m.methods.len(), 1);
+        assert_eq!(m.methods[&key("a", "a", "(ILjava/lang/String;)V")], "add");
+        assert!(Mappings::parse_srg("FD: a com/example/Foo/count").is_err());
+        assert!(Mappings::parse_srg("XX: a b").is_err());
+    }
+
+    #[test]
+    fn private_methods_are_not_inherited() {
+        let mut mappings = Mappings::new();
+        mappings.methods.insert(key("Base", "run", "()V"), "a".to_owned());
+        mappings.methods.insert(key("Base", "stop", "()V"), "b".to_owned());
+        let mut hierarchy = Hierarchy::new();
+        for (name, super_name) in &[("Base", "java/lang/Object"), ("Sub", "Base")] {
+            hierarchy.add(ClassNode { name: name.to_string(), super_name: Some(super_name.to_string()), interfaces: vec![], is_interface: false });
+        }
+        let mut private = HashSet::new();
+        private.insert(key("Base", "run", "()V"));
+        let r = Inheriting { mappings: &mappings, hierarchy, private };
+        assert_eq!(r.map_method("Base", "run", "()V"), "a");
+        assert_eq!(r.map_method("Sub", "run", "()V"), "run");
+        assert_eq!(r.map_method("Sub", "stop", "()V"), "b");
+        assert_eq!(r.map_method("Sub", "stop", "(I)V"), "stop");
+    }
+}
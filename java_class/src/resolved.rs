@@ -0,0 +1,239 @@
+use annotations::{self, AnnotationValue, ResolvedAnnotation};
+use attributes::*;
+use class::{AccessFlags as ClassFlags, JavaClass};
+use code::CodeBody;
+use cp::{CPIndex, ConstantPool};
+use descriptor::{FieldType, MethodDescriptor};
+use fields::{self, AccessFlags as FieldFlags};
+use malformed;
+use methods::{self, AccessFlags as MethodFlags};
+use opcodes::Opcode;
+use std::fmt;
+use std::io;
+use symbolic::{resolve_constant, Constant};
+
+/// A class with its constant pool references resolved.
+/// The view borrows the `JavaClass` it was built from, which stays reachable through `class`.
+#[derive(Debug, Clone)]
+pub struct ClassInfo<'a> {
+    pub class: &'a JavaClass,
+    /// binary name, e.g. `java/lang/String`
+    pub name: String,
+    /// `None` only for `java/lang/Object` (and `module-info`)
+    pub super_name: Option<String>,
+    pub interfaces: Vec<String>,
+    pub access: ClassFlags,
+    /// the generic signature, if the class has one
+    pub signature: Option<String>,
+    pub fields: Vec<FieldInfo<'a>>,
+    pub methods: Vec<MethodInfo<'a>>,
+    pub annotations: Vec<ResolvedAnnotation>,
+}
+
+impl<'a> ClassInfo<'a> {
+    pub fn new(class: &'a JavaClass) -> io::Result<ClassInfo<'a>> {
+        let cp = &class.constant_pool;
+        let name_of = |i| cp.get_class_name(i).ok_or_else(|| malformed("Class index did not point to Class"));
+        let mut fields = Vec::with_capacity(class.fields.len());
+        for f in &class.fields {
+            fields.push(FieldInfo::new(f, cp)?);
+        }
+        let mut methods = Vec::with_capacity(class.methods.len());
+        for m in &class.methods {
+            methods.push(MethodInfo::new(m, cp)?);
+        }
+        Ok(ClassInfo {
+            class,
+            name: name_of(class.this_class)?,
+            super_name: if class.super_class.as_u16() == 0 { None } else { Some(name_of(class.super_class)?) },
+            interfaces: class.interfaces.iter().map(|i| name_of(*i)).collect::<io::Result<_>>()?,
+            access: class.flags(),
+            signature: signature(&class.attributes, cp)?,
+            fields,
+            methods,
+            annotations: annotations::annotations(&class.attributes, cp)?,
+        })
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.access.contains(ClassFlags::INTERFACE)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldInfo<'a>> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn method(&self, name: &str, descriptor: &str) -> Option<&MethodInfo<'a>> {
+        self.methods.iter().find(|m| m.name == name && m.descriptor_str() == descriptor)
+    }
+
+    /// Every overload of the method `name`
+    pub fn methods_named<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'b MethodInfo<'a>> {
+        self.methods.iter().filter(move |m| m.name == name)
+    }
+}
+
+/// A field with its constant pool references resolved
+#[derive(Debug, Clone)]
+pub struct FieldInfo<'a> {
+    pub field: &'a fields::FieldInfo,
+    pub name: String,
+    pub descriptor: FieldType,
+    pub access: FieldFlags,
+    pub signature: Option<String>,
+    /// the `ConstantValue` of a constant field
+    pub constant_value: Option<Constant>,
+    pub annotations: Vec<ResolvedAnnotation>,
+}
+
+impl<'a> FieldInfo<'a> {
+    pub fn new(field: &'a fields::FieldInfo, cp: &ConstantPool) -> io::Result<FieldInfo<'a>> {
+        let mut constant_value = None;
+        for a in &field.attributes {
+            if let Attribute::ConstantValue { constantvalue_index } = a {
+                constant_value = Some(resolve_constant(cp, *constantvalue_index)?);
+            }
+        }
+        Ok(FieldInfo {
+            field,
+            name: cp.utf8(field.name_index)?,
+            descriptor: FieldType::parse(&cp.utf8(field.descriptor_index)?)?,
+            access: field.flags(),
+            signature: signature(&field.attributes, cp)?,
+            constant_value,
+            annotations: annotations::annotations(&field.attributes, cp)?,
+        })
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access.contains(FieldFlags::STATIC)
+    }
+}
+
+impl<'a> fmt::Display for FieldInfo<'a> {
+    /// Formats the field as a Java declaration, e.g. `private static final int count`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modifiers = self.access - (FieldFlags::SYNTHETIC | FieldFlags::ENUM);
+        if !modifiers.is_empty() {
+            write!(f, "{} ", modifiers)?;
+        }
+        write!(f, "{} {}", self.descriptor.java_name(), self.name)
+    }
+}
+
+/// A method with its constant pool references resolved
+#[derive(Debug, Clone)]
+pub struct MethodInfo<'a> {
+    pub method: &'a methods::MethodInfo,
+    pub name: String,
+    pub descriptor: MethodDescriptor,
+    pub access: MethodFlags,
+    pub signature: Option<String>,
+    /// `None` for abstract and native methods
+    pub code: Option<Code<'a>>,
+    /// binary names of the classes in the `Exceptions` attribute
+    pub exceptions: Vec<String>,
+    pub annotations: Vec<ResolvedAnnotation>,
+    pub parameter_annotations: Vec<Vec<ResolvedAnnotation>>,
+    /// the default value of an annotation interface element
+    pub annotation_default: Option<AnnotationValue>,
+}
+
+impl<'a> MethodInfo<'a> {
+    pub fn new(method: &'a methods::MethodInfo, cp: &ConstantPool) -> io::Result<MethodInfo<'a>> {
+        let mut code = None;
+        let mut exceptions = Vec::new();
+        for a in &method.attributes {
+            match a {
+                Attribute::Code { max_stack, max_locals, code: bytecode, exception_table, attributes } => code = Some(Code {
+                    max_stack: *max_stack,
+                    max_locals: *max_locals,
+                    code: bytecode,
+                    exception_table,
+                    attributes,
+                    attribute: a,
+                }),
+                Attribute::Exceptions { exception_index_table } => for i in exception_index_table {
+                    exceptions.push(cp.get_class_name(CPIndex::from(*i)).ok_or_else(|| malformed("Exception did not point to Class"))?);
+                },
+                _ => {}
+            }
+        }
+        Ok(MethodInfo {
+            method,
+            name: cp.utf8(method.name_index)?,
+            descriptor: MethodDescriptor::parse(&cp.utf8(method.descriptor_index)?)?,
+            access: method.flags(),
+            signature: signature(&method.attributes, cp)?,
+            code,
+            exceptions,
+            annotations: annotations::annotations(&method.attributes, cp)?,
+            parameter_annotations: annotations::parameter_annotations(&method.attributes, cp)?,
+            annotation_default: annotations::annotation_default(&method.attributes, cp)?,
+        })
+    }
+
+    /// The descriptor in its class file form
+    pub fn descriptor_str(&self) -> String {
+        self.descriptor.to_string()
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access.contains(MethodFlags::STATIC)
+    }
+
+    pub fn is_constructor(&self) -> bool {
+        self.name == "<init>"
+    }
+}
+
+impl<'a> fmt::Display for MethodInfo<'a> {
+    /// Formats the method as a Java declaration without its body,
+    /// e.g. `public static void main(java.lang.String[]) throws java.io.IOException`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modifiers = self.access - (MethodFlags::BRIDGE | MethodFlags::VARARGS | MethodFlags::SYNTHETIC);
+        if !modifiers.is_empty() {
+            write!(f, "{} ", modifiers)?;
+        }
+        let parameters: Vec<String> = self.descriptor.parameters.iter().map(FieldType::java_name).collect();
+        write!(f, "{} {}({})", self.descriptor.java_return_type(), self.name, parameters.join(", "))?;
+        if !self.exceptions.is_empty() {
+            let exceptions: Vec<String> = self.exceptions.iter().map(|e| e.replace('/', ".")).collect();
+            write!(f, " throws {}", exceptions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A borrowed `Code` attribute
+#[derive(Debug, Clone)]
+pub struct Code<'a> {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: &'a [Opcode],
+    pub exception_table: &'a [ExceptionTableEntry],
+    pub attributes: &'a [Attribute],
+    attribute: &'a Attribute,
+}
+
+impl<'a> Code<'a> {
+    /// The `Code` attribute itself
+    pub fn attribute(&self) -> &'a Attribute {
+        self.attribute
+    }
+
+    /// Converts the code to symbolic form; `class` must be the class the method belongs to
+    pub fn to_body(&self, class: &JavaClass) -> io::Result<CodeBody> {
+        CodeBody::from_attribute(self.attribute, &class.constant_pool, class.bootstrap_methods())
+    }
+}
+
+fn signature(attributes: &[Attribute], cp: &ConstantPool) -> io::Result<Option<String>> {
+    for a in attributes {
+        if let Attribute::Signature { signature_index } = a {
+            return cp.utf8(*signature_index).map(Some);
+        }
+    }
+    Ok(None)
+}
+
@@ -0,0 +1,953 @@
+use attributes::{Annotation, Attribute, ElementValue, UndecodedContents};
+use callgraph::MethodId;
+use class::JavaClass;
+use classpath::{class_name_of, ClassPath};
+use code::CodeBody;
+use compact::Order;
+use dependencies::signature_classes;
+use hierarchy::{ClassNode, Hierarchy};
+use malformed;
+use methods::AccessFlags;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use symbolic::{BootstrapMethod, Constant, MemberRef, MethodHandle, SymOpcode};
+use visitor::{accept_class, ClassVisitor, ClassWriter, FieldVisitor, MemberHeader, MethodVisitor, SymbolTable};
+use zip::write::{FileOptions, ZipWriter};
+
+/// A field, identified by the class that declares it, its name and its descriptor
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FieldId {
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl FieldId {
+    pub fn new(owner: &str, name: &str, descriptor: &str) -> FieldId {
+        FieldId { owner: owner.to_owned(), name: name.to_owned(), descriptor: descriptor.to_owned() }
+    }
+}
+
+impl fmt::Display for FieldId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.owner, self.name, self.descriptor)
+    }
+}
+
+/// Says what to keep no matter whether it's used.
+/// Class patterns match binary names, with either `.` or `/` between packages: `*` matches any part of a name
+/// within a package, `**` matches anything, and `?` matches one character other than `/`.
+/// Member patterns match member names, where `*` matches anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeepRule {
+    /// keeps matching classes and all of their members
+    Classes(String),
+    /// keeps matching classes, and their fields and methods whose name matches `member`
+    Members { class: String, member: String },
+    /// keeps classes with the given annotation, by binary name, and all of their members
+    AnnotatedClasses(String),
+    /// keeps fields and methods with the given annotation, by binary name, and their classes
+    AnnotatedMembers(String),
+    /// keeps the `public static void main(String[])` method of matching classes
+    Main(String),
+}
+
+impl KeepRule {
+    /// Parses a rule written as one of
+    /// `class PATTERN`, `members PATTERN MEMBER`, `annotated-class ANNOTATION`,
+    /// `annotated-members ANNOTATION` or `main PATTERN`
+    pub fn parse(rule: &str) -> io::Result<KeepRule> {
+        let words: Vec<&str> = rule.split_whitespace().collect();
+        Ok(match words.as_slice() {
+            ["class", p] => KeepRule::Classes(p.to_string()),
+            ["members", p, m] => KeepRule::Members { class: p.to_string(), member: m.to_string() },
+            ["annotated-class", a] => KeepRule::AnnotatedClasses(a.replace('.', "/")),
+            ["annotated-members", a] => KeepRule::AnnotatedMembers(a.replace('.', "/")),
+            ["main", p] => KeepRule::Main(p.to_string()),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid keep rule: {}", rule))),
+        })
+    }
+
+    /// Parses one rule per line, skipping blank lines and lines starting with `#`
+    pub fn parse_all(rules: &str) -> io::Result<Vec<KeepRule>> {
+        rules.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).map(KeepRule::parse).collect()
+    }
+}
+
+/// Returns true if the binary class name `name` matches a `KeepRule` class pattern
+pub fn matches_class(pattern: &str, name: &str) -> bool {
+    glob(pattern.replace('.', "/").as_bytes(), name.as_bytes(), true)
+}
+
+fn glob(p: &[u8], s: &[u8], is_class: bool) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some(b'*') if p.get(1) == Some(&b'*') || !is_class => {
+            let rest = if is_class { &p[2..] } else { &p[1..] };
+            (0..=s.len()).any(|i| glob(rest, &s[i..], is_class))
+        }
+        Some(b'*') => {
+            let limit = s.iter().position(|&c| c == b'/').unwrap_or(s.len());
+            (0..=limit).any(|i| glob(&p[1..], &s[i..], is_class))
+        }
+        Some(b'?') => s.first().is_some_and(|&c| c != b'/') && glob(&p[1..], &s[1..], is_class),
+        Some(c) => s.first() == Some(c) && glob(&p[1..], &s[1..], is_class),
+    }
+}
+
+/// What a shrinker found to be reachable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reachable {
+    pub classes: BTreeSet<String>,
+    pub methods: BTreeSet<MethodId>,
+    pub fields: BTreeSet<FieldId>,
+}
+
+/// The output of a shrinker: the kept classes, with unused members removed and their constant pools compacted,
+/// the resources to copy alongside them, and what was removed
+#[derive(Debug)]
+pub struct Shrunk {
+    pub classes: BTreeMap<String, JavaClass>,
+    pub resources: BTreeMap<String, Vec<u8>>,
+    pub removed_classes: BTreeSet<String>,
+    pub removed_methods: BTreeSet<MethodId>,
+    pub removed_fields: BTreeSet<FieldId>,
+}
+
+impl Shrunk {
+    /// Writes the classes and resources to a jar
+    pub fn write_jar<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut zip = ZipWriter::new(File::create(path)?);
+        //the manifest has to come first for `JarInputStream` to find it
+        let manifest = self.resources.iter().filter(|(n, _)| *n == "META-INF/MANIFEST.MF");
+        let rest = self.resources.iter().filter(|(n, _)| *n != "META-INF/MANIFEST.MF");
+        for (name, bytes) in manifest.chain(rest) {
+            zip.start_file(name.as_str(), FileOptions::default())?;
+            zip.write_all(bytes)?;
+        }
+        for (name, class) in &self.classes {
+            zip.start_file(format!("{}.class", name), FileOptions::default())?;
+            zip.write_all(&class.to_bytes())?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+}
+
+/// Removes the classes, methods and fields that can't be reached from a set of keep rules (ProGuard's shrinking step).
+/// Reachability is conservative: a virtual call keeps every override in a reachable subclass, and a method is kept
+/// whenever it overrides a method of a class that isn't part of the program, since code outside the program may call it.
+#[derive(Default)]
+pub struct Shrinker {
+    classes: BTreeMap<String, JavaClass>,
+    resources: BTreeMap<String, Vec<u8>>,
+    hierarchy: Hierarchy,
+    library: Option<ClassPath>,
+    rules: Vec<KeepRule>,
+}
+
+impl Shrinker {
+    pub fn new() -> Shrinker {
+        Shrinker::default()
+    }
+
+    /// Adds every class on a classpath to the program, and keeps its other resources for the output
+    pub fn from_classpath(cp: &ClassPath) -> io::Result<Shrinker> {
+        let mut ans = Shrinker::new();
+        for name in cp.resource_names()? {
+            if class_name_of(&name).is_some() {
+                continue;
+            }
+            if let Some(bytes) = cp.find_resource(&name)? {
+                ans.add_resource(&name, bytes);
+            }
+        }
+        for (_, class) in cp.classes()? {
+            ans.add_class(class?)?;
+        }
+        Ok(ans)
+    }
+
+    /// Adds a class to the program, replacing any class with the same name
+    pub fn add_class(&mut self, class: JavaClass) -> io::Result<()> {
+        let node = ClassNode::from_class(&class)?;
+        self.classes.insert(node.name.clone(), class);
+        self.hierarchy.add(node);
+        Ok(())
+    }
+
+    /// Adds a resource to copy to the output as is
+    pub fn add_resource(&mut self, name: &str, bytes: Vec<u8>) {
+        self.resources.insert(name.to_owned(), bytes);
+    }
+
+    /// Sets the classes the program runs against, which aren't shrunk.
+    /// Without them, methods of reachable classes that have a superclass or interface outside the program
+    /// are all kept, except for the ones that could only override a method of `java/lang/Object`.
+    pub fn set_library(&mut self, library: ClassPath) {
+        self.library = Some(library);
+    }
+
+    pub fn keep(&mut self, rule: KeepRule) {
+        self.rules.push(rule);
+    }
+
+    /// Finds every class, method and field reachable from the keep rules
+    pub fn reachable(&self) -> io::Result<Reachable> {
+        let mut walk = Walk { shrinker: self, program: HashMap::new(), library: HashMap::new(), reached: Reachable::default(),
+            queue: VecDeque::new(), virtual_calls: HashMap::new() };
+        for (name, class) in &self.classes {
+            walk.program.insert(name.clone(), ProgramClass::new(class)?);
+        }
+        walk.apply_rules();
+        walk.run()?;
+        Ok(walk.reached)
+    }
+
+    /// Removes everything that isn't reachable
+    pub fn shrink(&self) -> io::Result<Shrunk> {
+        let reached = self.reachable()?;
+        let mut ans = Shrunk {
+            classes: BTreeMap::new(),
+            resources: self.resources.clone(),
+            removed_classes: BTreeSet::new(),
+            removed_methods: BTreeSet::new(),
+            removed_fields: BTreeSet::new(),
+        };
+        let removed_classes: BTreeSet<String> = self.classes.keys().filter(|c| !reached.classes.contains(*c)).cloned().collect();
+        for (name, class) in &self.classes {
+            if removed_classes.contains(name) {
+                continue;
+            }
+            let mut removed_methods = BTreeSet::new();
+            let mut removed_fields = BTreeSet::new();
+            let mut table = SymbolTable::from_class(class);
+            let mut writer = ClassWriter::new();
+            accept_class(class, &mut RemoveUnreachable {
+                next: &mut writer,
+                class: name,
+                cp: &class.constant_pool,
+                reached: &reached,
+                removed_classes: &removed_classes,
+                removed_methods: &mut removed_methods,
+                removed_fields: &mut removed_fields,
+            }, &mut table)?;
+            let mut shrunk = writer.to_class(&mut table)?;
+            shrunk.compact_constant_pool(Order::Original)?;
+            ans.removed_methods.extend(removed_methods);
+            ans.removed_fields.extend(removed_fields);
+            ans.classes.insert(name.clone(), shrunk);
+        }
+        ans.removed_classes = removed_classes;
+        Ok(ans)
+    }
+}
+
+const OBJECT_METHODS: &[(&str, &str)] = &[
+    ("equals", "(Ljava/lang/Object;)Z"),
+    ("hashCode", "()I"),
+    ("toString", "()Ljava/lang/String;"),
+    ("clone", "()Ljava/lang/Object;"),
+    ("finalize", "()V"),
+];
+
+/// The parts of a program class the walk looks at
+struct ProgramClass {
+    access_flags: u16,
+    /// annotation types, as descriptors
+    annotations: Vec<String>,
+    /// classes used by the class itself rather than by a member
+    uses: Vec<String>,
+    fields: BTreeMap<(String, String), Member>,
+    methods: BTreeMap<(String, String), Member>,
+}
+
+#[derive(Default)]
+struct Member {
+    access_flags: u16,
+    annotations: Vec<String>,
+    uses: Uses,
+}
+
+/// Everything a member refers to
+#[derive(Default)]
+struct Uses {
+    classes: Vec<String>,
+    fields: Vec<MemberRef>,
+    /// calls that are dispatched on the receiver's class
+    virtual_calls: Vec<MemberRef>,
+    /// calls whose target is known statically
+    direct_calls: Vec<MemberRef>,
+}
+
+impl ProgramClass {
+    fn new(class: &JavaClass) -> io::Result<ProgramClass> {
+        let cp = &class.constant_pool;
+        let mut uses = Uses::default();
+        let annotations = scan_attributes(class, &class.attributes, &mut uses)?;
+        let members = |list: Vec<(u16, ::cp::CPIndex, ::cp::CPIndex, &Vec<Attribute>)>| -> io::Result<BTreeMap<(String, String), Member>> {
+            let mut ans = BTreeMap::new();
+            for (access_flags, name, descriptor, attributes) in list {
+                let name = cp.get_utf8(name).ok_or_else(|| malformed("Member name did not point to Utf8"))?;
+                let descriptor = cp.get_utf8(descriptor).ok_or_else(|| malformed("Member descriptor did not point to Utf8"))?;
+                let mut m = Member { access_flags, ..Member::default() };
+                m.uses.classes.extend(signature_classes(&descriptor));
+                m.annotations = scan_attributes(class, attributes, &mut m.uses)?;
+                ans.insert((name, descriptor), m);
+            }
+            Ok(ans)
+        };
+        let fields = members(class.fields.iter().map(|f| (f.access_flags, f.name_index, f.descriptor_index, &f.attributes)).collect())?;
+        let methods = members(class.methods.iter().map(|m| (m.access_flags, m.name_index, m.descriptor_index, &m.attributes)).collect())?;
+        Ok(ProgramClass { access_flags: class.access_flags, annotations, uses: uses.classes, fields, methods })
+    }
+}
+
+/// Collects what a list of attributes uses, and returns the types of the annotations among them
+fn scan_attributes(class: &JavaClass, attributes: &[Attribute], uses: &mut Uses) -> io::Result<Vec<String>> {
+    let cp = &class.constant_pool;
+    let mut annotations = vec!();
+    for a in attributes {
+        match a {
+            Attribute::Code { .. } => {
+                let code = CodeBody::from_attribute(a, cp, class.bootstrap_methods())?;
+                uses.classes.extend(code.exception_table.iter().filter_map(|e| e.catch_type.clone()));
+                for op in &code.instructions {
+                    scan_instruction(op, uses);
+                }
+            }
+            Attribute::Exceptions { exception_index_table } => {
+                uses.classes.extend(exception_index_table.iter().filter_map(|i| cp.get_class_name((*i).into())));
+            }
+            Attribute::Signature { signature_index } => {
+                uses.classes.extend(cp.get_utf8(*signature_index).iter().flat_map(|s| signature_classes(s)));
+            }
+            Attribute::EnclosingMethod { class_index, .. } => uses.classes.extend(cp.get_class_name(*class_index)),
+            Attribute::RuntimeVisibleAnnotations { annotations: list } |
+            Attribute::RuntimeInvisibleAnnotations { annotations: list } => {
+                for an in list {
+                    annotations.extend(cp.get_utf8(an.type_index));
+                    scan_annotation(cp, an, uses);
+                }
+            }
+            Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } |
+            Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+                for an in parameter_annotations.iter().flatten() {
+                    scan_annotation(cp, an, uses);
+                }
+            }
+            Attribute::AnnotationDefault { default_value } => scan_element_value(cp, default_value, uses),
+            _ => {}
+        }
+    }
+    Ok(annotations)
+}
+
+fn scan_annotation(cp: &::cp::ConstantPool, an: &Annotation, uses: &mut Uses) {
+    uses.classes.extend(cp.get_utf8(an.type_index).iter().flat_map(|s| signature_classes(s)));
+    for p in &an.element_value_pairs {
+        scan_element_value(cp, &p.value, uses);
+    }
+}
+
+fn scan_element_value(cp: &::cp::ConstantPool, v: &ElementValue, uses: &mut Uses) {
+    match v {
+        ElementValue::EnumConstValue { type_name_index, .. } => {
+            uses.classes.extend(cp.get_utf8(*type_name_index).iter().flat_map(|s| signature_classes(s)));
+        }
+        ElementValue::ClassInfoIndex(i) => uses.classes.extend(cp.get_utf8(*i).iter().flat_map(|s| signature_classes(s))),
+        ElementValue::AnnotationValue(an) => scan_annotation(cp, an, uses),
+        ElementValue::ArrayValue(values) => {
+            for v in values {
+                scan_element_value(cp, v, uses);
+            }
+        }
+        ElementValue::ConstValueIndex { .. } => {}
+    }
+}
+
+fn scan_instruction(op: &SymOpcode, uses: &mut Uses) {
+    match op {
+        SymOpcode::Ldc(c) => scan_constant(c, uses),
+        SymOpcode::GetStatic(m) | SymOpcode::PutStatic(m) | SymOpcode::GetField(m) | SymOpcode::PutField(m) => uses.fields.push(m.clone()),
+        SymOpcode::InvokeVirtual(m) | SymOpcode::InvokeInterface(m) => uses.virtual_calls.push(m.clone()),
+        SymOpcode::InvokeSpecial(m) | SymOpcode::InvokeStatic(m) => uses.direct_calls.push(m.clone()),
+        SymOpcode::InvokeDynamic { bootstrap, descriptor, .. } => {
+            scan_bootstrap(bootstrap, uses);
+            uses.classes.extend(signature_classes(descriptor));
+        }
+        SymOpcode::New(c) | SymOpcode::ANewArray(c) | SymOpcode::CheckCast(c) | SymOpcode::InstanceOf(c) |
+        SymOpcode::MultiANewArray { class: c, .. } => uses.classes.push(element_class(c)),
+        _ => {}
+    }
+}
+
+fn scan_bootstrap(b: &BootstrapMethod, uses: &mut Uses) {
+    scan_handle(&b.handle, uses);
+    for c in &b.arguments {
+        scan_constant(c, uses);
+    }
+}
+
+fn scan_constant(c: &Constant, uses: &mut Uses) {
+    match c {
+        Constant::Class(c) => uses.classes.push(element_class(c)),
+        Constant::MethodType(d) => uses.classes.extend(signature_classes(d)),
+        Constant::MethodHandle(h) => scan_handle(h, uses),
+        _ => {}
+    }
+}
+
+fn scan_handle(h: &MethodHandle, uses: &mut Uses) {
+    match h.reference_kind {
+        1..=4 => uses.fields.push(h.member.clone()),
+        5 | 9 => uses.virtual_calls.push(h.member.clone()),
+        _ => uses.direct_calls.push(h.member.clone()),
+    }
+}
+
+/// Turns the operand of `anewarray` or `ldc` (a class name or an array descriptor) into a class name
+fn element_class(name: &str) -> String {
+    if name.starts_with('[') {
+        signature_classes(name).into_iter().next().unwrap_or_default()
+    } else {
+        name.to_owned()
+    }
+}
+
+enum Item {
+    Class(String),
+    Method(MethodId),
+    Field(FieldId),
+}
+
+/// The supertypes of a library class, and the names and descriptors of its overridable methods
+type LibraryClass = (Vec<String>, BTreeSet<(String, String)>);
+
+/// The state of a reachability search
+struct Walk<'a> {
+    shrinker: &'a Shrinker,
+    program: HashMap<String, ProgramClass>,
+    /// the instance methods of library classes that have been looked up, or `None` for classes that couldn't be found
+    library: HashMap<String, Option<LibraryClass>>,
+    reached: Reachable,
+    queue: VecDeque<Item>,
+    /// the owners of the virtual calls made so far, by name and descriptor
+    virtual_calls: HashMap<(String, String), BTreeSet<String>>,
+}
+
+impl<'a> Walk<'a> {
+    fn apply_rules(&mut self) {
+        let mut classes = vec!();
+        let mut methods = vec!();
+        let mut fields = vec!();
+        for (name, class) in &self.program {
+            let mut keep = |filter: &dyn Fn(&str, &str, &Member) -> bool| {
+                methods.extend(class.methods.iter().filter(|((n, d), m)| filter(n, d, m)).map(|((n, d), _)| MethodId::new(name, n, d)));
+                fields.extend(class.fields.iter().filter(|((n, d), m)| filter(n, d, m)).map(|((n, d), _)| FieldId::new(name, n, d)));
+            };
+            for rule in &self.shrinker.rules {
+                match rule {
+                    KeepRule::Classes(p) if matches_class(p, name) => keep(&|_, _, _| true),
+                    KeepRule::Members { class: p, member } if matches_class(p, name) => {
+                        keep(&|n, _, _| glob(member.as_bytes(), n.as_bytes(), false))
+                    }
+                    KeepRule::AnnotatedClasses(a) if class.annotations.contains(&format!("L{};", a)) => keep(&|_, _, _| true),
+                    KeepRule::AnnotatedMembers(a) => {
+                        //the class is reached through its members, if any match
+                        let a = format!("L{};", a);
+                        keep(&|_, _, m| m.annotations.contains(&a));
+                        continue;
+                    }
+                    KeepRule::Main(p) if matches_class(p, name) => keep(&|n, d, m| {
+                        n == "main" && d == "([Ljava/lang/String;)V" && AccessFlags::from_bits(m.access_flags).contains(AccessFlags::STATIC)
+                    }),
+                    _ => continue,
+                }
+                classes.push(name.clone());
+            }
+        }
+        self.queue.extend(classes.into_iter().map(Item::Class));
+        self.queue.extend(methods.into_iter().map(Item::Method));
+        self.queue.extend(fields.into_iter().map(Item::Field));
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(item) = self.queue.pop_front() {
+            match item {
+                Item::Class(c) => self.visit_class(c)?,
+                Item::Method(m) => self.visit_method(m),
+                Item::Field(f) => self.visit_field(f),
+            }
+        }
+        Ok(())
+    }
+
+    fn mark_class(&mut self, name: &str) {
+        if self.program.contains_key(name) && !self.reached.classes.contains(name) {
+            self.queue.push_back(Item::Class(name.to_owned()));
+        }
+    }
+
+    fn mark_method(&mut self, m: MethodId) {
+        if !self.reached.methods.contains(&m) {
+            self.queue.push_back(Item::Method(m));
+        }
+    }
+
+    fn visit_class(&mut self, name: String) -> io::Result<()> {
+        if !self.reached.classes.insert(name.clone()) {
+            return Ok(());
+        }
+        let class = &self.program[&name];
+        let mut classes: Vec<String> = class.uses.clone();
+        classes.extend(self.shrinker.hierarchy.get(&name).iter().flat_map(|n| n.supertypes().cloned()));
+        let mut methods = vec!();
+        if class.methods.contains_key(&("<clinit>".to_owned(), "()V".to_owned())) {
+            methods.push(("<clinit>".to_owned(), "()V".to_owned()));
+        }
+        if ::class::AccessFlags::from_bits(class.access_flags).contains(::class::AccessFlags::ENUM) {
+            //`Enum.valueOf` and `EnumSet` find these reflectively
+            let values = format!("()[L{};", name);
+            methods.extend(class.methods.keys().filter(|(n, d)| (n == "values" && *d == values) || n == "valueOf").cloned());
+        }
+        let overridable: Vec<(String, String)> = class.methods.iter()
+            .filter(|((n, _), m)| is_overridable(n, m.access_flags))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for (n, d) in overridable {
+            if self.is_called_virtually(&name, &n, &d) || self.overrides_library(&name, &n, &d)? {
+                methods.push((n, d));
+            }
+        }
+        for c in classes {
+            self.mark_class(&c);
+        }
+        for (n, d) in methods {
+            self.mark_method(MethodId::new(&name, &n, &d));
+        }
+        Ok(())
+    }
+
+    fn visit_method(&mut self, m: MethodId) {
+        if !self.program.get(&m.owner).is_some_and(|c| c.methods.contains_key(&(m.name.clone(), m.descriptor.clone()))) {
+            return;
+        }
+        if !self.reached.methods.insert(m.clone()) {
+            return;
+        }
+        self.mark_class(&m.owner);
+        let uses = &self.program[&m.owner].methods[&(m.name.clone(), m.descriptor.clone())].uses;
+        let classes: Vec<String> = uses.classes.iter().chain(uses.fields.iter().chain(&uses.virtual_calls).chain(&uses.direct_calls).map(|r| &r.owner)).cloned().collect();
+        let fields: Vec<FieldId> = uses.fields.iter().filter_map(|f| self.resolve_field(f)).collect();
+        let direct: Vec<MethodId> = uses.direct_calls.iter().filter_map(|c| self.resolve_method(&c.owner, &c.name, &c.descriptor)).collect();
+        let virtual_calls: Vec<MemberRef> = uses.virtual_calls.clone();
+        for c in classes {
+            self.mark_class(&c);
+        }
+        for f in fields {
+            self.queue.push_back(Item::Field(f));
+        }
+        for m in direct {
+            self.mark_method(m);
+        }
+        for call in virtual_calls {
+            if let Some(decl) = self.resolve_method(&call.owner, &call.name, &call.descriptor) {
+                self.mark_method(decl);
+            }
+            let key = (call.name.clone(), call.descriptor.clone());
+            if !self.virtual_calls.entry(key).or_default().insert(call.owner.clone()) {
+                continue;
+            }
+            //overrides in classes that were reached before the call was
+            let targets: Vec<MethodId> = self.shrinker.hierarchy.subtypes(&call.owner).into_iter()
+                .filter(|s| self.reached.classes.contains(s))
+                .filter(|s| self.program[s].methods.get(&(call.name.clone(), call.descriptor.clone())).is_some_and(|m| is_overridable(&call.name, m.access_flags)))
+                .map(|s| MethodId::new(&s, &call.name, &call.descriptor))
+                .collect();
+            for t in targets {
+                self.mark_method(t);
+            }
+        }
+    }
+
+    fn visit_field(&mut self, f: FieldId) {
+        let field = match self.program.get(&f.owner).and_then(|c| c.fields.get(&(f.name.clone(), f.descriptor.clone()))) {
+            Some(field) => field,
+            None => return,
+        };
+        if !self.reached.fields.insert(f.clone()) {
+            return;
+        }
+        let classes: Vec<String> = ::std::iter::once(f.owner.clone()).chain(field.uses.classes.iter().cloned()).collect();
+        for c in classes {
+            self.mark_class(&c);
+        }
+    }
+
+    /// Finds the program class declaring a referenced field: the class, its superinterfaces, then its superclass (JVMS §5.4.3.2)
+    fn resolve_field(&self, f: &MemberRef) -> Option<FieldId> {
+        let key = (f.name.clone(), f.descriptor.clone());
+        ::std::iter::once(f.owner.clone())
+            .chain(self.shrinker.hierarchy.supertypes(&f.owner))
+            .find(|c| self.program.get(c).is_some_and(|c| c.fields.contains_key(&key)))
+            .map(|c| FieldId::new(&c, &f.name, &f.descriptor))
+    }
+
+    /// Finds the program class declaring a referenced method: the class and its superclasses, then its superinterfaces (JVMS §5.4.3.3)
+    fn resolve_method(&self, owner: &str, name: &str, descriptor: &str) -> Option<MethodId> {
+        let key = (name.to_owned(), descriptor.to_owned());
+        let h = &self.shrinker.hierarchy;
+        ::std::iter::once(owner.to_owned())
+            .chain(h.superclasses(owner))
+            .chain(h.supertypes(owner))
+            .find(|c| self.program.get(c).is_some_and(|c| c.methods.contains_key(&key)))
+            .map(|c| MethodId::new(&c, name, descriptor))
+    }
+
+    fn is_called_virtually(&self, class: &str, name: &str, descriptor: &str) -> bool {
+        self.virtual_calls.get(&(name.to_owned(), descriptor.to_owned()))
+            .is_some_and(|owners| owners.iter().any(|o| self.shrinker.hierarchy.is_subtype_of(class, o)))
+    }
+
+    /// Returns true if a method may override a method of a library class
+    fn overrides_library(&mut self, class: &str, name: &str, descriptor: &str) -> io::Result<bool> {
+        let key = (name.to_owned(), descriptor.to_owned());
+        //every class is a subtype of Object, even if the hierarchy can't show it
+        let mut library: Vec<String> = vec!("java/lang/Object".to_owned());
+        library.extend(self.shrinker.hierarchy.missing_supertypes(class));
+        let mut seen = BTreeSet::new();
+        while let Some(l) = library.pop() {
+            if !seen.insert(l.clone()) {
+                continue;
+            }
+            match self.library_class(&l)? {
+                Some((supertypes, methods)) => {
+                    if methods.contains(&key) {
+                        return Ok(true);
+                    }
+                    library.extend(supertypes);
+                }
+                None if l == "java/lang/Object" => {
+                    if OBJECT_METHODS.contains(&(name, descriptor)) {
+                        return Ok(true);
+                    }
+                }
+                None => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Looks up the supertypes and overridable methods of a library class
+    fn library_class(&mut self, name: &str) -> io::Result<Option<LibraryClass>> {
+        if !self.library.contains_key(name) {
+            let found = match &self.shrinker.library {
+                Some(cp) => cp.find_class(name)?,
+                None => None,
+            };
+            let entry = match found {
+                Some(class) => {
+                    let node = ClassNode::from_class(&class)?;
+                    let cp = &class.constant_pool;
+                    let methods = class.methods.iter()
+                        .filter_map(|m| Some((cp.get_utf8(m.name_index)?, cp.get_utf8(m.descriptor_index)?, m.access_flags)))
+                        .filter(|(n, _, flags)| is_overridable(n, *flags))
+                        .map(|(n, d, _)| (n, d))
+                        .collect();
+                    Some((node.supertypes().cloned().collect(), methods))
+                }
+                None => None,
+            };
+            self.library.insert(name.to_owned(), entry);
+        }
+        Ok(self.library[name].clone())
+    }
+}
+
+/// Returns true if a method takes part in virtual dispatch
+fn is_overridable(name: &str, access_flags: u16) -> bool {
+    !AccessFlags::from_bits(access_flags).intersects(AccessFlags::STATIC | AccessFlags::PRIVATE) && !name.starts_with('<')
+}
+
+/// Drops the members that weren't reached, and the inner class, nest member and permitted subclass entries
+/// for classes that were removed
+struct RemoveUnreachable<'a> {
+    next: &'a mut dyn ClassVisitor,
+    class: &'a str,
+    cp: &'a ::cp::ConstantPool,
+    reached: &'a Reachable,
+    removed_classes: &'a BTreeSet<String>,
+    removed_methods: &'a mut BTreeSet<MethodId>,
+    removed_fields: &'a mut BTreeSet<FieldId>,
+}
+
+impl<'a> ClassVisitor for RemoveUnreachable<'a> {
+    fn delegate(&mut self) -> Option<&mut dyn ClassVisitor> {
+        Some(self.next)
+    }
+
+    fn visit_field<'b>(&'b mut self, table: &mut SymbolTable, field: MemberHeader) -> Option<Box<dyn FieldVisitor + 'b>> {
+        let id = FieldId::new(self.class, &field.name, &field.descriptor);
+        if !self.reached.fields.contains(&id) {
+            self.removed_fields.insert(id);
+            return None;
+        }
+        self.next.visit_field(table, field)
+    }
+
+    fn visit_method<'b>(&'b mut self, table: &mut SymbolTable, method: MemberHeader) -> Option<Box<dyn MethodVisitor + 'b>> {
+        let id = MethodId::new(self.class, &method.name, &method.descriptor);
+        if !self.reached.methods.contains(&id) {
+            self.removed_methods.insert(id);
+            return None;
+        }
+        self.next.visit_method(table, method)
+    }
+
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        match attribute {
+            Attribute::InnerClasses { mut classes } => {
+                let (cp, removed) = (self.cp, self.removed_classes);
+                classes.retain(|c| [c.inner_class_info_index, c.outer_class_info_index].iter()
+                    .filter_map(|i| cp.get_class_name(*i))
+                    .all(|n| !removed.contains(&n)));
+                self.next.visit_attribute(table, Attribute::InnerClasses { classes });
+            }
+            Attribute::Unknown { name_index, info } => {
+                let name = self.cp.get_utf8(name_index).unwrap_or_default();
+                match UndecodedContents::read(&name, &info, self.cp) {
+                    Ok(Some(UndecodedContents::ClassList(mut classes))) => {
+                        let (cp, removed) = (self.cp, self.removed_classes);
+                        classes.retain(|i| cp.get_class_name(*i).is_none_or(|n| !removed.contains(&n)));
+                        //a list with nothing left in it says nothing
+                        if !classes.is_empty() {
+                            let info = UndecodedContents::ClassList(classes).to_bytes();
+                            self.next.visit_attribute(table, Attribute::Unknown { name_index, info });
+                        }
+                    }
+                    _ => self.next.visit_attribute(table, Attribute::Unknown { name_index, info }),
+                }
+            }
+            a => self.next.visit_attribute(table, a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use attributes::Annotation;
+    use check::check_format;
+    use opcodes::Opcode::*;
+    use std::fs;
+    use test_classes::{interface_method, member, ClassSpec};
+
+    fn init(superclass: &str) -> Vec<SymOpcode> {
+        vec![SymOpcode::Plain(aload_0), SymOpcode::InvokeSpecial(member(superclass, "<init>", "()V")), SymOpcode::Plain(return_)]
+    }
+
+    /// A class with a constructor, extending `superclass`
+    fn class(name: &str, superclass: &str) -> ClassSpec {
+        ClassSpec::new(0x0021, name, Some(superclass)).method(0x0001, "<init>", "()V", init(superclass))
+    }
+
+    /// `p/Main`, whose `main` runs `code` and returns
+    fn main(mut code: Vec<SymOpcode>) -> ClassSpec {
+        code.push(SymOpcode::Plain(return_));
+        class("p/Main", "java/lang/Object").method(0x0009, "main", "([Ljava/lang/String;)V", code)
+    }
+
+    fn create(class: &str) -> Vec<SymOpcode> {
+        vec![SymOpcode::New(class.to_owned()), SymOpcode::Plain(dup), SymOpcode::InvokeSpecial(member(class, "<init>", "()V"))]
+    }
+
+    fn shrinker(classes: Vec<JavaClass>, rules: &str) -> Shrinker {
+        let mut ans = Shrinker::new();
+        for c in classes {
+            ans.add_class(c).unwrap();
+        }
+        for rule in KeepRule::parse_all(rules).unwrap() {
+            ans.keep(rule);
+        }
+        ans
+    }
+
+    fn names<'a, T: ToString + 'a, I: IntoIterator<Item = &'a T>>(items: I) -> Vec<String> {
+        items.into_iter().map(T::to_string).collect()
+    }
+
+    /// The classes named by the class's `NestMembers` and `PermittedSubclasses` attributes
+    fn class_lists(class: &JavaClass) -> Vec<(String, Vec<String>)> {
+        let cp = &class.constant_pool;
+        class.attributes.iter().filter_map(|a| match a {
+            Attribute::Unknown { name_index, info } => {
+                let name = cp.utf8(*name_index).unwrap();
+                match UndecodedContents::read(&name, info, cp).unwrap() {
+                    Some(UndecodedContents::ClassList(classes)) => Some((name, classes.iter().map(|i| cp.class_name(*i).unwrap()).collect())),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn class_patterns() {
+        for &(pattern, name, matches) in &[
+            ("p.*", "p/A", true), ("p.*", "p/q/A", false), ("p.**", "p/q/A", true), ("p/**", "p/A", true),
+            ("p.?", "p/A", true), ("p.?", "p/AB", false), ("p?A", "p/A", false), ("**.A", "p/q/A", true),
+            ("p.*Test", "p/FooTest", true), ("p.*Test", "p/q/FooTest", false), ("p.A$*", "p/A$1", true),
+        ] {
+            assert_eq!(matches_class(pattern, name), matches, "{} {}", pattern, name);
+        }
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rules = "# comment\n\nclass p.**\nmembers p.A get*\nannotated-class p.Keep\n  annotated-members p.Keep\nmain p.Main\n";
+        assert_eq!(KeepRule::parse_all(rules).unwrap(), vec![
+            KeepRule::Classes("p.**".to_owned()),
+            KeepRule::Members { class: "p.A".to_owned(), member: "get*".to_owned() },
+            KeepRule::AnnotatedClasses("p/Keep".to_owned()),
+            KeepRule::AnnotatedMembers("p/Keep".to_owned()),
+            KeepRule::Main("p.Main".to_owned()),
+        ]);
+        assert!(KeepRule::parse("members p.A").is_err());
+        assert!(KeepRule::parse("keep p.A").is_err());
+    }
+
+    /// Only the implementations in classes that get created are kept for a call through an interface
+    #[test]
+    fn interface_calls_keep_the_overrides_of_created_classes() {
+        let shape = ClassSpec::new(0x0601, "p/Shape", Some("java/lang/Object")).method(0x0401, "area", "()D", vec![]).build();
+        let implementation = |name| class(name, "java/lang/Object").implements(&["p/Shape"])
+            .method(0x0001, "area", "()D", vec![SymOpcode::Plain(dconst_0), SymOpcode::Plain(dreturn)])
+            .method(0x0001, "scale", "()V", vec![SymOpcode::Plain(return_)])
+            .build();
+        let mut code = create("p/Circle");
+        code.extend(vec![SymOpcode::InvokeInterface(interface_method("p/Shape", "area", "()D")), SymOpcode::Plain(pop2)]);
+        let classes = vec![main(code).build(), shape, implementation("p/Circle"), implementation("p/Square")];
+
+        let shrunk = shrinker(classes, "main p.Main").shrink().unwrap();
+        assert_eq!(names(shrunk.classes.keys()), vec!["p/Circle", "p/Main", "p/Shape"]);
+        assert_eq!(names(&shrunk.removed_classes), vec!["p/Square"]);
+        assert_eq!(names(&shrunk.removed_methods), vec!["p/Circle.scale()V", "p/Main.<init>()V"]);
+        let circle = &shrunk.classes["p/Circle"];
+        assert_eq!(circle.methods.len(), 2);
+        assert!(check_format(circle).is_empty());
+    }
+
+    /// Methods that may override a library method are kept even though the program never calls them
+    #[test]
+    fn overrides_of_library_methods_are_kept() {
+        let dir = ::std::env::temp_dir().join(format!("java_class_shrink_{}", ::std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let base = class("lib/Base", "java/lang/Object").method(0x0001, "onEvent", "()V", vec![SymOpcode::Plain(return_)]).build();
+        fs::write(dir.join("lib/Base.class"), base.to_bytes()).unwrap();
+
+        let handler = || class("p/Handler", "lib/Base")
+            .method(0x0001, "onEvent", "()V", vec![SymOpcode::Plain(return_)])
+            .method(0x0001, "toString", "()Ljava/lang/String;", vec![SymOpcode::Plain(aconst_null), SymOpcode::Plain(areturn)])
+            .method(0x0001, "helper", "()V", vec![SymOpcode::Plain(return_)])
+            .build();
+        let classes = || vec![main(create("p/Handler")).build(), handler()];
+
+        let mut with_library = shrinker(classes(), "main p.Main");
+        with_library.set_library(ClassPath::from_paths([&dir]).unwrap());
+        let reached = with_library.reachable().unwrap();
+        assert!(reached.methods.contains(&MethodId::new("p/Handler", "onEvent", "()V")));
+        assert!(reached.methods.contains(&MethodId::new("p/Handler", "toString", "()Ljava/lang/String;")));
+        assert!(!reached.methods.contains(&MethodId::new("p/Handler", "helper", "()V")));
+
+        //without the library, anything could override a method of lib/Base
+        let reached = shrinker(classes(), "main p.Main").reachable().unwrap();
+        assert!(reached.methods.contains(&MethodId::new("p/Handler", "helper", "()V")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_rules_choose_the_roots() {
+        let annotation = |cp: &mut ::builders::cp::CPBuilder| Attribute::RuntimeVisibleAnnotations {
+            annotations: vec![Annotation { type_index: cp.add_utf8("Lp/Keep;".to_owned()), element_value_pairs: vec![] }],
+        };
+        let classes = vec![
+            class("p/api/Service", "java/lang/Object")
+                .method(0x0001, "getName", "()V", vec![SymOpcode::Plain(return_)])
+                .method(0x0001, "setName", "()V", vec![SymOpcode::Plain(return_)])
+                .build(),
+            class("p/Fields", "java/lang/Object").field(0x0001, "kept", "I").field_attribute(annotation).field(0x0001, "dropped", "I").build(),
+            class("p/Entry", "java/lang/Object").attribute(annotation).method(0x0001, "run", "()V", vec![SymOpcode::Plain(return_)]).build(),
+            class("p/Unused", "java/lang/Object").build(),
+        ];
+        let reached = shrinker(classes, "members p.api.* get*\nannotated-members p.Keep\nannotated-class p.Keep").reachable().unwrap();
+        assert_eq!(names(&reached.classes), vec!["p/Entry", "p/Fields", "p/api/Service"]);
+        assert_eq!(names(&reached.methods), vec!["p/Entry.<init>()V", "p/Entry.run()V", "p/api/Service.getName()V"]);
+        assert_eq!(names(&reached.fields), vec!["p/Fields.kept:I"]);
+    }
+
+    /// A lambda's implementation method is reached through the bootstrap method arguments of its `invokedynamic`
+    #[test]
+    fn lambdas_keep_their_implementation_methods() {
+        let handle = |kind, m| Constant::MethodHandle(MethodHandle { reference_kind: kind, member: m });
+        let metafactory = MethodHandle {
+            reference_kind: 6,
+            member: member("java/lang/invoke/LambdaMetafactory", "metafactory",
+                           "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;\
+                            Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/CallSite;"),
+        };
+        let lambda = SymOpcode::InvokeDynamic {
+            bootstrap: BootstrapMethod {
+                handle: metafactory,
+                arguments: vec![Constant::MethodType("()V".to_owned()), handle(6, member("p/Main", "lambda$main$0", "()V")), Constant::MethodType("()V".to_owned())],
+            },
+            name: "run".to_owned(),
+            descriptor: "()Ljava/lang/Runnable;".to_owned(),
+        };
+        let classes = vec![
+            main(vec![lambda, SymOpcode::Plain(pop)])
+                .method(0x100a, "lambda$main$0", "()V", vec![SymOpcode::InvokeStatic(member("p/Helper", "help", "()V")), SymOpcode::Plain(return_)])
+                .method(0x100a, "lambda$unused$1", "()V", vec![SymOpcode::Plain(return_)])
+                .build(),
+            ClassSpec::new(0x0021, "p/Helper", Some("java/lang/Object")).method(0x0009, "help", "()V", vec![SymOpcode::Plain(return_)]).build(),
+        ];
+
+        let shrunk = shrinker(classes, "main p.Main").shrink().unwrap();
+        assert_eq!(names(shrunk.classes.keys()), vec!["p/Helper", "p/Main"]);
+        assert_eq!(names(&shrunk.removed_methods), vec!["p/Main.<init>()V", "p/Main.lambda$unused$1()V"]);
+        let main = &shrunk.classes["p/Main"];
+        assert_eq!(main.bootstrap_methods().len(), 1);
+        assert!(check_format(main).is_empty());
+    }
+
+    #[test]
+    fn removed_classes_leave_nests_and_permitted_subclasses() {
+        let list = |name: &'static str| move |cp: &mut ::builders::cp::CPBuilder| {
+            let classes = vec![cp.add_class("p/Outer$Used".to_owned()), cp.add_class("p/Outer$Gone".to_owned())];
+            Attribute::Unknown { name_index: cp.add_utf8(name.to_owned()), info: UndecodedContents::ClassList(classes).to_bytes() }
+        };
+        let host = |cp: &mut ::builders::cp::CPBuilder| {
+            Attribute::Unknown { name_index: cp.add_utf8("NestHost".to_owned()), info: UndecodedContents::NestHost(cp.add_class("p/Outer".to_owned())).to_bytes() }
+        };
+        let classes = || vec![
+            ClassSpec::new(0x0421, "p/Outer", Some("java/lang/Object")).attribute(list("NestMembers")).attribute(list("PermittedSubclasses")).build(),
+            ClassSpec::new(0x0031, "p/Outer$Used", Some("p/Outer")).attribute(host).build(),
+            ClassSpec::new(0x0031, "p/Outer$Gone", Some("p/Outer")).attribute(host).build(),
+        ];
+
+        let shrunk = shrinker(classes(), "class p.Outer$Used").shrink().unwrap();
+        assert_eq!(names(&shrunk.removed_classes), vec!["p/Outer$Gone"]);
+        assert_eq!(class_lists(&shrunk.classes["p/Outer"]), vec![
+            ("NestMembers".to_owned(), vec!["p/Outer$Used".to_owned()]),
+            ("PermittedSubclasses".to_owned(), vec!["p/Outer$Used".to_owned()]),
+        ]);
+
+        //with none of the classes left, the attributes go too
+        let shrunk = shrinker(classes(), "class p.Outer").shrink().unwrap();
+        assert_eq!(names(shrunk.classes.keys()), vec!["p/Outer"]);
+        assert_eq!(class_lists(&shrunk.classes["p/Outer"]), vec![]);
+    }
+}
@@ -0,0 +1,569 @@
+use attributes::BootstrapMethodsEntry;
+use builders::cp::CPBuilder;
+use bytecode_tools::instruction_offsets;
+use cp::{CPIndex, ConstantPool};
+use cp_info::CPInfo;
+use descriptor::MethodDescriptor;
+use malformed;
+use opcodes::Opcode;
+use std::convert::TryInto;
+use std::io;
+
+/// A constant pool value that can be loaded by `ldc` or passed to a bootstrap method
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    /// a class, by binary name
+    Class(String),
+    /// a method descriptor
+    MethodType(String),
+    MethodHandle(MethodHandle),
+}
+
+impl PartialEq for Constant {
+    /// Floating point constants are compared by their bits, so NaN constants are equal to themselves
+    fn eq(&self, other: &Constant) -> bool {
+        use self::Constant::*;
+        match (self, other) {
+            (Integer(a), Integer(b)) => a == b,
+            (Float(a), Float(b)) => a.to_bits() == b.to_bits(),
+            (Long(a), Long(b)) => a == b,
+            (Double(a), Double(b)) => a.to_bits() == b.to_bits(),
+            (String(a), String(b)) => a == b,
+            (Class(a), Class(b)) => a == b,
+            (MethodType(a), MethodType(b)) => a == b,
+            (MethodHandle(a), MethodHandle(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+/// A field or method reference with its owner, name, and descriptor resolved
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemberRef {
+    /// binary name of the class the member belongs to
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+    /// true if the reference was an `InterfaceMethodref`
+    pub is_interface: bool,
+}
+
+/// A resolved `MethodHandle` constant
+/// `reference_kind` is one of the JVMS §5.4.3.5 kinds (1 = getField through 9 = invokeInterface)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodHandle {
+    pub reference_kind: u8,
+    pub member: MemberRef,
+}
+
+/// A resolved entry of the `BootstrapMethods` attribute
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootstrapMethod {
+    pub handle: MethodHandle,
+    pub arguments: Vec<Constant>,
+}
+
+/// The kinds of branch instructions with a single target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BranchKind {
+    Ifeq,
+    Ifne,
+    Iflt,
+    Ifge,
+    Ifgt,
+    Ifle,
+    IfIcmpeq,
+    IfIcmpne,
+    IfIcmplt,
+    IfIcmpge,
+    IfIcmpgt,
+    IfIcmple,
+    IfAcmpeq,
+    IfAcmpne,
+    Ifnull,
+    Ifnonnull,
+    /// `goto` and `goto_w`
+    Goto,
+    /// `jsr` and `jsr_w`
+    Jsr,
+}
+
+/// An opcode whose constant pool operands are resolved to the values they point at,
+/// and whose branch offsets are replaced by the index of the target instruction.
+/// Symbolic code doesn't depend on a particular constant pool, so it can be moved between classes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymOpcode {
+    /// an opcode with no constant pool or branch operands, kept as is
+    Plain(Opcode),
+    /// `ldc`, `ldc_w` and `ldc2_w`
+    Ldc(Constant),
+    GetStatic(MemberRef),
+    PutStatic(MemberRef),
+    GetField(MemberRef),
+    PutField(MemberRef),
+    InvokeVirtual(MemberRef),
+    InvokeSpecial(MemberRef),
+    InvokeStatic(MemberRef),
+    InvokeInterface(MemberRef),
+    InvokeDynamic { bootstrap: BootstrapMethod, name: String, descriptor: String },
+    New(String),
+    ANewArray(String),
+    CheckCast(String),
+    InstanceOf(String),
+    MultiANewArray { class: String, dimensions: u8 },
+    Branch { kind: BranchKind, target: usize },
+    /// `targets` are the targets for `low`, `low + 1` and so on; there has to be at least one,
+    /// and the last key can't be past `i32::MAX`
+    TableSwitch { default: usize, low: i32, targets: Vec<usize> },
+    LookupSwitch { default: usize, pairs: Vec<(i32, usize)> },
+}
+
+/// Converts a method's code to its symbolic form
+/// # Parameters:
+/// * code: the opcodes of a `Code` attribute
+/// * cp: the constant pool the opcodes index into
+/// * bootstrap_methods: the class's `BootstrapMethods`, used to resolve `invokedynamic`
+pub fn to_symbolic(code: &[Opcode], cp: &ConstantPool, bootstrap_methods: &[BootstrapMethodsEntry]) -> io::Result<Vec<SymOpcode>> {
+    use opcodes::Opcode::*;
+    let offsets = instruction_offsets(code);
+    let target = |i: usize, branch: i64| -> io::Result<usize> {
+        let pc = offsets[i] as i64 + branch;
+        if pc < 0 {
+            return Err(malformed("Branch target before start of code"));
+        }
+        //the last offset is the end of the code, which is not a valid target
+        match offsets[..code.len()].binary_search(&(pc as u32)) {
+            Ok(ind) => Ok(ind),
+            Err(_) => Err(malformed("Branch target is not the start of an instruction"))
+        }
+    };
+    let mut ans = Vec::with_capacity(code.len());
+    for (i, op) in code.iter().enumerate() {
+        let kind = match op {
+            ifeq { .. } => Some(BranchKind::Ifeq),
+            ifne { .. } => Some(BranchKind::Ifne),
+            iflt { .. } => Some(BranchKind::Iflt),
+            ifge { .. } => Some(BranchKind::Ifge),
+            ifgt { .. } => Some(BranchKind::Ifgt),
+            ifle { .. } => Some(BranchKind::Ifle),
+            if_icmpeq { .. } => Some(BranchKind::IfIcmpeq),
+            if_icmpne { .. } => Some(BranchKind::IfIcmpne),
+            if_icmplt { .. } => Some(BranchKind::IfIcmplt),
+            if_icmpge { .. } => Some(BranchKind::IfIcmpge),
+            if_icmpgt { .. } => Some(BranchKind::IfIcmpgt),
+            if_icmple { .. } => Some(BranchKind::IfIcmple),
+            if_acmpeq { .. } => Some(BranchKind::IfAcmpeq),
+            if_acmpne { .. } => Some(BranchKind::IfAcmpne),
+            ifnull { .. } => Some(BranchKind::Ifnull),
+            ifnonnull { .. } => Some(BranchKind::Ifnonnull),
+            goto { .. } | goto_w { .. } => Some(BranchKind::Goto),
+            jsr { .. } | jsr_w { .. } => Some(BranchKind::Jsr),
+            _ => None
+        };
+        if let Some(kind) = kind {
+            let branch = match op {
+                goto_w { branch } | jsr_w { branch } => *branch as i64,
+                _ => branch_of(op) as i64
+            };
+            ans.push(SymOpcode::Branch { kind, target: target(i, branch)? });
+            continue;
+        }
+        ans.push(match op {
+            ldc { index } | ldc_w { index } | ldc2_w { index } => SymOpcode::Ldc(resolve_constant(cp, *index)?),
+            getstatic { index } => SymOpcode::GetStatic(resolve_member(cp, *index)?),
+            putstatic { index } => SymOpcode::PutStatic(resolve_member(cp, *index)?),
+            getfield { index } => SymOpcode::GetField(resolve_member(cp, *index)?),
+            putfield { index } => SymOpcode::PutField(resolve_member(cp, *index)?),
+            invokevirtual { index } => SymOpcode::InvokeVirtual(resolve_member(cp, *index)?),
+            invokespecial { index } => SymOpcode::InvokeSpecial(resolve_member(cp, *index)?),
+            invokestatic { index } => SymOpcode::InvokeStatic(resolve_member(cp, *index)?),
+            invokeinterface { index, .. } => SymOpcode::InvokeInterface(resolve_member(cp, *index)?),
+            invokedynamic { index } => {
+                let (bsm, nat) = match cp.get(*index) {
+                    Some(CPInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index }) => (*bootstrap_method_attr_index, *name_and_type_index),
+                    _ => return Err(malformed("invokedynamic did not point to InvokeDynamic"))
+                };
+                let (name, descriptor) = cp.get_name_and_type(nat).ok_or_else(|| malformed("Bad NameAndType"))?;
+                let entry = bootstrap_methods.get(bsm.as_u16() as usize).ok_or_else(|| malformed("Bootstrap method index out of range"))?;
+                SymOpcode::InvokeDynamic { bootstrap: resolve_bootstrap_method(cp, entry)?, name, descriptor }
+            }
+            new { index } => SymOpcode::New(resolve_class(cp, *index)?),
+            anewarray { index } => SymOpcode::ANewArray(resolve_class(cp, *index)?),
+            checkcast { index } => SymOpcode::CheckCast(resolve_class(cp, *index)?),
+            instanceof { index } => SymOpcode::InstanceOf(resolve_class(cp, *index)?),
+            multianewarray { index, dimensions } => SymOpcode::MultiANewArray { class: resolve_class(cp, *index)?, dimensions: *dimensions },
+            tableswitch { default, low, jump_offsets, .. } => {
+                let mut targets = Vec::with_capacity(jump_offsets.len());
+                for off in jump_offsets {
+                    targets.push(target(i, *off as i64)?);
+                }
+                SymOpcode::TableSwitch { default: target(i, *default as i64)?, low: *low, targets }
+            }
+            lookupswitch { default, match_offset_pairs, .. } => {
+                let mut pairs = Vec::with_capacity(match_offset_pairs.len());
+                for (m, off) in match_offset_pairs {
+                    pairs.push((*m, target(i, *off as i64)?));
+                }
+                SymOpcode::LookupSwitch { default: target(i, *default as i64)?, pairs }
+            }
+            op => SymOpcode::Plain(op.clone())
+        });
+    }
+    Ok(ans)
+}
+
+/// Converts symbolic code back to opcodes, interning every operand through `cp`.
+/// Bootstrap methods used by `invokedynamic` are looked up in, or appended to, `bootstrap_methods`.
+/// `goto` and `jsr` are widened to `goto_w` and `jsr_w` when their target is out of range.
+pub fn from_symbolic(code: &[SymOpcode], cp: &mut CPBuilder, bootstrap_methods: &mut Vec<BootstrapMethodsEntry>) -> io::Result<Vec<Opcode>> {
+    use opcodes::Opcode::*;
+    //first intern everything; branches are filled in once the layout is known
+    let mut ans = Vec::with_capacity(code.len());
+    for op in code {
+        ans.push(match op {
+            SymOpcode::Plain(op) => op.clone(),
+            SymOpcode::Ldc(c) => {
+                let index = intern_constant(cp, c);
+                match c {
+                    Constant::Long(_) | Constant::Double(_) => ldc2_w { index },
+                    _ if index.as_u16() <= 0xff => ldc { index },
+                    _ => ldc_w { index }
+                }
+            }
+            SymOpcode::GetStatic(m) => getstatic { index: intern_member(cp, m, true) },
+            SymOpcode::PutStatic(m) => putstatic { index: intern_member(cp, m, true) },
+            SymOpcode::GetField(m) => getfield { index: intern_member(cp, m, true) },
+            SymOpcode::PutField(m) => putfield { index: intern_member(cp, m, true) },
+            SymOpcode::InvokeVirtual(m) => invokevirtual { index: intern_member(cp, m, false) },
+            SymOpcode::InvokeSpecial(m) => invokespecial { index: intern_member(cp, m, false) },
+            SymOpcode::InvokeStatic(m) => invokestatic { index: intern_member(cp, m, false) },
+            SymOpcode::InvokeInterface(m) => {
+                let slots = MethodDescriptor::parse(&m.descriptor)?.arg_slots() + 1;
+                if slots > 255 {
+                    return Err(malformed("invokeinterface takes more than 255 slots of arguments"));
+                }
+                invokeinterface { index: intern_member(cp, m, false), count: slots as u8 }
+            }
+            SymOpcode::InvokeDynamic { bootstrap, name, descriptor } => {
+                let entry = intern_bootstrap_method(cp, bootstrap);
+                let bsm = match bootstrap_methods.iter().position(|b| *b == entry) {
+                    Some(i) => i,
+                    None => {
+                        bootstrap_methods.push(entry);
+                        bootstrap_methods.len() - 1
+                    }
+                };
+                invokedynamic { index: cp.add_invoke_dynamic(bsm as u16, name.clone(), descriptor.clone()) }
+            }
+            SymOpcode::New(c) => new { index: cp.add_class(c.clone()) },
+            SymOpcode::ANewArray(c) => anewarray { index: cp.add_class(c.clone()) },
+            SymOpcode::CheckCast(c) => checkcast { index: cp.add_class(c.clone()) },
+            SymOpcode::InstanceOf(c) => instanceof { index: cp.add_class(c.clone()) },
+            SymOpcode::MultiANewArray { class, dimensions } => multianewarray { index: cp.add_class(class.clone()), dimensions: *dimensions },
+            SymOpcode::Branch { target, .. } |
+            SymOpcode::TableSwitch { default: target, .. } |
+            SymOpcode::LookupSwitch { default: target, .. } => {
+                if *target >= code.len() {
+                    return Err(malformed("Branch target out of range"));
+                }
+                nop
+            }
+        });
+    }
+    for op in code {
+        let targets: Vec<usize> = match op {
+            SymOpcode::TableSwitch { targets, .. } => targets.clone(),
+            SymOpcode::LookupSwitch { pairs, .. } => pairs.iter().map(|p| p.1).collect(),
+            _ => continue
+        };
+        if targets.iter().any(|t| *t >= code.len()) {
+            return Err(malformed("Branch target out of range"));
+        }
+    }
+    //widening a goto moves everything after it, so repeat until nothing needs widening
+    let mut wide = vec![false; code.len()];
+    loop {
+        let mut offsets = Vec::with_capacity(code.len());
+        let mut pos: u32 = 0;
+        for (i, op) in code.iter().enumerate() {
+            offsets.push(pos);
+            pos += match op {
+                SymOpcode::Branch { .. } => if wide[i] { 5 } else { 3 },
+                SymOpcode::TableSwitch { targets, .. } => 13 + switch_padding(pos) as u32 + 4 * targets.len() as u32,
+                SymOpcode::LookupSwitch { pairs, .. } => 9 + switch_padding(pos) as u32 + 8 * pairs.len() as u32,
+                _ => ans[i].len_bytes() as u32
+            };
+        }
+        let rel = |from: usize, to: usize| offsets[to] as i64 - offsets[from] as i64;
+        let mut changed = false;
+        for (i, op) in code.iter().enumerate() {
+            match op {
+                SymOpcode::Branch { kind, target } => {
+                    let branch = rel(i, *target);
+                    let fits = branch >= i16::MIN as i64 && branch <= i16::MAX as i64;
+                    ans[i] = match kind {
+                        BranchKind::Goto if wide[i] || !fits => {
+                            changed |= !wide[i];
+                            wide[i] = true;
+                            goto_w { branch: branch as i32 }
+                        }
+                        BranchKind::Jsr if wide[i] || !fits => {
+                            changed |= !wide[i];
+                            wide[i] = true;
+                            jsr_w { branch: branch as i32 }
+                        }
+                        _ if !fits => return Err(malformed("Conditional branch target out of range")),
+                        kind => with_branch(*kind, branch as i16)
+                    };
+                }
+                SymOpcode::TableSwitch { default, low, targets } => {
+                    let high = match targets.len() {
+                        0 => None,
+                        n => (n - 1).try_into().ok().and_then(|n| low.checked_add(n)),
+                    };
+                    let high = high.ok_or_else(|| malformed("tableswitch needs at least one target and keys no higher than i32::MAX"))?;
+                    ans[i] = tableswitch {
+                        default: rel(i, *default) as i32,
+                        low: *low,
+                        high,
+                        jump_offsets: targets.iter().map(|t| rel(i, *t) as i32).collect(),
+                        padding: switch_padding(offsets[i]),
+                    };
+                }
+                SymOpcode::LookupSwitch { default, pairs } => {
+                    ans[i] = lookupswitch {
+                        default: rel(i, *default) as i32,
+                        match_offset_pairs: pairs.iter().map(|(m, t)| (*m, rel(i, *t) as i32)).collect(),
+                        padding: switch_padding(offsets[i]),
+                    };
+                }
+                _ => {}
+            }
+        }
+        if !changed {
+            return Ok(ans);
+        }
+    }
+}
+
+/// Resolves a loadable constant pool entry to a `Constant`
+pub fn resolve_constant(cp: &ConstantPool, index: CPIndex) -> io::Result<Constant> {
+    Ok(match cp.get(index) {
+        Some(CPInfo::Integer { bytes }) => Constant::Integer(*bytes as i32),
+        Some(CPInfo::Float { bytes }) => Constant::Float(f32::from_bits(*bytes)),
+        Some(CPInfo::Long { bytes }) => Constant::Long(*bytes as i64),
+        Some(CPInfo::Double { bytes }) => Constant::Double(f64::from_bits(*bytes)),
+        Some(CPInfo::String { string_index }) => Constant::String(cp.get_utf8(*string_index).ok_or_else(|| malformed("String did not point to Utf8"))?),
+        Some(CPInfo::Class { .. }) => Constant::Class(resolve_class(cp, index)?),
+        Some(CPInfo::MethodType { descriptor_index }) => Constant::MethodType(cp.get_utf8(*descriptor_index).ok_or_else(|| malformed("MethodType did not point to Utf8"))?),
+        Some(CPInfo::MethodHandle { .. }) => Constant::MethodHandle(resolve_method_handle(cp, index)?),
+        _ => return Err(malformed("Constant pool index is not a loadable constant"))
+    })
+}
+
+/// Resolves a `Fieldref`, `Methodref` or `InterfaceMethodref` to a `MemberRef`
+pub fn resolve_member(cp: &ConstantPool, index: CPIndex) -> io::Result<MemberRef> {
+    let is_interface = match cp.get(index) {
+        Some(CPInfo::InterfaceMethodref { .. }) => true,
+        Some(CPInfo::Fieldref { .. }) | Some(CPInfo::Methodref { .. }) => false,
+        _ => return Err(malformed("Constant pool index is not a member reference"))
+    };
+    let (owner, name, descriptor) = cp.get_member_ref(index).ok_or_else(|| malformed("Bad member reference"))?;
+    Ok(MemberRef { owner, name, descriptor, is_interface })
+}
+
+/// Resolves a `MethodHandle` constant
+pub fn resolve_method_handle(cp: &ConstantPool, index: CPIndex) -> io::Result<MethodHandle> {
+    match cp.get(index) {
+        Some(CPInfo::MethodHandle { reference_kind, reference_index }) => Ok(MethodHandle {
+            reference_kind: *reference_kind,
+            member: resolve_member(cp, *reference_index)?,
+        }),
+        _ => Err(malformed("Constant pool index is not a MethodHandle"))
+    }
+}
+
+/// Resolves an entry of the `BootstrapMethods` attribute
+pub fn resolve_bootstrap_method(cp: &ConstantPool, entry: &BootstrapMethodsEntry) -> io::Result<BootstrapMethod> {
+    let mut arguments = Vec::with_capacity(entry.bootstrap_arguments.len());
+    for arg in &entry.bootstrap_arguments {
+        arguments.push(resolve_constant(cp, *arg)?);
+    }
+    Ok(BootstrapMethod { handle: resolve_method_handle(cp, entry.bootstrap_method_ref)?, arguments })
+}
+
+fn resolve_class(cp: &ConstantPool, index: CPIndex) -> io::Result<String> {
+    cp.get_class_name(index).ok_or_else(|| malformed("Constant pool index is not a Class"))
+}
+
+/// Interns a `Constant`, returning its index in `cp`
+pub fn intern_constant(cp: &mut CPBuilder, c: &Constant) -> CPIndex {
+    match c {
+        Constant::Integer(v) => cp.add_integer(*v as u32),
+        Constant::Float(v) => cp.add_float_f32(*v),
+        Constant::Long(v) => cp.add_long(*v as u64),
+        Constant::Double(v) => cp.add_double_f64(*v),
+        Constant::String(s) => cp.add_string(s.clone()),
+        Constant::Class(s) => cp.add_class(s.clone()),
+        Constant::MethodType(s) => cp.add_method_type(s.clone()),
+        Constant::MethodHandle(h) => intern_method_handle(cp, h),
+    }
+}
+
+/// Interns a `MethodHandle`, along with the member reference it points at
+pub fn intern_method_handle(cp: &mut CPBuilder, h: &MethodHandle) -> CPIndex {
+    //kinds 1 to 4 are getField, getStatic, putField and putStatic
+    let reference_index = intern_member(cp, &h.member, h.reference_kind <= 4);
+    cp.add_method_handle(h.reference_kind, reference_index)
+}
+
+/// Interns a `BootstrapMethod`, returning the attribute entry that refers to it
+pub fn intern_bootstrap_method(cp: &mut CPBuilder, b: &BootstrapMethod) -> BootstrapMethodsEntry {
+    BootstrapMethodsEntry {
+        bootstrap_method_ref: intern_method_handle(cp, &b.handle),
+        bootstrap_arguments: b.arguments.iter().map(|a| intern_constant(cp, a)).collect(),
+    }
+}
+
+fn intern_member(cp: &mut CPBuilder, m: &MemberRef, is_field: bool) -> CPIndex {
+    if is_field {
+        cp.add_fieldref(m.owner.clone(), m.name.clone(), m.descriptor.clone())
+    } else if m.is_interface {
+        cp.add_interface_methodref(m.owner.clone(), m.name.clone(), m.descriptor.clone())
+    } else {
+        cp.add_methodref(m.owner.clone(), m.name.clone(), m.descriptor.clone())
+    }
+}
+
+fn branch_of(op: &Opcode) -> i16 {
+    use opcodes::Opcode::*;
+    match op {
+        ifeq { branch } | ifne { branch } | iflt { branch } | ifge { branch } |
+        ifgt { branch } | ifle { branch } | if_icmpeq { branch } | if_icmpne { branch } |
+        if_icmplt { branch } | if_icmpge { branch } | if_icmpgt { branch } | if_icmple { branch } |
+        if_acmpeq { branch } | if_acmpne { branch } | ifnull { branch } | ifnonnull { branch } |
+        goto { branch } | jsr { branch } => *branch,
+        _ => 0
+    }
+}
+
+fn with_branch(kind: BranchKind, branch: i16) -> Opcode {
+    use opcodes::Opcode::*;
+    match kind {
+        BranchKind::Ifeq => ifeq { branch },
+        BranchKind::Ifne => ifne { branch },
+        BranchKind::Iflt => iflt { branch },
+        BranchKind::Ifge => ifge { branch },
+        BranchKind::Ifgt => ifgt { branch },
+        BranchKind::Ifle => ifle { branch },
+        BranchKind::IfIcmpeq => if_icmpeq { branch },
+        BranchKind::IfIcmpne => if_icmpne { branch },
+        BranchKind::IfIcmplt => if_icmplt { branch },
+        BranchKind::IfIcmpge => if_icmpge { branch },
+        BranchKind::IfIcmpgt => if_icmpgt { branch },
+        BranchKind::IfIcmple => if_icmple { branch },
+        BranchKind::IfAcmpeq => if_acmpeq { branch },
+        BranchKind::IfAcmpne => if_acmpne { branch },
+        BranchKind::Ifnull => ifnull { branch },
+        BranchKind::Ifnonnull => ifnonnull { branch },
+        BranchKind::Goto => goto { branch },
+        BranchKind::Jsr => jsr { branch },
+    }
+}
+
+/// the number of padding bytes after a switch opcode at `pc`, so its operands are 4-byte aligned
+fn switch_padding(pc: u32) -> u8 {
+    ((4 - (pc + 1) % 4) % 4) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytecode_tools::to_bytecode;
+    use opcodes::Opcode::*;
+
+    fn assemble(code: &[SymOpcode]) -> io::Result<Vec<Opcode>> {
+        from_symbolic(code, &mut CPBuilder::new(), &mut vec![])
+    }
+
+    fn goto(target: usize) -> SymOpcode {
+        SymOpcode::Branch { kind: BranchKind::Goto, target }
+    }
+
+    #[test]
+    fn goto_widens_past_32k() {
+        let mut code = vec![goto(2), SymOpcode::Plain(nop), goto(33_003)];
+        code.extend((0..33_000).map(|_| SymOpcode::Plain(nop)));
+        code.push(SymOpcode::Plain(return_));
+        let ops = assemble(&code).unwrap();
+        //the first goto only crosses a nop; the second crosses 33000 of them, so it takes 5 bytes
+        assert_eq!(ops[0], goto { branch: 4 });
+        assert_eq!(ops[2], goto_w { branch: 33_005 });
+        assert_eq!(instruction_offsets(&ops)[code.len() - 1], 33_009);
+        let cp = ConstantPool::new_with_info(vec![]);
+        assert_eq!(to_symbolic(&ops, &cp, &[]).unwrap(), code);
+
+        //backward jumps widen too, and conditional branches can't
+        code.push(goto(0));
+        assert_eq!(assemble(&code).unwrap()[code.len() - 1], goto_w { branch: -33_010 });
+        code.push(SymOpcode::Branch { kind: BranchKind::Ifeq, target: 0 });
+        assert!(assemble(&code).is_err());
+    }
+
+    #[test]
+    fn table_switch_keys_must_fit() {
+        let switch = |low, targets| vec![SymOpcode::TableSwitch { default: 1, low, targets }, SymOpcode::Plain(return_)];
+        assert!(assemble(&switch(i32::MIN, vec![])).is_err());
+        assert!(assemble(&switch(0, vec![])).is_err());
+        assert!(assemble(&switch(i32::MAX, vec![1, 1])).is_err());
+        match &assemble(&switch(i32::MIN, vec![1])).unwrap()[0] {
+            tableswitch { low, high, .. } => assert_eq!((*low, *high), (i32::MIN, i32::MIN)),
+            op => panic!("expected a tableswitch, got {:?}", op)
+        }
+        match &assemble(&switch(i32::MAX - 1, vec![1, 1])).unwrap()[0] {
+            tableswitch { high, .. } => assert_eq!(*high, i32::MAX),
+            op => panic!("expected a tableswitch, got {:?}", op)
+        }
+    }
+
+    #[test]
+    fn switches_are_padded_to_four_bytes() {
+        for start in 0..4 {
+            let mut code: Vec<SymOpcode> = (0..start).map(|_| SymOpcode::Plain(nop)).collect();
+            let end = start + 2;
+            code.push(SymOpcode::TableSwitch { default: end, low: -1, targets: vec![end, start + 1] });
+            code.push(SymOpcode::LookupSwitch { default: end, pairs: vec![(7, start)] });
+            code.push(SymOpcode::Plain(return_));
+            let ops = assemble(&code).unwrap();
+            let offsets = instruction_offsets(&ops);
+            let mut bytes = vec![];
+            for op in ops.iter().cloned() {
+                let (opcode, args) = to_bytecode(op);
+                bytes.push(opcode);
+                bytes.extend(args);
+            }
+            assert_eq!(bytes.len() as u32, offsets[ops.len()]);
+            for i in start..start + 2 {
+                let pad = switch_padding(offsets[i]) as u32;
+                //the operands after the padding start on a multiple of 4 from the start of the code
+                assert_eq!((offsets[i] + 1 + pad) % 4, 0);
+                assert!(bytes[offsets[i] as usize + 1..][..pad as usize].iter().all(|b| *b == 0));
+            }
+            match &ops[start] {
+                tableswitch { default, low, high, jump_offsets, .. } => {
+                    let here = offsets[start] as i32;
+                    assert_eq!((*default, *low, *high), (offsets[end] as i32 - here, -1, 0));
+                    assert_eq!(*jump_offsets, vec![offsets[end] as i32 - here, offsets[start + 1] as i32 - here]);
+                }
+                op => panic!("expected a tableswitch, got {:?}", op)
+            }
+            let cp = ConstantPool::new_with_info(vec![]);
+            assert_eq!(to_symbolic(&ops, &cp, &[]).unwrap(), code);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Small classes for tests, built from symbolic code so they don't depend on constant pool indices
+
+use attributes::{Attribute, BootstrapMethodsEntry};
+use builders::cp::CPBuilder;
+use class::JavaClass;
+use code::CodeBody;
+use fields::FieldInfo;
+use methods::MethodInfo;
+use symbolic::{MemberRef, SymOpcode};
+
+pub struct ClassSpec {
+    class: JavaClass,
+    cp: CPBuilder,
+    bootstrap_methods: Vec<BootstrapMethodsEntry>,
+}
+
+impl ClassSpec {
+    /// A version 52 class named `name`, extending `super_name` unless it's `None`
+    pub fn new(access_flags: u16, name: &str, super_name: Option<&str>) -> ClassSpec {
+        let mut cp = CPBuilder::new();
+        let mut class = JavaClass::empty();
+        class.major_version = 52;
+        class.access_flags = access_flags;
+        class.this_class = cp.add_class(name.to_owned());
+        if let Some(s) = super_name {
+            class.super_class = cp.add_class(s.to_owned());
+        }
+        ClassSpec { class, cp, bootstrap_methods: vec![] }
+    }
+
+    pub fn implements(mut self, interfaces: &[&str]) -> ClassSpec {
+        for i in interfaces {
+            let index = self.cp.add_class(i.to_string());
+            self.class.interfaces.push(index);
+        }
+        self
+    }
+
+    pub fn field(mut self, access_flags: u16, name: &str, descriptor: &str) -> ClassSpec {
+        let name_index = self.cp.add_utf8(name.to_owned());
+        let descriptor_index = self.cp.add_utf8(descriptor.to_owned());
+        self.class.fields.push(FieldInfo { access_flags, name_index, descriptor_index, attributes: vec![] });
+        self
+    }
+
+    /// Adds an attribute to the last field added, which can add constants to the class's constant pool
+    pub fn field_attribute<F: FnOnce(&mut CPBuilder) -> Attribute>(mut self, attribute: F) -> ClassSpec {
+        let a = attribute(&mut self.cp);
+        self.class.fields.last_mut().expect("no field to add the attribute to").attributes.push(a);
+        self
+    }
+
+    /// Adds a method, with a `Code` attribute unless `code` is empty
+    pub fn method(mut self, access_flags: u16, name: &str, descriptor: &str, code: Vec<SymOpcode>) -> ClassSpec {
+        let name_index = self.cp.add_utf8(name.to_owned());
+        let descriptor_index = self.cp.add_utf8(descriptor.to_owned());
+        let mut attributes = vec![];
+        if !code.is_empty() {
+            let mut body = CodeBody { max_stack: 0, max_locals: 8, instructions: code, exception_table: vec![], attributes: vec![] };
+            body.max_stack = body.compute_max_stack().unwrap();
+            attributes.push(body.to_attribute(&mut self.cp, &mut self.bootstrap_methods).unwrap());
+        }
+        self.class.methods.push(MethodInfo { access_flags, name_index, descriptor_index, attributes });
+        self
+    }
+
+    /// Adds a class attribute, which can add constants to the class's constant pool
+    pub fn attribute<F: FnOnce(&mut CPBuilder) -> Attribute>(mut self, attribute: F) -> ClassSpec {
+        let a = attribute(&mut self.cp);
+        self.class.attributes.push(a);
+        self
+    }
+
+    pub fn build(mut self) -> JavaClass {
+        if !self.bootstrap_methods.is_empty() {
+            self.class.attributes.push(Attribute::BootstrapMethods { bootstrap_methods: self.bootstrap_methods });
+        }
+        self.class.constant_pool = self.cp.build();
+        self.class
+    }
+}
+
+/// A reference to a member of a class (not an interface)
+pub fn member(owner: &str, name: &str, descriptor: &str) -> MemberRef {
+    MemberRef { owner: owner.to_owned(), name: name.to_owned(), descriptor: descriptor.to_owned(), is_interface: false }
+}
+
+/// A reference to a method of an interface
+pub fn interface_method(owner: &str, name: &str, descriptor: &str) -> MemberRef {
+    MemberRef { is_interface: true, ..member(owner, name, descriptor) }
+}
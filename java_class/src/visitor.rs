@@ -0,0 +1,969 @@
+use attributes::*;
+use builders::cp::CPBuilder;
+use class::{build_cp, read_attribute, JavaClass, JavaClassReader};
+use code::CodeBody;
+use cp::{CPIndex, ConstantPool};
+use malformed;
+use remap::{remap_code, Identity, Remapper};
+use std::io;
+use writer::{push_u16, push_u32, write_attribute, write_class_file};
+
+/// The constant pool and bootstrap methods shared by every visitor in a chain.
+/// Visitors intern new names and constants here, and a `ClassWriter` writes it out at the end.
+pub struct SymbolTable {
+    pub cp: CPBuilder,
+    pub bootstrap_methods: Vec<BootstrapMethodsEntry>,
+    /// true if every index of the reader's constant pool is still valid in `cp`,
+    /// which lets raw attributes be copied without decoding them
+    raw_compatible: bool,
+    /// how many class attributes came before `BootstrapMethods` in the class that was read,
+    /// so a `ClassWriter` can put it back in the same place
+    bootstrap_position: Option<usize>,
+}
+
+impl SymbolTable {
+    /// Creates an empty `SymbolTable`
+    pub fn new() -> SymbolTable {
+        SymbolTable { cp: CPBuilder::new(), bootstrap_methods: vec!(), raw_compatible: false, bootstrap_position: None }
+    }
+
+    /// Creates a `SymbolTable` starting out with the constant pool and bootstrap methods of `class`
+    pub fn from_class(class: &JavaClass) -> SymbolTable {
+        SymbolTable {
+            cp: CPBuilder::from_constant_pool(&class.constant_pool),
+            bootstrap_methods: class.bootstrap_methods().to_vec(),
+            raw_compatible: false,
+            bootstrap_position: class.attributes.iter().position(|a| matches!(a, Attribute::BootstrapMethods { .. })),
+        }
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    /// Copies an attribute read alongside the constant pool `from` and bootstrap methods `from_bootstrap`,
+    /// interning everything it refers to in this table.
+    /// Fails on an undecoded attribute other than `NestHost`, `NestMembers`, `PermittedSubclasses` and `Record`.
+    pub fn import_attribute(&mut self, a: &Attribute, from: &ConstantPool, from_bootstrap: &[BootstrapMethodsEntry]) -> io::Result<Attribute> {
+        self.remap_attribute(a, from, from_bootstrap, &Identity, "")
+    }
+
+    /// Like `import_attribute`, but renames the classes, members, descriptors and signatures the attribute refers to.
+    /// `class` is the old name of the class the attribute belongs to.
+    pub fn remap_attribute(&mut self, a: &Attribute, from: &ConstantPool, from_bootstrap: &[BootstrapMethodsEntry],
+                           remapper: &dyn Remapper, class: &str) -> io::Result<Attribute> {
+        let src = Source { cp: from, bootstrap_methods: from_bootstrap, remapper, class };
+        self.import_attribute_from(a, &src)
+    }
+
+    fn import_attribute_from(&mut self, a: &Attribute, src: &Source) -> io::Result<Attribute> {
+        let r = src.remapper;
+        Ok(match a {
+            Attribute::Code { .. } => {
+                let mut code = CodeBody::from_attribute(a, src.cp, src.bootstrap_methods)?;
+                remap_code(&mut code, r);
+                code.attributes = code.attributes.iter().map(|a| self.import_attribute_from(a, src)).collect::<io::Result<_>>()?;
+                code.to_attribute(&mut self.cp, &mut self.bootstrap_methods)?
+            }
+            Attribute::ConstantValue { constantvalue_index } => Attribute::ConstantValue { constantvalue_index: self.import(src, *constantvalue_index)? },
+            Attribute::StackMapTable { entries } => Attribute::StackMapTable {
+                entries: entries.iter().map(|f| self.import_frame(f, src)).collect::<io::Result<_>>()?,
+            },
+            Attribute::Exceptions { exception_index_table } => Attribute::Exceptions {
+                exception_index_table: exception_index_table.iter().map(|i| Ok(self.import_class(src, (*i).into())?.as_u16())).collect::<io::Result<_>>()?,
+            },
+            Attribute::InnerClasses { classes } => Attribute::InnerClasses {
+                classes: classes.iter().map(|c| {
+                    let inner = src.cp.class_name(c.inner_class_info_index)?;
+                    let inner_name_index = if c.inner_name_index.as_u16() == 0 {
+                        c.inner_name_index
+                    } else {
+                        //the simple name has to follow the renamed class
+                        let old = src.cp.utf8(c.inner_name_index)?;
+                        let new = r.map_class(&inner);
+                        let simple = if new == inner { old } else { new.rsplit(['$', '/']).next().unwrap_or(&new).to_owned() };
+                        self.cp.add_utf8(simple)
+                    };
+                    Ok(InnerClassInfo {
+                        inner_class_info_index: self.import_class(src, c.inner_class_info_index)?,
+                        outer_class_info_index: self.import_optional_class(src, c.outer_class_info_index)?,
+                        inner_name_index,
+                        inner_class_access_flags: c.inner_class_access_flags,
+                    })
+                }).collect::<io::Result<_>>()?,
+            },
+            Attribute::EnclosingMethod { class_index, method_index } => {
+                let method_index = if method_index.as_u16() == 0 {
+                    *method_index
+                } else {
+                    let owner = src.cp.class_name(*class_index)?;
+                    let (name, descriptor) = src.cp.get_name_and_type(*method_index).ok_or_else(|| malformed("Index did not point to NameAndType"))?;
+                    self.cp.add_name_type(r.map_method(&owner, &name, &descriptor), r.map_descriptor(&descriptor))
+                };
+                Attribute::EnclosingMethod { class_index: self.import_class(src, *class_index)?, method_index }
+            }
+            Attribute::Signature { signature_index } => Attribute::Signature { signature_index: self.import_signature(src, *signature_index)? },
+            Attribute::SourceFile { sourcefile_index } => Attribute::SourceFile { sourcefile_index: self.import(src, *sourcefile_index)? },
+            Attribute::LocalVariableTable { local_variable_table } => Attribute::LocalVariableTable {
+                local_variable_table: local_variable_table.iter().map(|l| Ok(LocalVariableTableEntry {
+                    name_index: self.import(src, l.name_index)?,
+                    descriptor_index: self.import_descriptor(src, l.descriptor_index)?,
+                    ..l.clone()
+                })).collect::<io::Result<_>>()?,
+            },
+            Attribute::LocalVariableTypeTable { local_variable_type_table } => Attribute::LocalVariableTypeTable {
+                local_variable_type_table: local_variable_type_table.iter().map(|l| Ok(LocalVariableTypeTableEntry {
+                    name_index: self.import(src, l.name_index)?,
+                    signature_index: self.import_signature(src, l.signature_index)?,
+                    ..l.clone()
+                })).collect::<io::Result<_>>()?,
+            },
+            Attribute::RuntimeVisibleAnnotations { annotations } => Attribute::RuntimeVisibleAnnotations { annotations: self.import_annotations(annotations, src)? },
+            Attribute::RuntimeInvisibleAnnotations { annotations } => Attribute::RuntimeInvisibleAnnotations { annotations: self.import_annotations(annotations, src)? },
+            Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } => Attribute::RuntimeVisibleParameterAnnotations {
+                parameter_annotations: parameter_annotations.iter().map(|p| self.import_annotations(p, src)).collect::<io::Result<_>>()?,
+            },
+            Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => Attribute::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations: parameter_annotations.iter().map(|p| self.import_annotations(p, src)).collect::<io::Result<_>>()?,
+            },
+            Attribute::RuntimeVisibleTypeAnnotations { annotations } => Attribute::RuntimeVisibleTypeAnnotations { annotations: self.import_type_annotations(annotations, src)? },
+            Attribute::RuntimeInvisibleTypeAnnotations { annotations } => Attribute::RuntimeInvisibleTypeAnnotations { annotations: self.import_type_annotations(annotations, src)? },
+            Attribute::AnnotationDefault { default_value } => Attribute::AnnotationDefault { default_value: self.import_element_value(default_value, src)? },
+            Attribute::BootstrapMethods { .. } => return Err(malformed("BootstrapMethods can't be imported on its own")),
+            Attribute::MethodParameters { parameters } => Attribute::MethodParameters {
+                parameters: parameters.iter().map(|p| Ok(MethodParameterEntry {
+                    name_index: if p.name_index.as_u16() == 0 { p.name_index } else { self.import(src, p.name_index)? },
+                    access_flags: p.access_flags,
+                })).collect::<io::Result<_>>()?,
+            },
+            Attribute::Unknown { name_index, info } => {
+                let name = src.cp.utf8(*name_index)?;
+                let info = self.import_unknown(&name, info, src)?;
+                Attribute::Unknown { name_index: self.cp.add_utf8(name), info }
+            }
+            a @ Attribute::Synthetic | a @ Attribute::Deprecated |
+            a @ Attribute::SourceDebugExtenson { .. } | a @ Attribute::LineNumberTable { .. } => a.clone(),
+        })
+    }
+
+    /// imports a constant without renaming anything, for literals and local variable names
+    fn import(&mut self, src: &Source, index: CPIndex) -> io::Result<CPIndex> {
+        self.cp.import(src.cp, index).ok_or_else(|| malformed("Constant pool index out of range"))
+    }
+
+    fn import_class(&mut self, src: &Source, index: CPIndex) -> io::Result<CPIndex> {
+        let name = src.cp.class_name(index)?;
+        Ok(self.cp.add_class(src.remapper.map_type(&name)))
+    }
+
+    /// imports a class index that may be 0
+    fn import_optional_class(&mut self, src: &Source, index: CPIndex) -> io::Result<CPIndex> {
+        if index.as_u16() == 0 {
+            Ok(index)
+        } else {
+            self.import_class(src, index)
+        }
+    }
+
+    fn import_descriptor(&mut self, src: &Source, index: CPIndex) -> io::Result<CPIndex> {
+        let d = src.cp.utf8(index)?;
+        Ok(self.cp.add_utf8(src.remapper.map_descriptor(&d)))
+    }
+
+    fn import_signature(&mut self, src: &Source, index: CPIndex) -> io::Result<CPIndex> {
+        let s = src.cp.utf8(index)?;
+        Ok(self.cp.add_utf8(src.remapper.map_signature(&s)))
+    }
+
+    fn import_frame(&mut self, frame: &StackMapFrame, src: &Source) -> io::Result<StackMapFrame> {
+        let mut types = |v: &[VerificationTypeInfo]| v.iter().map(|t| Ok(match t {
+            VerificationTypeInfo::Object { cpool_index } => VerificationTypeInfo::Object { cpool_index: self.import_class(src, *cpool_index)? },
+            t => t.clone(),
+        })).collect::<io::Result<Vec<_>>>();
+        Ok(match frame {
+            StackMapFrame::SameLocals1Item { offset_delta, stack } => StackMapFrame::SameLocals1Item {
+                offset_delta: *offset_delta,
+                stack: types(::std::slice::from_ref(stack))?.remove(0),
+            },
+            StackMapFrame::SameLocals1ItemExtended { offset_delta, stack } => StackMapFrame::SameLocals1ItemExtended {
+                offset_delta: *offset_delta,
+                stack: types(::std::slice::from_ref(stack))?.remove(0),
+            },
+            StackMapFrame::AppendFrame { offset_delta, locals } => StackMapFrame::AppendFrame { offset_delta: *offset_delta, locals: types(locals)? },
+            StackMapFrame::FullFrame { offset_delta, locals, stack } => StackMapFrame::FullFrame {
+                offset_delta: *offset_delta,
+                locals: types(locals)?,
+                stack: types(stack)?,
+            },
+            f => f.clone(),
+        })
+    }
+
+    fn import_annotations(&mut self, annotations: &[Annotation], src: &Source) -> io::Result<Vec<Annotation>> {
+        annotations.iter().map(|a| self.import_annotation(a, src)).collect()
+    }
+
+    fn import_annotation(&mut self, a: &Annotation, src: &Source) -> io::Result<Annotation> {
+        Ok(Annotation {
+            type_index: self.import_descriptor(src, a.type_index)?,
+            element_value_pairs: self.import_pairs(a.type_index, &a.element_value_pairs, src)?,
+        })
+    }
+
+    fn import_type_annotations(&mut self, annotations: &[TypeAnnotation], src: &Source) -> io::Result<Vec<TypeAnnotation>> {
+        annotations.iter().map(|a| Ok(TypeAnnotation {
+            type_index: self.import_descriptor(src, a.type_index)?,
+            element_value_pairs: self.import_pairs(a.type_index, &a.element_value_pairs, src)?,
+            ..a.clone()
+        })).collect()
+    }
+
+    /// `type_index` is the old index of the descriptor of the annotation the pairs belong to
+    fn import_pairs(&mut self, type_index: CPIndex, pairs: &[ElementValuePair], src: &Source) -> io::Result<Vec<ElementValuePair>> {
+        let descriptor = src.cp.utf8(type_index)?;
+        let annotation = descriptor.trim_start_matches('L').trim_end_matches(';');
+        pairs.iter().map(|p| Ok(ElementValuePair {
+            element_name_index: self.cp.add_utf8(src.remapper.map_annotation_element(annotation, &src.cp.utf8(p.element_name_index)?)),
+            value: self.import_element_value(&p.value, src)?,
+        })).collect()
+    }
+
+    fn import_element_value(&mut self, v: &ElementValue, src: &Source) -> io::Result<ElementValue> {
+        Ok(match v {
+            ElementValue::ConstValueIndex { tag, index } => ElementValue::ConstValueIndex { tag: *tag, index: self.import(src, *index)? },
+            ElementValue::EnumConstValue { type_name_index, const_name_index } => {
+                let descriptor = src.cp.utf8(*type_name_index)?;
+                let enum_class = descriptor.trim_start_matches('L').trim_end_matches(';');
+                let constant = src.remapper.map_field(enum_class, &src.cp.utf8(*const_name_index)?, &descriptor);
+                ElementValue::EnumConstValue {
+                    type_name_index: self.import_descriptor(src, *type_name_index)?,
+                    const_name_index: self.cp.add_utf8(constant),
+                }
+            }
+            ElementValue::ClassInfoIndex(i) => ElementValue::ClassInfoIndex(self.import_descriptor(src, *i)?),
+            ElementValue::AnnotationValue(a) => ElementValue::AnnotationValue(self.import_annotation(a, src)?),
+            ElementValue::ArrayValue(values) => ElementValue::ArrayValue(values.iter().map(|v| self.import_element_value(v, src)).collect::<io::Result<_>>()?),
+        })
+    }
+
+    /// translates the contents of the undecoded attributes whose layout is known
+    fn import_unknown(&mut self, name: &str, info: &[u8], src: &Source) -> io::Result<Vec<u8>> {
+        let mut contents = UndecodedContents::read(name, info, src.cp)?
+            .ok_or_else(|| malformed(&format!("Attribute {} can't be moved to another constant pool", name)))?;
+        match &mut contents {
+            UndecodedContents::NestHost(i) => *i = self.import_class(src, *i)?,
+            UndecodedContents::ClassList(classes) => for i in classes {
+                *i = self.import_class(src, *i)?;
+            },
+            UndecodedContents::Record(components) => for c in components {
+                //each component has a field of the same name
+                let name = src.cp.utf8(c.name_index)?;
+                let descriptor = src.cp.utf8(c.descriptor_index)?;
+                c.name_index = self.cp.add_utf8(src.remapper.map_field(src.class, &name, &descriptor));
+                c.descriptor_index = self.cp.add_utf8(src.remapper.map_descriptor(&descriptor));
+                for (name_index, a) in &mut c.attributes {
+                    *name_index = self.cp.add_utf8(src.cp.utf8(*name_index)?);
+                    *a = self.import_attribute_from(a, src)?;
+                }
+            },
+        }
+        Ok(contents.to_bytes())
+    }
+}
+
+/// Where an attribute being imported comes from
+struct Source<'a> {
+    cp: &'a ConstantPool,
+    bootstrap_methods: &'a [BootstrapMethodsEntry],
+    remapper: &'a dyn Remapper,
+    /// the old name of the class the attribute belongs to
+    class: &'a str,
+}
+
+/// The parts of a class that come before its fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassHeader {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub access_flags: u16,
+    /// binary name of the class
+    pub name: String,
+    /// binary name of the superclass, `None` only for `java/lang/Object`
+    pub super_name: Option<String>,
+    pub interfaces: Vec<String>,
+}
+
+/// The name, descriptor and flags of a field or method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberHeader {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// An attribute that hasn't been decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAttribute {
+    pub name_index: CPIndex,
+    pub info: Vec<u8>,
+}
+
+impl RawAttribute {
+    /// Decodes the attribute using the constant pool it was read alongside
+    pub fn decode(&self, cp: &ConstantPool) -> io::Result<Attribute> {
+        let mut r = JavaClassReader::new_from_bytes(self.info.clone())?;
+        let ans = read_attribute(&mut r, cp, self.name_index, self.info.len() as u32)?;
+        if r.dist() as usize != self.info.len() {
+            return Err(malformed("Attribute length did not match its contents"));
+        }
+        Ok(ans)
+    }
+
+    fn name(&self, cp: &ConstantPool) -> Option<String> {
+        cp.get_utf8(self.name_index)
+    }
+}
+
+/// A field or method whose attributes haven't been decoded
+#[derive(Debug, Clone)]
+pub struct RawMember {
+    pub access_flags: u16,
+    pub name_index: CPIndex,
+    pub descriptor_index: CPIndex,
+    pub attributes: Vec<RawAttribute>,
+}
+
+/// Receives the parts of a class in order: the header, each field, each method, each class attribute, then the end.
+/// Every event has a default that forwards it to `delegate`, so a transformation only needs to
+/// implement the events it changes. Without a delegate, fields and methods are dropped and other events ignored.
+pub trait ClassVisitor {
+    /// The visitor events are forwarded to by default
+    fn delegate(&mut self) -> Option<&mut dyn ClassVisitor> {
+        None
+    }
+
+    fn visit_header(&mut self, table: &mut SymbolTable, header: ClassHeader) {
+        if let Some(d) = self.delegate() {
+            d.visit_header(table, header);
+        }
+    }
+
+    /// Visits a field. Returning `None` removes the field.
+    fn visit_field<'a>(&'a mut self, table: &mut SymbolTable, field: MemberHeader) -> Option<Box<dyn FieldVisitor + 'a>> {
+        self.delegate()?.visit_field(table, field)
+    }
+
+    /// Visits a method. Returning `None` removes the method.
+    fn visit_method<'a>(&'a mut self, table: &mut SymbolTable, method: MemberHeader) -> Option<Box<dyn MethodVisitor + 'a>> {
+        self.delegate()?.visit_method(table, method)
+    }
+
+    /// Visits a class attribute. `BootstrapMethods` is never visited, since it lives in the `SymbolTable`.
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        if let Some(d) = self.delegate() {
+            d.visit_attribute(table, attribute);
+        }
+    }
+
+    /// Called after everything else; new fields, methods and attributes can be added here
+    fn visit_end(&mut self, table: &mut SymbolTable) {
+        if let Some(d) = self.delegate() {
+            d.visit_end(table);
+        }
+    }
+}
+
+/// Receives the attributes of a field
+pub trait FieldVisitor {
+    fn delegate(&mut self) -> Option<&mut dyn FieldVisitor> {
+        None
+    }
+
+    /// Offers the field's attributes without decoding them. Returns true if they were consumed,
+    /// in which case `visit_attribute` isn't called for them.
+    /// This is forwarded to the delegate, so a visitor that overrides `visit_attribute` should override this to return false.
+    fn visit_raw(&mut self, table: &mut SymbolTable, attributes: &[RawAttribute]) -> bool {
+        match self.delegate() {
+            Some(d) => d.visit_raw(table, attributes),
+            None => false
+        }
+    }
+
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        if let Some(d) = self.delegate() {
+            d.visit_attribute(table, attribute);
+        }
+    }
+
+    fn visit_end(&mut self, table: &mut SymbolTable) {
+        if let Some(d) = self.delegate() {
+            d.visit_end(table);
+        }
+    }
+}
+
+/// Receives the attributes and code of a method
+pub trait MethodVisitor {
+    fn delegate(&mut self) -> Option<&mut dyn MethodVisitor> {
+        None
+    }
+
+    /// Offers the method's attributes, including `Code`, without decoding them.
+    /// Returns true if they were consumed, in which case neither `visit_attribute` nor `visit_code` is called.
+    /// This is forwarded to the delegate, so a visitor that overrides `visit_attribute` or `visit_code`
+    /// should override this to return false, or to only pass on attributes it wouldn't change.
+    fn visit_raw(&mut self, table: &mut SymbolTable, attributes: &[RawAttribute]) -> bool {
+        match self.delegate() {
+            Some(d) => d.visit_raw(table, attributes),
+            None => false
+        }
+    }
+
+    /// Visits every attribute of the method except `Code`
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        if let Some(d) = self.delegate() {
+            d.visit_attribute(table, attribute);
+        }
+    }
+
+    /// Visits the method's code, if it has any
+    fn visit_code(&mut self, table: &mut SymbolTable, code: CodeBody) {
+        if let Some(d) = self.delegate() {
+            d.visit_code(table, code);
+        }
+    }
+
+    fn visit_end(&mut self, table: &mut SymbolTable) {
+        if let Some(d) = self.delegate() {
+            d.visit_end(table);
+        }
+    }
+}
+
+/// A class file read only far enough to split it into its members.
+/// Attributes stay as raw bytes until a visitor asks for them.
+#[derive(Debug, Clone)]
+pub struct ClassReader {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub constant_pool: ConstantPool,
+    pub access_flags: u16,
+    pub this_class: CPIndex,
+    pub super_class: CPIndex,
+    pub interfaces: Vec<CPIndex>,
+    pub fields: Vec<RawMember>,
+    pub methods: Vec<RawMember>,
+    pub attributes: Vec<RawAttribute>,
+}
+
+impl ClassReader {
+    pub fn new(bytes: Vec<u8>) -> io::Result<ClassReader> {
+        let mut r = JavaClassReader::new_from_bytes(bytes)?;
+        let magic = r.next32()?;
+        if magic != 0xCAFEBABE { return Err(malformed("Wrong magic number")); }
+        let minor_version = r.next16()?;
+        let major_version = r.next16()?;
+        let cp_count = r.next16()?;
+        let constant_pool = build_cp(&mut r, cp_count)?;
+        let access_flags = r.next16()?;
+        let this_class = r.next16()?.into();
+        let super_class = r.next16()?.into();
+        let interfaces_count = r.next16()?;
+        let mut interfaces = Vec::with_capacity(interfaces_count as usize);
+        for _ in 0..interfaces_count {
+            interfaces.push(r.next16()?.into());
+        }
+        let fields = read_members(&mut r)?;
+        let methods = read_members(&mut r)?;
+        let attributes = read_raw_attributes(&mut r)?;
+        Ok(ClassReader {
+            minor_version,
+            major_version,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+        })
+    }
+
+    /// Creates the `SymbolTable` to pass to `accept`.
+    /// Since it starts with this class's constant pool, a `ClassWriter` can copy untouched members as raw bytes.
+    pub fn symbol_table(&self) -> io::Result<SymbolTable> {
+        let mut bootstrap_methods = vec!();
+        let mut bootstrap_position = None;
+        for (i, a) in self.attributes.iter().enumerate() {
+            if a.name(&self.constant_pool).as_deref() == Some("BootstrapMethods") {
+                if let Attribute::BootstrapMethods { bootstrap_methods: b } = a.decode(&self.constant_pool)? {
+                    bootstrap_methods = b;
+                    bootstrap_position = Some(i);
+                }
+            }
+        }
+        Ok(SymbolTable {
+            cp: CPBuilder::from_constant_pool(&self.constant_pool),
+            bootstrap_methods,
+            raw_compatible: true,
+            bootstrap_position,
+        })
+    }
+
+    /// Sends the class through `cv`
+    pub fn accept(&self, cv: &mut dyn ClassVisitor, table: &mut SymbolTable) -> io::Result<()> {
+        let cp = &self.constant_pool;
+        let bootstrap_methods = self.symbol_table()?.bootstrap_methods;
+        cv.visit_header(table, ClassHeader {
+            minor_version: self.minor_version,
+            major_version: self.major_version,
+            access_flags: self.access_flags,
+            name: cp.class_name(self.this_class)?,
+            super_name: if self.super_class.as_u16() == 0 { None } else { Some(cp.class_name(self.super_class)?) },
+            interfaces: self.interfaces.iter().map(|i| cp.class_name(*i)).collect::<io::Result<_>>()?,
+        });
+        for f in &self.fields {
+            if let Some(mut fv) = cv.visit_field(table, member_header(cp, f.access_flags, f.name_index, f.descriptor_index)?) {
+                if !fv.visit_raw(table, &f.attributes) {
+                    for a in &f.attributes {
+                        fv.visit_attribute(table, a.decode(cp)?);
+                    }
+                }
+                fv.visit_end(table);
+            }
+        }
+        for m in &self.methods {
+            if let Some(mut mv) = cv.visit_method(table, member_header(cp, m.access_flags, m.name_index, m.descriptor_index)?) {
+                if !mv.visit_raw(table, &m.attributes) {
+                    for a in &m.attributes {
+                        visit_method_attribute(&mut *mv, table, a.decode(cp)?, cp, &bootstrap_methods)?;
+                    }
+                }
+                mv.visit_end(table);
+            }
+        }
+        for a in &self.attributes {
+            let a = a.decode(cp)?;
+            if let Attribute::BootstrapMethods { .. } = a {
+                continue;
+            }
+            cv.visit_attribute(table, a);
+        }
+        cv.visit_end(table);
+        Ok(())
+    }
+}
+
+/// Sends an already decoded class through `cv`. Nothing can be passed through raw.
+/// `table` would normally be made with `SymbolTable::from_class`.
+pub fn accept_class(class: &JavaClass, cv: &mut dyn ClassVisitor, table: &mut SymbolTable) -> io::Result<()> {
+    let cp = &class.constant_pool;
+    let bootstrap_methods = class.bootstrap_methods();
+    cv.visit_header(table, ClassHeader {
+        minor_version: class.minor_version,
+        major_version: class.major_version,
+        access_flags: class.access_flags,
+        name: cp.class_name(class.this_class)?,
+        super_name: if class.super_class.as_u16() == 0 { None } else { Some(cp.class_name(class.super_class)?) },
+        interfaces: class.interfaces.iter().map(|i| cp.class_name(*i)).collect::<io::Result<_>>()?,
+    });
+    for f in &class.fields {
+        if let Some(mut fv) = cv.visit_field(table, member_header(cp, f.access_flags, f.name_index, f.descriptor_index)?) {
+            for a in &f.attributes {
+                fv.visit_attribute(table, a.clone());
+            }
+            fv.visit_end(table);
+        }
+    }
+    for m in &class.methods {
+        if let Some(mut mv) = cv.visit_method(table, member_header(cp, m.access_flags, m.name_index, m.descriptor_index)?) {
+            for a in &m.attributes {
+                visit_method_attribute(&mut *mv, table, a.clone(), cp, bootstrap_methods)?;
+            }
+            mv.visit_end(table);
+        }
+    }
+    for a in &class.attributes {
+        if let Attribute::BootstrapMethods { .. } = a {
+            continue;
+        }
+        cv.visit_attribute(table, a.clone());
+    }
+    cv.visit_end(table);
+    Ok(())
+}
+
+fn visit_method_attribute(mv: &mut dyn MethodVisitor, table: &mut SymbolTable, a: Attribute, cp: &ConstantPool, bootstrap_methods: &[BootstrapMethodsEntry]) -> io::Result<()> {
+    if let Attribute::Code { .. } = a {
+        mv.visit_code(table, CodeBody::from_attribute(&a, cp, bootstrap_methods)?);
+    } else {
+        mv.visit_attribute(table, a);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum WrittenAttribute {
+    Raw(RawAttribute),
+    Decoded(Attribute),
+    Code(CodeBody),
+}
+
+#[derive(Debug, Clone)]
+struct WrittenMember {
+    access_flags: u16,
+    name_index: CPIndex,
+    descriptor_index: CPIndex,
+    attributes: Vec<WrittenAttribute>,
+}
+
+/// A `ClassVisitor` that rebuilds a class from the events it receives.
+/// Members it receives as raw attributes are copied as is.
+#[derive(Debug, Clone, Default)]
+pub struct ClassWriter {
+    minor_version: u16,
+    major_version: u16,
+    access_flags: u16,
+    this_class: CPIndex,
+    super_class: CPIndex,
+    interfaces: Vec<CPIndex>,
+    fields: Vec<WrittenMember>,
+    methods: Vec<WrittenMember>,
+    attributes: Vec<Attribute>,
+}
+
+impl ClassWriter {
+    pub fn new() -> ClassWriter {
+        ClassWriter::default()
+    }
+
+    /// Serializes the written class. Code is converted back to bytecode here, so this is where
+    /// errors such as out of range branches are reported.
+    pub fn to_bytes(&self, table: &mut SymbolTable) -> io::Result<Vec<u8>> {
+        //members and attributes have to be written before the constant pool, since writing them can add constants
+        let mut body = vec!();
+        push_u16(&mut body, self.access_flags);
+        push_u16(&mut body, self.this_class.as_u16());
+        push_u16(&mut body, self.super_class.as_u16());
+        push_u16(&mut body, self.interfaces.len() as u16);
+        for i in &self.interfaces {
+            push_u16(&mut body, i.as_u16());
+        }
+        for members in &[&self.fields, &self.methods] {
+            push_u16(&mut body, members.len() as u16);
+            for m in members.iter() {
+                push_u16(&mut body, m.access_flags);
+                push_u16(&mut body, m.name_index.as_u16());
+                push_u16(&mut body, m.descriptor_index.as_u16());
+                push_u16(&mut body, m.attributes.len() as u16);
+                for a in &m.attributes {
+                    match a {
+                        WrittenAttribute::Raw(raw) => {
+                            push_u16(&mut body, raw.name_index.as_u16());
+                            push_u32(&mut body, raw.info.len() as u32);
+                            body.extend_from_slice(&raw.info);
+                        }
+                        WrittenAttribute::Decoded(a) => write_attribute(a, &mut table.cp, &mut body),
+                        WrittenAttribute::Code(c) => {
+                            let a = c.to_attribute(&mut table.cp, &mut table.bootstrap_methods)?;
+                            write_attribute(&a, &mut table.cp, &mut body);
+                        }
+                    }
+                }
+            }
+        }
+        let bootstrap = if table.bootstrap_methods.is_empty() {
+            None
+        } else {
+            Some(Attribute::BootstrapMethods { bootstrap_methods: table.bootstrap_methods.clone() })
+        };
+        let mut attributes: Vec<&Attribute> = self.attributes.iter().collect();
+        if let Some(b) = &bootstrap {
+            //attributes removed on the way may have come before it, so it may not fit where it was
+            let at = table.bootstrap_position.map_or(attributes.len(), |p| p.min(attributes.len()));
+            attributes.insert(at, b);
+        }
+        push_u16(&mut body, attributes.len() as u16);
+        for a in attributes {
+            write_attribute(a, &mut table.cp, &mut body);
+        }
+        let cp = ::std::mem::take(&mut table.cp).build();
+        let ans = write_class_file(self.minor_version, self.major_version, &cp, &body);
+        table.cp = CPBuilder::from_constant_pool(&cp);
+        Ok(ans)
+    }
+
+    /// Builds the written class as a `JavaClass`
+    pub fn to_class(&self, table: &mut SymbolTable) -> io::Result<JavaClass> {
+        JavaClass::new_from_bytes(self.to_bytes(table)?)
+    }
+}
+
+impl ClassVisitor for ClassWriter {
+    fn visit_header(&mut self, table: &mut SymbolTable, header: ClassHeader) {
+        self.minor_version = header.minor_version;
+        self.major_version = header.major_version;
+        self.access_flags = header.access_flags;
+        self.this_class = table.cp.add_class(header.name);
+        self.super_class = match header.super_name {
+            Some(name) => table.cp.add_class(name),
+            None => 0.into()
+        };
+        self.interfaces = header.interfaces.into_iter().map(|i| table.cp.add_class(i)).collect();
+    }
+
+    fn visit_field<'a>(&'a mut self, table: &mut SymbolTable, field: MemberHeader) -> Option<Box<dyn FieldVisitor + 'a>> {
+        self.fields.push(new_member(table, field));
+        Some(Box::new(MemberWriter { member: self.fields.last_mut()? }))
+    }
+
+    fn visit_method<'a>(&'a mut self, table: &mut SymbolTable, method: MemberHeader) -> Option<Box<dyn MethodVisitor + 'a>> {
+        self.methods.push(new_member(table, method));
+        Some(Box::new(MemberWriter { member: self.methods.last_mut()? }))
+    }
+
+    fn visit_attribute(&mut self, _table: &mut SymbolTable, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    fn visit_end(&mut self, _table: &mut SymbolTable) {}
+}
+
+fn new_member(table: &mut SymbolTable, header: MemberHeader) -> WrittenMember {
+    WrittenMember {
+        access_flags: header.access_flags,
+        name_index: table.cp.add_utf8(header.name),
+        descriptor_index: table.cp.add_utf8(header.descriptor),
+        attributes: vec!(),
+    }
+}
+
+struct MemberWriter<'a> {
+    member: &'a mut WrittenMember,
+}
+
+impl<'a> MemberWriter<'a> {
+    fn visit_raw(&mut self, table: &mut SymbolTable, attributes: &[RawAttribute]) -> bool {
+        if !table.raw_compatible {
+            return false;
+        }
+        self.member.attributes.extend(attributes.iter().cloned().map(WrittenAttribute::Raw));
+        true
+    }
+}
+
+impl<'a> FieldVisitor for MemberWriter<'a> {
+    fn visit_raw(&mut self, table: &mut SymbolTable, attributes: &[RawAttribute]) -> bool {
+        MemberWriter::visit_raw(self, table, attributes)
+    }
+
+    fn visit_attribute(&mut self, _table: &mut SymbolTable, attribute: Attribute) {
+        self.member.attributes.push(WrittenAttribute::Decoded(attribute));
+    }
+}
+
+impl<'a> MethodVisitor for MemberWriter<'a> {
+    fn visit_raw(&mut self, table: &mut SymbolTable, attributes: &[RawAttribute]) -> bool {
+        MemberWriter::visit_raw(self, table, attributes)
+    }
+
+    fn visit_attribute(&mut self, _table: &mut SymbolTable, attribute: Attribute) {
+        self.member.attributes.push(WrittenAttribute::Decoded(attribute));
+    }
+
+    fn visit_code(&mut self, _table: &mut SymbolTable, code: CodeBody) {
+        self.member.attributes.push(WrittenAttribute::Code(code));
+    }
+}
+
+/// A transformation that removes debugging information: `SourceFile`, `SourceDebugExtension`,
+/// `LineNumberTable`, `LocalVariableTable` and `LocalVariableTypeTable`
+pub struct StripDebug<'a> {
+    pub next: &'a mut dyn ClassVisitor,
+}
+
+impl<'a> ClassVisitor for StripDebug<'a> {
+    fn delegate(&mut self) -> Option<&mut dyn ClassVisitor> {
+        Some(self.next)
+    }
+
+    fn visit_method<'b>(&'b mut self, table: &mut SymbolTable, method: MemberHeader) -> Option<Box<dyn MethodVisitor + 'b>> {
+        let next = self.next.visit_method(table, method)?;
+        Some(Box::new(StripDebugMethod { next }))
+    }
+
+    fn visit_attribute(&mut self, table: &mut SymbolTable, attribute: Attribute) {
+        match attribute {
+            Attribute::SourceFile { .. } | Attribute::SourceDebugExtenson { .. } => {}
+            a => self.next.visit_attribute(table, a)
+        }
+    }
+}
+
+struct StripDebugMethod<'a> {
+    next: Box<dyn MethodVisitor + 'a>,
+}
+
+impl<'a> MethodVisitor for StripDebugMethod<'a> {
+    fn delegate(&mut self) -> Option<&mut dyn MethodVisitor> {
+        Some(&mut *self.next)
+    }
+
+    /// The debugging attributes of a method are all inside its `Code`, so a method without code passes through as is
+    fn visit_raw(&mut self, table: &mut SymbolTable, attributes: &[RawAttribute]) -> bool {
+        if attributes.iter().any(|a| table.cp.get_utf8(a.name_index).as_deref() == Some("Code")) {
+            return false;
+        }
+        self.next.visit_raw(table, attributes)
+    }
+
+    fn visit_code(&mut self, table: &mut SymbolTable, mut code: CodeBody) {
+        code.attributes.retain(|a| !matches!(a,
+            Attribute::LineNumberTable { .. } | Attribute::LocalVariableTable { .. } | Attribute::LocalVariableTypeTable { .. }));
+        self.next.visit_code(table, code);
+    }
+}
+
+/// Builds a `JavaClass` from a decoded class by sending it through `cv` into a `ClassWriter`.
+/// `make` receives the writer and returns the transformation to run in front of it.
+pub fn transform<F>(class: &JavaClass, make: F) -> io::Result<JavaClass>
+    where F: for<'a> FnOnce(&'a mut ClassWriter) -> Box<dyn ClassVisitor + 'a> {
+    let mut table = SymbolTable::from_class(class);
+    let mut writer = ClassWriter::new();
+    {
+        let mut cv = make(&mut writer);
+        accept_class(class, &mut *cv, &mut table)?;
+    }
+    writer.to_class(&mut table)
+}
+
+fn read_members(r: &mut JavaClassReader) -> io::Result<Vec<RawMember>> {
+    let count = r.next16()?;
+    let mut ans = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        ans.push(RawMember {
+            access_flags: r.next16()?,
+            name_index: r.next16()?.into(),
+            descriptor_index: r.next16()?.into(),
+            attributes: read_raw_attributes(r)?,
+        });
+    }
+    Ok(ans)
+}
+
+fn read_raw_attributes(r: &mut JavaClassReader) -> io::Result<Vec<RawAttribute>> {
+    let count = r.next16()?;
+    let mut ans = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name_index = r.next16()?.into();
+        let len = r.next32()?;
+        let mut info = Vec::with_capacity(len.min(0x10000) as usize);
+        for _ in 0..len {
+            info.push(r.next8()?);
+        }
+        ans.push(RawAttribute { name_index, info });
+    }
+    Ok(ans)
+}
+
+
+
+fn member_header(cp: &ConstantPool, access_flags: u16, name_index: CPIndex, descriptor_index: CPIndex) -> io::Result<MemberHeader> {
+    Ok(MemberHeader {
+        access_flags,
+        name: cp.get_utf8(name_index).ok_or_else(|| malformed("Name index did not point to Utf8"))?,
+        descriptor: cp.get_utf8(descriptor_index).ok_or_else(|| malformed("Descriptor index did not point to Utf8"))?,
+    })
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use methods::MethodInfo;
+    use opcodes::Opcode;
+
+    /// a class with a method without code, a method with code, and `BootstrapMethods` between two other attributes
+    fn class_bytes() -> Vec<u8> {
+        let mut cp = CPBuilder::new();
+        let mut class = JavaClass::empty();
+        class.major_version = 52;
+        class.this_class = cp.add_class("T".to_owned());
+        class.super_class = cp.add_class("java/lang/Object".to_owned());
+        for (flags, name, code) in &[(0x0401, "abstract_", None), (0x0009, "concrete", Some(vec![Opcode::return_]))] {
+            let mut attributes = vec![Attribute::Signature { signature_index: cp.add_utf8("()V".to_owned()) }];
+            if let Some(code) = code {
+                attributes.push(Attribute::Code { max_stack: 0, max_locals: 0, code: code.clone(), exception_table: vec![], attributes: vec![] });
+            }
+            class.methods.push(MethodInfo { access_flags: *flags, name_index: cp.add_utf8(name.to_string()), descriptor_index: cp.add_utf8("()V".to_owned()), attributes });
+        }
+        let handle = cp.add_methodref("T".to_owned(), "concrete".to_owned(), "()V".to_owned());
+        let bootstrap_method_ref = cp.add_method_handle(6, handle);
+        class.attributes = vec![
+            Attribute::SourceFile { sourcefile_index: cp.add_utf8("T.java".to_owned()) },
+            Attribute::BootstrapMethods { bootstrap_methods: vec![BootstrapMethodsEntry { bootstrap_method_ref, bootstrap_arguments: vec![] }] },
+            Attribute::InnerClasses { classes: vec![] },
+        ];
+        class.constant_pool = cp.build();
+        class.to_bytes()
+    }
+
+    #[test]
+    fn writer_keeps_bytes_and_attribute_order() {
+        let bytes = class_bytes();
+        let reader = ClassReader::new(bytes.clone()).unwrap();
+        let mut table = reader.symbol_table().unwrap();
+        let mut writer = ClassWriter::new();
+        reader.accept(&mut writer, &mut table).unwrap();
+        assert_eq!(writer.to_bytes(&mut table).unwrap(), bytes);
+
+        let class = JavaClass::new_from_bytes(bytes).unwrap();
+        let mut table = SymbolTable::from_class(&class);
+        let mut writer = ClassWriter::new();
+        accept_class(&class, &mut writer, &mut table).unwrap();
+        let written = writer.to_class(&mut table).unwrap();
+        let names: Vec<_> = written.attributes.iter().map(|a| a.name().unwrap()).collect();
+        assert_eq!(names, ["SourceFile", "BootstrapMethods", "InnerClasses"]);
+    }
+
+    /// Counts the methods whose attributes arrive raw
+    struct CountRaw {
+        raw: usize,
+    }
+
+    impl ClassVisitor for CountRaw {
+        fn visit_method<'a>(&'a mut self, _table: &mut SymbolTable, _method: MemberHeader) -> Option<Box<dyn MethodVisitor + 'a>> {
+            Some(Box::new(CountRawMethod { raw: &mut self.raw }))
+        }
+    }
+
+    struct CountRawMethod<'a> {
+        raw: &'a mut usize,
+    }
+
+    impl<'a> MethodVisitor for CountRawMethod<'a> {
+        fn visit_raw(&mut self, _table: &mut SymbolTable, _attributes: &[RawAttribute]) -> bool {
+            *self.raw += 1;
+            true
+        }
+    }
+
+    #[test]
+    fn raw_attributes_pass_through_wrapping_visitors() {
+        let reader = ClassReader::new(class_bytes()).unwrap();
+        let mut count = CountRaw { raw: 0 };
+        reader.accept(&mut count, &mut reader.symbol_table().unwrap()).unwrap();
+        assert_eq!(count.raw, 2);
+        //StripDebug has to look inside `Code`, but lets the abstract method through untouched
+        let mut count = CountRaw { raw: 0 };
+        reader.accept(&mut StripDebug { next: &mut count }, &mut reader.symbol_table().unwrap()).unwrap();
+        assert_eq!(count.raw, 1);
+    }
+}
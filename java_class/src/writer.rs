@@ -0,0 +1,372 @@
+use attributes::*;
+use attributes::Attribute::*;
+use builders::cp::CPBuilder;
+use bytecode_tools::to_bytecode;
+use class::JavaClass;
+use cp::{CPIndex, ConstantPool};
+use fields::FieldInfo;
+use methods::MethodInfo;
+
+fn push_u8(out: &mut Vec<u8>, v: u8) {
+    out.push(v);
+}
+
+pub(crate) fn push_u16(out: &mut Vec<u8>, v: u16) {
+    out.push((v >> 8) as u8);
+    out.push(v as u8);
+}
+
+pub(crate) fn push_u32(out: &mut Vec<u8>, v: u32) {
+    out.push((v >> 24) as u8);
+    out.push((v >> 16) as u8);
+    out.push((v >> 8) as u8);
+    out.push(v as u8);
+}
+
+fn push_index(out: &mut Vec<u8>, v: CPIndex) {
+    push_u16(out, v.as_u16());
+}
+
+impl JavaClass {
+    /// Serializes the class to the bytes of a .class file
+    /// Attribute names that are missing from the constant pool are appended to it,
+    /// so the written constant pool may be longer than `self.constant_pool`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut cp = CPBuilder::from_constant_pool(&self.constant_pool);
+        let mut body = vec!();
+        push_u16(&mut body, self.access_flags);
+        push_index(&mut body, self.this_class);
+        push_index(&mut body, self.super_class);
+        push_u16(&mut body, self.interfaces.len() as u16);
+        for i in &self.interfaces {
+            push_index(&mut body, *i);
+        }
+        push_u16(&mut body, self.fields.len() as u16);
+        for f in &self.fields {
+            write_field(f, &mut cp, &mut body);
+        }
+        push_u16(&mut body, self.methods.len() as u16);
+        for m in &self.methods {
+            write_method(m, &mut cp, &mut body);
+        }
+        write_attributes(&self.attributes, &mut cp, &mut body);
+        write_class_file(self.minor_version, self.major_version, &cp.build(), &body)
+    }
+}
+
+/// Assembles a class file from its version, constant pool, and everything that follows the constant pool
+pub fn write_class_file(minor_version: u16, major_version: u16, cp: &ConstantPool, body: &[u8]) -> Vec<u8> {
+    let mut ans = Vec::with_capacity(body.len() + 16 * cp.items().len());
+    push_u32(&mut ans, 0xCAFEBABE);
+    push_u16(&mut ans, minor_version);
+    push_u16(&mut ans, major_version);
+    push_u16(&mut ans, cp.len());
+    for c in cp.items() {
+        ans.extend_from_slice(&c.as_bytes());
+    }
+    ans.extend_from_slice(body);
+    ans
+}
+
+/// Writes a field_info structure
+pub fn write_field(f: &FieldInfo, cp: &mut CPBuilder, out: &mut Vec<u8>) {
+    push_u16(out, f.access_flags);
+    push_index(out, f.name_index);
+    push_index(out, f.descriptor_index);
+    write_attributes(&f.attributes, cp, out);
+}
+
+/// Writes a method_info structure
+pub fn write_method(m: &MethodInfo, cp: &mut CPBuilder, out: &mut Vec<u8>) {
+    push_u16(out, m.access_flags);
+    push_index(out, m.name_index);
+    push_index(out, m.descriptor_index);
+    write_attributes(&m.attributes, cp, out);
+}
+
+/// Writes an attribute count followed by each attribute
+pub fn write_attributes(attributes: &[Attribute], cp: &mut CPBuilder, out: &mut Vec<u8>) {
+    push_u16(out, attributes.len() as u16);
+    for a in attributes {
+        write_attribute(a, cp, out);
+    }
+}
+
+/// Writes a single attribute, including its name index and length
+pub fn write_attribute(attr: &Attribute, cp: &mut CPBuilder, out: &mut Vec<u8>) {
+    let name_index = match (attr, attr.name()) {
+        (Unknown { name_index, .. }, _) => *name_index,
+        (_, Some(name)) => cp.add_utf8(name.to_owned()),
+        (_, None) => unreachable!("only Unknown attributes have no name")
+    };
+    let mut body = vec!();
+    write_attribute_body(attr, cp, &mut body);
+    push_index(out, name_index);
+    push_u32(out, body.len() as u32);
+    out.extend_from_slice(&body);
+}
+
+/// Writes the info of an attribute, without its name index and length
+pub fn write_attribute_body(attr: &Attribute, cp: &mut CPBuilder, out: &mut Vec<u8>) {
+    match attr {
+        ConstantValue { constantvalue_index } => push_index(out, *constantvalue_index),
+        Code { max_stack, max_locals, code, exception_table, attributes } => {
+            push_u16(out, *max_stack);
+            push_u16(out, *max_locals);
+            let mut bytes = vec!();
+            for op in code {
+                let (byte, args) = to_bytecode(op.clone());
+                bytes.push(byte);
+                bytes.extend_from_slice(&args);
+            }
+            push_u32(out, bytes.len() as u32);
+            out.extend_from_slice(&bytes);
+            push_u16(out, exception_table.len() as u16);
+            for e in exception_table {
+                push_u16(out, e.start_pc);
+                push_u16(out, e.end_pc);
+                push_u16(out, e.handler_pc);
+                push_index(out, e.catch_type);
+            }
+            write_attributes(attributes, cp, out);
+        }
+        StackMapTable { entries } => {
+            push_u16(out, entries.len() as u16);
+            for frame in entries {
+                write_stack_map_frame(frame, out);
+            }
+        }
+        Exceptions { exception_index_table } => {
+            push_u16(out, exception_index_table.len() as u16);
+            for e in exception_index_table {
+                push_u16(out, *e);
+            }
+        }
+        InnerClasses { classes } => {
+            push_u16(out, classes.len() as u16);
+            for c in classes {
+                push_index(out, c.inner_class_info_index);
+                push_index(out, c.outer_class_info_index);
+                push_index(out, c.inner_name_index);
+                push_u16(out, c.inner_class_access_flags);
+            }
+        }
+        EnclosingMethod { class_index, method_index } => {
+            push_index(out, *class_index);
+            push_index(out, *method_index);
+        }
+        Synthetic | Deprecated => {}
+        Signature { signature_index } => push_index(out, *signature_index),
+        SourceFile { sourcefile_index } => push_index(out, *sourcefile_index),
+        SourceDebugExtenson { debug_extension } => out.extend_from_slice(debug_extension),
+        LineNumberTable { line_number_table } => {
+            push_u16(out, line_number_table.len() as u16);
+            for l in line_number_table {
+                push_u16(out, l.start_pc);
+                push_u16(out, l.line_number);
+            }
+        }
+        LocalVariableTable { local_variable_table } => {
+            push_u16(out, local_variable_table.len() as u16);
+            for l in local_variable_table {
+                push_u16(out, l.start_pc);
+                push_u16(out, l.length);
+                push_index(out, l.name_index);
+                push_index(out, l.descriptor_index);
+                push_u16(out, l.index);
+            }
+        }
+        LocalVariableTypeTable { local_variable_type_table } => {
+            push_u16(out, local_variable_type_table.len() as u16);
+            for l in local_variable_type_table {
+                push_u16(out, l.start_pc);
+                push_u16(out, l.length);
+                push_index(out, l.name_index);
+                push_index(out, l.signature_index);
+                push_u16(out, l.index);
+            }
+        }
+        RuntimeVisibleAnnotations { annotations } | RuntimeInvisibleAnnotations { annotations } => {
+            write_annotations(annotations, out);
+        }
+        RuntimeVisibleParameterAnnotations { parameter_annotations } |
+        RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+            push_u8(out, parameter_annotations.len() as u8);
+            for a in parameter_annotations {
+                write_annotations(a, out);
+            }
+        }
+        RuntimeVisibleTypeAnnotations { annotations } | RuntimeInvisibleTypeAnnotations { annotations } => {
+            push_u16(out, annotations.len() as u16);
+            for a in annotations {
+                write_type_annotation(a, out);
+            }
+        }
+        AnnotationDefault { default_value } => write_element_value(default_value, out),
+        BootstrapMethods { bootstrap_methods } => {
+            push_u16(out, bootstrap_methods.len() as u16);
+            for b in bootstrap_methods {
+                push_index(out, b.bootstrap_method_ref);
+                push_u16(out, b.bootstrap_arguments.len() as u16);
+                for a in &b.bootstrap_arguments {
+                    push_index(out, *a);
+                }
+            }
+        }
+        MethodParameters { parameters } => {
+            push_u8(out, parameters.len() as u8);
+            for p in parameters {
+                push_index(out, p.name_index);
+                push_u16(out, p.access_flags);
+            }
+        }
+        Unknown { info, .. } => out.extend_from_slice(info),
+    }
+}
+
+fn write_stack_map_frame(frame: &StackMapFrame, out: &mut Vec<u8>) {
+    match frame {
+        StackMapFrame::SameFrame { offset_delta } => push_u8(out, *offset_delta),
+        StackMapFrame::SameLocals1Item { offset_delta, stack } => {
+            push_u8(out, offset_delta + 64);
+            write_verification_type_info(stack, out);
+        }
+        StackMapFrame::SameLocals1ItemExtended { offset_delta, stack } => {
+            push_u8(out, 247);
+            push_u16(out, *offset_delta);
+            write_verification_type_info(stack, out);
+        }
+        StackMapFrame::ChopFrame { absent_locals, offset_delta } => {
+            push_u8(out, 251 - absent_locals);
+            push_u16(out, *offset_delta);
+        }
+        StackMapFrame::SameFrameExtended { offset_delta } => {
+            push_u8(out, 251);
+            push_u16(out, *offset_delta);
+        }
+        StackMapFrame::AppendFrame { offset_delta, locals } => {
+            push_u8(out, 251 + locals.len() as u8);
+            push_u16(out, *offset_delta);
+            for l in locals {
+                write_verification_type_info(l, out);
+            }
+        }
+        StackMapFrame::FullFrame { offset_delta, locals, stack } => {
+            push_u8(out, 255);
+            push_u16(out, *offset_delta);
+            push_u16(out, locals.len() as u16);
+            for l in locals {
+                write_verification_type_info(l, out);
+            }
+            push_u16(out, stack.len() as u16);
+            for s in stack {
+                write_verification_type_info(s, out);
+            }
+        }
+    }
+}
+
+fn write_verification_type_info(vti: &VerificationTypeInfo, out: &mut Vec<u8>) {
+    match vti {
+        VerificationTypeInfo::Top => push_u8(out, 0),
+        VerificationTypeInfo::Integer => push_u8(out, 1),
+        VerificationTypeInfo::Float => push_u8(out, 2),
+        VerificationTypeInfo::Double => push_u8(out, 3),
+        VerificationTypeInfo::Long => push_u8(out, 4),
+        VerificationTypeInfo::Null => push_u8(out, 5),
+        VerificationTypeInfo::UninitializedThis => push_u8(out, 6),
+        VerificationTypeInfo::Object { cpool_index } => {
+            push_u8(out, 7);
+            push_index(out, *cpool_index);
+        }
+        VerificationTypeInfo::UninitializedVariable { offset } => {
+            push_u8(out, 8);
+            push_u16(out, *offset);
+        }
+    }
+}
+
+fn write_annotations(annotations: &[Annotation], out: &mut Vec<u8>) {
+    push_u16(out, annotations.len() as u16);
+    for a in annotations {
+        write_annotation(a, out);
+    }
+}
+
+fn write_annotation(a: &Annotation, out: &mut Vec<u8>) {
+    push_index(out, a.type_index);
+    write_element_value_pairs(&a.element_value_pairs, out);
+}
+
+fn write_element_value_pairs(pairs: &[ElementValuePair], out: &mut Vec<u8>) {
+    push_u16(out, pairs.len() as u16);
+    for p in pairs {
+        push_index(out, p.element_name_index);
+        write_element_value(&p.value, out);
+    }
+}
+
+fn write_element_value(v: &ElementValue, out: &mut Vec<u8>) {
+    match v {
+        ElementValue::ConstValueIndex { tag, index } => {
+            push_u8(out, *tag);
+            push_index(out, *index);
+        }
+        ElementValue::EnumConstValue { type_name_index, const_name_index } => {
+            push_u8(out, b'e');
+            push_index(out, *type_name_index);
+            push_index(out, *const_name_index);
+        }
+        ElementValue::ClassInfoIndex(index) => {
+            push_u8(out, b'c');
+            push_index(out, *index);
+        }
+        ElementValue::AnnotationValue(a) => {
+            push_u8(out, b'@');
+            write_annotation(a, out);
+        }
+        ElementValue::ArrayValue(vs) => {
+            push_u8(out, b'[');
+            push_u16(out, vs.len() as u16);
+            for v in vs {
+                write_element_value(v, out);
+            }
+        }
+    }
+}
+
+fn write_type_annotation(a: &TypeAnnotation, out: &mut Vec<u8>) {
+    push_u8(out, a.target_type);
+    match &a.target_info {
+        TargetInfo::TypeParameterTarget { type_parameter_index } => push_u8(out, *type_parameter_index),
+        TargetInfo::SupertypeTarget { supertype_index } => push_u16(out, *supertype_index),
+        TargetInfo::TypeParameterBoundTarget { type_parameter_index, bound_index } => {
+            push_u8(out, *type_parameter_index);
+            push_u8(out, *bound_index);
+        }
+        TargetInfo::EmptyTarget => {}
+        TargetInfo::FormalParameterTarget { formal_parameter_index } => push_u8(out, *formal_parameter_index),
+        TargetInfo::ThrowsTarget { throws_type_index } => push_u16(out, *throws_type_index),
+        TargetInfo::LocalVarTarget { table } => {
+            push_u16(out, table.len() as u16);
+            for e in table {
+                push_u16(out, e.start_pc);
+                push_u16(out, e.length);
+                push_u16(out, e.index);
+            }
+        }
+        TargetInfo::CatchTarget { exception_table_index } => push_u16(out, *exception_table_index),
+        TargetInfo::OffsetTarget { offset } => push_u16(out, *offset),
+        TargetInfo::TypeArgumentTarget { offset, type_argument_index } => {
+            push_u16(out, *offset);
+            push_u8(out, *type_argument_index);
+        }
+    }
+    push_u8(out, a.target_path.path.len() as u8);
+    for p in &a.target_path.path {
+        push_u8(out, p.type_path_kind);
+        push_u8(out, p.type_argument_index);
+    }
+    push_index(out, a.type_index);
+    write_element_value_pairs(&a.element_value_pairs, out);
+}
@@ -0,0 +1,438 @@
+use java_class::attributes::Attribute;
+use java_class::check::check_format;
+use java_class::class::{self, JavaClass};
+use java_class::code::{CodeBody, ExceptionHandler};
+use java_class::fields;
+use java_class::frames::{compress, Frame, FrameType};
+use java_class::methods;
+use java_class::opcode_info::{Flow, OpcodeInfo, OperandKind};
+use java_class::opcodes::Opcode;
+use java_class::symbolic::{BranchKind, Constant, MemberRef, SymOpcode};
+use java_class::visitor::{ClassHeader, ClassVisitor, ClassWriter, MemberHeader, SymbolTable};
+use proc_macro2::Span;
+use std::collections::HashMap;
+use syn::{Error, Ident, LitStr};
+use syntax::{ClassDef, FieldDef, MethodDef, Operand, Statement, TypeDef};
+
+/// Classes default to Java 5, the last version that doesn't need stack map frames
+const DEFAULT_VERSION: (u16, u16) = (49, 0);
+
+/// Assembles the class and checks that `java_class` can parse it back
+pub fn assemble(def: &ClassDef) -> syn::Result<Vec<u8>> {
+    let (major_version, minor_version) = def.version.unwrap_or(DEFAULT_VERSION);
+    let name = def.name.value();
+    let mut access_flags = flags(&def.flags, class::AccessFlags::from_name)?;
+    if def.is_interface {
+        access_flags |= class::AccessFlags::INTERFACE | class::AccessFlags::ABSTRACT;
+    } else {
+        access_flags |= class::AccessFlags::SUPER;
+    }
+    let mut table = SymbolTable::new();
+    let mut writer = ClassWriter::new();
+    writer.visit_header(&mut table, ClassHeader {
+        minor_version,
+        major_version,
+        access_flags: access_flags.bits(),
+        name: name.clone(),
+        super_name: match &def.super_name {
+            Some(s) => Some(s.value()),
+            None if name == "java/lang/Object" => None,
+            None => Some("java/lang/Object".to_owned()),
+        },
+        interfaces: def.interfaces.iter().map(LitStr::value).collect(),
+    });
+    for f in &def.fields {
+        field(f, &mut writer, &mut table)?;
+    }
+    for m in &def.methods {
+        method(m, &name, major_version, &mut writer, &mut table)?;
+    }
+    if let Some(source) = &def.source_file {
+        let sourcefile_index = table.cp.add_utf8(source.value());
+        writer.visit_attribute(&mut table, Attribute::SourceFile { sourcefile_index });
+    }
+    writer.visit_end(&mut table);
+    let bytes = writer.to_bytes(&mut table).map_err(|e| Error::new(def.name.span(), e))?;
+    let class = JavaClass::new_from_bytes(bytes.clone()).map_err(|e| Error::new(def.name.span(), e))?;
+    let errors = check_format(&class);
+    if !errors.is_empty() {
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        return Err(Error::new(def.name.span(), messages.join("\n")));
+    }
+    Ok(bytes)
+}
+
+fn field(f: &FieldDef, writer: &mut ClassWriter, table: &mut SymbolTable) -> syn::Result<()> {
+    let access_flags = flags(&f.flags, fields::AccessFlags::from_name)?;
+    let value = match &f.value {
+        Some(v) => Some(match (f.descriptor.value().as_str(), v) {
+            ("I", Operand::Int { .. }) | ("S", Operand::Int { .. }) | ("C", Operand::Int { .. }) |
+            ("B", Operand::Int { .. }) | ("Z", Operand::Int { .. }) => table.cp.add_integer(int::<i32>(v)? as u32),
+            ("J", Operand::Int { .. }) => table.cp.add_long(int::<i64>(v)? as u64),
+            ("F", Operand::Int { value, .. }) => table.cp.add_float_f32(*value as f32),
+            ("F", Operand::Float { value, .. }) => table.cp.add_float_f32(*value as f32),
+            ("D", Operand::Int { value, .. }) => table.cp.add_double_f64(*value as f64),
+            ("D", Operand::Float { value, .. }) => table.cp.add_double_f64(*value),
+            ("Ljava/lang/String;", Operand::Str(s)) => table.cp.add_string(s.value()),
+            _ => return Err(Error::new(v.span(), "The constant doesn't match the field's type")),
+        }),
+        None => None
+    };
+    let header = MemberHeader { access_flags: access_flags.bits(), name: f.name.value(), descriptor: f.descriptor.value() };
+    if let Some(mut fv) = writer.visit_field(table, header) {
+        if let Some(constantvalue_index) = value {
+            fv.visit_attribute(table, Attribute::ConstantValue { constantvalue_index });
+        }
+        fv.visit_end(table);
+    }
+    Ok(())
+}
+
+fn method(m: &MethodDef, class: &str, major_version: u16, writer: &mut ClassWriter, table: &mut SymbolTable) -> syn::Result<()> {
+    let access_flags = flags(&m.flags, methods::AccessFlags::from_name)?;
+    let (name, descriptor) = (m.name.value(), m.descriptor.value());
+    let code = match &m.body {
+        Some(statements) => {
+            let is_static = access_flags.contains(methods::AccessFlags::STATIC);
+            let initial = Frame::initial(class, &name, &descriptor, is_static).map_err(|e| Error::new(m.descriptor.span(), e))?;
+            Some(code(statements, &initial, major_version, table, m.name.span())?)
+        }
+        None => None
+    };
+    let exception_index_table: Vec<u16> = m.throws.iter().map(|t| table.cp.add_class(t.value()).as_u16()).collect();
+    let header = MemberHeader { access_flags: access_flags.bits(), name, descriptor };
+    if let Some(mut mv) = writer.visit_method(table, header) {
+        if let Some(code) = code {
+            mv.visit_code(table, code);
+        }
+        if !exception_index_table.is_empty() {
+            mv.visit_attribute(table, Attribute::Exceptions { exception_index_table });
+        }
+        mv.visit_end(table);
+    }
+    Ok(())
+}
+
+/// Assembles a method body. `span` is where errors that can't be pinned on a statement are reported.
+fn code(statements: &[Statement], initial: &Frame, major_version: u16, table: &mut SymbolTable, span: Span) -> syn::Result<CodeBody> {
+    //labels are the index of the instruction after them, so the first pass only counts instructions
+    let mut labels = HashMap::new();
+    let mut count = 0;
+    for s in statements {
+        match s {
+            Statement::Label(l) if labels.insert(l.to_string(), count).is_some() => {
+                return Err(Error::new(l.span(), format!("Label `{}` is defined twice", l)));
+            }
+            Statement::Instruction { .. } => count += 1,
+            _ => {}
+        }
+    }
+    let label = |l: &Ident| labels.get(&l.to_string()).cloned().ok_or_else(|| Error::new(l.span(), format!("Unknown label `{}`", l)));
+    let mut instructions = Vec::with_capacity(count);
+    let mut spans = Vec::with_capacity(count);
+    let mut exception_table = vec![];
+    let mut frames: Vec<(Frame, Span)> = vec![];
+    let (mut max_stack, mut max_locals) = (None, None);
+    for s in statements {
+        match s {
+            Statement::Label(_) => {}
+            Statement::Instruction { mnemonic, operands } => {
+                instructions.push(instruction(mnemonic, operands, &label)?);
+                spans.push(mnemonic.span());
+            }
+            Statement::MaxStack(n) => max_stack = Some(*n),
+            Statement::MaxLocals(n) => max_locals = Some(*n),
+            Statement::Catch { catch_type, start, end, handler } => exception_table.push(ExceptionHandler {
+                start: label(start)?,
+                end: label(end)?,
+                handler: label(handler)?,
+                catch_type: catch_type.as_ref().map(LitStr::value),
+            }),
+            Statement::Frame { locals, stack, span } => {
+                let offset = instructions.len();
+                if frames.last().is_some_and(|f| f.0.offset as usize == offset) || offset == count {
+                    return Err(Error::new(*span, "A frame has to be followed by an instruction without another frame"));
+                }
+                let types = |types: &[TypeDef]| types.iter().map(|t| frame_type(t, &label)).collect::<syn::Result<Vec<_>>>();
+                frames.push((Frame { offset: offset as u32, locals: types(locals)?, stack: types(stack)? }, *span));
+            }
+        }
+    }
+    if instructions.is_empty() {
+        return Err(Error::new(span, "A method body needs at least one instruction"));
+    }
+    if major_version >= 50 {
+        check_frames(&instructions, &spans, &exception_table, &frames)?;
+    }
+    let mut body = CodeBody { max_stack: 0, max_locals: 0, instructions, exception_table, attributes: vec![] };
+    body.max_stack = match max_stack {
+        Some(n) => n,
+        None => body.compute_max_stack().map_err(|e| Error::new(span, e))?,
+    };
+    body.max_locals = match max_locals {
+        Some(n) => n,
+        None => {
+            let used = body.instructions.iter().filter_map(|op| match op {
+                SymOpcode::Plain(op) => local_end(op),
+                _ => None
+            });
+            let params = initial.local_slots().len() as u32;
+            let n = used.chain(Some(params)).max().unwrap_or(0);
+            if n > u16::MAX as u32 {
+                return Err(Error::new(span, "Too many locals"));
+            }
+            n as u16
+        }
+    };
+    if !frames.is_empty() {
+        let frames: Vec<Frame> = frames.into_iter().map(|f| f.0).collect();
+        let entries = compress(initial, &frames, &mut table.cp).map_err(|e| Error::new(span, e))?;
+        body.attributes.push(Attribute::StackMapTable { entries });
+    }
+    Ok(body)
+}
+
+fn instruction<L>(mnemonic: &Ident, operands: &[Operand], label: &L) -> syn::Result<SymOpcode>
+    where L: Fn(&Ident) -> syn::Result<usize> {
+    let m = mnemonic.to_string();
+    let arity = |n: usize| -> syn::Result<()> {
+        if operands.len() == n {
+            Ok(())
+        } else {
+            Err(Error::new(mnemonic.span(), format!("`{}` takes {} operand{}", m, n, if n == 1 { "" } else { "s" })))
+        }
+    };
+    //`interface` in front of the owner marks an `InterfaceMethodref`
+    let member = |is_interface: bool| -> syn::Result<MemberRef> {
+        let (is_interface, rest) = match operands.first() {
+            Some(Operand::Name(n)) if n == "interface" => (true, &operands[1..]),
+            _ => (is_interface, operands),
+        };
+        match rest {
+            [owner, name, descriptor] => Ok(MemberRef { owner: string(owner)?, name: string(name)?, descriptor: string(descriptor)?, is_interface }),
+            _ => Err(Error::new(mnemonic.span(), format!("`{}` takes an owner, a name and a descriptor", m))),
+        }
+    };
+    let target = |o: &Operand| match o {
+        Operand::Name(l) => label(l),
+        o => Err(Error::new(o.span(), "Expected a label")),
+    };
+    Ok(match m.as_str() {
+        "ldc" | "ldc_w" | "ldc2_w" => SymOpcode::Ldc(constant(mnemonic, operands)?),
+        "getstatic" => SymOpcode::GetStatic(member(false)?),
+        "putstatic" => SymOpcode::PutStatic(member(false)?),
+        "getfield" => SymOpcode::GetField(member(false)?),
+        "putfield" => SymOpcode::PutField(member(false)?),
+        "invokevirtual" => SymOpcode::InvokeVirtual(member(false)?),
+        "invokespecial" => SymOpcode::InvokeSpecial(member(false)?),
+        "invokestatic" => SymOpcode::InvokeStatic(member(false)?),
+        "invokeinterface" => SymOpcode::InvokeInterface(member(true)?),
+        "new" | "anewarray" | "checkcast" | "instanceof" => {
+            arity(1)?;
+            let class = string(&operands[0])?;
+            match m.as_str() {
+                "new" => SymOpcode::New(class),
+                "anewarray" => SymOpcode::ANewArray(class),
+                "checkcast" => SymOpcode::CheckCast(class),
+                _ => SymOpcode::InstanceOf(class),
+            }
+        }
+        "multianewarray" => {
+            arity(2)?;
+            SymOpcode::MultiANewArray { class: string(&operands[0])?, dimensions: int(&operands[1])? }
+        }
+        //tableswitch low [targets] default label;
+        "tableswitch" => match operands {
+            [low, Operand::List(targets, _), Operand::Name(d), default] if d == "default" => SymOpcode::TableSwitch {
+                default: target(default)?,
+                low: int(low)?,
+                targets: targets.iter().map(|(key, l)| match key {
+                    Some(k) => Err(Error::new(k.span(), "tableswitch targets don't have keys")),
+                    None => label(l),
+                }).collect::<syn::Result<_>>()?,
+            },
+            _ => return Err(Error::new(mnemonic.span(), "Expected `tableswitch low [targets] default label`")),
+        },
+        //lookupswitch [key: label, ...] default label;
+        //the keys can be in any order, they are sorted as the JVM requires
+        "lookupswitch" => match operands {
+            [Operand::List(pairs, span), Operand::Name(d), default] if d == "default" => {
+                let mut pairs = pairs.iter().map(|(key, l)| match key {
+                    Some(k) => Ok((int(k)?, label(l)?)),
+                    None => Err(Error::new(l.span(), "lookupswitch targets need a key, as in `[1: label]`")),
+                }).collect::<syn::Result<Vec<(i32, usize)>>>()?;
+                pairs.sort_by_key(|p| p.0);
+                if pairs.windows(2).any(|w| w[0].0 == w[1].0) {
+                    return Err(Error::new(*span, "lookupswitch keys have to be distinct"));
+                }
+                SymOpcode::LookupSwitch { default: target(default)?, pairs }
+            }
+            _ => return Err(Error::new(mnemonic.span(), "Expected `lookupswitch [key: label, ...] default label`")),
+        },
+        _ => match branch_kind(&m) {
+            Some(kind) => {
+                arity(1)?;
+                SymOpcode::Branch { kind, target: target(&operands[0])? }
+            }
+            None => SymOpcode::Plain(plain(mnemonic, operands)?),
+        }
+    })
+}
+
+/// Assembles an instruction without constant pool or branch operands, widening it if its local index needs it
+fn plain(mnemonic: &Ident, operands: &[Operand]) -> syn::Result<Opcode> {
+    let m = mnemonic.to_string();
+    let info = OpcodeInfo::from_mnemonic(&m).ok_or_else(|| Error::new(mnemonic.span(), format!("Unknown instruction `{}`", m)))?;
+    if info.operands.iter().any(|(_, kind)| *kind == OperandKind::Constant || *kind == OperandKind::Branch) {
+        return Err(Error::new(mnemonic.span(), format!("`{}` isn't supported", m)));
+    }
+    if operands.len() != info.operands.len() {
+        return Err(Error::new(mnemonic.span(), format!("`{}` takes {} operands", m, info.operands.len())));
+    }
+    let mut text = m.clone();
+    for o in operands {
+        match o {
+            Operand::Int { value, .. } => text += &format!(" {}", value),
+            Operand::Name(n) => text += &format!(" {}", n),
+            o => return Err(Error::new(o.span(), "Expected a number")),
+        }
+    }
+    let wide = info.operands.first().is_some_and(|(_, kind)| *kind == OperandKind::Local);
+    text.parse::<Opcode>()
+        .or_else(|e| if wide { format!("wide {}", text).parse::<Opcode>() } else { Err(e) })
+        .map_err(|_| Error::new(mnemonic.span(), format!("Operand out of range for `{}`", m)))
+}
+
+/// Parses the operand of `ldc`: a number, a string, or `class "name"`.
+/// `i64` and `f32` suffixes make a long and a float; other floating point numbers are doubles.
+fn constant(mnemonic: &Ident, operands: &[Operand]) -> syn::Result<Constant> {
+    Ok(match operands {
+        [Operand::Str(s)] => Constant::String(s.value()),
+        [Operand::Name(c), Operand::Str(s)] if c == "class" => Constant::Class(s.value()),
+        [o @ Operand::Int { suffix, .. }] if suffix == "i64" || suffix == "L" => Constant::Long(int(o)?),
+        [o @ Operand::Int { suffix, .. }] if suffix.is_empty() || suffix == "i32" => Constant::Integer(int(o)?),
+        [Operand::Int { value, suffix, .. }] if suffix == "f32" => Constant::Float(*value as f32),
+        [Operand::Int { value, suffix, .. }] if suffix == "f64" => Constant::Double(*value as f64),
+        [Operand::Float { value, suffix, .. }] if suffix == "f32" => Constant::Float(*value as f32),
+        [Operand::Float { value, suffix, .. }] if suffix.is_empty() || suffix == "f64" => Constant::Double(*value),
+        [o] => return Err(Error::new(o.span(), "Expected a number with no suffix or an i32, i64, f32 or f64 suffix")),
+        _ => return Err(Error::new(mnemonic.span(), "Expected a number, a string or `class \"name\"`")),
+    })
+}
+
+fn frame_type<L>(t: &TypeDef, label: &L) -> syn::Result<FrameType>
+    where L: Fn(&Ident) -> syn::Result<usize> {
+    Ok(match t {
+        TypeDef::Object(name) => FrameType::Object(name.value()),
+        TypeDef::Uninitialized(l) => FrameType::Uninitialized(label(l)? as u32),
+        TypeDef::Simple(word) => match word.to_string().as_str() {
+            "top" => FrameType::Top,
+            "int" => FrameType::Integer,
+            "float" => FrameType::Float,
+            "long" => FrameType::Long,
+            "double" => FrameType::Double,
+            "null" => FrameType::Null,
+            "uninitializedThis" => FrameType::UninitializedThis,
+            _ => return Err(Error::new(word.span(), "Expected a type: top, int, float, long, double, null, uninitializedThis, \
+                                                    a class name or uninitialized(label)")),
+        },
+    })
+}
+
+/// Class files from version 50 need a frame at every branch target, every handler,
+/// and every instruction that can't be reached by falling through
+fn check_frames(code: &[SymOpcode], spans: &[Span], handlers: &[ExceptionHandler], frames: &[(Frame, Span)]) -> syn::Result<()> {
+    let mut needed = vec![false; code.len()];
+    for (i, op) in code.iter().enumerate() {
+        match op {
+            SymOpcode::Branch { target, .. } => needed[*target] = true,
+            SymOpcode::TableSwitch { default, targets, .. } => for t in targets.iter().chain(Some(default)) {
+                needed[*t] = true;
+            },
+            SymOpcode::LookupSwitch { default, pairs } => for t in pairs.iter().map(|p| &p.1).chain(Some(default)) {
+                needed[*t] = true;
+            },
+            _ => {}
+        }
+        if !matches!(op.flow(), Flow::Next | Flow::ConditionalBranch) && i + 1 < code.len() {
+            needed[i + 1] = true;
+        }
+    }
+    for h in handlers {
+        if let Some(n) = needed.get_mut(h.handler) {
+            *n = true;
+        }
+    }
+    for f in frames {
+        needed[f.0.offset as usize] = false;
+    }
+    match needed.iter().position(|n| *n) {
+        Some(i) => Err(Error::new(spans[i], "Class files from version 50 need a `frame [locals] [stack];` before this instruction")),
+        None => Ok(()),
+    }
+}
+
+/// The slot after the last local variable slot the instruction uses
+fn local_end(op: &Opcode) -> Option<u32> {
+    let info = op.info();
+    let (mnemonic, index) = match op {
+        Opcode::wide { opcode, index } => (OpcodeInfo::from_byte(*opcode)?.mnemonic, *index as u32),
+        _ => match info.operands.first() {
+            Some((_, OperandKind::Local)) => (info.mnemonic, op.operands()[0] as u32),
+            //e.g. lload_2
+            _ => match info.mnemonic.split_once('_') {
+                Some((m, n)) if m.ends_with("load") || m.ends_with("store") => (info.mnemonic, n.parse().ok()?),
+                _ => return None
+            }
+        }
+    };
+    Some(index + if mnemonic.starts_with('l') || mnemonic.starts_with('d') { 2 } else { 1 })
+}
+
+fn branch_kind(mnemonic: &str) -> Option<BranchKind> {
+    Some(match mnemonic {
+        "ifeq" => BranchKind::Ifeq,
+        "ifne" => BranchKind::Ifne,
+        "iflt" => BranchKind::Iflt,
+        "ifge" => BranchKind::Ifge,
+        "ifgt" => BranchKind::Ifgt,
+        "ifle" => BranchKind::Ifle,
+        "if_icmpeq" => BranchKind::IfIcmpeq,
+        "if_icmpne" => BranchKind::IfIcmpne,
+        "if_icmplt" => BranchKind::IfIcmplt,
+        "if_icmpge" => BranchKind::IfIcmpge,
+        "if_icmpgt" => BranchKind::IfIcmpgt,
+        "if_icmple" => BranchKind::IfIcmple,
+        "if_acmpeq" => BranchKind::IfAcmpeq,
+        "if_acmpne" => BranchKind::IfAcmpne,
+        "ifnull" => BranchKind::Ifnull,
+        "ifnonnull" => BranchKind::Ifnonnull,
+        "goto" | "goto_w" => BranchKind::Goto,
+        "jsr" | "jsr_w" => BranchKind::Jsr,
+        _ => return None
+    })
+}
+
+/// Combines flag names such as `public` and `static` with `from_name`
+fn flags<T, F>(words: &[Ident], from_name: F) -> syn::Result<T>
+    where T: Default + ::std::ops::BitOrAssign, F: Fn(&str) -> Option<T> {
+    let mut ans = T::default();
+    for w in words {
+        ans |= from_name(&w.to_string().to_uppercase()).ok_or_else(|| Error::new(w.span(), format!("Unknown flag `{}`", w)))?;
+    }
+    Ok(ans)
+}
+
+fn string(o: &Operand) -> syn::Result<String> {
+    match o {
+        Operand::Str(s) => Ok(s.value()),
+        o => Err(Error::new(o.span(), "Expected a string")),
+    }
+}
+
+fn int<T: ::std::convert::TryFrom<i128>>(o: &Operand) -> syn::Result<T> {
+    match o {
+        Operand::Int { value, span, .. } => T::try_from(*value).map_err(|_| Error::new(*span, "Number out of range")),
+        o => Err(Error::new(o.span(), "Expected an integer")),
+    }
+}
@@ -0,0 +1,81 @@
+extern crate java_class;
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+mod assemble;
+mod syntax;
+
+use proc_macro::TokenStream;
+use quote::quote;
+
+/// Assembles a class file at compile time and expands to its bytes, as a `&'static [u8]`.
+/// The class is parsed back with `java_class` and checked with `check_format`, and mistakes
+/// (unknown labels, missing stack map frames, bad operands) are reported as compile errors.
+///
+/// ```
+/// # extern crate java_class;
+/// # extern crate java_class_macros;
+/// # use java_class::class::JavaClass;
+/// # use java_class_macros::java_class;
+/// let bytes: &[u8] = java_class! {
+///     version 52;
+///     source "Counter.java";
+///     public class "test/Counter" extends "java/lang/Object" implements "java/lang/Runnable" {
+///         private static field "count" "I";
+///         public static final field "LIMIT" "J" = 10;
+///
+///         public method "<init>" "()V" {
+///             aload_0;
+///             invokespecial "java/lang/Object" "<init>" "()V";
+///             return;
+///         }
+///
+///         public method "run" "()V" {
+///         top:
+///             frame ["test/Counter"] [];
+///             getstatic "test/Counter" "count" "I";
+///             ldc 100;
+///             if_icmpge done;
+///             getstatic "test/Counter" "count" "I";
+///             iconst_1;
+///             iadd;
+///             putstatic "test/Counter" "count" "I";
+///             goto top;
+///         done:
+///             frame ["test/Counter"] [];
+///             return;
+///         }
+///
+///         public static native method "poke" "(Ljava/lang/Object;)V" throws "java/io/IOException";
+///     }
+/// };
+/// let class = JavaClass::new_from_bytes(bytes.to_vec()).unwrap();
+/// assert_eq!(class.get_name(), "test/Counter");
+/// ```
+///
+/// * The version defaults to 49, the last one that doesn't need stack map frames. From version 50
+///   every branch target, handler and instruction after a jump needs a `frame [locals] [stack];`
+///   in front of it. Types are `int`, `float`, `long`, `double`, `null`, `top`, `uninitializedThis`,
+///   a class name or `uninitialized(label)`.
+/// * Labels are written `name:` and refer to the next instruction.
+/// * `ldc` takes a string, `class "name"` or a number: `i64` makes a long, `f32` a float, and
+///   floating point numbers without a suffix are doubles. `ldc_w` and `ldc2_w` are picked as needed.
+/// * Field and method instructions take the owner, the name and the descriptor. `interface` in front
+///   of the owner refers to an interface method.
+/// * Switches are written `tableswitch 0 [a, b] default c;` and `lookupswitch [1: a, 10: b] default c;`.
+/// * Exception handlers are written `catch "java/lang/Exception" from start to end using handler;`,
+///   or `catch any ...` for every exception.
+/// * `max_stack` and `max_locals` are computed, and `stack N;` and `locals N;` override them.
+#[proc_macro]
+pub fn java_class(input: TokenStream) -> TokenStream {
+    let def = syn::parse_macro_input!(input as syntax::ClassDef);
+    match assemble::assemble(&def) {
+        Ok(bytes) => {
+            let lit = proc_macro2::Literal::byte_string(&bytes);
+            quote!((#lit as &'static [u8])).into()
+        }
+        Err(e) => e.to_compile_error().into(),
+    }
+}
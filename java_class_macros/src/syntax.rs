@@ -0,0 +1,302 @@
+use proc_macro2::Span;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::{bracketed, braced, parenthesized, Ident, Lit, LitStr, Token};
+
+/// The whole input of `java_class!`
+pub struct ClassDef {
+    /// major and minor version
+    pub version: Option<(u16, u16)>,
+    pub source_file: Option<LitStr>,
+    pub flags: Vec<Ident>,
+    pub is_interface: bool,
+    pub name: LitStr,
+    pub super_name: Option<LitStr>,
+    pub interfaces: Vec<LitStr>,
+    pub fields: Vec<FieldDef>,
+    pub methods: Vec<MethodDef>,
+}
+
+pub struct FieldDef {
+    pub flags: Vec<Ident>,
+    pub name: LitStr,
+    pub descriptor: LitStr,
+    pub value: Option<Operand>,
+}
+
+pub struct MethodDef {
+    pub flags: Vec<Ident>,
+    pub name: LitStr,
+    pub descriptor: LitStr,
+    pub throws: Vec<LitStr>,
+    /// `None` for abstract and native methods
+    pub body: Option<Vec<Statement>>,
+}
+
+pub enum Statement {
+    Label(Ident),
+    Instruction { mnemonic: Ident, operands: Vec<Operand> },
+    /// `stack N;`, which overrides the computed `max_stack`
+    MaxStack(u16),
+    /// `locals N;`, which overrides the computed `max_locals`
+    MaxLocals(u16),
+    /// `catch "type" from start to end using handler;`, where the type may be `any`
+    Catch { catch_type: Option<LitStr>, start: Ident, end: Ident, handler: Ident },
+    /// `frame [locals] [stack];`, the stack map frame at the next instruction
+    Frame { locals: Vec<TypeDef>, stack: Vec<TypeDef>, span: Span },
+}
+
+/// A verification type in a `frame` statement
+pub enum TypeDef {
+    /// `int`, `float`, `long`, `double`, `null`, `top` or `uninitializedThis`
+    Simple(Ident),
+    /// a class name
+    Object(LitStr),
+    /// `uninitialized(label)`, the object created by the `new` at `label`
+    Uninitialized(Ident),
+}
+
+pub enum Operand {
+    Int { value: i128, suffix: String, span: Span },
+    Float { value: f64, suffix: String, span: Span },
+    Str(LitStr),
+    Name(Ident),
+    /// `[a, b]` or `[1: a, 2: b]`
+    List(Vec<(Option<Operand>, Ident)>, Span),
+}
+
+impl Operand {
+    pub fn span(&self) -> Span {
+        match self {
+            Operand::Int { span, .. } | Operand::Float { span, .. } | Operand::List(_, span) => *span,
+            Operand::Str(s) => s.span(),
+            Operand::Name(n) => n.span(),
+        }
+    }
+}
+
+impl Parse for ClassDef {
+    fn parse(input: ParseStream) -> syn::Result<ClassDef> {
+        let mut version = None;
+        let mut source_file = None;
+        let mut flags = vec![];
+        let is_interface = loop {
+            let word = Ident::parse_any(input)?;
+            match word.to_string().as_str() {
+                "version" if version.is_none() && flags.is_empty() => {
+                    version = Some(parse_version(input)?);
+                    input.parse::<Token![;]>()?;
+                }
+                "source" if source_file.is_none() && flags.is_empty() => {
+                    source_file = Some(input.parse()?);
+                    input.parse::<Token![;]>()?;
+                }
+                "class" => break false,
+                "interface" => break true,
+                _ => flags.push(word),
+            }
+        };
+        let name = input.parse()?;
+        let super_name = if keyword(input, "extends")? { Some(input.parse()?) } else { None };
+        let mut interfaces = vec![];
+        if keyword(input, "implements")? {
+            loop {
+                interfaces.push(input.parse()?);
+                if input.parse::<Option<Token![,]>>()?.is_none() {
+                    break;
+                }
+            }
+        }
+        let content;
+        braced!(content in input);
+        let (mut fields, mut methods) = (vec![], vec![]);
+        while !content.is_empty() {
+            let mut flags = vec![];
+            let word = loop {
+                let word = Ident::parse_any(&content)?;
+                match word.to_string().as_str() {
+                    "field" | "method" => break word,
+                    _ => flags.push(word),
+                }
+            };
+            let name = content.parse()?;
+            let descriptor = content.parse()?;
+            if word == "field" {
+                let value = if content.parse::<Option<Token![=]>>()?.is_some() { Some(content.parse()?) } else { None };
+                content.parse::<Token![;]>()?;
+                fields.push(FieldDef { flags, name, descriptor, value });
+            } else {
+                let mut throws = vec![];
+                if keyword(&content, "throws")? {
+                    loop {
+                        throws.push(content.parse()?);
+                        if content.parse::<Option<Token![,]>>()?.is_none() {
+                            break;
+                        }
+                    }
+                }
+                let body = if content.parse::<Option<Token![;]>>()?.is_some() {
+                    None
+                } else {
+                    let code;
+                    braced!(code in content);
+                    let mut statements = vec![];
+                    while !code.is_empty() {
+                        statements.push(code.parse()?);
+                    }
+                    Some(statements)
+                };
+                methods.push(MethodDef { flags, name, descriptor, throws, body });
+            }
+        }
+        if !input.is_empty() {
+            return Err(input.error("Expected the end of the class"));
+        }
+        Ok(ClassDef { version, source_file, flags, is_interface, name, super_name, interfaces, fields, methods })
+    }
+}
+
+impl Parse for Statement {
+    fn parse(input: ParseStream) -> syn::Result<Statement> {
+        let word = Ident::parse_any(input)?;
+        if input.peek(Token![:]) && !input.peek(Token![::]) {
+            input.parse::<Token![:]>()?;
+            return Ok(Statement::Label(word));
+        }
+        let ans = match word.to_string().as_str() {
+            "stack" if input.peek(syn::LitInt) => Statement::MaxStack(input.parse::<syn::LitInt>()?.base10_parse()?),
+            "locals" if input.peek(syn::LitInt) => Statement::MaxLocals(input.parse::<syn::LitInt>()?.base10_parse()?),
+            "catch" => {
+                let catch_type = if input.peek(LitStr) {
+                    Some(input.parse()?)
+                } else {
+                    let any = Ident::parse_any(input)?;
+                    if any != "any" {
+                        return Err(syn::Error::new(any.span(), "Expected a class name or `any`"));
+                    }
+                    None
+                };
+                expect_keyword(input, "from")?;
+                let start = Ident::parse_any(input)?;
+                expect_keyword(input, "to")?;
+                let end = Ident::parse_any(input)?;
+                expect_keyword(input, "using")?;
+                let handler = Ident::parse_any(input)?;
+                Statement::Catch { catch_type, start, end, handler }
+            }
+            "frame" => {
+                let (locals, stack);
+                bracketed!(locals in input);
+                bracketed!(stack in input);
+                Statement::Frame { locals: parse_types(&locals)?, stack: parse_types(&stack)?, span: word.span() }
+            }
+            _ => {
+                let mut operands = vec![];
+                while !input.peek(Token![;]) && !input.is_empty() {
+                    operands.push(input.parse()?);
+                }
+                Statement::Instruction { mnemonic: word, operands }
+            }
+        };
+        input.parse::<Token![;]>()?;
+        Ok(ans)
+    }
+}
+
+impl Parse for Operand {
+    fn parse(input: ParseStream) -> syn::Result<Operand> {
+        if input.peek(syn::token::Bracket) {
+            let list;
+            let bracket = bracketed!(list in input);
+            let mut items = vec![];
+            while !list.is_empty() {
+                let key = if list.peek(Token![-]) || list.peek2(Token![:]) { Some(list.parse()?) } else { None };
+                if key.is_some() {
+                    list.parse::<Token![:]>()?;
+                }
+                items.push((key, Ident::parse_any(&list)?));
+                if list.parse::<Option<Token![,]>>()?.is_none() {
+                    break;
+                }
+            }
+            if !list.is_empty() {
+                return Err(list.error("Expected `,` or `]`"));
+            }
+            return Ok(Operand::List(items, bracket.span.join()));
+        }
+        let negative = input.parse::<Option<Token![-]>>()?.is_some();
+        if !negative && !input.peek(Lit) {
+            return Ok(Operand::Name(Ident::parse_any(input)?));
+        }
+        Ok(match input.parse::<Lit>()? {
+            Lit::Int(i) => {
+                let value: i128 = i.base10_parse()?;
+                Operand::Int { value: if negative { -value } else { value }, suffix: i.suffix().to_owned(), span: i.span() }
+            }
+            Lit::Float(f) => {
+                let value: f64 = f.base10_parse()?;
+                Operand::Float { value: if negative { -value } else { value }, suffix: f.suffix().to_owned(), span: f.span() }
+            }
+            Lit::Str(s) if !negative => Operand::Str(s),
+            l => return Err(syn::Error::new(l.span(), "Expected a number, a string, a name or a list")),
+        })
+    }
+}
+
+fn parse_types(input: ParseStream) -> syn::Result<Vec<TypeDef>> {
+    let mut ans = vec![];
+    while !input.is_empty() {
+        ans.push(if input.peek(LitStr) {
+            TypeDef::Object(input.parse()?)
+        } else {
+            let word = Ident::parse_any(input)?;
+            if word == "uninitialized" {
+                let label;
+                parenthesized!(label in input);
+                TypeDef::Uninitialized(Ident::parse_any(&label)?)
+            } else {
+                TypeDef::Simple(word)
+            }
+        });
+        if input.parse::<Option<Token![,]>>()?.is_none() {
+            break;
+        }
+    }
+    if !input.is_empty() {
+        return Err(input.error("Expected `,` or `]`"));
+    }
+    Ok(ans)
+}
+
+/// Parses `major` or `major.minor`
+fn parse_version(input: ParseStream) -> syn::Result<(u16, u16)> {
+    match input.parse::<Lit>()? {
+        Lit::Int(i) => Ok((i.base10_parse()?, 0)),
+        Lit::Float(f) => {
+            let digits = f.base10_digits();
+            let (major, minor) = digits.split_once('.').unwrap_or((digits, "0"));
+            match (major.parse(), minor.parse()) {
+                (Ok(major), Ok(minor)) => Ok((major, minor)),
+                _ => Err(syn::Error::new(f.span(), "Expected a class file version such as `52` or `45.3`")),
+            }
+        }
+        l => Err(syn::Error::new(l.span(), "Expected a class file version such as `52` or `45.3`")),
+    }
+}
+
+/// Consumes `word` if it comes next
+fn keyword(input: ParseStream, word: &str) -> syn::Result<bool> {
+    if input.fork().call(Ident::parse_any).is_ok_and(|i| i == word) {
+        Ident::parse_any(input)?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+fn expect_keyword(input: ParseStream, word: &str) -> syn::Result<()> {
+    if keyword(input, word)? {
+        Ok(())
+    } else {
+        Err(input.error(format!("Expected `{}`", word)))
+    }
+}
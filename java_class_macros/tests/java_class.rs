@@ -0,0 +1,119 @@
+extern crate java_class;
+extern crate java_class_macros;
+
+use java_class::check::check_format;
+use java_class::class::JavaClass;
+use java_class::code::ExceptionHandler;
+use java_class::resolved::ClassInfo;
+use java_class::symbolic::{BranchKind, Constant, SymOpcode};
+use java_class_macros::java_class;
+
+fn parse(bytes: &[u8]) -> JavaClass {
+    let class = JavaClass::new_from_bytes(bytes.to_vec()).unwrap();
+    assert_eq!(check_format(&class), vec![]);
+    class
+}
+
+#[test]
+fn class_structure() {
+    let class = parse(java_class! {
+        version 52;
+        source "Counter.java";
+        public class "test/Counter" extends "java/lang/Object" implements "java/lang/Runnable" {
+            private static field "count" "I";
+            public static final field "LIMIT" "J" = 10;
+
+            public method "<init>" "()V" {
+                aload_0;
+                invokespecial "java/lang/Object" "<init>" "()V";
+                return;
+            }
+
+            public method "run" "()V" {
+            top:
+                frame ["test/Counter"] [];
+                getstatic "test/Counter" "count" "I";
+                ldc 100;
+                if_icmpge done;
+                getstatic "test/Counter" "count" "I";
+                iconst_1;
+                iadd;
+                putstatic "test/Counter" "count" "I";
+                goto top;
+            done:
+                frame ["test/Counter"] [];
+                return;
+            }
+
+            public static native method "poke" "(Ljava/lang/Object;)V" throws "java/io/IOException";
+        }
+    });
+    assert_eq!(class.major_version, 52);
+    let info = ClassInfo::new(&class).unwrap();
+    assert_eq!(info.name, "test/Counter");
+    assert_eq!(info.super_name.as_deref(), Some("java/lang/Object"));
+    assert_eq!(info.interfaces, ["java/lang/Runnable"]);
+    assert_eq!(info.field("LIMIT").unwrap().constant_value, Some(Constant::Long(10)));
+    assert_eq!(info.method("poke", "(Ljava/lang/Object;)V").unwrap().exceptions, ["java/io/IOException"]);
+
+    let run = info.method("run", "()V").unwrap();
+    let code = run.code.as_ref().unwrap();
+    assert_eq!((code.max_stack, code.max_locals), (2, 1));
+    let body = code.to_body(&class).unwrap();
+    assert_eq!(body.instructions.len(), 9);
+    assert_eq!(body.instructions[2], SymOpcode::Branch { kind: BranchKind::IfIcmpge, target: 8 });
+    assert_eq!(body.instructions[7], SymOpcode::Branch { kind: BranchKind::Goto, target: 0 });
+}
+
+#[test]
+fn switches_constants_and_handlers() {
+    let class = parse(java_class! {
+        public class "test/Switches" {
+            public static method "pick" "(I)I" {
+                iload_0;
+                lookupswitch [10: ten, 1: one] default other;
+            one:
+                iconst_1;
+                ireturn;
+            ten:
+                bipush 10;
+                ireturn;
+            other:
+                iload_0;
+                tableswitch 5 [one, ten] default fail;
+            fail:
+                iconst_m1;
+                ireturn;
+            }
+
+            public static method "constants" "()Ljava/lang/Object;" {
+            start:
+                ldc 1234567890123i64;
+                pop2;
+                ldc 1.5f32;
+                pop;
+                ldc class "java/lang/String";
+            end:
+                areturn;
+            handler:
+                areturn;
+                catch "java/lang/Exception" from start to end using handler;
+            }
+        }
+    });
+    assert_eq!(class.major_version, 49);
+    let info = ClassInfo::new(&class).unwrap();
+
+    let pick = info.method("pick", "(I)I").unwrap().code.as_ref().unwrap().to_body(&class).unwrap();
+    //the keys of a lookupswitch are sorted
+    assert_eq!(pick.instructions[1], SymOpcode::LookupSwitch { default: 6, pairs: vec![(1, 2), (10, 4)] });
+    assert_eq!(pick.instructions[7], SymOpcode::TableSwitch { default: 8, low: 5, targets: vec![2, 4] });
+
+    let constants = info.method("constants", "()Ljava/lang/Object;").unwrap().code.as_ref().unwrap();
+    let body = constants.to_body(&class).unwrap();
+    assert_eq!(body.instructions[0], SymOpcode::Ldc(Constant::Long(1234567890123)));
+    assert_eq!(body.instructions[2], SymOpcode::Ldc(Constant::Float(1.5)));
+    assert_eq!(body.instructions[4], SymOpcode::Ldc(Constant::Class("java/lang/String".to_owned())));
+    assert_eq!(body.exception_table, vec![ExceptionHandler { start: 0, end: 5, handler: 6, catch_type: Some("java/lang/Exception".to_owned()) }]);
+    assert_eq!(constants.max_stack, 2);
+}
@@ -1,17 +1,13 @@
 use java_class::class::JavaClass;
-use java_class::cp::CPIndex;
-use java_class::cp_info::CPInfo;
+use java_class::classpath::ClassPath;
+use java_class::resolved::ClassInfo;
 use std::collections::HashMap;
-use std::fs::File;
-use std::path::Path;
-use std::path::PathBuf;
 use std::str;
 use std::sync::Mutex;
 use std::sync::{Arc, RwLock};
 use types;
 use types::{Class, ClassRef};
 use types::JavaType;
-use zip::ZipArchive;
 use threads::*;
 
 static mut JVM_INSTANCE: *const Arc<RwLock<JVM>> = 0 as *const Arc<RwLock<JVM>>;
@@ -29,8 +25,7 @@ fn jvm() -> Arc<RwLock<JVM>> {
 /// struct containing all runtime information about the JVM'
 #[allow(clippy::upper_case_acronyms)]
 struct JVM {
-    pub jars: Vec<ZipArchive<File>>,
-    pub classpath: Box<[String]>,
+    pub classpath: ClassPath,
     pub classes: HashMap<String, ClassRef>,
     pub to_load: Vec<String>,
     pub to_init: Vec<(ClassRef, Arc<JavaClass>)>,
@@ -47,16 +42,21 @@ struct JVM {
 pub fn start(classpath: Box<[String]>, entry_point: &str) {
     info!("Starting JVM");
     debug!("Start");
-    let mut jars = vec!();
     let java_path = r#"C:\Program Files\Java\jdk1.8.0_201"#;
-    jars.push(ZipArchive::new(File::open(java_path.to_owned() + "/jre/lib/rt.jar").unwrap()).unwrap());
-    jars.push(ZipArchive::new(File::open(java_path.to_owned() + "/jre/lib/jce.jar").unwrap()).unwrap());
-    // jars.push(ZipArchive::new(File::open(::std::env::var("JAVA_HOME").unwrap() + "/jre/lib/rt.jar").unwrap()).unwrap());
-    // jars.push(ZipArchive::new(File::open(::std::env::var("JAVA_HOME").unwrap() + "/jre/lib/jce.jar").unwrap()).unwrap());
-    //let stdlib = ZipArchive::new(File::open(::std::env::var("JAVA_HOME").unwrap()+"/jre/lib/rt.jar").unwrap()).unwrap();
+    let mut cp = ClassPath::new();
+    //multi-release jars should resolve to the classes for the version of Java we implement
+    cp.set_release(Some(MAJOR_VERSION - 44));
+    cp.push(java_path.to_owned() + "/jre/lib/rt.jar").unwrap();
+    cp.push(java_path.to_owned() + "/jre/lib/jce.jar").unwrap();
+    // cp.push(::std::env::var("JAVA_HOME").unwrap() + "/jre/lib/rt.jar").unwrap();
+    // cp.push(::std::env::var("JAVA_HOME").unwrap() + "/jre/lib/jce.jar").unwrap();
+    for path in classpath.iter() {
+        if let Err(e) = cp.push(path) {
+            error!("Could not open classpath entry {}: {:?}", path, e);
+        }
+    }
     let jvm = JVM {
-        jars,
-        classpath,
+        classpath: cp,
         classes: HashMap::<String, ClassRef>::new(),
         to_load: Vec::new(),
         to_init: Vec::new(),
@@ -99,45 +99,21 @@ pub fn get_class(name: &str) -> Option<ClassRef> {
     }
 }
 
-fn find_class_jar(name: &str) -> Option<Vec<u8>> {
-    let jvm = jvm();
-    let mut jvm = jvm.write().unwrap();
-    for jar in &mut jvm.jars {
-        let x = jar.by_name(&(name.to_string()+".class"));
-        let mut file = match x {
-            Ok(s) => s,
-            Err(_) => continue
-        };
-        let mut ans = vec!();
-        use std::io::Read;
-        if let Err(a) = file.read_to_end(&mut ans) {
-            error!("Error reading from jar: {:?}", a);
-            return None;
-        }
-        return Some(ans)
-    }
-    None
-}
-
-/// returns the path to a .class file of a given class, if it exists and is in the classpath
-fn find_class(name: &str) -> Option<PathBuf> {
-    let mut parts = Vec::new();
-    name.split('/').for_each(|s| parts.push(s.to_owned()));
-    let l = parts.len();
-    parts[l - 1] += ".class";
+/// returns the bytes of the .class file of a given class, if it is in the classpath
+fn find_class(name: &str) -> Option<Vec<u8>> {
     let jvm = jvm();
     let jvm = jvm.read().unwrap();
-    for bpath in jvm.classpath.iter() {
-        let mut p = Path::new(bpath).to_path_buf();
-        for x in &parts {
-            p = p.join(x);
+    match jvm.classpath.find_class_bytes(name) {
+        Ok(Some(bytes)) => Some(bytes),
+        Ok(None) => {
+            error!("Could not find class {}", name);
+            None
         }
-        if p.exists() {
-            return Some(p);
+        Err(e) => {
+            error!("Error reading class {}: {:?}", name, e);
+            None
         }
     }
-    error!("Could not find class {}", name);
-    None
 }
 
 /// returns true if the bootstrap classloader has already loaded the class
@@ -165,34 +141,6 @@ pub fn add_to_load(name: &str) {
     }
 }
 
-/// helper function to get the names of values from a "wrapper" CPInfo struct such as CPInfo::Class
-pub fn get_name(class: &JavaClass, info: &CPInfo) -> String {
-    match info {
-        CPInfo::Class { name_index } => {
-            get_name(class, &class.constant_pool[*name_index])
-        }
-        CPInfo::Utf8 { bytes, .. } => {
-            ::java_class::class::read_string(bytes)
-        }
-        _ => panic!("Invalid CPInfo for get_name")
-    }
-}
-
-pub fn get_name_cp(cp: &::java_class::cp::ConstantPool, index: CPIndex) -> String {
-    match &cp[index] {
-        CPInfo::Class { name_index } => {
-            get_name_cp(cp, *name_index)
-        }
-        CPInfo::String { string_index } => {
-            get_name_cp(cp, *string_index)
-        }
-        CPInfo::Utf8 { bytes, .. } => {
-            ::java_class::class::read_string(bytes)
-        }
-        _ => panic!("Invalid CPInfo for get_name")
-    }
-}
-
 fn has_to_load() -> bool {
     let jvm = jvm();
     let jvm = jvm.read().unwrap();
@@ -266,11 +214,8 @@ pub fn load_class(name: &str) -> Option<ClassRef> {
             debug!("Found class to initialize");
             let cx = get_to_init();
 
-            let name_2 = if cx.1.constant_pool.len() > 1 {
-                get_name_cp(&cx.1.constant_pool, cx.1.this_class)
-            } else {
-                "Unknown class name".to_string()
-            };
+            let name_2 = cx.1.constant_pool.get_class_name(cx.1.this_class)
+                .unwrap_or_else(|| "Unknown class name".to_string());
             debug!("Initializing {}", name_2);
             unsafe {
                 let c = std::mem::transmute::<ClassRef, *mut Class>(cx.0);
@@ -373,28 +318,20 @@ fn load_class_2(name: &str) -> Option<ClassRef> {
 
     //load the .class into a static representation
     debug!("Trying to load class {}", name);
-    let jc = match find_class_jar(name) {
+    let jc = match find_class(name) {
         Some(bytes) => {
             match JavaClass::new_from_bytes(bytes) {
                 Ok(c) => c,
                 Err(a) => {
-                    error!("Class {} could not be loaded from zip: {:?}", name, a);
+                    error!("Class {} could not be loaded: {:?}", name, a);
                     return None;
                 }
             }
         }
         None => {
-            match find_class(name) {
-                Some(p) => match JavaClass::new(p.to_str().unwrap()) {
-                    Ok(c) => c,
-                    Err(_) => return None
-                },
-                None => {
-                    error!("Class could not be found");
-                    panic!();
-                    //return None;
-                }
-            }
+            error!("Class could not be found");
+            panic!();
+            //return None;
         }
     };
     let jc = Arc::new(jc);
@@ -405,22 +342,15 @@ fn load_class_2(name: &str) -> Option<ClassRef> {
 pub fn load_class_from_binary(jc: Arc<JavaClass>) -> Option<ClassRef> {
     let name = jc.get_name();
     //load superinterfaces and superclasses
-    //if the class is not java.lang.Object, attempt to load its superclass
-    if name != "java/lang/Object" {
-        let super_class_index = jc.super_class;
-        let super_class_name = get_name(&jc, &jc.constant_pool[super_class_index]);
-        //a class may not be its own superclass
-        if super_class_name==name {
-            return None;
-        }
-        //superclasses may not be interfaces
+    let info = ClassInfo::new(&jc).ok()?;
+    //a class may not be its own superclass
+    //superclasses may not be interfaces
+    if info.super_name.as_ref() == Some(&name) {
+        return None;
     }
-    for interface_index in &jc.interfaces {
-        let interface_name = get_name(&jc, &jc.constant_pool[*interface_index]);
-        //an interface may not be its own superinterface
-        if interface_name==name {
-            return None;
-        }
+    //an interface may not be its own superinterface
+    if info.interfaces.contains(&name) {
+        return None;
     }
     debug!("Making Class struct");
     let class = Class::default();
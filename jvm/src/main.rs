@@ -20,7 +20,6 @@ extern crate env_logger;
 extern crate java_class;
 #[macro_use]
 extern crate log;
-extern crate zip;
 extern crate va_list;
 
 //will uncomment the following when it's time to work on the JNI
@@ -957,7 +957,7 @@ impl JvmThread {
                     exception!("", method.unwrap_err());
                 };
                 use java_class::methods::AccessFlags;
-                if method.access_flags & AccessFlags::Native as u16 != 0 {
+                if method.access_flags & AccessFlags::NATIVE.bits() != 0 {
                     //TODO: native methods
                     todo!("Native methods are unimplemented");
                 } else {
@@ -987,7 +987,7 @@ impl JvmThread {
                 //of the current class, and the method is not declared in the same 
                 //run-time package (§5.3) as the current class, then the class of objectref 
                 //must be either the current class or a subclass of the current class.
-                let required_superclass = if method.access_flags & AccessFlags::Protected as u16 != 0 {
+                let required_superclass = if method.access_flags & AccessFlags::PROTECTED.bits() != 0 {
                     if method.class.get_package() != class.get_package() {
                         Some(class)
                     } else {
@@ -1005,7 +1005,7 @@ impl JvmThread {
                 if method.name.ends_with("/<init>") && method.class != class {
                     exception!("", "NoSuchMethodError");
                 }
-                if method.access_flags & AccessFlags::Static as u16 != 0 {
+                if method.access_flags & AccessFlags::STATIC.bits() != 0 {
                     exception!("", "IncompatibleClassChangeError");
                 }
                 if method.is_abstract() {
@@ -1071,7 +1071,7 @@ impl JvmThread {
                 } else {
                     exception!("", m.unwrap_err());
                 };
-                if m.access_flags & java_class::methods::AccessFlags::Static as u16 == 0 {
+                if m.access_flags & java_class::methods::AccessFlags::STATIC.bits() == 0 {
                     exception!("Attempted to invokestatic on instance method", "IncompatibleClassChangeError");
                 }
                 let count = m.parameters.len();
@@ -5,6 +5,7 @@ use java_class::opcodes::Opcode;
 use java_class::cp_info::CPInfo;
 use java_class::fields::FieldInfo;
 use java_class::methods::MethodInfo;
+use java_class::resolved::ClassInfo;
 use jvm;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -82,7 +83,7 @@ impl Class {
     pub fn initialize_start(&self, class: &JavaClass) -> Option<()> {
         for cp_info in class.constant_pool.items() {
             if let CPInfo::Class { name_index } = cp_info {
-                let name = jvm::get_name_cp(&class.constant_pool, *name_index);
+                let name = class.constant_pool.get_utf8(*name_index)?;
                 jvm::add_to_load(&name);
             } 
         }
@@ -98,12 +99,12 @@ impl Class {
                 sub_name = x;
             } else {
                 //it's an array of primitives
-                self.access_flags = ::java_class::class::AccessFlags::Public as u16;
+                self.access_flags = ::java_class::class::AccessFlags::PUBLIC.bits();
                 return Some(());
             }
             //unwrap should be ok since the subclass goes ahead of the array class in initialization order
             let subclass = jvm::get_class(&sub_name).unwrap();
-            self.access_flags = subclass.access_flags & ::java_class::class::AccessFlags::Public as u16;
+            self.access_flags = subclass.access_flags & ::java_class::class::AccessFlags::PUBLIC.bits();
             self.array_inner = Some(subclass);
             return Some(());
         }
@@ -112,26 +113,27 @@ impl Class {
         self.major_version = class.major_version;
         self.constant_pool = RuntimeConstantPool::new(&class.constant_pool)?;
         self.access_flags = class.access_flags;
-        self.name = jvm::get_name_cp(&class.constant_pool, class.this_class);
+        let info = ClassInfo::new(class).ok()?;
+        self.name = info.name.clone();
 
         // if this is java/lang/Object it has no super class
-        self.super_class = if self.name == "java/lang/Object" || class.super_class.as_u16() == 0 {
-            None
-        } else {
-            //shouldn't need to guard against circular superclassing since that's done while loading the .class in ::jvm
-            let ans = jvm::get_class(&jvm::get_name_cp(&class.constant_pool, class.super_class)).unwrap();
-            Some(ans)
+        self.super_class = match &info.super_name {
+            Some(super_name) if self.name != "java/lang/Object" => {
+                //shouldn't need to guard against circular superclassing since that's done while loading the .class in ::jvm
+                Some(jvm::get_class(super_name).unwrap())
+            }
+            _ => None
         };
-        self.interfaces = Vec::with_capacity(class.interfaces.len());
-        for interface_index in &class.interfaces {
+        self.interfaces = Vec::with_capacity(info.interfaces.len());
+        for interface in &info.interfaces {
             //shouldn't need to guard against circular interfacing since that's done while loading the .class in ::jvm
-            let ans = jvm::get_class(&jvm::get_name_cp(&class.constant_pool, *interface_index)).unwrap();
+            let ans = jvm::get_class(interface).unwrap();
             self.interfaces.push(ans);
         }
         self.fields = HashMap::new();
         self.instance_fields = vec!();
         for field in &class.fields {
-            if field.access_flags & (::java_class::fields::AccessFlags::Static as u16) != 0 {
+            if field.flags().contains(::java_class::fields::AccessFlags::STATIC) {
                 //static field
                 let field_n = Field::new(class, field);
                 self.fields.insert(field_n.name.to_owned(), field_n);
@@ -150,7 +152,7 @@ impl Class {
     }
 
     pub fn is_interface(&self) -> bool {
-        self.access_flags & (::java_class::class::AccessFlags::Interface as u16) != 0
+        ::java_class::class::AccessFlags::from_bits(self.access_flags).contains(::java_class::class::AccessFlags::INTERFACE)
     }
 
     pub fn is_array(&self) -> bool {
@@ -244,7 +246,7 @@ impl Class {
         for &i in &self.interfaces {
             if let Some(&m) = i.methods.get(repr) {
                 use java_class::methods::AccessFlags;
-                if !m.is_abstract() && m.access_flags & (AccessFlags::Private as u16 | AccessFlags::Static as u16) == 0 {
+                if !m.is_abstract() && m.access_flags & (AccessFlags::PRIVATE | AccessFlags::STATIC).bits() == 0 {
                     if found.is_some() {
                         return Err("IncompatibleClassChangeError");
                     } else {
@@ -336,7 +338,7 @@ impl Class {
         //its ACC_STATIC flag set, method lookup succeeds.
         let object = jvm::get_class("java/lang/Object").unwrap();
         if let Some(&m) = object.methods.get(&repr) {
-            if m.access_flags & (AccessFlags::Public as u16 | AccessFlags::Abstract as u16 | AccessFlags::Static as u16) == AccessFlags::Public as u16 {
+            if m.access_flags & (AccessFlags::PUBLIC | AccessFlags::ABSTRACT | AccessFlags::STATIC).bits() == AccessFlags::PUBLIC.bits() {
                 return Ok(m);
             }
         }
@@ -360,7 +362,7 @@ impl Class {
         //specified by the method reference that has neither its ACC_PRIVATE flag nor its 
         //ACC_STATIC flag set, one of these is arbitrarily chosen and method lookup succeeds.
         use java_class::methods::AccessFlags;
-        if let Some(m) = methods.iter().find(|c| {c.access_flags & (AccessFlags::Private as u16 | AccessFlags::Static as u16) == 0}) {
+        if let Some(m) = methods.iter().find(|c| {c.access_flags & (AccessFlags::PRIVATE | AccessFlags::STATIC).bits() == 0}) {
             return Ok(m);
         }
         //6. Otherwise, method lookup fails.
@@ -371,7 +373,7 @@ impl Class {
     pub fn resolve_method_invokespecial(&self, name: &str, descriptor: &str) -> Result<&'static Method, &str> {
         //this procedure is taken from the description of invokespecial
         let repr = name.to_owned() + descriptor;
-        let c = if !name.ends_with("/<init>") && !self.is_interface() && (self.access_flags & java_class::class::AccessFlags::Super as u16 != 0) {
+        let c = if !name.ends_with("/<init>") && !self.is_interface() && (self.access_flags & java_class::class::AccessFlags::SUPER.bits() != 0) {
             self.super_class.unwrap()
         } else {
             self
@@ -394,7 +396,7 @@ impl Class {
                 //Otherwise, if C is an interface and the class Object contains a declaration of a 
                 //public instance method with the same name and descriptor as the resolved method, 
                 //then it is the method to be invoked.
-                if m.access_flags & (AccessFlags::Public as u16 | AccessFlags::Abstract as u16 | AccessFlags::Static as u16) == AccessFlags::Public as u16 {
+                if m.access_flags & (AccessFlags::PUBLIC | AccessFlags::ABSTRACT | AccessFlags::STATIC).bits() == AccessFlags::PUBLIC.bits() {
                     return Ok(m);
                 }
             }
@@ -475,7 +477,7 @@ impl Class {
         use java_class::methods::AccessFlags;
         let repr = name.to_owned() + descriptor;
         if let Some(m) = self.methods.get(&repr) {
-            if m.access_flags & AccessFlags::Public as u16 == 0 {
+            if m.access_flags & AccessFlags::PUBLIC.bits() == 0 {
                 return Err("IllegalAccessError");
             }
             if m.is_abstract() {
@@ -487,7 +489,7 @@ impl Class {
         while curr.is_some() {
             let c = curr.unwrap();
             if let Some(m) = c.methods.get(&repr) {
-                if m.access_flags & AccessFlags::Public as u16 == 0 {
+                if m.access_flags & AccessFlags::PUBLIC.bits() == 0 {
                     return Err("IllegalAccessError");
                 }
                 if m.is_abstract() {
@@ -500,7 +502,7 @@ impl Class {
         let mut ans = None;
         for i in &self.interfaces {
             if let Some(m) = i.methods.get(&repr) {
-                if !m.is_abstract() && m.access_flags & (AccessFlags::Private as u16 | AccessFlags::Static as u16) == 0 {
+                if !m.is_abstract() && m.access_flags & (AccessFlags::PRIVATE | AccessFlags::STATIC).bits() == 0 {
                     if ans.is_none() {
                         ans = Some(*m)
                     } else {
@@ -616,50 +618,29 @@ impl RuntimeConstantPool {
             //debug!("CP item {:?}", cp_info);
             let next = match cp_info {
                 CPInfo::Class { name_index } => {
-                    let name = jvm::get_name_cp(cp, *name_index);
+                    let name = cp.get_utf8(*name_index)?;
                     RuntimeConstantPoolEntry::Class(jvm::get_or_load_class(&name)?)
                 }
                 CPInfo::Fieldref { class_index, name_and_type_index } => {
-                    let class_name = jvm::get_name_cp(cp, *class_index);
-                    let name_and_type = &cp[*name_and_type_index];
-                    let (name, type_) = match name_and_type {
-                        CPInfo::NameAndType { name_index, descriptor_index } => {
-                            (jvm::get_name_cp(cp, *name_index),
-                             jvm::get_name_cp(cp, *descriptor_index))
-                        }
-                        _ => panic!()
-                    };
+                    let class_name = cp.get_class_name(*class_index)?;
+                    let (name, type_) = cp.get_name_and_type(*name_and_type_index)?;
                     let class = jvm::get_or_load_class(&class_name)?;
                     RuntimeConstantPoolEntry::Fieldref { class, name, type_: jvm::get_or_load_class(&parse_type(&type_))? }
                 }
                 CPInfo::Methodref { class_index, name_and_type_index } => {
-                    let class_name = jvm::get_name_cp(cp, *class_index);
-                    let name_and_type = &cp[*name_and_type_index];
-                    let (name, descriptor) = match name_and_type {
-                        CPInfo::NameAndType { name_index, descriptor_index } => {
-                            (jvm::get_name_cp(cp, *name_index),
-                             jvm::get_name_cp(cp, *descriptor_index))
-                        }
-                        _ => panic!()
-                    };
+                    let class_name = cp.get_class_name(*class_index)?;
+                    let (name, descriptor) = cp.get_name_and_type(*name_and_type_index)?;
                     let class = jvm::get_or_load_class(&class_name)?;
                     RuntimeConstantPoolEntry::Methodref { class, name, descriptor }
                 }
                 CPInfo::InterfaceMethodref { class_index, name_and_type_index } => {
-                    let class_name = jvm::get_name_cp(cp, *class_index);
-                    let name_and_type = &cp[*name_and_type_index];
-                    let (name, descriptor) = match name_and_type {
-                        CPInfo::NameAndType { name_index, descriptor_index } => {
-                            (jvm::get_name_cp(cp, *name_index),
-                             jvm::get_name_cp(cp, *descriptor_index))
-                        }
-                        _ => panic!()
-                    };
+                    let class_name = cp.get_class_name(*class_index)?;
+                    let (name, descriptor) = cp.get_name_and_type(*name_and_type_index)?;
                     let class = jvm::get_or_load_class(&class_name)?;
                     RuntimeConstantPoolEntry::InterfaceMethodref { class, name, descriptor }
                 }
                 CPInfo::String { string_index } => {
-                    RuntimeConstantPoolEntry::String(jvm::get_or_intern_string(jvm::get_name_cp(cp, *string_index)))
+                    RuntimeConstantPoolEntry::String(jvm::get_or_intern_string(cp.get_utf8(*string_index)?))
                 }
                 CPInfo::Integer { bytes } => RuntimeConstantPoolEntry::Integer(*bytes as i32),
                 CPInfo::Float { bytes } => {
@@ -736,8 +717,8 @@ pub struct Field {
 impl Field {
     pub fn new(class: &JavaClass, field_info: &FieldInfo) -> Field {
         let access_flags = field_info.access_flags;
-        let name = jvm::get_name(class, &class.constant_pool[field_info.name_index]);
-        let descriptor_raw = jvm::get_name(class, &class.constant_pool[field_info.descriptor_index]);
+        let name = class.constant_pool.get_utf8(field_info.name_index).unwrap();
+        let descriptor_raw = class.constant_pool.get_utf8(field_info.descriptor_index).unwrap();
         let d_r_2 = descriptor_raw.to_owned();
         let mut descriptor_chars = d_r_2.chars();
         let descriptor = parse_type_started(descriptor_chars.next().unwrap(), &mut descriptor_chars);
@@ -761,11 +742,11 @@ impl Field {
     }
 
     pub fn is_static(&self) -> bool {
-        self.access_flags & java_class::fields::AccessFlags::Static as u16 != 0
+        self.access_flags & java_class::fields::AccessFlags::STATIC.bits() != 0
     }
 
     pub fn is_final(&self) -> bool {
-        self.access_flags & java_class::fields::AccessFlags::Final as u16 != 0
+        self.access_flags & java_class::fields::AccessFlags::FINAL.bits() != 0
     }
 }
 
@@ -789,8 +770,8 @@ pub struct InstanceFieldInfo {
 impl InstanceFieldInfo {
     fn new(class: &JavaClass, field_info: &FieldInfo) -> InstanceFieldInfo {
         let access_flags = field_info.access_flags;
-        let name = Box::leak(jvm::get_name(class, &class.constant_pool[field_info.name_index]).into_boxed_str());
-        let descriptor_raw = Box::leak(jvm::get_name(class, &class.constant_pool[field_info.descriptor_index]).into_boxed_str());
+        let name = Box::leak(class.constant_pool.get_utf8(field_info.name_index).unwrap().into_boxed_str());
+        let descriptor_raw = Box::leak(class.constant_pool.get_utf8(field_info.descriptor_index).unwrap().into_boxed_str());
         let d_r_2 = descriptor_raw.to_owned();
         let mut descriptor_chars = d_r_2.chars();
         let descriptor = Box::leak(parse_type_started(descriptor_chars.next().unwrap(), &mut descriptor_chars).into_boxed_str());
@@ -860,10 +841,9 @@ pub struct Method {
 impl Method {
     
     pub fn new(class: ClassRef, jc: &JavaClass, method_info: &MethodInfo) -> Option<Method> {
-        let (parameters, return_type) =
-            parse_parameters_return(&jvm::get_name(jc, &jc.constant_pool[method_info.descriptor_index]));
-        let name = jvm::get_name(jc, &jc.constant_pool[method_info.name_index]);
-        let descriptor = jvm::get_name(jc, &jc.constant_pool[method_info.descriptor_index]);
+        let name = jc.constant_pool.get_utf8(method_info.name_index)?;
+        let descriptor = jc.constant_pool.get_utf8(method_info.descriptor_index)?;
+        let (parameters, return_type) = parse_parameters_return(&descriptor);
         let repr = name.to_owned() + &descriptor;
         let access_flags = method_info.access_flags;
         let attributes = method_info.attributes.clone();
@@ -888,19 +868,19 @@ impl Method {
         Some(Method { class, name, descriptor, repr, parameters, return_type, access_flags, attributes, code, native_fn, signature: (), visible_annotations: (), invisible_annotations: () })
     }
     pub fn is_abstract(&self) -> bool {
-        self.access_flags & java_class::methods::AccessFlags::Abstract as u16 != 0
+        self.access_flags & java_class::methods::AccessFlags::ABSTRACT.bits() != 0
     }
     pub fn is_native(&self) -> bool {
-        self.access_flags & java_class::methods::AccessFlags::Native as u16 != 0
+        self.access_flags & java_class::methods::AccessFlags::NATIVE.bits() != 0
     }
     pub fn is_public(&self) -> bool {
-        self.access_flags & java_class::methods::AccessFlags::Public as u16 != 0
+        self.access_flags & java_class::methods::AccessFlags::PUBLIC.bits() != 0
     }
     pub fn is_protected(&self) -> bool {
-        self.access_flags & java_class::methods::AccessFlags::Protected as u16 != 0
+        self.access_flags & java_class::methods::AccessFlags::PROTECTED.bits() != 0
     }
     pub fn is_private(&self) -> bool {
-        self.access_flags & java_class::methods::AccessFlags::Private as u16 != 0
+        self.access_flags & java_class::methods::AccessFlags::PRIVATE.bits() != 0
     }
 }
 
@@ -1285,52 +1265,3 @@ impl ::std::cmp::PartialEq for JavaType {
         }
     }
 }
-
-// pub enum JavaTypeKind {
-//     Byte, Char, Short, Int, Long, Float, Double, Ref, 
-//     ByteArray, CharArray, ShortArray, IntArray, LongArray,
-//     FloatArray, DoubleArray, RefArray
-// }
-
-// pub enum SymOpcode {
-//     //Stack manipulation
-//     Push {type_: JavaTypeKind},
-//     Pop {type_: JavaTypeKind},
-//     Duplicate {pos: u8, wide: bool},
-//     PushImm {type_: JavaTypeKind, val: JavaType},
-
-//     //Local manipulation
-//     LoadLocal {index: u16, type_: JavaTypeKind},
-//     StoreLocal {index: u16, type_: JavaTypeKind},
-
-//     //Arrays
-//     LoadFromArray {type_: JavaTypeKind},
-//     StoreToArray {type_: JavaTypeKind},
-//     GetArrayLength,
-//     NewPrimitiveArray {type_: JavaTypeKind},
-//     NewRefArray {type_: Arc<RwLock<JavaClass>>},
-//     NewMultiArray {array_type: Arc<RwLock<JavaClass>>, dimensions: u8},
-    
-//     //Control flow
-//     Return {type_: JavaTypeKind},
-//     Throw,
-//     Breakpoint,
-
-//     //Types
-//     CheckCast {type_: Arc<RwLock<JavaClass>>},
-//     Cast {from: JavaTypeKind, to: JavaTypeKind},
-
-//     //Primitive operations
-//     Add {type_: JavaTypeKind},
-//     Subtract {type_: JavaTypeKind},
-//     Multiply {type_: JavaTypeKind},
-//     Divide {type_: JavaTypeKind},
-//     Negate {type_: JavaTypeKind},
-//     Remainder {type_: JavaTypeKind},
-//     Compare {type_: JavaTypeKind},
-//     CompareNaNIsLess {type_: JavaTypeKind},
-
-//     //Reference operations
-//     GetField {class: Arc<RwLock<JavaClass>>, name: String},
-//     GetStatic {class: Arc<RwLock<JavaClass>>, name: String}
-// }
\ No newline at end of file